@@ -0,0 +1,110 @@
+// Times the random scene with a linear list of hittables against the BVH at every packet
+// width, run with `cargo bench`.
+
+use std::time::Instant;
+
+use learning_rust_with_ray_tracing::{
+    cameras::{camera::Camera, perspective_camera::PerspectiveCamera},
+    float::Float,
+    hittables::{
+        bvh::{build_bvh, PacketWidth},
+        hittable::Hittable,
+    },
+    lights::sky_light::Sky,
+    ray::Ray,
+    renderer::{calculate_color, PathDepths},
+    scene::generate_random_scene,
+    vector::{Point3, Vec3},
+};
+
+const WIDTH: usize = 300;
+const HEIGHT: usize = 200;
+const SAMPLES_PER_PIXEL: usize = 4;
+
+fn main() {
+    let world = generate_random_scene().expect("The random scene is valid");
+    let camera = PerspectiveCamera::new(
+        &Point3::new(13.0, 2.0, -3.0),
+        &Point3::ORIGIN,
+        &Vec3::new(0.0, 1.0, 0.0),
+        WIDTH as Float / HEIGHT as Float,
+        20.0,
+        0.1,
+        10.0,
+    )
+    .expect("The camera is valid");
+
+    // The same camera rays for every structure, only the intersection differs.
+    let rays: Vec<Ray> = (0..HEIGHT)
+        .flat_map(|y| (0..WIDTH).map(move |x| (x, y)))
+        .flat_map(|(x, y)| {
+            let camera = &camera;
+            (0..SAMPLES_PER_PIXEL).filter_map(move |sample| {
+                let offset = sample as Float / SAMPLES_PER_PIXEL as Float;
+                camera.get_ray(
+                    (x as Float + offset) / WIDTH as Float,
+                    (y as Float + offset) / HEIGHT as Float,
+                )
+            })
+        })
+        .collect();
+
+    println!(
+        "{} spheres, {} camera rays, Russian roulette from bounce {}",
+        world.hittables.len(),
+        rays.len(),
+        PathDepths::default().roulette
+    );
+    println!(
+        "{:<16}{:>14}{:>10}{:>14}{:>10}",
+        "structure", "primary Mray/s", "speedup", "paths Mray/s", "speedup"
+    );
+
+    let (list_primary, list_paths) = measure(&world, &rays);
+    report("list", list_primary, list_paths, list_primary, list_paths);
+
+    for (name, packet_width) in [
+        ("bvh scalar", PacketWidth::Scalar),
+        ("bvh 4-wide", PacketWidth::Four),
+        ("bvh 8-wide", PacketWidth::Eight),
+    ] {
+        let bvh = build_bvh(&world.hittables, packet_width, 0.0, 0.0);
+        let (primary, paths) = measure(bvh.as_ref(), &rays);
+        report(name, primary, paths, list_primary, list_paths);
+    }
+}
+
+// Rays per second for closest hits of the camera rays and for full paths.
+fn measure(world: &dyn Hittable, rays: &[Ray]) -> (f64, f64) {
+    let timer = Instant::now();
+    let hits = rays
+        .iter()
+        .filter(|ray| world.hit(ray, 0.0, Float::MAX).is_some())
+        .count();
+    let primary = rays.len() as f64 / timer.elapsed().as_secs_f64();
+    assert!(hits > 0);
+
+    let timer = Instant::now();
+    for ray in rays.iter() {
+        std::hint::black_box(calculate_color(
+            ray,
+            world,
+            &Sky::default(),
+            &PathDepths::default(),
+        ));
+    }
+    let paths = rays.len() as f64 / timer.elapsed().as_secs_f64();
+
+    (primary, paths)
+}
+
+fn report(name: &str, primary: f64, paths: f64, list_primary: f64, list_paths: f64) {
+    println!(
+        "{:<16}{:>14.2}{:>9.1}x{:>14.2}{:>9.1}x",
+        name,
+        primary / 1e6,
+        primary / list_primary,
+        paths / 1e6,
+        paths / list_paths
+    );
+}
@@ -0,0 +1,7 @@
+pub mod aperture;
+pub mod camera;
+pub mod fisheye_camera;
+pub mod orthographic_camera;
+pub mod panoramic_camera;
+pub mod perspective_camera;
+pub mod realistic_camera;
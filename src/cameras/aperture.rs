@@ -0,0 +1,181 @@
+use crate::float::consts::PI;
+
+use rand::Rng;
+
+use crate::{
+    error::Error,
+    float::Float,
+    image::Image,
+    sampler,
+    vector::{random_in_unit_disc, Vec3},
+};
+
+// Shape of the lens opening, it's what gives out of focus highlights (bokeh) their look.
+// All shapes are defined inside of the unit disc and scaled by the lens radius.
+pub enum Aperture {
+    Circle,
+    // Regular polygon formed by the diaphragm blades.
+    Polygon { blades: u32, rotation_deg: Float },
+    Mask(ApertureMask),
+}
+
+impl Aperture {
+    pub fn polygon(blades: u32, rotation_deg: Float) -> Result<Aperture, Error> {
+        if blades < 3 {
+            return Err(Error::InvalidCamera(format!(
+                "aperture needs at least 3 blades, got {}",
+                blades
+            )));
+        }
+
+        Ok(Aperture::Polygon {
+            blades,
+            rotation_deg,
+        })
+    }
+
+    // Random point on the aperture, z is always 0.
+    pub fn sample(&self) -> Vec3 {
+        match self {
+            Aperture::Circle => random_in_unit_disc(),
+            Aperture::Polygon {
+                blades,
+                rotation_deg,
+            } => sample_polygon(*blades, rotation_deg.to_radians()),
+            Aperture::Mask(mask) => mask.sample(),
+        }
+    }
+
+    pub fn contains(&self, x: Float, y: Float) -> bool {
+        match self {
+            Aperture::Circle => x * x + y * y <= 1.0,
+            Aperture::Polygon {
+                blades,
+                rotation_deg,
+            } => polygon_contains(*blades, rotation_deg.to_radians(), x, y),
+            Aperture::Mask(mask) => mask.contains(x, y),
+        }
+    }
+}
+
+fn polygon_vertex(index: u32, blades: u32, rotation: Float) -> Vec3 {
+    let angle = rotation + 2.0 * PI * index as Float / blades as Float;
+    Vec3::new(angle.cos(), angle.sin(), 0.0)
+}
+
+fn sample_polygon(blades: u32, rotation: Float) -> Vec3 {
+    let mut random = sampler::rng();
+
+    // All triangles between the centre and two neighbouring vertices have the same area.
+    let triangle = random.gen_range(0..blades);
+    let a = polygon_vertex(triangle, blades, rotation);
+    let b = polygon_vertex(triangle + 1, blades, rotation);
+
+    let mut s: Float = random.gen();
+    let mut t: Float = random.gen();
+    if s + t > 1.0 {
+        s = 1.0 - s;
+        t = 1.0 - t;
+    }
+
+    s * a + t * b
+}
+
+fn polygon_contains(blades: u32, rotation: Float, x: Float, y: Float) -> bool {
+    let sector_angle = 2.0 * PI / blades as Float;
+    let angle = (y.atan2(x) - rotation).rem_euclid(sector_angle);
+
+    // Distance from the centre to the polygon edge at this angle.
+    let apothem = (sector_angle / 2.0).cos();
+    let edge_distance = apothem / (angle - sector_angle / 2.0).cos();
+
+    (x * x + y * y).sqrt() <= edge_distance
+}
+
+// Grayscale image stretched over the [-1..1] square, brighter pixels let more light through.
+// Only the disc inscribed in the image is open, like the other shapes, the corners are black.
+pub struct ApertureMask {
+    image: Image,
+    // Cumulative transmittance of the pixels, used to pick pixels proportionally to it. Pixels
+    // entirely outside of the disc count as black.
+    cdf: Vec<Float>,
+}
+
+impl ApertureMask {
+    pub fn load(path: &str) -> Result<ApertureMask, Error> {
+        ApertureMask::new(Image::load(path)?)
+    }
+
+    pub fn new(image: Image) -> Result<ApertureMask, Error> {
+        let mut cdf: Vec<Float> = Vec::with_capacity(image.pixels.len());
+        let mut total: Float = 0.0;
+        for y in 0..image.height {
+            for x in 0..image.width {
+                if ApertureMask::touches_disc(&image, x, y) {
+                    total += image.luminance(x, y) as Float;
+                }
+                cdf.push(total);
+            }
+        }
+
+        if total <= 0.0 {
+            return Err(Error::InvalidCamera(String::from(
+                "aperture mask is black within the unit disc",
+            )));
+        }
+
+        Ok(ApertureMask { image, cdf })
+    }
+
+    // Whether any part of the pixel lies within the unit disc.
+    fn touches_disc(image: &Image, x: usize, y: usize) -> bool {
+        // Nearest point of the pixel to the centre, per axis.
+        let nearest = |pixel: usize, size: usize| {
+            let low = 2.0 * pixel as Float / size as Float - 1.0;
+            let high = 2.0 * (pixel + 1) as Float / size as Float - 1.0;
+            low.max(high.min(0.0))
+        };
+        let (nx, ny) = (nearest(x, image.width), nearest(y, image.height));
+        nx * nx + ny * ny < 1.0
+    }
+
+    fn sample(&self) -> Vec3 {
+        let mut random = sampler::rng();
+
+        // Points in the corners of the pixels on the rim are rejected, the pixels picked still
+        // overlap the disc so this ends.
+        let total = *self.cdf.last().unwrap();
+        loop {
+            let target = random.gen::<Float>() * total;
+            let index = self
+                .cdf
+                .partition_point(|value| *value <= target)
+                .min(self.cdf.len() - 1);
+
+            let x = (index % self.image.width) as Float + random.gen::<Float>();
+            let y = (index / self.image.width) as Float + random.gen::<Float>();
+
+            let point = Vec3::new(
+                2.0 * x / self.image.width as Float - 1.0,
+                1.0 - 2.0 * y / self.image.height as Float,
+                0.0,
+            );
+            if point.x() * point.x() + point.y() * point.y() <= 1.0 {
+                return point;
+            }
+        }
+    }
+
+    fn contains(&self, x: Float, y: Float) -> bool {
+        if x * x + y * y > 1.0 {
+            return false;
+        }
+
+        let pixel_x =
+            (((x + 1.0) / 2.0 * self.image.width as Float) as usize).min(self.image.width - 1);
+        let pixel_y =
+            (((1.0 - y) / 2.0 * self.image.height as Float) as usize).min(self.image.height - 1);
+
+        sampler::rng().gen::<Float>() < self.image.luminance(pixel_x, pixel_y) as Float
+    }
+}
@@ -0,0 +1,101 @@
+use crate::{
+    error::Error,
+    float::Float,
+    ray::Ray,
+    vector::{cross, Point3, Vec3},
+};
+
+pub trait Camera {
+    // Ray through the image point (u, v), both in [0..1] with v going up.
+    // None means the point is outside of the projection, e.g. around a fisheye image circle.
+    fn get_ray(&self, u: Float, v: Float) -> Option<Ray>;
+
+    // What bidirectional methods need to connect light paths to the camera, None when the
+    // projection doesn't provide it.
+    fn importance(&self) -> Option<&dyn CameraImportance> {
+        None
+    }
+}
+
+// Point on the lens picked for connecting a point in the scene to the camera.
+pub struct LensSample {
+    pub point: Point3,
+    pub importance: Float,
+    // Density by solid angle at the reference point.
+    pub pdf: Float,
+    // Image point the connection goes through.
+    pub u: Float,
+    pub v: Float,
+}
+
+// The camera as a sensor that emits importance, the adjoint of radiance (Veach's thesis 4.4).
+// It's normalized so that the importance over the whole image integrates to one.
+pub trait CameraImportance {
+    // Importance of `ray` leaving the lens and the image point (u, v) it maps to, None when it
+    // misses the image.
+    fn evaluate(&self, ray: &Ray) -> Option<(Float, Float, Float)>;
+
+    // Densities of `Camera::get_ray` generating `ray`, of its origin by area on the lens and of
+    // its direction by solid angle.
+    fn ray_pdf(&self, ray: &Ray) -> (Float, Float);
+
+    // Samples a point on the lens as seen from `reference`.
+    fn sample_lens(&self, reference: &Point3) -> Option<LensSample>;
+}
+
+// Orthonormal basis shared by all projections, built from the look from / look at / up setup.
+#[derive(Copy, Clone)]
+pub struct CameraFrame {
+    pub origin: Point3,
+    pub forward: Vec3,
+    pub right: Vec3,
+    pub up: Vec3,
+}
+
+impl CameraFrame {
+    pub fn new(origin: &Point3, target: &Point3, up: &Vec3) -> Result<CameraFrame, Error> {
+        if !origin.is_finite() || !target.is_finite() || !up.is_finite() {
+            return Err(Error::InvalidCamera(String::from(
+                "origin, target and up must be finite",
+            )));
+        }
+        if ((*target) - (*origin)).is_near_zero() {
+            return Err(Error::InvalidCamera(String::from(
+                "target must differ from origin",
+            )));
+        }
+        if cross(up, &((*target) - (*origin))).is_near_zero() {
+            return Err(Error::InvalidCamera(String::from(
+                "up must not be parallel to the view direction",
+            )));
+        }
+
+        // Use Unity-like left hand Y-up, Z-forward coordinate system.
+        let forward = ((*target) - (*origin)).normalize();
+        let right = cross(up, &forward).normalize();
+        let up = -cross(&right, &forward);
+
+        Ok(CameraFrame {
+            origin: *origin,
+            forward,
+            right,
+            up,
+        })
+    }
+
+    // Converts a direction from camera space (right, up, forward) to world space.
+    pub fn to_world(&self, direction: &Vec3) -> Vec3 {
+        direction.x() * self.right + direction.y() * self.up + direction.z() * self.forward
+    }
+}
+
+pub fn validate_aspect_ratio(aspect_ratio: Float) -> Result<(), Error> {
+    if !(aspect_ratio > 0.0 && aspect_ratio.is_finite()) {
+        return Err(Error::InvalidCamera(format!(
+            "aspect ratio {} must be positive",
+            aspect_ratio
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,83 @@
+use crate::{
+    error::Error,
+    float::Float,
+    ray::Ray,
+    vector::{Point3, Vec3},
+};
+
+use super::camera::{validate_aspect_ratio, Camera, CameraFrame};
+
+#[derive(Copy, Clone)]
+pub enum FisheyeProjection {
+    // Distance from the image centre is proportional to the angle from the view direction.
+    Equidistant,
+    // Preserves solid angles, r = 2 * f * sin(theta / 2).
+    Equisolid,
+}
+
+// Circular fisheye, the image circle touches the top and bottom of the frame.
+pub struct FisheyeCamera {
+    frame: CameraFrame,
+    aspect_ratio: Float,
+    half_fov: Float,
+    projection: FisheyeProjection,
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        origin: &Point3,
+        target: &Point3,
+        up: &Vec3,
+        aspect_ratio: Float,
+        fov_deg: Float,
+        projection: FisheyeProjection,
+    ) -> Result<FisheyeCamera, Error> {
+        let frame = CameraFrame::new(origin, target, up)?;
+        validate_aspect_ratio(aspect_ratio)?;
+        if !(fov_deg > 0.0 && fov_deg <= 360.0) {
+            return Err(Error::InvalidCamera(format!(
+                "fisheye fov {} must be in (0, 360] degrees",
+                fov_deg
+            )));
+        }
+
+        Ok(FisheyeCamera {
+            frame,
+            aspect_ratio,
+            half_fov: fov_deg.to_radians() / 2.0,
+            projection,
+        })
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn get_ray(&self, u: Float, v: Float) -> Option<Ray> {
+        // Image point relative to the centre, the image circle has radius 1.
+        let x = (2.0 * u - 1.0) * self.aspect_ratio;
+        let y = 2.0 * v - 1.0;
+        let radius = (x * x + y * y).sqrt();
+        if radius > 1.0 {
+            return None;
+        }
+
+        let theta = match self.projection {
+            FisheyeProjection::Equidistant => radius * self.half_fov,
+            FisheyeProjection::Equisolid => {
+                2.0 * (radius * (self.half_fov / 2.0).sin()).min(1.0).asin()
+            }
+        };
+        let phi = y.atan2(x);
+
+        let direction = Vec3::new(
+            theta.sin() * phi.cos(),
+            theta.sin() * phi.sin(),
+            theta.cos(),
+        );
+
+        Some(Ray {
+            origin: self.frame.origin,
+            direction: self.frame.to_world(&direction),
+            time: 0.0,
+        })
+    }
+}
@@ -0,0 +1,57 @@
+use crate::{
+    error::Error,
+    float::Float,
+    ray::Ray,
+    vector::{Point3, Vec3},
+};
+
+use super::camera::{validate_aspect_ratio, Camera, CameraFrame};
+
+// Parallel projection without perspective foreshortening, handy for technical views.
+pub struct OrthographicCamera {
+    frame: CameraFrame,
+    // Size of the visible area in world units.
+    view_width: Float,
+    view_height: Float,
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        origin: &Point3,
+        target: &Point3,
+        up: &Vec3,
+        aspect_ratio: Float,
+        view_height: Float,
+    ) -> Result<OrthographicCamera, Error> {
+        let frame = CameraFrame::new(origin, target, up)?;
+        validate_aspect_ratio(aspect_ratio)?;
+        if !(view_height > 0.0 && view_height.is_finite()) {
+            return Err(Error::InvalidCamera(format!(
+                "view height {} must be positive",
+                view_height
+            )));
+        }
+
+        Ok(OrthographicCamera {
+            frame,
+            view_width: aspect_ratio * view_height,
+            view_height,
+        })
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn get_ray(&self, u: Float, v: Float) -> Option<Ray> {
+        let offset = Vec3::new(
+            (u - 0.5) * self.view_width,
+            (v - 0.5) * self.view_height,
+            0.0,
+        );
+
+        Some(Ray {
+            origin: self.frame.origin + self.frame.to_world(&offset),
+            direction: self.frame.forward,
+            time: 0.0,
+        })
+    }
+}
@@ -0,0 +1,87 @@
+use crate::float::consts::PI;
+
+use crate::{
+    error::Error,
+    float::Float,
+    ray::Ray,
+    vector::{Point3, Vec3},
+};
+
+use super::camera::{Camera, CameraFrame};
+
+#[derive(Copy, Clone)]
+pub enum PanoramicProjection {
+    // Longitude along u and latitude along v, render with a 2:1 aspect ratio.
+    Equirectangular,
+    // Six 90 degree faces in a row ordered +X, -X, +Y, -Y, +Z, -Z in camera space,
+    // render with a 6:1 aspect ratio.
+    Cubemap,
+}
+
+// Full 360 degree view around the camera origin, used for VR previews.
+pub struct PanoramicCamera {
+    frame: CameraFrame,
+    projection: PanoramicProjection,
+}
+
+impl PanoramicCamera {
+    pub fn new(
+        origin: &Point3,
+        target: &Point3,
+        up: &Vec3,
+        projection: PanoramicProjection,
+    ) -> Result<PanoramicCamera, Error> {
+        Ok(PanoramicCamera {
+            frame: CameraFrame::new(origin, target, up)?,
+            projection,
+        })
+    }
+}
+
+impl Camera for PanoramicCamera {
+    fn get_ray(&self, u: Float, v: Float) -> Option<Ray> {
+        let direction = match self.projection {
+            PanoramicProjection::Equirectangular => equirectangular_direction(u, v),
+            PanoramicProjection::Cubemap => cubemap_direction(u, v),
+        };
+
+        Some(Ray {
+            origin: self.frame.origin,
+            direction: self.frame.to_world(&direction),
+            time: 0.0,
+        })
+    }
+}
+
+// The centre of the image looks forward.
+fn equirectangular_direction(u: Float, v: Float) -> Vec3 {
+    let longitude = (u - 0.5) * 2.0 * PI;
+    let latitude = (v - 0.5) * PI;
+
+    Vec3::new(
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        latitude.cos() * longitude.cos(),
+    )
+}
+
+fn cubemap_direction(u: Float, v: Float) -> Vec3 {
+    let face = ((u * 6.0) as usize).min(5);
+    // Position on the face in [-1..1].
+    let a = 2.0 * (u * 6.0 - face as Float) - 1.0;
+    let b = 2.0 * v - 1.0;
+
+    // Each face is seen from inside of the cube with Y up, except the top and bottom faces
+    // that are seen by tilting the view up or down from forward: the top face has backward as
+    // its up and the bottom face has forward, so both touch the forward face along an edge.
+    let data = match face {
+        0 => [1.0, b, -a],
+        1 => [-1.0, b, a],
+        2 => [a, 1.0, -b],
+        3 => [a, -1.0, b],
+        4 => [a, b, 1.0],
+        _ => [-a, b, -1.0],
+    };
+
+    Vec3 { data }
+}
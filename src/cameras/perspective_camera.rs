@@ -0,0 +1,210 @@
+use crate::{
+    error::Error,
+    float::{consts::PI, Float},
+    ray::Ray,
+    vector::{dot, random_in_unit_disc, Point3, Vec3},
+};
+
+use super::{
+    aperture::Aperture,
+    camera::{validate_aspect_ratio, Camera, CameraFrame, CameraImportance, LensSample},
+};
+
+// Thin lens perspective projection.
+pub struct PerspectiveCamera {
+    origin: Point3,
+    lower_left_corner: Point3,
+    viewport_width: Vec3,
+    viewport_height: Vec3,
+    right: Vec3,
+    up: Vec3,
+    forward: Vec3,
+    focus_distance: Float,
+    // Of the image on a plane at distance 1.
+    image_area: Float,
+    lens_radius: Float,
+    aperture: Aperture,
+    // How far the lens barrel shifts towards the image centre at the image corners,
+    // relative to the lens radius. 0 disables cat's eye vignetting.
+    cat_eye: Float,
+}
+
+impl PerspectiveCamera {
+    pub fn new(
+        origin: &Point3,
+        target: &Point3,
+        up: &Vec3,
+        aspect_ratio: Float,
+        vertical_fov_deg: Float,
+        aperture: Float,
+        focus_distance: Float,
+    ) -> Result<PerspectiveCamera, Error> {
+        let frame = CameraFrame::new(origin, target, up)?;
+        validate_aspect_ratio(aspect_ratio)?;
+        if !(vertical_fov_deg > 0.0 && vertical_fov_deg < 180.0) {
+            return Err(Error::InvalidCamera(format!(
+                "vertical fov {} must be in (0, 180) degrees",
+                vertical_fov_deg
+            )));
+        }
+        if !(aperture >= 0.0 && aperture.is_finite()) {
+            return Err(Error::InvalidCamera(format!(
+                "aperture {} must not be negative",
+                aperture
+            )));
+        }
+        if !(focus_distance > 0.0 && focus_distance.is_finite()) {
+            return Err(Error::InvalidCamera(format!(
+                "focus distance {} must be positive",
+                focus_distance
+            )));
+        }
+
+        let theta = vertical_fov_deg.to_radians();
+        let h = (theta / 2.0).tan();
+
+        let height: Float = 2.0 * h;
+        let width: Float = aspect_ratio * height;
+
+        let viewport_width: Vec3 = focus_distance * width * frame.right;
+        let viewport_height: Vec3 = focus_distance * height * frame.up;
+
+        let lower_left_corner: Point3 = frame.origin - viewport_width / 2.0 - viewport_height / 2.0
+            + focus_distance * frame.forward;
+
+        Ok(PerspectiveCamera {
+            origin: frame.origin,
+            lower_left_corner,
+            viewport_width,
+            viewport_height,
+            right: frame.right,
+            up: frame.up,
+            forward: frame.forward,
+            focus_distance,
+            image_area: width * height,
+            lens_radius: aperture / 2.0,
+            aperture: Aperture::Circle,
+            cat_eye: 0.0,
+        })
+    }
+
+    pub fn with_aperture(mut self, aperture: Aperture) -> PerspectiveCamera {
+        self.aperture = aperture;
+        self
+    }
+
+    pub fn with_cat_eye_vignetting(mut self, cat_eye: Float) -> PerspectiveCamera {
+        self.cat_eye = cat_eye.max(0.0);
+        self
+    }
+
+    // A pinhole counts as a lens of unit area, which keeps the densities finite.
+    fn lens_area(&self) -> Float {
+        if self.lens_radius > 0.0 {
+            PI * self.lens_radius * self.lens_radius
+        } else {
+            1.0
+        }
+    }
+
+    // Cosine of `direction` with the optical axis and the image point it goes through from any
+    // point of the lens, None off the image.
+    fn image_point(&self, origin: &Point3, direction: &Vec3) -> Option<(Float, Float, Float)> {
+        let direction = direction.normalize();
+        let cos_theta = dot(&direction, &self.forward);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+
+        // Rays through the same point of the plane of focus come from the same image point.
+        let focus = *origin + (self.focus_distance / cos_theta) * direction;
+        let relative = focus - self.lower_left_corner;
+        let u = dot(&relative, &self.right) / self.viewport_width.length();
+        let v = dot(&relative, &self.up) / self.viewport_height.length();
+        if !((0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)) {
+            return None;
+        }
+
+        Some((cos_theta, u, v))
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn get_ray(&self, u: Float, v: Float) -> Option<Ray> {
+        let lens_sample = self.aperture.sample();
+
+        // Off-axis, the lens barrel clips the aperture into the cat's eye shape.
+        if self.cat_eye > 0.0 {
+            let image_position = Vec3::new(2.0 * u - 1.0, 2.0 * v - 1.0, 0.0);
+            if (lens_sample - self.cat_eye * image_position).squared_length() > 1.0 {
+                return None;
+            }
+        }
+
+        let random_position_on_lens: Vec3 = self.lens_radius * lens_sample;
+        let offset =
+            random_position_on_lens.x() * self.right + random_position_on_lens.y() * self.up;
+
+        Some(Ray {
+            origin: self.origin + offset,
+            direction: self.lower_left_corner + u * self.viewport_width + v * self.viewport_height
+                - self.origin
+                - offset,
+            time: 0.0,
+        })
+    }
+
+    // Only the plain thin lens, other apertures and cat's eye vignetting aren't modelled.
+    fn importance(&self) -> Option<&dyn CameraImportance> {
+        match self.aperture {
+            Aperture::Circle if self.cat_eye == 0.0 => Some(self),
+            _ => None,
+        }
+    }
+}
+
+// pbrt's 16.1.1, the image is sampled uniformly, so the density of directions falls off with
+// the cube of the cosine to the axis.
+impl CameraImportance for PerspectiveCamera {
+    fn evaluate(&self, ray: &Ray) -> Option<(Float, Float, Float)> {
+        let (cos_theta, u, v) = self.image_point(&ray.origin, &ray.direction)?;
+        let importance = 1.0 / (self.image_area * self.lens_area() * cos_theta.powi(4));
+        Some((importance, u, v))
+    }
+
+    fn ray_pdf(&self, ray: &Ray) -> (Float, Float) {
+        match self.image_point(&ray.origin, &ray.direction) {
+            Some((cos_theta, _, _)) => (
+                1.0 / self.lens_area(),
+                1.0 / (self.image_area * cos_theta.powi(3)),
+            ),
+            None => (0.0, 0.0),
+        }
+    }
+
+    fn sample_lens(&self, reference: &Point3) -> Option<LensSample> {
+        let lens_sample = self.lens_radius * random_in_unit_disc();
+        let point = self.origin + lens_sample.x() * self.right + lens_sample.y() * self.up;
+
+        let to_lens = point - *reference;
+        let distance = to_lens.length();
+        if distance == 0.0 {
+            return None;
+        }
+        let ray = Ray {
+            origin: point,
+            direction: -to_lens / distance,
+            time: 0.0,
+        };
+        let (importance, u, v) = self.evaluate(&ray)?;
+        let cos_theta = dot(&ray.direction, &self.forward);
+
+        Some(LensSample {
+            point,
+            importance,
+            pdf: distance * distance / (cos_theta * self.lens_area()),
+            u,
+            v,
+        })
+    }
+}
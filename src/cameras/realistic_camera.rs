@@ -0,0 +1,516 @@
+use std::fs;
+
+use rand::Rng;
+
+use crate::{
+    error::Error,
+    float::Float,
+    ray::Ray,
+    sampler,
+    vector::{dot, Normal3, Point3, Vec3},
+};
+
+use super::{
+    aperture::Aperture,
+    camera::{validate_aspect_ratio, Camera, CameraFrame},
+};
+
+// Lens prescriptions are written in millimetres, the scene is in metres.
+const MILLIMETRES_TO_SCENE: Float = 0.001;
+
+// Number of film radius ranges with their own exit pupil bounds.
+const EXIT_PUPIL_BOUNDS: usize = 16;
+
+// One spherical interface of the lens system, or the aperture stop if the radius is 0.
+#[derive(Copy, Clone)]
+struct LensElement {
+    curvature_radius: Float,
+    // Distance along the axis to the next element towards the film.
+    thickness: Float,
+    // Index of refraction of the medium behind this interface, 0 means air.
+    eta: Float,
+    aperture_radius: Float,
+}
+
+// 2D bounds on the plane of the rear element.
+#[derive(Copy, Clone)]
+struct Bounds {
+    min: [Float; 2],
+    max: [Float; 2],
+}
+
+// Traces camera rays through a multi-element lens system, in the same way as pbrt's
+// realistic camera. The lens looks down -z in its own space with the film at z = 0.
+pub struct RealisticCamera {
+    frame: CameraFrame,
+    // Ordered from the front (scene side) element to the rear (film side) one.
+    elements: Vec<LensElement>,
+    film_width: Float,
+    film_height: Float,
+    // Shape of the aperture stop opening.
+    aperture: Aperture,
+    exit_pupil_bounds: Vec<Option<Bounds>>,
+}
+
+impl RealisticCamera {
+    // `lens_path` points to a lens prescription with one interface per line:
+    // curvature radius, thickness, index of refraction and aperture diameter, all in millimetres.
+    // `f_number` stops the aperture down, otherwise the stop diameter of the prescription is used.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        origin: &Point3,
+        target: &Point3,
+        up: &Vec3,
+        aspect_ratio: Float,
+        lens_path: &str,
+        film_diagonal_mm: Float,
+        f_number: Option<Float>,
+        focus_distance: Float,
+        aperture: Aperture,
+    ) -> Result<RealisticCamera, Error> {
+        let frame = CameraFrame::new(origin, target, up)?;
+        validate_aspect_ratio(aspect_ratio)?;
+        if !(film_diagonal_mm > 0.0 && film_diagonal_mm.is_finite()) {
+            return Err(Error::InvalidCamera(format!(
+                "film diagonal {} must be positive",
+                film_diagonal_mm
+            )));
+        }
+        if !(focus_distance > 0.0 && focus_distance.is_finite()) {
+            return Err(Error::InvalidCamera(format!(
+                "focus distance {} must be positive",
+                focus_distance
+            )));
+        }
+
+        let prescription = fs::read_to_string(lens_path)
+            .map_err(|error| Error::io(format!("can't read lens file {}", lens_path), error))?;
+        let elements = parse_lens_prescription(&prescription, lens_path)?;
+
+        let film_diagonal = film_diagonal_mm * MILLIMETRES_TO_SCENE;
+        let film_height = film_diagonal / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+
+        let mut camera = RealisticCamera {
+            frame,
+            elements,
+            film_width: aspect_ratio * film_height,
+            film_height,
+            aperture,
+            exit_pupil_bounds: Vec::new(),
+        };
+
+        if let Some(f_number) = f_number {
+            camera.set_f_number(f_number)?;
+        }
+
+        let film_distance = camera.focus_thick_lens(focus_distance)?;
+        camera.elements.last_mut().unwrap().thickness = film_distance;
+        camera.exit_pupil_bounds = camera.compute_exit_pupil_bounds();
+
+        Ok(camera)
+    }
+
+    // Sizes the stop so that the entrance pupil is focal length / f-number wide,
+    // assuming the pupil magnification of the lens is close to 1.
+    fn set_f_number(&mut self, f_number: Float) -> Result<(), Error> {
+        let (principal, focal) = self.compute_thick_lens_approximation()?;
+        let focal_length = focal[0] - principal[0];
+
+        let stop = self
+            .elements
+            .iter_mut()
+            .find(|element| element.curvature_radius == 0.0)
+            .ok_or_else(|| Error::InvalidCamera(String::from("lens has no aperture stop")))?;
+
+        let radius = focal_length / (2.0 * f_number);
+        if !(radius > 0.0 && radius <= stop.aperture_radius) {
+            return Err(Error::InvalidCamera(format!(
+                "lens can't open to f/{}, its widest aperture is f/{:.1}",
+                f_number,
+                focal_length / (2.0 * stop.aperture_radius)
+            )));
+        }
+        stop.aperture_radius = radius;
+
+        Ok(())
+    }
+
+    fn lens_rear_z(&self) -> Float {
+        self.elements.last().unwrap().thickness
+    }
+
+    fn lens_front_z(&self) -> Float {
+        self.elements.iter().map(|element| element.thickness).sum()
+    }
+
+    fn rear_element_radius(&self) -> Float {
+        self.elements.last().unwrap().aperture_radius
+    }
+
+    fn passes_aperture(&self, element: &LensElement, hit: &Point3) -> bool {
+        let x = hit.x() / element.aperture_radius;
+        let y = hit.y() / element.aperture_radius;
+        if element.curvature_radius == 0.0 {
+            self.aperture.contains(x, y)
+        } else {
+            x * x + y * y <= 1.0
+        }
+    }
+
+    // Takes a ray in camera space leaving the film, returns it after it left the front element.
+    fn trace_lenses_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut element_z: Float = 0.0;
+        let mut lens_ray = flip_z(ray);
+
+        for (index, element) in self.elements.iter().enumerate().rev() {
+            element_z -= element.thickness;
+
+            let is_stop = element.curvature_radius == 0.0;
+            let (t, normal) = if is_stop {
+                // The ray could be reflected back to the film by an earlier interface.
+                if lens_ray.direction.z() >= 0.0 {
+                    return None;
+                }
+                (
+                    (element_z - lens_ray.origin.z()) / lens_ray.direction.z(),
+                    Normal3::default(),
+                )
+            } else {
+                let centre_z = element_z + element.curvature_radius;
+                intersect_spherical_element(element.curvature_radius, centre_z, &lens_ray)?
+            };
+
+            let hit = lens_ray.point_at_parameter(t);
+            if !self.passes_aperture(element, &hit) {
+                return None;
+            }
+            lens_ray.origin = hit;
+
+            if !is_stop {
+                let eta_incident = element.eta;
+                let eta_transmitted = if index > 0 && self.elements[index - 1].eta != 0.0 {
+                    self.elements[index - 1].eta
+                } else {
+                    1.0
+                };
+                lens_ray.direction = refract(
+                    &-lens_ray.direction.normalize(),
+                    &normal,
+                    eta_incident / eta_transmitted,
+                )?;
+            }
+        }
+
+        Some(flip_z(&lens_ray))
+    }
+
+    // Takes a ray in camera space coming from the scene, returns it after it left the rear element.
+    fn trace_lenses_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut element_z = -self.lens_front_z();
+        let mut lens_ray = flip_z(ray);
+
+        for (index, element) in self.elements.iter().enumerate() {
+            let is_stop = element.curvature_radius == 0.0;
+            let (t, normal) = if is_stop {
+                (
+                    (element_z - lens_ray.origin.z()) / lens_ray.direction.z(),
+                    Normal3::default(),
+                )
+            } else {
+                let centre_z = element_z + element.curvature_radius;
+                intersect_spherical_element(element.curvature_radius, centre_z, &lens_ray)?
+            };
+
+            let hit = lens_ray.point_at_parameter(t);
+            if !self.passes_aperture(element, &hit) {
+                return None;
+            }
+            lens_ray.origin = hit;
+
+            if !is_stop {
+                let eta_incident = if index == 0 || self.elements[index - 1].eta == 0.0 {
+                    1.0
+                } else {
+                    self.elements[index - 1].eta
+                };
+                let eta_transmitted = if element.eta != 0.0 { element.eta } else { 1.0 };
+                lens_ray.direction = refract(
+                    &-lens_ray.direction.normalize(),
+                    &normal,
+                    eta_incident / eta_transmitted,
+                )?;
+            }
+
+            element_z += element.thickness;
+        }
+
+        Some(flip_z(&lens_ray))
+    }
+
+    // Principal plane and focal point positions of the thick lens approximation,
+    // for rays entering from the scene side [0] and from the film side [1].
+    fn compute_thick_lens_approximation(&self) -> Result<([Float; 2], [Float; 2]), Error> {
+        let paraxial_height = 0.001
+            * (self.film_width * self.film_width + self.film_height * self.film_height).sqrt();
+        let failed = || Error::InvalidCamera(String::from("paraxial ray can't pass the lens"));
+
+        let scene_ray = Ray {
+            origin: Point3::new(paraxial_height, 0.0, self.lens_front_z() + 1.0),
+            direction: Vec3::new(0.0, 0.0, -1.0),
+            time: 0.0,
+        };
+        let film_ray = self
+            .trace_lenses_from_scene(&scene_ray)
+            .ok_or_else(failed)?;
+        let (principal_scene, focal_scene) = compute_cardinal_points(&scene_ray, &film_ray);
+
+        let film_ray = Ray {
+            origin: Point3::new(paraxial_height, 0.0, self.lens_rear_z() - 1.0),
+            direction: Vec3::new(0.0, 0.0, 1.0),
+            time: 0.0,
+        };
+        let scene_ray = self.trace_lenses_from_film(&film_ray).ok_or_else(failed)?;
+        let (principal_film, focal_film) = compute_cardinal_points(&film_ray, &scene_ray);
+
+        Ok(([principal_scene, principal_film], [focal_scene, focal_film]))
+    }
+
+    // Distance between the rear element and the film that brings `focus_distance` into focus.
+    fn focus_thick_lens(&self, focus_distance: Float) -> Result<Float, Error> {
+        let (principal, focal) = self.compute_thick_lens_approximation()?;
+
+        let focal_length = focal[0] - principal[0];
+        let z = -focus_distance;
+        let c = (principal[1] - z - principal[0])
+            * (principal[1] - z - 4.0 * focal_length - principal[0]);
+        if c <= 0.0 {
+            return Err(Error::InvalidCamera(format!(
+                "focus distance {} is too close for the lens",
+                focus_distance
+            )));
+        }
+
+        let delta = 0.5 * (principal[1] - z + principal[0] - c.sqrt());
+        Ok(self.lens_rear_z() + delta)
+    }
+
+    // Bounds of the points on the rear element that let light from the film through, one per
+    // film radius range. Sampling only inside them wastes far fewer rays on the lens housing.
+    fn compute_exit_pupil_bounds(&self) -> Vec<Option<Bounds>> {
+        let half_diagonal =
+            0.5 * (self.film_width * self.film_width + self.film_height * self.film_height).sqrt();
+        let rear_radius = self.rear_element_radius();
+        let rear_z = self.lens_rear_z();
+        let grid_size = 64;
+        let cell = 2.0 * rear_radius / grid_size as Float;
+
+        (0..EXIT_PUPIL_BOUNDS)
+            .map(|bound| {
+                let film_radius_start = bound as Float / EXIT_PUPIL_BOUNDS as Float * half_diagonal;
+                let film_radius_end =
+                    (bound + 1) as Float / EXIT_PUPIL_BOUNDS as Float * half_diagonal;
+
+                let mut bounds: Option<Bounds> = None;
+                for film_step in 0..4 {
+                    let film_x = film_radius_start
+                        + (film_radius_end - film_radius_start) * film_step as Float / 3.0;
+
+                    for grid_y in 0..grid_size {
+                        for grid_x in 0..grid_size {
+                            let rear_x = -rear_radius + (grid_x as Float + 0.5) * cell;
+                            let rear_y = -rear_radius + (grid_y as Float + 0.5) * cell;
+                            let ray = Ray {
+                                origin: Point3::new(film_x, 0.0, 0.0),
+                                direction: Vec3::new(rear_x - film_x, rear_y, rear_z),
+                                time: 0.0,
+                            };
+                            if self.trace_lenses_from_film(&ray).is_none() {
+                                continue;
+                            }
+
+                            let point = [rear_x, rear_y];
+                            bounds = Some(match bounds {
+                                None => Bounds {
+                                    min: point,
+                                    max: point,
+                                },
+                                Some(bounds) => Bounds {
+                                    min: [bounds.min[0].min(rear_x), bounds.min[1].min(rear_y)],
+                                    max: [bounds.max[0].max(rear_x), bounds.max[1].max(rear_y)],
+                                },
+                            });
+                        }
+                    }
+                }
+
+                // Grow by a cell, the grid only saw the centres of the cells.
+                bounds.map(|bounds| Bounds {
+                    min: [bounds.min[0] - cell, bounds.min[1] - cell],
+                    max: [bounds.max[0] + cell, bounds.max[1] + cell],
+                })
+            })
+            .collect()
+    }
+
+    // Point on the rear element plane for a film point, rotated from the +x axis bounds.
+    fn sample_exit_pupil(&self, film_x: Float, film_y: Float) -> Option<Point3> {
+        let film_radius = (film_x * film_x + film_y * film_y).sqrt();
+        let half_diagonal =
+            0.5 * (self.film_width * self.film_width + self.film_height * self.film_height).sqrt();
+        let bound = ((film_radius / half_diagonal * EXIT_PUPIL_BOUNDS as Float) as usize)
+            .min(EXIT_PUPIL_BOUNDS - 1);
+        let bounds = self.exit_pupil_bounds[bound]?;
+
+        let mut random = sampler::rng();
+        let x = bounds.min[0] + (bounds.max[0] - bounds.min[0]) * random.gen::<Float>();
+        let y = bounds.min[1] + (bounds.max[1] - bounds.min[1]) * random.gen::<Float>();
+
+        let (sin_theta, cos_theta) = if film_radius > 0.0 {
+            (film_y / film_radius, film_x / film_radius)
+        } else {
+            (0.0, 1.0)
+        };
+
+        Some(Point3::new(
+            cos_theta * x - sin_theta * y,
+            sin_theta * x + cos_theta * y,
+            self.lens_rear_z(),
+        ))
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn get_ray(&self, u: Float, v: Float) -> Option<Ray> {
+        // The lens flips the image, so the film is flipped as well.
+        let film_point = Point3::new(
+            -(u - 0.5) * self.film_width,
+            -(v - 0.5) * self.film_height,
+            0.0,
+        );
+        let rear_point = self.sample_exit_pupil(film_point.x(), film_point.y())?;
+
+        let film_ray = Ray {
+            origin: film_point,
+            direction: rear_point - film_point,
+            time: 0.0,
+        };
+        let ray = self.trace_lenses_from_film(&film_ray)?;
+
+        Some(Ray {
+            origin: self.frame.origin + self.frame.to_world(&(ray.origin - Point3::ORIGIN)),
+            direction: self.frame.to_world(&ray.direction),
+            time: 0.0,
+        })
+    }
+}
+
+fn parse_lens_prescription(text: &str, path: &str) -> Result<Vec<LensElement>, Error> {
+    let mut elements: Vec<LensElement> = Vec::new();
+
+    for (line_index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let values: Vec<Float> = line
+            .split_whitespace()
+            .map(|value| value.parse::<Float>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| {
+                Error::InvalidCamera(format!(
+                    "{}:{}: expected numbers, found \"{}\"",
+                    path,
+                    line_index + 1,
+                    line
+                ))
+            })?;
+        if values.len() != 4 {
+            return Err(Error::InvalidCamera(format!(
+                "{}:{}: expected radius, thickness, eta and aperture",
+                path,
+                line_index + 1
+            )));
+        }
+
+        elements.push(LensElement {
+            curvature_radius: values[0] * MILLIMETRES_TO_SCENE,
+            thickness: values[1] * MILLIMETRES_TO_SCENE,
+            eta: values[2],
+            aperture_radius: values[3] * MILLIMETRES_TO_SCENE / 2.0,
+        });
+    }
+
+    if elements.is_empty() {
+        return Err(Error::InvalidCamera(format!(
+            "lens file {} has no elements",
+            path
+        )));
+    }
+
+    Ok(elements)
+}
+
+fn flip_z(ray: &Ray) -> Ray {
+    Ray {
+        origin: Point3::new(ray.origin.x(), ray.origin.y(), -ray.origin.z()),
+        direction: Vec3::new(ray.direction.x(), ray.direction.y(), -ray.direction.z()),
+        time: ray.time,
+    }
+}
+
+// Returns the ray parameter and the normal facing the incoming ray.
+fn intersect_spherical_element(
+    radius: Float,
+    centre_z: Float,
+    ray: &Ray,
+) -> Option<(Float, Normal3)> {
+    let oc = ray.origin - Point3::new(0.0, 0.0, centre_z);
+    let a = ray.direction.squared_length();
+    let half_b = dot(&oc, &ray.direction);
+    let c = oc.squared_length() - radius * radius;
+
+    let discriminant = half_b * half_b - a * c;
+    if discriminant < 0.0 {
+        return None;
+    }
+
+    let discriminant_sqrt = discriminant.sqrt();
+    let t_near = (-half_b - discriminant_sqrt) / a;
+    let t_far = (-half_b + discriminant_sqrt) / a;
+
+    // Which of the two hits is on the lens surface depends on the ray direction and
+    // on whether the surface is convex or concave.
+    let use_closer = (ray.direction.z() > 0.0) ^ (radius < 0.0);
+    let t = if use_closer { t_near } else { t_far };
+    if t < 0.0 {
+        return None;
+    }
+
+    let normal = Normal3::from((oc + t * ray.direction).normalize());
+
+    Some((t, -normal.face_forward(&ray.direction)))
+}
+
+// `incident` points away from the surface, `eta` is the ratio of incident to transmitted indices.
+// None on total internal reflection.
+fn refract(incident: &Vec3, normal: &Normal3, eta: Float) -> Option<Vec3> {
+    let cos_theta_i = dot(normal, incident);
+    let sin2_theta_t = eta * eta * (1.0 - cos_theta_i * cos_theta_i).max(0.0);
+    if sin2_theta_t >= 1.0 {
+        return None;
+    }
+
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some(eta * -(*incident) + (eta * cos_theta_i - cos_theta_t) * Vec3::from(*normal))
+}
+
+fn compute_cardinal_points(ray_in: &Ray, ray_out: &Ray) -> (Float, Float) {
+    let t_focal = -ray_out.origin.x() / ray_out.direction.x();
+    let focal_z = -ray_out.point_at_parameter(t_focal).z();
+
+    let t_principal = (ray_in.origin.x() - ray_out.origin.x()) / ray_out.direction.x();
+    let principal_z = -ray_out.point_at_parameter(t_principal).z();
+
+    (principal_z, focal_z)
+}
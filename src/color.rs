@@ -0,0 +1,314 @@
+// Color science constants are kept exactly as published.
+#![allow(clippy::excessive_precision)]
+
+use std::ops;
+
+// Linear sRGB radiance or reflectance.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct Color {
+    pub data: [f32; 3],
+}
+
+// CIE 1931 XYZ tristimulus values.
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct Xyz {
+    pub data: [f32; 3],
+}
+
+impl Color {
+    pub const BLACK: Color = Color::new(0.0, 0.0, 0.0);
+    pub const WHITE: Color = Color::new(1.0, 1.0, 1.0);
+
+    pub const fn new(r: f32, g: f32, b: f32) -> Color {
+        Color { data: [r, g, b] }
+    }
+
+    pub fn r(&self) -> f32 {
+        self.data[0]
+    }
+
+    pub fn g(&self) -> f32 {
+        self.data[1]
+    }
+
+    pub fn b(&self) -> f32 {
+        self.data[2]
+    }
+
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.r() + 0.7152 * self.g() + 0.0722 * self.b()
+    }
+
+    pub fn max_component(&self) -> f32 {
+        self.r().max(self.g()).max(self.b())
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.data.iter().all(|value| *value == 0.0)
+    }
+
+    pub fn lerp(&self, other: &Color, t: f32) -> Color {
+        (1.0 - t) * (*self) + t * (*other)
+    }
+
+    pub fn to_xyz(&self) -> Xyz {
+        Xyz {
+            data: transform(&SRGB_TO_XYZ, &self.data),
+        }
+    }
+
+    pub fn from_xyz(xyz: &Xyz) -> Color {
+        Color {
+            data: transform(&XYZ_TO_SRGB, &xyz.data),
+        }
+    }
+
+    // Linear transform of the components, like a white balance.
+    pub fn transform(&self, matrix: &Matrix) -> Color {
+        Color {
+            data: transform(matrix, &self.data),
+        }
+    }
+
+    // Encoded with the sRGB transfer function, as stored in 8 bit images.
+    pub fn to_srgb_encoded(&self) -> Color {
+        let encode = |value: f32| {
+            if value <= 0.0031308 {
+                12.92 * value
+            } else {
+                1.055 * value.powf(1.0 / 2.4) - 0.055
+            }
+        };
+        Color {
+            data: self.data.map(encode),
+        }
+    }
+
+    pub fn from_srgb_encoded(&self) -> Color {
+        let decode = |value: f32| {
+            if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        Color {
+            data: self.data.map(decode),
+        }
+    }
+}
+
+impl Xyz {
+    pub const fn new(x: f32, y: f32, z: f32) -> Xyz {
+        Xyz { data: [x, y, z] }
+    }
+
+    pub fn x(&self) -> f32 {
+        self.data[0]
+    }
+
+    pub fn y(&self) -> f32 {
+        self.data[1]
+    }
+
+    pub fn z(&self) -> f32 {
+        self.data[2]
+    }
+}
+
+impl ops::Add for Color {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Color {
+            data: [
+                self.data[0] + rhs.data[0],
+                self.data[1] + rhs.data[1],
+                self.data[2] + rhs.data[2],
+            ],
+        }
+    }
+}
+
+impl ops::AddAssign for Color {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+// Component-wise, attenuation of light by a surface.
+impl ops::Mul for Color {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Color {
+            data: [
+                self.data[0] * rhs.data[0],
+                self.data[1] * rhs.data[1],
+                self.data[2] * rhs.data[2],
+            ],
+        }
+    }
+}
+
+impl ops::MulAssign for Color {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl ops::Mul<Color> for f32 {
+    type Output = Color;
+
+    fn mul(self, rhs: Color) -> Self::Output {
+        Color {
+            data: rhs.data.map(|value| self * value),
+        }
+    }
+}
+
+impl ops::Mul<f32> for Color {
+    type Output = Color;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl ops::Div<f32> for Color {
+    type Output = Color;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        Color {
+            data: self.data.map(|value| value / rhs),
+        }
+    }
+}
+
+impl ops::Add for Xyz {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Xyz {
+            data: [
+                self.data[0] + rhs.data[0],
+                self.data[1] + rhs.data[1],
+                self.data[2] + rhs.data[2],
+            ],
+        }
+    }
+}
+
+impl ops::AddAssign for Xyz {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl ops::Mul<Xyz> for f32 {
+    type Output = Xyz;
+
+    fn mul(self, rhs: Xyz) -> Self::Output {
+        Xyz {
+            data: rhs.data.map(|value| self * value),
+        }
+    }
+}
+
+impl ops::Div<f32> for Xyz {
+    type Output = Xyz;
+
+    fn div(self, rhs: f32) -> Self::Output {
+        Xyz {
+            data: self.data.map(|value| value / rhs),
+        }
+    }
+}
+
+pub type Matrix = [[f32; 3]; 3];
+
+pub const SRGB_TO_XYZ: Matrix = [
+    [0.4124564, 0.3575761, 0.1804375],
+    [0.2126729, 0.7151522, 0.0721750],
+    [0.0193339, 0.1191920, 0.9503041],
+];
+
+pub const XYZ_TO_SRGB: Matrix = [
+    [3.2404542, -1.5371385, -0.4985314],
+    [-0.9692660, 1.8760108, 0.0415560],
+    [0.0556434, -0.2040259, 1.0572252],
+];
+
+const BRADFORD: Matrix = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+const BRADFORD_INVERSE: Matrix = [
+    [0.9869929, -0.1470543, 0.1599627],
+    [0.4323053, 0.5183603, 0.0492912],
+    [-0.0085287, 0.0400428, 0.9684867],
+];
+
+pub const D65_WHITE: Xyz = Xyz::new(0.95047, 1.0, 1.08883);
+
+// Linear sRGB transform that makes light of the given color temperature white,
+// using a Bradford chromatic adaptation to D65.
+pub fn white_balance_matrix(kelvin: f32) -> Matrix {
+    let adaptation = chromatic_adaptation(&blackbody_white_point(kelvin), &D65_WHITE);
+
+    multiply(&XYZ_TO_SRGB, &multiply(&adaptation, &SRGB_TO_XYZ))
+}
+
+// XYZ transform that maps `source_white` to `target_white` in the Bradford cone space.
+pub fn chromatic_adaptation(source_white: &Xyz, target_white: &Xyz) -> Matrix {
+    let source_cone = transform(&BRADFORD, &source_white.data);
+    let target_cone = transform(&BRADFORD, &target_white.data);
+    let gain = |index: usize| target_cone[index] / source_cone[index];
+    let scale: Matrix = [
+        [gain(0), 0.0, 0.0],
+        [0.0, gain(1), 0.0],
+        [0.0, 0.0, gain(2)],
+    ];
+
+    multiply(&BRADFORD_INVERSE, &multiply(&scale, &BRADFORD))
+}
+
+// XYZ with Y = 1 of a black body, from the cubic fit of the Planckian locus by Kim et al.
+pub fn blackbody_white_point(kelvin: f32) -> Xyz {
+    let t = kelvin;
+    let x = if t <= 4000.0 {
+        -0.2661239e9 / (t * t * t) - 0.2343589e6 / (t * t) + 0.8776956e3 / t + 0.179910
+    } else {
+        -3.0258469e9 / (t * t * t) + 2.1070379e6 / (t * t) + 0.2226347e3 / t + 0.240390
+    };
+
+    let y = if t <= 2222.0 {
+        -1.1063814 * x * x * x - 1.34811020 * x * x + 2.18555832 * x - 0.20219683
+    } else if t <= 4000.0 {
+        -0.9549476 * x * x * x - 1.37418593 * x * x + 2.09137015 * x - 0.16748867
+    } else {
+        3.0817580 * x * x * x - 5.87338670 * x * x + 3.75112997 * x - 0.37001483
+    };
+
+    Xyz::new(x / y, 1.0, (1.0 - x - y) / y)
+}
+
+pub fn transform(matrix: &Matrix, vector: &[f32; 3]) -> [f32; 3] {
+    let row = |index: usize| {
+        matrix[index][0] * vector[0] + matrix[index][1] * vector[1] + matrix[index][2] * vector[2]
+    };
+
+    [row(0), row(1), row(2)]
+}
+
+pub fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result: Matrix = [[0.0; 3]; 3];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    result
+}
@@ -0,0 +1,142 @@
+use crate::{
+    color::Color,
+    float::to_f32,
+    vector::{dot, Normal3},
+};
+
+// Per-pixel auxiliary buffers (AOVs) gathered from the first hit of every camera ray.
+pub struct FeatureBuffers {
+    pub albedo: Vec<Color>,
+    pub normal: Vec<Normal3>,
+    pub depth: Vec<f32>,
+}
+
+impl FeatureBuffers {
+    pub fn new(pixel_count: usize) -> FeatureBuffers {
+        FeatureBuffers {
+            albedo: vec![Color::default(); pixel_count],
+            normal: vec![Normal3::default(); pixel_count],
+            depth: vec![0.0; pixel_count],
+        }
+    }
+}
+
+pub struct DenoiserSettings {
+    pub iterations: u32,
+    pub sigma_color: f32,
+    pub sigma_normal: f32,
+    pub sigma_albedo: f32,
+    pub sigma_depth: f32,
+}
+
+impl Default for DenoiserSettings {
+    fn default() -> Self {
+        DenoiserSettings {
+            iterations: 5,
+            sigma_color: 0.6,
+            sigma_normal: 0.1,
+            sigma_albedo: 0.1,
+            sigma_depth: 0.1,
+        }
+    }
+}
+
+// B3-spline kernel of the à-trous wavelet transform.
+const KERNEL: [f32; 5] = [1.0 / 16.0, 1.0 / 4.0, 3.0 / 8.0, 1.0 / 4.0, 1.0 / 16.0];
+
+// Edge-avoiding à-trous wavelet filter (Dammertz et al. 2010) guided by the feature buffers.
+// Every iteration doubles the distance between the taps, so a 5x5 kernel covers a large
+// footprint in a few passes, while the feature weights stop it from blurring across edges.
+pub fn denoise(
+    color: &[Color],
+    features: &FeatureBuffers,
+    width: usize,
+    height: usize,
+    settings: &DenoiserSettings,
+) -> Vec<Color> {
+    let mut input: Vec<Color> = color.to_vec();
+    let mut output: Vec<Color> = vec![Color::default(); color.len()];
+
+    for iteration in 0..settings.iterations {
+        let step = 1_i32 << iteration;
+        // Noise drops after each pass, so the color weight has to get stricter.
+        let sigma_color = settings.sigma_color / (1 << iteration) as f32;
+
+        for y in 0..height as i32 {
+            for x in 0..width as i32 {
+                let index = (y * width as i32 + x) as usize;
+
+                let mut sum = Color::default();
+                let mut weight_sum: f32 = 0.0;
+
+                for (ky, kernel_y) in KERNEL.iter().enumerate() {
+                    for (kx, kernel_x) in KERNEL.iter().enumerate() {
+                        let sample_x = x + (kx as i32 - 2) * step;
+                        let sample_y = y + (ky as i32 - 2) * step;
+                        if sample_x < 0
+                            || sample_y < 0
+                            || sample_x >= width as i32
+                            || sample_y >= height as i32
+                        {
+                            continue;
+                        }
+
+                        let sample_index = (sample_y * width as i32 + sample_x) as usize;
+
+                        let color_distance = squared_distance(&input[index], &input[sample_index]);
+                        let normal_distance = to_f32(
+                            (1.0 - dot(&features.normal[index], &features.normal[sample_index]))
+                                .max(0.0),
+                        );
+                        let albedo_distance = squared_distance(
+                            &features.albedo[index],
+                            &features.albedo[sample_index],
+                        );
+                        let depth_distance = relative_depth_distance(
+                            features.depth[index],
+                            features.depth[sample_index],
+                        ) / step as f32;
+
+                        let weight = kernel_x
+                            * kernel_y
+                            * (-color_distance / (sigma_color * sigma_color)).exp()
+                            * (-normal_distance / settings.sigma_normal).exp()
+                            * (-albedo_distance / (settings.sigma_albedo * settings.sigma_albedo))
+                                .exp()
+                            * (-depth_distance / settings.sigma_depth).exp();
+
+                        sum += weight * input[sample_index];
+                        weight_sum += weight;
+                    }
+                }
+
+                output[index] = if weight_sum > 0.0 {
+                    sum / weight_sum
+                } else {
+                    input[index]
+                };
+            }
+        }
+
+        std::mem::swap(&mut input, &mut output);
+    }
+
+    input
+}
+
+fn relative_depth_distance(depth: f32, other_depth: f32) -> f32 {
+    let scale = depth.max(other_depth);
+    if scale <= 0.0 {
+        return 0.0;
+    }
+
+    (depth - other_depth).abs() / scale
+}
+
+fn squared_distance(a: &Color, b: &Color) -> f32 {
+    a.data
+        .iter()
+        .zip(b.data.iter())
+        .map(|(a, b)| (a - b) * (a - b))
+        .sum()
+}
@@ -0,0 +1,64 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    // Command line or render settings that can't be used.
+    InvalidSettings(String),
+    // Scene description problems, e.g. a degenerate camera or a negative sphere radius.
+    InvalidCamera(String),
+    InvalidGeometry(String),
+    InvalidMaterial(String),
+    MissingTexture(String),
+    // The render was stopped through its cancellation token.
+    Cancelled,
+    // Failed to read or write a file, `context` says which one and why.
+    Io {
+        context: String,
+        source: std::io::Error,
+    },
+}
+
+impl Error {
+    pub fn io(context: impl Into<String>, source: std::io::Error) -> Error {
+        Error::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    // Process exit code of each failure category, 0 and 1 are left for success and panics.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Error::InvalidSettings(_) => 2,
+            Error::InvalidCamera(_)
+            | Error::InvalidGeometry(_)
+            | Error::InvalidMaterial(_)
+            | Error::MissingTexture(_) => 3,
+            Error::Cancelled => 4,
+            Error::Io { .. } => 5,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidSettings(message) => write!(f, "invalid settings: {}", message),
+            Error::InvalidCamera(message) => write!(f, "invalid camera: {}", message),
+            Error::InvalidGeometry(message) => write!(f, "invalid geometry: {}", message),
+            Error::InvalidMaterial(message) => write!(f, "invalid material: {}", message),
+            Error::MissingTexture(path) => write!(f, "missing texture: {}", path),
+            Error::Cancelled => write!(f, "render was cancelled"),
+            Error::Io { context, source } => write!(f, "{}: {}", context, source),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
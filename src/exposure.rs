@@ -0,0 +1,154 @@
+use crate::{
+    color::{white_balance_matrix, Color},
+    error::Error,
+};
+
+// Height of a full frame 35mm sensor in scene units (metres).
+pub const FULL_FRAME_SENSOR_HEIGHT: f32 = 0.024;
+
+// Camera settings that turn scene radiance into image values, like a real camera does.
+pub struct Exposure {
+    // Drives both the lens radius and the amount of light reaching the sensor.
+    pub f_number: f32,
+    // Seconds the shutter stays open, rays are spread over this interval for motion blur.
+    pub shutter_time: f32,
+    pub iso: f32,
+    // Replace the manual exposure with one metered from the image luminance histogram.
+    pub auto_exposure: bool,
+    // Color temperature in Kelvin of the light that should look white.
+    pub white_balance: Option<f32>,
+}
+
+// The "sunny 16" rule, f/16 at 1/100 s and ISO 100 leaves radiance untouched.
+impl Default for Exposure {
+    fn default() -> Self {
+        Exposure {
+            f_number: 16.0,
+            shutter_time: 1.0 / 100.0,
+            iso: 100.0,
+            auto_exposure: false,
+            white_balance: None,
+        }
+    }
+}
+
+impl Exposure {
+    pub fn validate(&self) -> Result<(), Error> {
+        if !(self.f_number > 0.0 && self.f_number.is_finite()) {
+            return Err(Error::InvalidSettings(format!(
+                "f-number {} must be positive",
+                self.f_number
+            )));
+        }
+        if !(self.shutter_time >= 0.0 && self.shutter_time.is_finite()) {
+            return Err(Error::InvalidSettings(format!(
+                "shutter time {} must not be negative",
+                self.shutter_time
+            )));
+        }
+        if !(self.iso > 0.0 && self.iso.is_finite()) {
+            return Err(Error::InvalidSettings(format!(
+                "ISO {} must be positive",
+                self.iso
+            )));
+        }
+        if let Some(kelvin) = self.white_balance {
+            if !(1667.0..=25000.0).contains(&kelvin) {
+                return Err(Error::InvalidSettings(format!(
+                    "white balance {}K must be in [1667, 25000] K",
+                    kelvin
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    // Diameter of the entrance pupil of a lens with the given focal length.
+    pub fn aperture_diameter(&self, focal_length: f32) -> f32 {
+        focal_length / self.f_number
+    }
+
+    // Light gathered relative to the "sunny 16" settings.
+    pub fn scale(&self) -> f32 {
+        let reference = Exposure::default();
+        let gathered = |exposure: &Exposure| {
+            exposure.iso * exposure.shutter_time / (exposure.f_number * exposure.f_number)
+        };
+
+        gathered(self) / gathered(&reference)
+    }
+
+    pub fn apply(&self, pixels: &mut [Color]) {
+        if let Some(kelvin) = self.white_balance {
+            let matrix = white_balance_matrix(kelvin);
+            for pixel in pixels.iter_mut() {
+                *pixel = pixel.transform(&matrix);
+            }
+        }
+
+        let scale = if self.auto_exposure {
+            auto_exposure_scale(pixels)
+        } else {
+            self.scale()
+        };
+
+        for pixel in pixels.iter_mut() {
+            *pixel = scale * (*pixel);
+        }
+    }
+}
+
+// Focal length of a lens covering `vertical_fov_deg` on a sensor of the given height.
+pub fn focal_length(vertical_fov_deg: f32, sensor_height: f32) -> f32 {
+    sensor_height / (2.0 * (vertical_fov_deg.to_radians() / 2.0).tan())
+}
+
+const HISTOGRAM_BINS: usize = 64;
+const HISTOGRAM_MIN_EV: f32 = -12.0;
+const HISTOGRAM_MAX_EV: f32 = 12.0;
+// Darkest and brightest fractions of the pixels ignored by the metering.
+const HISTOGRAM_LOW_PERCENT: f32 = 0.1;
+const HISTOGRAM_HIGH_PERCENT: f32 = 0.9;
+const MIDDLE_GRAY: f32 = 0.18;
+
+// Scale that brings the average log luminance of the image to middle gray.
+// The average is taken over a log2 histogram without its extremes, so a few
+// very dark or very bright pixels don't drive the exposure.
+pub fn auto_exposure_scale(pixels: &[Color]) -> f32 {
+    let mut histogram = [0_usize; HISTOGRAM_BINS];
+    let bin_size = (HISTOGRAM_MAX_EV - HISTOGRAM_MIN_EV) / HISTOGRAM_BINS as f32;
+
+    for pixel in pixels.iter() {
+        let ev = pixel
+            .luminance()
+            .max(f32::MIN_POSITIVE)
+            .log2()
+            .clamp(HISTOGRAM_MIN_EV, HISTOGRAM_MAX_EV);
+        let bin = (((ev - HISTOGRAM_MIN_EV) / bin_size) as usize).min(HISTOGRAM_BINS - 1);
+        histogram[bin] += 1;
+    }
+
+    let low = HISTOGRAM_LOW_PERCENT * pixels.len() as f32;
+    let high = HISTOGRAM_HIGH_PERCENT * pixels.len() as f32;
+
+    let mut seen: f32 = 0.0;
+    let mut ev_sum: f32 = 0.0;
+    let mut weight_sum: f32 = 0.0;
+    for (bin, count) in histogram.iter().enumerate() {
+        // Only the part of the bin between the low and high percentiles counts.
+        let start = seen;
+        let end = seen + *count as f32;
+        let weight = (end.min(high) - start.max(low)).max(0.0);
+        seen = end;
+
+        ev_sum += weight * (HISTOGRAM_MIN_EV + (bin as f32 + 0.5) * bin_size);
+        weight_sum += weight;
+    }
+
+    if weight_sum <= 0.0 {
+        return 1.0;
+    }
+
+    MIDDLE_GRAY / (ev_sum / weight_sum).exp2()
+}
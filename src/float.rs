@@ -0,0 +1,38 @@
+// Precision of the geometry: positions, directions, ray distances and camera math.
+// The "f64" feature trades speed for less rounding error in large-scale scenes,
+// colors and spectra stay f32 either way.
+
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+#[cfg(not(feature = "f64"))]
+pub use std::f32::consts;
+
+#[cfg(feature = "f64")]
+pub use std::f64::consts;
+
+// Narrows a geometry value that feeds a color.
+#[allow(clippy::unnecessary_cast)]
+pub fn to_f32(value: Float) -> f32 {
+    value as f32
+}
+
+// Half an ulp of 1, bounds the relative error of a single rounded operation.
+pub const MACHINE_EPSILON: Float = Float::EPSILON * 0.5;
+
+// Bounds the relative error of `n` successive rounded operations, (1 ± ε)^n - 1 ≤ γn.
+pub fn gamma(n: i32) -> Float {
+    let n_epsilon = n as Float * MACHINE_EPSILON;
+    n_epsilon / (1.0 - n_epsilon)
+}
+
+// a * b - c * d without the catastrophic cancellation of the naive form, using the exact
+// rounding error of c * d recovered by a fused multiply-add.
+pub fn difference_of_products(a: Float, b: Float, c: Float, d: Float) -> Float {
+    let cd = c * d;
+    let error = (-c).mul_add(d, cd);
+    a.mul_add(b, -cd) + error
+}
@@ -0,0 +1,71 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use crate::{
+    color::Color,
+    denoiser::{denoise, DenoiserSettings, FeatureBuffers},
+    error::Error,
+};
+
+// Averaged radiance of every pixel, stored row by row starting from the top-left corner.
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+    // Only gathered when `RenderSettings::feature_buffers` is set.
+    pub features: Option<FeatureBuffers>,
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![Color::BLACK; width * height],
+            features: None,
+        }
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    // Returns a filtered copy, or None when the feature buffers were not gathered.
+    pub fn denoise(&self, settings: &DenoiserSettings) -> Option<Framebuffer> {
+        let features = self.features.as_ref()?;
+
+        Some(Framebuffer {
+            width: self.width,
+            height: self.height,
+            pixels: denoise(&self.pixels, features, self.width, self.height, settings),
+            features: None,
+        })
+    }
+
+    pub fn write_ppm(&self, path: &str) -> Result<(), Error> {
+        let file = File::create(path)
+            .map_err(|error| Error::io(format!("can't create {}", path), error))?;
+
+        self.write_ppm_to(&mut BufWriter::new(file))
+            .map_err(|error| Error::io(format!("can't write {}", path), error))
+    }
+
+    fn write_ppm_to(&self, output: &mut impl Write) -> Result<(), std::io::Error> {
+        write!(output, "P3\n{} {}\n255\n", self.width, self.height)?;
+
+        for color in self.pixels.iter() {
+            write_color(output, *color)?;
+        }
+
+        output.flush()
+    }
+}
+
+fn write_color(output: &mut impl Write, color: Color) -> Result<(), std::io::Error> {
+    let gamma: f32 = 1.0 / 2.2;
+
+    let r: u8 = (color.r().powf(gamma) * 255.99) as u8;
+    let g: u8 = (color.g().powf(gamma) * 255.99) as u8;
+    let b: u8 = (color.b().powf(gamma) * 255.99) as u8;
+    writeln!(output, "{} {} {}", r, g, b)
+}
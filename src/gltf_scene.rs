@@ -0,0 +1,743 @@
+use std::{collections::HashMap, fs, path::Path, rc::Rc};
+
+use gltf::{camera::Projection, khr_lights_punctual::Kind, mesh::Mode, Gltf};
+
+use crate::{
+    cameras::{
+        camera::Camera, orthographic_camera::OrthographicCamera,
+        perspective_camera::PerspectiveCamera,
+    },
+    color::Color,
+    error::Error,
+    float::Float,
+    hittables::{
+        aabb::Aabb,
+        hittable_list::HittableList,
+        instance::Instance,
+        mesh::TriangleMesh,
+        subdivision::{Displacement, SubdivisionSurface},
+        triangle::{is_degenerate, Triangle},
+    },
+    image::Image,
+    lights::punctual_light::PunctualLight,
+    materials::{
+        emissive_material::EmissiveMaterial, material::Material,
+        principled_material::PrincipledMaterial,
+    },
+    transform::{Matrix4, Transform},
+    vector::{Normal3, Point3, Vec3},
+};
+
+// Scene imported from a glTF 2.0 file, either a .gltf with its buffers and images embedded or
+// beside it, or a binary .glb. Every mesh is stored once and placed by instances with the
+// transforms of its nodes, metallic-roughness materials become principled ones with their base
+// color and metallic-roughness textures. Primitives with an emissive material are turned into
+// world space triangles of an emissive material instead, that's what lights can be sampled
+// from, with the emissive texture averaged. The camera is that of the first node with one, with
+// the aspect ratio of the render, and KHR_lights_punctual lights become punctual lights without
+// their range. Meshes with
+// "subdivision" in their extras are control cages, refined on load.
+pub struct GltfScene {
+    pub world: HittableList,
+    pub camera: Option<Box<dyn Camera>>,
+    pub lights: Vec<PunctualLight>,
+}
+
+impl GltfScene {
+    // Loads the default scene of the file, or its first one. Cameras get the aspect ratio of
+    // the render rather than their own.
+    pub fn load(path: &str, aspect_ratio: Float) -> Result<GltfScene, Error> {
+        let bytes =
+            fs::read(path).map_err(|error| Error::io(format!("can't read {}", path), error))?;
+        let gltf = Gltf::from_slice(&bytes)
+            .map_err(|error| invalid_data(format!("can't parse {}", path), error.to_string()))?;
+        let directory = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        let buffers = gltf
+            .buffers()
+            .map(|buffer| {
+                let data = match buffer.source() {
+                    gltf::buffer::Source::Bin => gltf.blob.clone().ok_or_else(|| {
+                        invalid_data(
+                            format!("can't read buffer {} of {}", buffer.index(), path),
+                            String::from("there is no binary chunk"),
+                        )
+                    })?,
+                    gltf::buffer::Source::Uri(uri) => read_uri(directory, uri)?,
+                };
+                if data.len() < buffer.length() {
+                    return Err(invalid_data(
+                        format!("can't read buffer {} of {}", buffer.index(), path),
+                        format!("{} bytes of {}", data.len(), buffer.length()),
+                    ));
+                }
+                Ok(data)
+            })
+            .collect::<Result<Vec<Vec<u8>>, Error>>()?;
+
+        let scene = gltf
+            .default_scene()
+            .or_else(|| gltf.scenes().next())
+            .ok_or_else(|| Error::InvalidGeometry(format!("{} has no scene", path)))?;
+        let mut importer = Importer {
+            document: &gltf.document,
+            directory,
+            buffers,
+            aspect_ratio,
+            materials: HashMap::new(),
+            textures: HashMap::new(),
+            meshes: HashMap::new(),
+            scene: GltfScene {
+                world: HittableList {
+                    hittables: Vec::new(),
+                },
+                camera: None,
+                lights: Vec::new(),
+            },
+        };
+        // glTF is right-handed and the cameras here are left-handed, flipping z keeps the images
+        // from coming out mirrored.
+        let handedness = Transform::scale(1.0, 1.0, -1.0)?;
+        for node in scene.nodes() {
+            importer.visit(&node, &handedness)?;
+        }
+
+        if importer.scene.world.hittables.is_empty() {
+            return Err(Error::InvalidGeometry(format!(
+                "{} has no triangles to render",
+                path
+            )));
+        }
+        Ok(importer.scene)
+    }
+}
+
+// Deepest refinement a mesh can ask for, every level has four times the triangles.
+const MAX_SUBDIVISION_LEVELS: u32 = 6;
+// Cage vertices closer than this fraction of the cage's size are welded.
+const WELD_TOLERANCE: Float = 1e-5;
+
+// Positions and triangles of a primitive, in its mesh's space.
+struct Geometry {
+    positions: Vec<Point3>,
+    triangles: Vec<[usize; 3]>,
+    normals: Option<Vec<Normal3>>,
+    uvs: Option<Vec<[Float; 2]>>,
+}
+
+// What the extras of a mesh ask of its subdivision.
+struct Subdivision {
+    levels: u32,
+    // Vertices of the primitive at either end of a creased edge, and its sharpness.
+    creases: Vec<(usize, usize, Float)>,
+    // Applied after refining.
+    displacement: Option<Displacement>,
+}
+
+struct Importer<'a> {
+    document: &'a gltf::Document,
+    directory: &'a Path,
+    buffers: Vec<Vec<u8>>,
+    aspect_ratio: Float,
+    // Shared by everything that refers to them, the default material under None.
+    materials: HashMap<Option<usize>, Rc<dyn Material>>,
+    // Images by index and whether they were sRGB encoded.
+    textures: HashMap<(usize, bool), Rc<Image>>,
+    // Meshes by mesh and primitive index.
+    meshes: HashMap<(usize, usize), Rc<TriangleMesh>>,
+    scene: GltfScene,
+}
+
+impl Importer<'_> {
+    fn visit(&mut self, node: &gltf::Node, parent: &Transform) -> Result<(), Error> {
+        let transform = *parent * Transform::from_matrix(row_major(&node.transform().matrix()))?;
+
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                self.add_primitive(&mesh, &primitive, &transform)?;
+            }
+        }
+        if let Some(camera) = node.camera() {
+            if self.scene.camera.is_none() {
+                self.scene.camera = Some(self.camera(&camera, &transform)?);
+            }
+        }
+        if let Some(light) = node.light() {
+            self.scene.lights.push(light_from(&light, &transform)?);
+        }
+
+        for child in node.children() {
+            self.visit(&child, &transform)?;
+        }
+        Ok(())
+    }
+
+    // Points and lines have no surface to hit, they are left out.
+    fn add_primitive(
+        &mut self,
+        mesh: &gltf::Mesh,
+        primitive: &gltf::Primitive,
+        transform: &Transform,
+    ) -> Result<(), Error> {
+        if !matches!(
+            primitive.mode(),
+            Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan
+        ) {
+            return Ok(());
+        }
+
+        let emission = self.emission(&primitive.material())?;
+        if !emission.is_black() {
+            let geometry = self.geometry(mesh, primitive)?;
+            let positions: Vec<Point3> = geometry
+                .positions
+                .iter()
+                .map(|position| transform.transform_point(position))
+                .collect();
+            for triangle in &geometry.triangles {
+                // Mirroring turns the winding around, glTF keeps the front faces.
+                let [p0, mut p1, mut p2] = triangle.map(|index| positions[index]);
+                if transform.swaps_handedness() {
+                    (p1, p2) = (p2, p1);
+                }
+                if is_degenerate(&[p0, p1, p2]) {
+                    continue;
+                }
+                self.scene.world.hittables.push(Box::new(Triangle::new(
+                    [p0, p1, p2],
+                    Box::new(EmissiveMaterial::new(emission)),
+                )?));
+            }
+            return Ok(());
+        }
+
+        let key = (mesh.index(), primitive.index());
+        let triangle_mesh = match self.meshes.get(&key) {
+            Some(triangle_mesh) => Rc::clone(triangle_mesh),
+            None => {
+                let geometry = self.geometry(mesh, primitive)?;
+                let material = self.material(&primitive.material())?;
+                let mut triangle_mesh =
+                    TriangleMesh::new(geometry.positions, geometry.triangles, material)?;
+                if let Some(normals) = geometry.normals {
+                    triangle_mesh = triangle_mesh.with_normals(normals)?;
+                }
+                if let Some(uvs) = geometry.uvs {
+                    triangle_mesh = triangle_mesh.with_uvs(uvs)?;
+                }
+                let triangle_mesh = Rc::new(triangle_mesh);
+                self.meshes.insert(key, Rc::clone(&triangle_mesh));
+                triangle_mesh
+            }
+        };
+        self.scene
+            .world
+            .hittables
+            .push(Box::new(Instance::new(triangle_mesh, *transform)));
+        Ok(())
+    }
+
+    fn geometry(
+        &mut self,
+        mesh: &gltf::Mesh,
+        primitive: &gltf::Primitive,
+    ) -> Result<Geometry, Error> {
+        let reader = primitive.reader(|buffer| self.buffers.get(buffer.index()).map(Vec::as_slice));
+        let positions: Vec<Point3> = reader
+            .read_positions()
+            .ok_or_else(|| Error::InvalidGeometry(String::from("glTF primitive has no positions")))?
+            .map(|[x, y, z]| Point3::new(x as Float, y as Float, z as Float))
+            .collect();
+        let indices: Vec<usize> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().map(|index| index as usize).collect(),
+            None => (0..positions.len()).collect(),
+        };
+        if let Some(index) = indices.iter().find(|index| **index >= positions.len()) {
+            return Err(Error::InvalidGeometry(format!(
+                "glTF primitive refers to vertex {} of {}",
+                index,
+                positions.len()
+            )));
+        }
+
+        // Strips and fans as the specification orders them, keeping the winding.
+        let triangles = match primitive.mode() {
+            Mode::TriangleStrip => (0..indices.len().saturating_sub(2))
+                .map(|i| [indices[i], indices[i + 1 + i % 2], indices[i + 2 - i % 2]])
+                .collect(),
+            Mode::TriangleFan => (0..indices.len().saturating_sub(2))
+                .map(|i| [indices[i + 1], indices[i + 2], indices[0]])
+                .collect(),
+            _ => indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+        };
+
+        let geometry = Geometry {
+            positions,
+            triangles,
+            normals: reader.read_normals().map(|normals| {
+                normals
+                    .map(|[x, y, z]| Normal3::new(x as Float, y as Float, z as Float))
+                    .collect()
+            }),
+            uvs: reader.read_tex_coords(0).map(|uvs| {
+                uvs.into_f32()
+                    .map(|[u, v]| [u as Float, v as Float])
+                    .collect()
+            }),
+        };
+        match self.subdivision(mesh)? {
+            Some(subdivision) => subdivided(geometry, subdivision),
+            None => Ok(geometry),
+        }
+    }
+
+    // {"subdivision": levels} in the extras of the mesh, or {"subdivision": {"levels": levels,
+    // "creases": [[start, end, sharpness], ...], "displacement": {"texture": index, "scale":
+    // distance, "vector": false}}} with everything optional. Creases go between vertices of the
+    // primitive, and the displacement texture is read as in `Displacement`.
+    fn subdivision(&mut self, mesh: &gltf::Mesh) -> Result<Option<Subdivision>, Error> {
+        let invalid = |message: String| {
+            Error::InvalidGeometry(format!(
+                "subdivision of glTF mesh {} {}",
+                mesh.index(),
+                message
+            ))
+        };
+        let Some(extras) = mesh.extras() else {
+            return Ok(None);
+        };
+        let extras: serde_json::Value = serde_json::from_str(extras.get())
+            .map_err(|error| invalid(format!("has unreadable extras: {}", error)))?;
+        let Some(subdivision) = extras.get("subdivision") else {
+            return Ok(None);
+        };
+        let levels = if subdivision.is_object() {
+            subdivision.get("levels")
+        } else {
+            Some(subdivision)
+        };
+        let levels = match levels {
+            None => 0,
+            Some(levels) => levels
+                .as_u64()
+                .filter(|levels| *levels <= MAX_SUBDIVISION_LEVELS as u64)
+                .ok_or_else(|| {
+                    invalid(format!(
+                        "must ask for a whole number of levels up to {}",
+                        MAX_SUBDIVISION_LEVELS
+                    ))
+                })? as u32,
+        };
+
+        let mut creases = Vec::new();
+        if let Some(list) = subdivision.get("creases") {
+            let invalid_crease =
+                || invalid(String::from("needs creases as [start, end, sharpness]"));
+            for crease in list.as_array().ok_or_else(invalid_crease)? {
+                let crease = crease
+                    .as_array()
+                    .filter(|crease| crease.len() == 3)
+                    .and_then(|crease| {
+                        Some((
+                            crease[0].as_u64()? as usize,
+                            crease[1].as_u64()? as usize,
+                            crease[2].as_f64()? as Float,
+                        ))
+                    })
+                    .ok_or_else(invalid_crease)?;
+                creases.push(crease);
+            }
+        }
+
+        let displacement = match subdivision.get("displacement") {
+            None => None,
+            Some(displacement) => {
+                let texture = displacement
+                    .get("texture")
+                    .and_then(serde_json::Value::as_u64)
+                    .and_then(|index| self.document.textures().nth(index as usize))
+                    .ok_or_else(|| {
+                        invalid(String::from("needs the index of a displacement texture"))
+                    })?;
+                let scale = displacement
+                    .get("scale")
+                    .map_or(Some(1.0), serde_json::Value::as_f64)
+                    .ok_or_else(|| invalid(String::from("needs a number to scale displacement")))?
+                    as Float;
+                let vector = displacement
+                    .get("vector")
+                    .map_or(Some(false), serde_json::Value::as_bool)
+                    .ok_or_else(|| invalid(String::from("needs vector displacement as a bool")))?;
+                let image = Image::clone(&*self.texture(&texture.source(), false)?);
+                Some(if vector {
+                    Displacement::Vector { image, scale }
+                } else {
+                    Displacement::Scalar { image, scale }
+                })
+            }
+        };
+
+        Ok(Some(Subdivision {
+            levels,
+            creases,
+            displacement,
+        }))
+    }
+
+    // Only the first set of texture coordinates is read, every texture uses it.
+    fn material(&mut self, material: &gltf::Material) -> Result<Rc<dyn Material>, Error> {
+        if let Some(shared) = self.materials.get(&material.index()) {
+            return Ok(Rc::clone(shared));
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _alpha] = pbr.base_color_factor();
+        let mut principled = PrincipledMaterial::new(
+            Color::new(r, g, b),
+            pbr.metallic_factor() as Float,
+            pbr.roughness_factor() as Float,
+        )?;
+        if let Some(info) = pbr.base_color_texture() {
+            principled =
+                principled.with_base_color_texture(self.texture(&info.texture().source(), true)?);
+        }
+        if let Some(info) = pbr.metallic_roughness_texture() {
+            principled = principled
+                .with_metallic_roughness_texture(self.texture(&info.texture().source(), false)?);
+        }
+
+        let shared: Rc<dyn Material> = Rc::new(principled);
+        self.materials.insert(material.index(), Rc::clone(&shared));
+        Ok(shared)
+    }
+
+    fn emission(&mut self, material: &gltf::Material) -> Result<Color, Error> {
+        let [r, g, b] = material.emissive_factor();
+        let mut emission = material.emissive_strength().unwrap_or(1.0) * Color::new(r, g, b);
+        if emission.is_black() {
+            return Ok(emission);
+        }
+        // Averaged, emissive triangles are lit evenly.
+        if let Some(info) = material.emissive_texture() {
+            let texture = self.texture(&info.texture().source(), true)?;
+            let mut total = Color::BLACK;
+            for pixel in &texture.pixels {
+                total += *pixel;
+            }
+            emission *= total / texture.pixels.len() as f32;
+        }
+        Ok(emission)
+    }
+
+    // Color textures are sRGB encoded, the others hold linear values. There's no JPEG decoder,
+    // files with JPEG images are rejected.
+    fn texture(&mut self, image: &gltf::Image, srgb: bool) -> Result<Rc<Image>, Error> {
+        if let Some(texture) = self.textures.get(&(image.index(), srgb)) {
+            return Ok(Rc::clone(texture));
+        }
+        if is_jpeg(image) {
+            return Err(Error::InvalidMaterial(format!(
+                "image {} of the glTF file is a JPEG, only PNG textures can be decoded",
+                image.index()
+            )));
+        }
+
+        let mut texture = match image.source() {
+            gltf::image::Source::View { view, .. } => {
+                let bytes = view
+                    .offset()
+                    .checked_add(view.length())
+                    .and_then(|end| {
+                        self.buffers
+                            .get(view.buffer().index())?
+                            .get(view.offset()..end)
+                    })
+                    .ok_or_else(|| {
+                        invalid_data(
+                            format!("can't read image {} of the glTF file", image.index()),
+                            format!("buffer view {} is out of its buffer", view.index()),
+                        )
+                    })?;
+                Image::decode(bytes, &format!("image {} of the glTF file", image.index()))?
+            }
+            gltf::image::Source::Uri { uri, .. } if uri.starts_with("data:") => Image::decode(
+                &read_uri(self.directory, uri)?,
+                &format!("image {} of the glTF file", image.index()),
+            )?,
+            gltf::image::Source::Uri { uri, .. } => {
+                Image::load(&self.directory.join(percent_decode(uri)).to_string_lossy())?
+            }
+        };
+        if srgb {
+            for pixel in texture.pixels.iter_mut() {
+                *pixel = pixel.from_srgb_encoded();
+            }
+        }
+
+        let texture = Rc::new(texture);
+        self.textures
+            .insert((image.index(), srgb), Rc::clone(&texture));
+        Ok(texture)
+    }
+
+    // glTF cameras look down their -z axis with y up, and a pinhole.
+    fn camera(
+        &self,
+        camera: &gltf::Camera,
+        transform: &Transform,
+    ) -> Result<Box<dyn Camera>, Error> {
+        let origin = transform.transform_point(&Point3::ORIGIN);
+        let target = origin + transform.transform_vector(&Vec3::new(0.0, 0.0, -1.0));
+        let up = transform.transform_vector(&Vec3::new(0.0, 1.0, 0.0));
+
+        Ok(match camera.projection() {
+            Projection::Perspective(perspective) => Box::new(PerspectiveCamera::new(
+                &origin,
+                &target,
+                &up,
+                self.aspect_ratio,
+                (perspective.yfov() as Float).to_degrees(),
+                0.0,
+                1.0,
+            )?),
+            Projection::Orthographic(orthographic) => Box::new(OrthographicCamera::new(
+                &origin,
+                &target,
+                &up,
+                self.aspect_ratio,
+                2.0 * orthographic.ymag() as Float,
+            )?),
+        })
+    }
+}
+
+// Intensities are in candela and the directional light's in lux, like punctual lights.
+fn light_from(
+    light: &gltf::khr_lights_punctual::Light,
+    transform: &Transform,
+) -> Result<PunctualLight, Error> {
+    let [r, g, b] = light.color();
+    let power = light.intensity() * Color::new(r, g, b);
+    let position = transform.transform_point(&Point3::ORIGIN);
+    let direction = transform.transform_vector(&Vec3::new(0.0, 0.0, -1.0));
+
+    match light.kind() {
+        Kind::Point => PunctualLight::point(position, power),
+        Kind::Spot {
+            inner_cone_angle,
+            outer_cone_angle,
+        } => PunctualLight::spot(
+            position,
+            position + direction,
+            power,
+            (outer_cone_angle as Float).to_degrees(),
+            (inner_cone_angle as Float).to_degrees(),
+        ),
+        Kind::Directional => PunctualLight::directional(direction, power),
+    }
+}
+
+// The triangles as a Catmull-Clark cage, refined, creased and displaced as asked. glTF splits
+// vertices wherever an attribute changes, they are welded back by position so the surface
+// stays closed across seams, with the texture coordinates of the first one. The normals of the
+// cage don't fit the refined surface, it gets its own.
+fn subdivided(geometry: Geometry, subdivision: Subdivision) -> Result<Geometry, Error> {
+    if let Some(uvs) = &geometry.uvs {
+        if uvs.len() != geometry.positions.len() {
+            return Err(Error::InvalidGeometry(format!(
+                "glTF primitive of {} positions has {} uvs",
+                geometry.positions.len(),
+                uvs.len()
+            )));
+        }
+    }
+
+    let (vertices, firsts) = weld(&geometry.positions);
+    let positions = firsts
+        .iter()
+        .map(|first| geometry.positions[*first])
+        .collect();
+    // Triangles that welding collapsed have no edges to share.
+    let faces: Vec<Vec<usize>> = geometry
+        .triangles
+        .iter()
+        .map(|triangle| triangle.map(|index| vertices[index]))
+        .filter(|[a, b, c]| a != b && b != c && c != a)
+        .map(Vec::from)
+        .collect();
+
+    let mut surface = SubdivisionSurface::new(positions, faces)?;
+    if let Some(uvs) = &geometry.uvs {
+        surface = surface.with_uvs(firsts.iter().map(|first| uvs[*first]).collect())?;
+    }
+    for (start, end, sharpness) in subdivision.creases {
+        let welded = |vertex: usize| {
+            vertices.get(vertex).copied().ok_or_else(|| {
+                Error::InvalidGeometry(format!(
+                    "glTF crease refers to vertex {} of {}",
+                    vertex,
+                    vertices.len()
+                ))
+            })
+        };
+        surface = surface.with_crease(welded(start)?, welded(end)?, sharpness)?;
+    }
+    let mut surface = surface.refine(subdivision.levels);
+    if let Some(displacement) = &subdivision.displacement {
+        surface = surface.displace(displacement)?;
+    }
+
+    Ok(Geometry {
+        triangles: surface.triangle_indices(),
+        normals: Some(surface.vertex_normals()),
+        uvs: (!surface.uvs.is_empty()).then_some(surface.uvs),
+        positions: surface.positions,
+    })
+}
+
+// Welded vertex of every position, and the first position of every welded vertex. Positions
+// within the tolerance are found in the cells of a grid around them, so -0 and 0 or rounding
+// noise from the exporter don't split them.
+fn weld(positions: &[Point3]) -> (Vec<usize>, Vec<usize>) {
+    let bounds = positions
+        .iter()
+        .fold(Aabb::empty(), |bounds, position| bounds.include(position));
+    let tolerance = (WELD_TOLERANCE * bounds.diagonal().length()).max(Float::MIN_POSITIVE);
+    let cell = |position: &Point3| {
+        position
+            .data
+            .map(|value| (value / tolerance).floor() as i64)
+    };
+
+    let mut grid: HashMap<[i64; 3], Vec<usize>> = HashMap::new();
+    let mut firsts: Vec<usize> = Vec::new();
+    let vertices = positions
+        .iter()
+        .enumerate()
+        .map(|(index, position)| {
+            let [x, y, z] = cell(position);
+            let neighbours = (-1..=1).flat_map(|dx| {
+                (-1..=1).flat_map(move |dy| (-1..=1).map(move |dz| [x + dx, y + dy, z + dz]))
+            });
+            for neighbour in neighbours {
+                let found = grid.get(&neighbour).and_then(|welded| {
+                    welded
+                        .iter()
+                        .copied()
+                        .find(|vertex| positions[firsts[*vertex]].distance(position) <= tolerance)
+                });
+                if let Some(vertex) = found {
+                    return vertex;
+                }
+            }
+            firsts.push(index);
+            grid.entry([x, y, z]).or_default().push(firsts.len() - 1);
+            firsts.len() - 1
+        })
+        .collect();
+    (vertices, firsts)
+}
+
+fn is_jpeg(image: &gltf::Image) -> bool {
+    match image.source() {
+        gltf::image::Source::View { mime_type, .. } => mime_type == "image/jpeg",
+        gltf::image::Source::Uri { uri, mime_type } => {
+            let uri = uri.to_ascii_lowercase();
+            mime_type == Some("image/jpeg")
+                || uri.starts_with("data:image/jpeg")
+                || uri.ends_with(".jpg")
+                || uri.ends_with(".jpeg")
+        }
+    }
+}
+
+// glTF matrices are stored column by column.
+fn row_major(columns: &[[f32; 4]; 4]) -> Matrix4 {
+    let mut matrix: Matrix4 = [[0.0; 4]; 4];
+    for (row, matrix_row) in matrix.iter_mut().enumerate() {
+        for (column, value) in matrix_row.iter_mut().enumerate() {
+            *value = columns[column][row] as Float;
+        }
+    }
+    matrix
+}
+
+// Base64 data URIs, or files relative to the glTF file.
+fn read_uri(directory: &Path, uri: &str) -> Result<Vec<u8>, Error> {
+    if let Some(data) = uri.strip_prefix("data:") {
+        let decoded = data
+            .split_once(";base64,")
+            .and_then(|(_media_type, encoded)| decode_base64(encoded));
+        return decoded.ok_or_else(|| {
+            invalid_data(
+                String::from("can't read a glTF data URI"),
+                String::from("only base64 data is supported"),
+            )
+        });
+    }
+
+    let path = directory.join(percent_decode(uri));
+    fs::read(&path).map_err(|error| Error::io(format!("can't read {}", path.display()), error))
+}
+
+fn decode_base64(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 3 / 4);
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    for character in encoded
+        .bytes()
+        .filter(|character| !character.is_ascii_whitespace())
+    {
+        let value = match character {
+            b'A'..=b'Z' => character - b'A',
+            b'a'..=b'z' => character - b'a' + 26,
+            b'0'..=b'9' => character - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            b'=' => break,
+            _ => return None,
+        };
+        bits = (bits << 6) | u32::from(value);
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            bytes.push((bits >> bit_count) as u8);
+            bits &= (1 << bit_count) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+// URIs escape characters like spaces in file names.
+fn percent_decode(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = (bytes[index] == b'%')
+            .then(|| uri.get(index + 1..index + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn invalid_data(context: String, message: String) -> Error {
+    Error::io(
+        context,
+        std::io::Error::new(std::io::ErrorKind::InvalidData, message),
+    )
+}
@@ -1,25 +1,58 @@
 use crate::{
+    float::Float,
+    hittables::hittable::Primitive,
     materials::material::Material,
-    ray::Ray,
-    vector::{dot, Vector},
+    ray::{offset_ray_origin, Ray},
+    vector::{dot, Normal3, Point3, Vec3},
 };
 
+#[derive(Copy, Clone)]
 pub struct HitRecord<'a> {
-    pub origin: Vector,
+    pub origin: Point3,
+    // Bounds the rounding error of `origin` on every axis.
+    pub error: Vec3,
     // It's always against the cast ray.
-    pub normal: Vector,
-    pub t: f32,
+    pub normal: Normal3,
+    // Normal of the surface itself, also against the ray, where `normal` is interpolated for
+    // shading. Rays leave along it, so they don't start under the surface.
+    pub geometric_normal: Normal3,
+    pub t: Float,
     pub is_front_face: bool,
     pub material: &'a dyn Material,
+    // Surface coordinates in [0, 1]: longitude and latitude on spheres, the barycentrics of the
+    // second and third vertex on triangles, along and across the fiber on curves.
+    pub uv: [Float; 2],
+    // Weights of the three vertices, only on triangles.
+    pub barycentrics: Option<[Float; 3]>,
+    // Unit direction along the fiber, only on curves.
+    pub tangent: Option<Vec3>,
+    // Sphere or triangle that was hit, so lights can be told apart.
+    pub primitive: Primitive<'a>,
 }
 
-pub fn get_face_and_normal_against_ray(ray: &Ray, outward_normal: Vector) -> (bool, Vector) {
-    let is_front_face = dot(&(*ray).direction, &outward_normal) < 0.0;
+impl HitRecord<'_> {
+    // Ray leaving the hit point, safe from hitting the surface it starts on.
+    pub fn spawn_ray(&self, direction: Vec3, time: Float) -> Ray {
+        Ray {
+            origin: offset_ray_origin(
+                &self.origin,
+                &self.error,
+                &self.geometric_normal,
+                &direction,
+            ),
+            direction,
+            time,
+        }
+    }
+}
+
+pub fn get_face_and_normal_against_ray(ray: &Ray, outward_normal: Normal3) -> (bool, Normal3) {
+    let is_front_face = dot(&ray.direction, &outward_normal) < 0.0;
     let normal = if is_front_face {
         outward_normal
     } else {
         -outward_normal
     };
 
-    return (is_front_face, normal);
+    (is_front_face, normal)
 }
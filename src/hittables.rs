@@ -1,3 +1,17 @@
-pub mod sphere;
+pub mod aabb;
+pub mod bvh;
+pub mod csg;
+pub mod cuboid;
+pub mod curve;
+pub mod cylinder;
+pub mod heightfield;
 pub mod hittable;
 pub mod hittable_list;
+pub mod index_bvh;
+pub mod instance;
+pub mod mesh;
+pub mod packet;
+pub mod sdf;
+pub mod sphere;
+pub mod subdivision;
+pub mod triangle;
@@ -0,0 +1,128 @@
+use crate::float::Float;
+use crate::vector::{Point3, Vec3};
+
+// Axis aligned bounding box.
+#[derive(Copy, Clone, Debug)]
+pub struct Aabb {
+    pub min: Point3,
+    pub max: Point3,
+}
+
+impl Aabb {
+    pub fn new(min: Point3, max: Point3) -> Aabb {
+        Aabb { min, max }
+    }
+
+    // Contains nothing, any union with it gives the other box.
+    pub fn empty() -> Aabb {
+        Aabb {
+            min: Point3::new(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+            max: Point3::new(
+                Float::NEG_INFINITY,
+                Float::NEG_INFINITY,
+                Float::NEG_INFINITY,
+            ),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: Point3::new(
+                self.min.x().min(other.min.x()),
+                self.min.y().min(other.min.y()),
+                self.min.z().min(other.min.z()),
+            ),
+            max: Point3::new(
+                self.max.x().max(other.max.x()),
+                self.max.y().max(other.max.y()),
+                self.max.z().max(other.max.z()),
+            ),
+        }
+    }
+
+    // Overlap of the boxes, squeezed flat between them where they don't overlap.
+    pub fn intersection(&self, other: &Aabb) -> Aabb {
+        let min = Point3::new(
+            self.min.x().max(other.min.x()),
+            self.min.y().max(other.min.y()),
+            self.min.z().max(other.min.z()),
+        );
+        let max = Point3::new(
+            self.max.x().min(other.max.x()).max(min.x()),
+            self.max.y().min(other.max.y()).max(min.y()),
+            self.max.z().min(other.max.z()).max(min.z()),
+        );
+        Aabb { min, max }
+    }
+
+    pub fn include(&self, point: &Point3) -> Aabb {
+        self.union(&Aabb::new(*point, *point))
+    }
+
+    pub fn centroid(&self) -> Point3 {
+        self.min.lerp(&self.max, 0.5)
+    }
+
+    pub fn diagonal(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> Float {
+        let diagonal = self.diagonal();
+        2.0 * (diagonal.x() * diagonal.y()
+            + diagonal.y() * diagonal.z()
+            + diagonal.z() * diagonal.x())
+    }
+
+    pub fn longest_axis(&self) -> usize {
+        let diagonal = self.diagonal();
+        if diagonal.x() >= diagonal.y() && diagonal.x() >= diagonal.z() {
+            0
+        } else if diagonal.y() >= diagonal.z() {
+            1
+        } else {
+            2
+        }
+    }
+
+    // Slab test, `inverse_direction` is 1 / direction per axis.
+    pub fn hit(
+        &self,
+        origin: &Point3,
+        inverse_direction: &Vec3,
+        t_min: Float,
+        t_max: Float,
+    ) -> bool {
+        self.hit_range(origin, inverse_direction, t_min, t_max)
+            .is_some()
+    }
+
+    // Part of [t_min, t_max] the ray spends inside the box.
+    pub fn hit_range(
+        &self,
+        origin: &Point3,
+        inverse_direction: &Vec3,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<(Float, Float)> {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for axis in 0..3 {
+            let t0 = (self.min.data[axis] - origin.data[axis]) * inverse_direction.data[axis];
+            let t1 = (self.max.data[axis] - origin.data[axis]) * inverse_direction.data[axis];
+            // A ray parallel to the slab that starts on one of its planes gives 0 * inf, NaN.
+            // It's within the slab, which leaves the interval as it is.
+            if t0.is_nan() || t1.is_nan() {
+                continue;
+            }
+            let (t_near, t_far) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+
+            t_min = if t_near > t_min { t_near } else { t_min };
+            t_max = if t_far < t_max { t_far } else { t_max };
+            if t_max < t_min {
+                return None;
+            }
+        }
+        Some((t_min, t_max))
+    }
+}
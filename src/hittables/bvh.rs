@@ -0,0 +1,404 @@
+use std::str::FromStr;
+
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Primitive},
+    packet::{PacketHit, RayLanes, SpherePacket, TrianglePacket},
+    sphere::Sphere,
+    triangle::Triangle,
+};
+use crate::{
+    error::Error,
+    float::Float,
+    hit_record::HitRecord,
+    ray::Ray,
+    simd::{FloatX1, FloatX4, FloatX8, Lanes},
+    vector::{Point3, Vec3},
+};
+
+// How many primitives of a leaf are intersected at once.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum PacketWidth {
+    Scalar,
+    Four,
+    Eight,
+}
+
+impl FromStr for PacketWidth {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "1" => Ok(PacketWidth::Scalar),
+            "4" => Ok(PacketWidth::Four),
+            "8" => Ok(PacketWidth::Eight),
+            _ => Err(Error::InvalidSettings(format!(
+                "packet width {} must be 1, 4 or 8",
+                value
+            ))),
+        }
+    }
+}
+
+// Bins of the surface area heuristic along the split axis.
+const SAH_BINS: usize = 12;
+const MAX_DEPTH: usize = 64;
+
+// Bounding volume hierarchy over borrowed hittables. Leaves hold up to one packet of spheres
+// and one of triangles, other hittables are tested one by one.
+pub struct Bvh<'a, L: Lanes> {
+    hittables: &'a [Box<dyn Hittable>],
+    // Depth first order, the first child of an interior node follows it.
+    nodes: Vec<Node>,
+    sphere_packets: Vec<SpherePacket<L>>,
+    triangle_packets: Vec<TrianglePacket<L>>,
+    others: Vec<usize>,
+    // Without a bounding box, tested for every ray.
+    unbounded: Vec<usize>,
+}
+
+struct Node {
+    bounds: Aabb,
+    content: NodeContent,
+}
+
+enum NodeContent {
+    Interior {
+        second_child: usize,
+        axis: usize,
+    },
+    Leaf {
+        spheres: (usize, usize),
+        triangles: (usize, usize),
+        others: (usize, usize),
+    },
+}
+
+#[derive(Copy, Clone)]
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Point3,
+}
+
+impl<'a, L: Lanes> Bvh<'a, L> {
+    // Bounds cover the motion of the hittables between the two times.
+    pub fn new(hittables: &'a [Box<dyn Hittable>], time_start: Float, time_end: Float) -> Self {
+        let mut items: Vec<BuildItem> = Vec::with_capacity(hittables.len());
+        let mut unbounded: Vec<usize> = Vec::new();
+        for (index, hittable) in hittables.iter().enumerate() {
+            match hittable.bounding_box(time_start, time_end) {
+                Some(bounds) => items.push(BuildItem {
+                    index,
+                    bounds,
+                    centroid: bounds.centroid(),
+                }),
+                None => unbounded.push(index),
+            }
+        }
+
+        let mut bvh = Bvh {
+            hittables,
+            nodes: Vec::new(),
+            sphere_packets: Vec::new(),
+            triangle_packets: Vec::new(),
+            others: Vec::new(),
+            unbounded,
+        };
+        if !items.is_empty() {
+            bvh.build(&mut items, 0);
+        }
+        bvh
+    }
+
+    // A leaf fills one packet, the scalar version still groups a few primitives per leaf.
+    fn leaf_size() -> usize {
+        L::WIDTH.max(4)
+    }
+
+    fn build(&mut self, items: &mut [BuildItem], depth: usize) -> usize {
+        let bounds = items
+            .iter()
+            .fold(Aabb::empty(), |bounds, item| bounds.union(&item.bounds));
+        let node_index = self.nodes.len();
+
+        if items.len() <= Self::leaf_size() || depth >= MAX_DEPTH {
+            self.push_leaf(bounds, items);
+            return node_index;
+        }
+
+        let centroid_bounds = items
+            .iter()
+            .fold(Aabb::empty(), |bounds, item| bounds.include(&item.centroid));
+        let axis = centroid_bounds.longest_axis();
+
+        let split = match sah_split(items, &centroid_bounds, axis) {
+            Some(split) => split,
+            // All centroids coincide, any split is as good as another.
+            None => items.len() / 2,
+        };
+        if split == 0 || split == items.len() {
+            self.push_leaf(bounds, items);
+            return node_index;
+        }
+
+        self.nodes.push(Node {
+            bounds,
+            content: NodeContent::Interior {
+                second_child: 0,
+                axis,
+            },
+        });
+        let (first, second) = items.split_at_mut(split);
+        self.build(first, depth + 1);
+        let second_index = self.build(second, depth + 1);
+        self.nodes[node_index].content = NodeContent::Interior {
+            second_child: second_index,
+            axis,
+        };
+
+        node_index
+    }
+
+    // Oversized leaves, from degenerate splits, get several packets.
+    fn push_leaf(&mut self, bounds: Aabb, items: &[BuildItem]) {
+        let mut spheres: Vec<(usize, &Sphere)> = Vec::new();
+        let mut triangles: Vec<(usize, &Triangle)> = Vec::new();
+        let others_start = self.others.len();
+        for item in items {
+            match self.hittables[item.index].primitive() {
+                Primitive::Sphere(sphere) => spheres.push((item.index, sphere)),
+                Primitive::Triangle(triangle) => triangles.push((item.index, triangle)),
+                Primitive::Other => self.others.push(item.index),
+            }
+        }
+
+        let spheres_start = self.sphere_packets.len();
+        self.sphere_packets
+            .extend(spheres.chunks(L::WIDTH).map(SpherePacket::new));
+        let triangles_start = self.triangle_packets.len();
+        self.triangle_packets
+            .extend(triangles.chunks(L::WIDTH).map(TrianglePacket::new));
+
+        self.nodes.push(Node {
+            bounds,
+            content: NodeContent::Leaf {
+                spheres: (spheres_start, self.sphere_packets.len()),
+                triangles: (triangles_start, self.triangle_packets.len()),
+                others: (others_start, self.others.len()),
+            },
+        });
+    }
+}
+
+// Partitions the items along `axis` at the bin border with the lowest surface area
+// heuristic cost, returns the count of items on the first side.
+fn sah_split(items: &mut [BuildItem], centroid_bounds: &Aabb, axis: usize) -> Option<usize> {
+    let start = centroid_bounds.min.data[axis];
+    let extent = centroid_bounds.max.data[axis] - start;
+    if extent <= 0.0 {
+        return None;
+    }
+
+    let bin_of = |item: &BuildItem| {
+        (((item.centroid.data[axis] - start) / extent * SAH_BINS as Float) as usize)
+            .min(SAH_BINS - 1)
+    };
+
+    let mut bin_bounds = [Aabb::empty(); SAH_BINS];
+    let mut bin_counts = [0_usize; SAH_BINS];
+    for item in items.iter() {
+        let bin = bin_of(item);
+        bin_bounds[bin] = bin_bounds[bin].union(&item.bounds);
+        bin_counts[bin] += 1;
+    }
+
+    let mut best: Option<(Float, usize)> = None;
+    for border in 1..SAH_BINS {
+        let side = |bins: std::ops::Range<usize>| {
+            bins.fold((Aabb::empty(), 0), |(bounds, count), bin| {
+                (bounds.union(&bin_bounds[bin]), count + bin_counts[bin])
+            })
+        };
+        let (first_bounds, first_count) = side(0..border);
+        let (second_bounds, second_count) = side(border..SAH_BINS);
+        if first_count == 0 || second_count == 0 {
+            continue;
+        }
+
+        let cost = first_bounds.surface_area() * first_count as Float
+            + second_bounds.surface_area() * second_count as Float;
+        if best.is_none_or(|(best_cost, _)| cost < best_cost) {
+            best = Some((cost, border));
+        }
+    }
+
+    let (_, border) = best?;
+    let mut split = 0;
+    for index in 0..items.len() {
+        if bin_of(&items[index]) < border {
+            items.swap(index, split);
+            split += 1;
+        }
+    }
+    Some(split)
+}
+
+impl<L: Lanes> Bvh<'_, L> {
+    // Record of the closest hit, from the scalar test that settled it. `cost` counts the
+    // boxes, packets and primitives tested.
+    fn closest(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        cost: &mut u32,
+    ) -> Option<HitRecord<'_>> {
+        let mut closest: Option<HitRecord<'_>> = None;
+        let mut t_closest = t_max;
+        let test =
+            |index: usize, t_closest: &mut Float, closest: &mut Option<_>, cost: &mut u32| {
+                *cost += 1;
+                match self.hittables[index].hit(ray, t_min, *t_closest) {
+                    Some(hit) => {
+                        *t_closest = hit.t;
+                        *closest = Some(hit);
+                        true
+                    }
+                    None => false,
+                }
+            };
+
+        for index in self.unbounded.iter() {
+            test(*index, &mut t_closest, &mut closest, cost);
+        }
+
+        if self.nodes.is_empty() {
+            return closest;
+        }
+
+        let ray_lanes = RayLanes::<L>::new(ray);
+        let inverse_direction = Vec3 {
+            data: ray.direction.data.map(|value| 1.0 / value),
+        };
+
+        let mut stack = [0_usize; MAX_DEPTH + 1];
+        let mut stack_size = 1;
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node = &self.nodes[stack[stack_size]];
+            *cost += 1;
+            if !node
+                .bounds
+                .hit(&ray.origin, &inverse_direction, t_min, t_closest)
+            {
+                continue;
+            }
+
+            match node.content {
+                NodeContent::Interior { second_child, axis } => {
+                    let first_child = stack[stack_size] + 1;
+                    // Visit the near child first, its hits shrink the far child's range.
+                    let (near, far) = if ray.direction.data[axis] < 0.0 {
+                        (second_child, first_child)
+                    } else {
+                        (first_child, second_child)
+                    };
+                    stack[stack_size] = far;
+                    stack[stack_size + 1] = near;
+                    stack_size += 2;
+                }
+                NodeContent::Leaf {
+                    spheres,
+                    triangles,
+                    others,
+                } => {
+                    // The packets use the error bounds of the scalar tests, yet round
+                    // differently. The scalar test settles the lanes a packet is unsure about
+                    // and confirms its closest lane, which gives the hit record. Should it
+                    // refuse, the rest of the packet gets scalar tests too.
+                    let confirm = |hit: Option<PacketHit>,
+                                   indices: &[usize],
+                                   t_closest: &mut Float,
+                                   closest: &mut Option<_>,
+                                   cost: &mut u32| {
+                        let Some(hit) = hit else {
+                            return;
+                        };
+                        for (lane, index) in indices.iter().enumerate() {
+                            if hit.uncertain >> lane & 1 != 0 {
+                                test(*index, t_closest, closest, cost);
+                            }
+                        }
+                        let Some((t, winner)) = hit.closest else {
+                            return;
+                        };
+                        if t <= *t_closest && !test(winner, t_closest, closest, cost) {
+                            for index in indices.iter().filter(|index| **index != winner) {
+                                test(*index, t_closest, closest, cost);
+                            }
+                        }
+                    };
+                    let sphere_packets = self.sphere_packets[spheres.0..spheres.1].iter();
+                    for packet in sphere_packets {
+                        *cost += 1;
+                        let hit = packet.hit(&ray_lanes, t_min, t_closest);
+                        confirm(hit, packet.indices(), &mut t_closest, &mut closest, cost);
+                    }
+                    let triangle_packets = self.triangle_packets[triangles.0..triangles.1].iter();
+                    for packet in triangle_packets {
+                        *cost += 1;
+                        let hit = packet.hit(&ray_lanes, t_min, t_closest);
+                        confirm(hit, packet.indices(), &mut t_closest, &mut closest, cost);
+                    }
+                    for index in self.others[others.0..others.1].iter() {
+                        test(*index, &mut t_closest, &mut closest, cost);
+                    }
+                }
+            }
+        }
+
+        closest
+    }
+}
+
+impl<L: Lanes> Hittable for Bvh<'_, L> {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        self.closest(ray, t_min, t_max, &mut 0)
+    }
+
+    fn traversal_cost(&self, ray: &Ray, t_min: Float, t_max: Float) -> u32 {
+        let mut cost = 0;
+        self.closest(ray, t_min, t_max, &mut cost);
+        cost
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        if !self.unbounded.is_empty() {
+            return None;
+        }
+        self.hittables
+            .iter()
+            .try_fold(Aabb::empty(), |bounds, hittable| {
+                Some(bounds.union(&hittable.bounding_box(time_start, time_end)?))
+            })
+    }
+
+    fn emits(&self) -> bool {
+        self.hittables.iter().any(|hittable| hittable.emits())
+    }
+}
+
+// Picks the packet type for the width at runtime.
+pub fn build_bvh<'a>(
+    hittables: &'a [Box<dyn Hittable>],
+    packet_width: PacketWidth,
+    time_start: Float,
+    time_end: Float,
+) -> Box<dyn Hittable + 'a> {
+    match packet_width {
+        PacketWidth::Scalar => Box::new(Bvh::<FloatX1>::new(hittables, time_start, time_end)),
+        PacketWidth::Four => Box::new(Bvh::<FloatX4>::new(hittables, time_start, time_end)),
+        PacketWidth::Eight => Box::new(Bvh::<FloatX8>::new(hittables, time_start, time_end)),
+    }
+}
@@ -0,0 +1,150 @@
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Span},
+};
+use crate::{error::Error, float::Float, hit_record::HitRecord, ray::Ray};
+
+// How a CSG node combines the volumes of its two children.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CsgOperation {
+    // Inside either of them.
+    Union,
+    // Inside both.
+    Intersection,
+    // Inside the first and not inside the second, which carves it away.
+    Difference,
+}
+
+impl CsgOperation {
+    fn contains(&self, in_first: bool, in_second: bool) -> bool {
+        match self {
+            CsgOperation::Union => in_first || in_second,
+            CsgOperation::Intersection => in_first && in_second,
+            CsgOperation::Difference => in_first && !in_second,
+        }
+    }
+}
+
+// Constructive solid geometry: the volume of two closed hittables combined, itself closed so
+// trees of them nest. Surfaces keep the material of the child they come from, a surface the
+// second child carves out of the first faces into the cavity.
+pub struct Csg {
+    pub operation: CsgOperation,
+    first: Box<dyn Hittable>,
+    second: Box<dyn Hittable>,
+}
+
+impl Csg {
+    pub fn new(
+        operation: CsgOperation,
+        first: Box<dyn Hittable>,
+        second: Box<dyn Hittable>,
+    ) -> Result<Csg, Error> {
+        if !first.is_closed() || !second.is_closed() {
+            return Err(Error::InvalidGeometry(String::from(
+                "CSG needs closed hittables like spheres, boxes, cylinders or other CSG nodes",
+            )));
+        }
+        // Lights are sampled over the whole shape, carved away parts included.
+        if first.emits() || second.emits() {
+            return Err(Error::InvalidMaterial(String::from(
+                "CSG can't combine emissive materials, make the lights separate hittables",
+            )));
+        }
+
+        Ok(Csg {
+            operation,
+            first,
+            second,
+        })
+    }
+
+    pub fn union(first: Box<dyn Hittable>, second: Box<dyn Hittable>) -> Result<Csg, Error> {
+        Csg::new(CsgOperation::Union, first, second)
+    }
+
+    pub fn intersection(first: Box<dyn Hittable>, second: Box<dyn Hittable>) -> Result<Csg, Error> {
+        Csg::new(CsgOperation::Intersection, first, second)
+    }
+
+    pub fn difference(first: Box<dyn Hittable>, second: Box<dyn Hittable>) -> Result<Csg, Error> {
+        Csg::new(CsgOperation::Difference, first, second)
+    }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        self.spans(ray)
+            .into_iter()
+            .flat_map(|span| [span.enter, span.exit])
+            .find(|boundary| boundary.t > t_min && boundary.t < t_max)
+    }
+
+    // The intersection can't reach beyond the overlap of its children, and the difference
+    // beyond its first child.
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        let first = self.first.bounding_box(time_start, time_end);
+        let second = self.second.bounding_box(time_start, time_end);
+        match self.operation {
+            CsgOperation::Union => Some(first?.union(&second?)),
+            CsgOperation::Intersection => match (first, second) {
+                (Some(first), Some(second)) => Some(first.intersection(&second)),
+                (first, second) => first.or(second),
+            },
+            CsgOperation::Difference => first,
+        }
+    }
+
+    fn traversal_cost(&self, ray: &Ray, t_min: Float, t_max: Float) -> u32 {
+        self.first.traversal_cost(ray, t_min, t_max) + self.second.traversal_cost(ray, t_min, t_max)
+    }
+
+    fn emits(&self) -> bool {
+        self.first.emits() || self.second.emits()
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    // Walks the boundaries of both children along the ray, the combined volume starts or ends
+    // wherever crossing one changes whether the operation holds.
+    fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        let mut boundaries: Vec<(HitRecord, usize, bool)> = Vec::new();
+        for (child, hittable) in [&self.first, &self.second].into_iter().enumerate() {
+            for span in hittable.spans(ray) {
+                boundaries.push((span.enter, child, true));
+                boundaries.push((span.exit, child, false));
+            }
+        }
+        boundaries.sort_by(|a, b| a.0.t.total_cmp(&b.0.t));
+
+        let mut inside = [false, false];
+        let mut combined_inside = false;
+        let mut entered: Option<HitRecord> = None;
+        let mut spans = Vec::new();
+        for (mut boundary, child, entering) in boundaries {
+            inside[child] = entering;
+            let now_inside = self.operation.contains(inside[0], inside[1]);
+            if now_inside == combined_inside {
+                continue;
+            }
+            combined_inside = now_inside;
+
+            // The normal already faces against the ray, only the side changes for carved
+            // surfaces.
+            boundary.is_front_face = now_inside;
+            if now_inside {
+                entered = Some(boundary);
+            } else if let Some(enter) = entered.take() {
+                if boundary.t > enter.t {
+                    spans.push(Span {
+                        enter,
+                        exit: boundary,
+                    });
+                }
+            }
+        }
+        spans
+    }
+}
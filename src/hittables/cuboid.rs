@@ -0,0 +1,156 @@
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Primitive, Span},
+};
+use crate::{
+    error::Error,
+    float::{gamma, Float},
+    hit_record::{get_face_and_normal_against_ray, HitRecord},
+    materials::material::Material,
+    ray::Ray,
+    vector::{Normal3, Point3, Vec3},
+};
+
+// Axis aligned box between two corners, private so `new` keeps them in order.
+pub struct Cuboid {
+    min: Point3,
+    max: Point3,
+    material: Box<dyn Material>,
+}
+
+impl Cuboid {
+    pub fn new(min: Point3, max: Point3, material: Box<dyn Material>) -> Result<Cuboid, Error> {
+        if !min.is_finite() || !max.is_finite() {
+            return Err(Error::InvalidGeometry(String::from(
+                "box corners must be finite",
+            )));
+        }
+        if (0..3).any(|axis| min.data[axis] >= max.data[axis]) {
+            return Err(Error::InvalidGeometry(String::from(
+                "box must have its minimum corner below its maximum on every axis",
+            )));
+        }
+
+        Ok(Cuboid { min, max, material })
+    }
+
+    pub fn min(&self) -> Point3 {
+        self.min
+    }
+
+    pub fn max(&self) -> Point3 {
+        self.max
+    }
+
+    pub fn material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    // Distances along the ray where it enters and leaves the box, with the axes of the faces
+    // it passes through.
+    fn slabs(&self, ray: &Ray) -> Option<((Float, usize), (Float, usize))> {
+        let mut enter = (Float::NEG_INFINITY, 0);
+        let mut exit = (Float::INFINITY, 0);
+        for axis in 0..3 {
+            let origin = ray.origin.data[axis];
+            let direction = ray.direction.data[axis];
+            if direction == 0.0 {
+                // Parallel to the slab, inside it everywhere or nowhere.
+                if origin < self.min.data[axis] || origin > self.max.data[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let t0 = (self.min.data[axis] - origin) / direction;
+            let t1 = (self.max.data[axis] - origin) / direction;
+            let (near, far) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+            if near > enter.0 {
+                enter = (near, axis);
+            }
+            if far < exit.0 {
+                exit = (far, axis);
+            }
+        }
+
+        if enter.0 > exit.0 || !enter.0.is_finite() || !exit.0.is_finite() {
+            return None;
+        }
+        Some((enter, exit))
+    }
+
+    // Hit on the face across `axis` at `t`, on the side the ray comes from when `entering`.
+    fn record_at(&self, ray: &Ray, t: Float, axis: usize, entering: bool) -> HitRecord<'_> {
+        let towards_max = (ray.direction.data[axis] > 0.0) != entering;
+        let mut point = ray.origin + t * ray.direction;
+        // The face coordinate is exact, the others carry the rounding of t.
+        point.data[axis] = if towards_max {
+            self.max.data[axis]
+        } else {
+            self.min.data[axis]
+        };
+        let mut error = gamma(5) * ((point - Point3::ORIGIN).abs() + (t * ray.direction).abs());
+        error.data[axis] = 0.0;
+
+        let mut outward = Vec3::default();
+        outward.data[axis] = if towards_max { 1.0 } else { -1.0 };
+        let (is_front_face, normal) = get_face_and_normal_against_ray(ray, Normal3::from(outward));
+
+        // The other two axes in order, across the face.
+        let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+        let across = |other: usize| {
+            ((point.data[other] - self.min.data[other])
+                / (self.max.data[other] - self.min.data[other]))
+                .clamp(0.0, 1.0)
+        };
+
+        HitRecord {
+            origin: point,
+            error,
+            normal,
+            geometric_normal: normal,
+            is_front_face,
+            t,
+            material: self.material.as_ref(),
+            uv: [across(u_axis), across(v_axis)],
+            barycentrics: None,
+            tangent: None,
+            primitive: Primitive::Other,
+        }
+    }
+}
+
+impl Hittable for Cuboid {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let ((enter, enter_axis), (exit, exit_axis)) = self.slabs(ray)?;
+        if enter > t_min && enter < t_max {
+            Some(self.record_at(ray, enter, enter_axis, true))
+        } else if exit > t_min && exit < t_max {
+            Some(self.record_at(ray, exit, exit_axis, false))
+        } else {
+            None
+        }
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<Aabb> {
+        Some(Aabb::new(self.min, self.max))
+    }
+
+    fn emits(&self) -> bool {
+        !self.material.emission().is_black()
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        match self.slabs(ray) {
+            Some(((enter, enter_axis), (exit, exit_axis))) => vec![Span {
+                enter: self.record_at(ray, enter, enter_axis, true),
+                exit: self.record_at(ray, exit, exit_axis, false),
+            }],
+            None => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,487 @@
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Primitive},
+    index_bvh::IndexBvh,
+};
+use crate::{
+    error::Error,
+    float::Float,
+    hit_record::{get_face_and_normal_against_ray, HitRecord},
+    materials::material::Material,
+    ray::Ray,
+    vector::{cross, dot, orthonormal_basis, Normal3, Point3, Vec3},
+};
+
+// Halvings of a segment before its pieces are tested as straight lines.
+const MAX_REFINEMENT: u32 = 10;
+
+// How the width of a curve turns into a surface.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum CurveShape {
+    // Ribbon always facing the ray, cheap for hair and fur seen from afar.
+    Flat,
+    // Flat ribbon shaded with the normals of a tube.
+    Cylinder,
+    // Ribbon turned by the normals of its strand, like blades of grass. Seen edge-on it
+    // vanishes.
+    Ribbon,
+}
+
+// Fiber of cubic Bézier segments, each one's last control point the next one's first.
+pub struct Strand {
+    pub points: Vec<Point3>,
+    // Width at each end of every segment.
+    pub widths: Vec<Float>,
+    // Facing of ribbons at each end of every segment, empty for the other shapes.
+    pub normals: Vec<Normal3>,
+}
+
+impl Strand {
+    pub fn new(points: Vec<Point3>, widths: Vec<Float>) -> Result<Strand, Error> {
+        if points.len() < 4 || !(points.len() - 1).is_multiple_of(3) {
+            return Err(Error::InvalidGeometry(format!(
+                "strand of cubic segments needs 3n + 1 control points, not {}",
+                points.len()
+            )));
+        }
+        if !points.iter().all(Point3::is_finite) {
+            return Err(Error::InvalidGeometry(String::from(
+                "strand control points must be finite",
+            )));
+        }
+        let segments = (points.len() - 1) / 3;
+        if widths.len() != segments + 1 {
+            return Err(Error::InvalidGeometry(format!(
+                "strand of {} segments needs {} widths, not {}",
+                segments,
+                segments + 1,
+                widths.len()
+            )));
+        }
+        if !widths
+            .iter()
+            .all(|width| *width >= 0.0 && width.is_finite())
+            || widths.windows(2).any(|ends| ends[0].max(ends[1]) <= 0.0)
+        {
+            return Err(Error::InvalidGeometry(String::from(
+                "strand widths must not be negative and every segment needs some",
+            )));
+        }
+
+        Ok(Strand {
+            points,
+            widths,
+            normals: Vec::new(),
+        })
+    }
+
+    pub fn with_normals(mut self, normals: Vec<Normal3>) -> Result<Strand, Error> {
+        if normals.len() != self.widths.len() {
+            return Err(Error::InvalidGeometry(format!(
+                "strand needs {} normals like its widths, not {}",
+                self.widths.len(),
+                normals.len()
+            )));
+        }
+        if normals
+            .iter()
+            .any(|normal| !(normal.length() > 0.0 && normal.length().is_finite()))
+        {
+            return Err(Error::InvalidGeometry(String::from(
+                "strand normals must not be zero",
+            )));
+        }
+        self.normals = normals.iter().map(Normal3::normalize).collect();
+        Ok(self)
+    }
+
+    fn segment_count(&self) -> usize {
+        (self.points.len() - 1) / 3
+    }
+}
+
+struct Segment {
+    control_points: [Point3; 4],
+    widths: [Float; 2],
+    normals: Option<[Normal3; 2]>,
+    // Range of the strand's u it covers.
+    u_range: [Float; 2],
+    max_refinement: u32,
+}
+
+impl Segment {
+    fn bounds(&self) -> Aabb {
+        let radius = 0.5 * self.widths[0].max(self.widths[1]);
+        let pad = Vec3::new(radius, radius, radius);
+        self.control_points
+            .iter()
+            .fold(Aabb::empty(), |bounds, point| {
+                bounds.include(&(*point - pad)).include(&(*point + pad))
+            })
+    }
+
+    fn width(&self, u: Float) -> Float {
+        self.widths[0] + (self.widths[1] - self.widths[0]) * u
+    }
+
+    // Spherical interpolation of the ribbon normals.
+    fn normal(&self, u: Float) -> Option<Vec3> {
+        let [start, end] = self.normals?.map(Vec3::from);
+        let cos_angle = dot(&start, &end).clamp(-1.0, 1.0);
+        let angle = cos_angle.acos();
+        if angle < 1e-4 {
+            return Some(start);
+        }
+        let sin_angle = angle.sin();
+        Some(
+            (((1.0 - u) * angle).sin() / sin_angle) * start + ((u * angle).sin() / sin_angle) * end,
+        )
+    }
+}
+
+// Curves sharing one material, like the hairs of a head or the blades of a lawn. They keep
+// their own hierarchy over the segments of every strand, which the scene sees as one object.
+// Hits have the u of the strand from root to tip and v across the width, with the fiber
+// direction as their tangent.
+pub struct Curves {
+    pub shape: CurveShape,
+    pub material: Box<dyn Material>,
+    segments: Vec<Segment>,
+    bvh: IndexBvh,
+}
+
+// Hit found by refining a segment, in the frame of the ray.
+struct CurveHit {
+    t: Float,
+    segment: usize,
+    u: Float,
+    width: Float,
+}
+
+impl Curves {
+    pub fn new(
+        strands: &[Strand],
+        shape: CurveShape,
+        material: Box<dyn Material>,
+    ) -> Result<Curves, Error> {
+        if strands.is_empty() {
+            return Err(Error::InvalidGeometry(String::from(
+                "curves need at least one strand",
+            )));
+        }
+        if shape == CurveShape::Ribbon && strands.iter().any(|strand| strand.normals.is_empty()) {
+            return Err(Error::InvalidGeometry(String::from(
+                "ribbon curves need normals on every strand",
+            )));
+        }
+
+        let mut segments = Vec::new();
+        for strand in strands {
+            let count = strand.segment_count();
+            for index in 0..count {
+                let control_points = [0, 1, 2, 3].map(|i| strand.points[3 * index + i]);
+                let widths = [strand.widths[index], strand.widths[index + 1]];
+                let normals = match shape {
+                    CurveShape::Ribbon => Some([strand.normals[index], strand.normals[index + 1]]),
+                    _ => None,
+                };
+                segments.push(Segment {
+                    control_points,
+                    widths,
+                    normals,
+                    u_range: [
+                        index as Float / count as Float,
+                        (index + 1) as Float / count as Float,
+                    ],
+                    max_refinement: refinement(&control_points, widths[0].max(widths[1])),
+                });
+            }
+        }
+
+        let bounds: Vec<Aabb> = segments.iter().map(Segment::bounds).collect();
+        Ok(Curves {
+            shape,
+            material,
+            segments,
+            bvh: IndexBvh::new(&bounds),
+        })
+    }
+
+    // Tests the segment in the frame of the ray, where it runs along +z from the origin and
+    // the curve is hit where its width covers the origin.
+    fn hit_segment(
+        &self,
+        index: usize,
+        frame: &RayFrame,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<CurveHit> {
+        let segment = &self.segments[index];
+        let control_points = segment.control_points.map(|point| frame.to_local(&point));
+        let mut closest = None;
+        self.refine(
+            segment,
+            index,
+            frame,
+            &control_points,
+            [0.0, 1.0],
+            segment.max_refinement,
+            t_min,
+            t_max,
+            &mut closest,
+        );
+        closest
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn refine(
+        &self,
+        segment: &Segment,
+        index: usize,
+        frame: &RayFrame,
+        control_points: &[Vec3; 4],
+        u_range: [Float; 2],
+        depth: u32,
+        t_min: Float,
+        t_max: Float,
+        closest: &mut Option<CurveHit>,
+    ) {
+        let z_min = t_min * frame.length;
+        let z_max = closest.as_ref().map_or(t_max, |hit| hit.t) * frame.length;
+
+        // Box of the piece, widened by its half width, has to cover the ray.
+        let radius = 0.5 * segment.width(u_range[0]).max(segment.width(u_range[1]));
+        let mut low = control_points[0];
+        let mut high = control_points[0];
+        for point in &control_points[1..] {
+            for axis in 0..3 {
+                low.data[axis] = low.data[axis].min(point.data[axis]);
+                high.data[axis] = high.data[axis].max(point.data[axis]);
+            }
+        }
+        if low.x() - radius > 0.0
+            || high.x() + radius < 0.0
+            || low.y() - radius > 0.0
+            || high.y() + radius < 0.0
+            || low.z() - radius > z_max
+            || high.z() + radius < z_min
+        {
+            return;
+        }
+
+        if depth > 0 {
+            let middle = 0.5 * (u_range[0] + u_range[1]);
+            let split = subdivide(control_points);
+            let halves = [
+                (
+                    [split[0], split[1], split[2], split[3]],
+                    [u_range[0], middle],
+                ),
+                (
+                    [split[3], split[4], split[5], split[6]],
+                    [middle, u_range[1]],
+                ),
+            ];
+            for (points, range) in halves {
+                self.refine(
+                    segment,
+                    index,
+                    frame,
+                    &points,
+                    range,
+                    depth - 1,
+                    t_min,
+                    t_max,
+                    closest,
+                );
+            }
+            return;
+        }
+
+        // Only the part between the perpendiculars through the ends belongs to this piece,
+        // the neighbours cover the rest.
+        let start = control_points[0];
+        let end = control_points[3];
+        if (control_points[1].y() - start.y()) * -start.y()
+            + start.x() * (start.x() - control_points[1].x())
+            < 0.0
+        {
+            return;
+        }
+        if (control_points[2].y() - end.y()) * -end.y()
+            + end.x() * (end.x() - control_points[2].x())
+            < 0.0
+        {
+            return;
+        }
+
+        // Closest approach of the straightened piece to the ray.
+        let direction = (end.x() - start.x(), end.y() - start.y());
+        let denominator = direction.0 * direction.0 + direction.1 * direction.1;
+        if denominator == 0.0 {
+            return;
+        }
+        let w = (-start.x() * direction.0 - start.y() * direction.1) / denominator;
+        let u = (u_range[0] + (u_range[1] - u_range[0]) * w).clamp(u_range[0], u_range[1]);
+        let mut width = segment.width(u);
+        if let Some(normal) = segment.normal(u) {
+            // Ribbons narrow as they turn edge-on.
+            width *= dot(&normal, &frame.direction).abs();
+        }
+
+        let (point, _) = bezier(control_points, w.clamp(0.0, 1.0));
+        if point.x() * point.x() + point.y() * point.y() > 0.25 * width * width {
+            return;
+        }
+        if point.z() <= z_min || point.z() >= z_max {
+            return;
+        }
+
+        *closest = Some(CurveHit {
+            t: point.z() / frame.length,
+            segment: index,
+            u,
+            width,
+        });
+    }
+}
+
+// Frame of a ray, with its unit direction as z.
+struct RayFrame {
+    origin: Point3,
+    direction: Vec3,
+    x: Vec3,
+    y: Vec3,
+    // Length of the ray's direction, turning distances along z into ray parameters.
+    length: Float,
+}
+
+impl RayFrame {
+    fn new(ray: &Ray) -> RayFrame {
+        let length = ray.direction.length();
+        let direction = ray.direction / length;
+        let (x, y) = orthonormal_basis(&direction);
+        RayFrame {
+            origin: ray.origin,
+            direction,
+            x,
+            y,
+            length,
+        }
+    }
+
+    fn to_local(&self, point: &Point3) -> Vec3 {
+        let offset = *point - self.origin;
+        Vec3::new(
+            dot(&offset, &self.x),
+            dot(&offset, &self.y),
+            dot(&offset, &self.direction),
+        )
+    }
+}
+
+// Halvings that leave pieces straight to a twentieth of the width, from how far the control
+// polygon bends.
+fn refinement(control_points: &[Point3; 4], width: Float) -> u32 {
+    let mut bend: Float = 0.0;
+    for i in 0..2 {
+        let second_difference = (control_points[i] - Point3::ORIGIN)
+            - 2.0 * (control_points[i + 1] - Point3::ORIGIN)
+            + (control_points[i + 2] - Point3::ORIGIN);
+        bend = bend.max(second_difference.length());
+    }
+    let epsilon = 0.05 * width;
+    let levels = ((Float::sqrt(2.0) * 6.0 * bend / (8.0 * epsilon)).log2() / 2.0).ceil();
+    if levels.is_nan() || levels < 0.0 {
+        0
+    } else {
+        (levels as u32).min(MAX_REFINEMENT)
+    }
+}
+
+// Control points of the two halves of a cubic Bézier curve, sharing the middle one.
+fn subdivide(control_points: &[Vec3; 4]) -> [Vec3; 7] {
+    let [p0, p1, p2, p3] = *control_points;
+    [
+        p0,
+        0.5 * (p0 + p1),
+        0.25 * (p0 + 2.0 * p1 + p2),
+        0.125 * (p0 + 3.0 * p1 + 3.0 * p2 + p3),
+        0.25 * (p1 + 2.0 * p2 + p3),
+        0.5 * (p2 + p3),
+        p3,
+    ]
+}
+
+// Point and derivative of a cubic Bézier curve by de Casteljau's algorithm.
+fn bezier(control_points: &[Vec3; 4], u: Float) -> (Vec3, Vec3) {
+    let lerp = |a: Vec3, b: Vec3| a + u * (b - a);
+    let [p0, p1, p2, p3] = *control_points;
+    let (a, b, c) = (lerp(p0, p1), lerp(p1, p2), lerp(p2, p3));
+    let (d, e) = (lerp(a, b), lerp(b, c));
+    let derivative = if (e - d).squared_length() > 0.0 {
+        3.0 * (e - d)
+    } else {
+        // Coincident control points at the ends.
+        p3 - p0
+    };
+    (lerp(d, e), derivative)
+}
+
+impl Hittable for Curves {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let frame = RayFrame::new(ray);
+        let hit = self.bvh.closest(ray, t_min, t_max, |index, t_closest| {
+            let hit = self.hit_segment(index, &frame, t_min, t_closest)?;
+            Some((hit.t, hit))
+        })?;
+        let segment = &self.segments[hit.segment];
+        let world_points = segment.control_points.map(|point| point - Point3::ORIGIN);
+        let (centre, derivative) = bezier(&world_points, hit.u);
+        let tangent = derivative.normalize();
+        let point = ray.point_at_parameter(hit.t);
+
+        // Facing the ray across the fiber, with the offset of the hit measured along the side
+        // direction from -1 to 1.
+        let towards_ray = -frame.direction;
+        let facing = (towards_ray - dot(&towards_ray, &tangent) * tangent).normalize();
+        let side = cross(&tangent, &facing);
+        let offset = (point - Point3::ORIGIN) - centre;
+        let h = (dot(&offset, &side) / (0.5 * hit.width)).clamp(-1.0, 1.0);
+
+        let outward = match self.shape {
+            CurveShape::Flat => facing,
+            // Turned around the fiber to where a tube would face at the offset.
+            CurveShape::Cylinder => (1.0 - h * h).max(0.0).sqrt() * facing + h * side,
+            CurveShape::Ribbon => {
+                let normal = segment.normal(hit.u).unwrap_or(facing);
+                (normal - dot(&normal, &tangent) * tangent).normalize()
+            }
+        };
+        let (is_front_face, normal) = get_face_and_normal_against_ray(ray, Normal3::from(outward));
+        let u = segment.u_range[0] + (segment.u_range[1] - segment.u_range[0]) * hit.u;
+
+        Some(HitRecord {
+            origin: point,
+            // The flattened curve is only as exact as its width.
+            error: Vec3::new(hit.width, hit.width, hit.width),
+            normal,
+            geometric_normal: normal,
+            t: hit.t,
+            is_front_face,
+            material: self.material.as_ref(),
+            uv: [u, 0.5 * (h + 1.0)],
+            barycentrics: None,
+            tangent: Some(tangent),
+            primitive: Primitive::Other,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<Aabb> {
+        Some(self.bvh.bounds())
+    }
+
+    fn emits(&self) -> bool {
+        !self.material.emission().is_black()
+    }
+}
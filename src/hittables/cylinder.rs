@@ -0,0 +1,244 @@
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Primitive, Span},
+};
+use crate::{
+    error::Error,
+    float::{consts::PI, gamma, Float},
+    hit_record::{get_face_and_normal_against_ray, HitRecord},
+    materials::material::Material,
+    ray::Ray,
+    vector::{dot, orthonormal_basis, Normal3, Point3, Vec3},
+};
+
+// Cylinder closed by flat caps, from the centre of its base to the centre of its top.
+pub struct Cylinder {
+    pub base: Point3,
+    pub top: Point3,
+    pub radius: Float,
+    pub material: Box<dyn Material>,
+    // Unit vector from the base to the top, and two more completing the frame.
+    axis: Vec3,
+    tangent: Vec3,
+    bitangent: Vec3,
+    height: Float,
+}
+
+// Part of the surface a ray passes through.
+#[derive(Copy, Clone)]
+enum Face {
+    Side,
+    Base,
+    Top,
+}
+
+impl Cylinder {
+    pub fn new(
+        base: Point3,
+        top: Point3,
+        radius: Float,
+        material: Box<dyn Material>,
+    ) -> Result<Cylinder, Error> {
+        if !base.is_finite() || !top.is_finite() {
+            return Err(Error::InvalidGeometry(String::from(
+                "cylinder ends must be finite",
+            )));
+        }
+        if !(radius > 0.0 && radius.is_finite()) {
+            return Err(Error::InvalidGeometry(format!(
+                "cylinder radius {} must be positive",
+                radius
+            )));
+        }
+        let height = (top - base).length();
+        if height == 0.0 {
+            return Err(Error::InvalidGeometry(String::from(
+                "cylinder base and top must not coincide",
+            )));
+        }
+        let axis = (top - base) / height;
+        let (tangent, bitangent) = orthonormal_basis(&axis);
+
+        Ok(Cylinder {
+            base,
+            top,
+            radius,
+            material,
+            axis,
+            tangent,
+            bitangent,
+            height,
+        })
+    }
+
+    // In the frame of the cylinder, with z along the axis from the base.
+    fn to_local(&self, vector: &Vec3) -> Vec3 {
+        Vec3::new(
+            dot(vector, &self.tangent),
+            dot(vector, &self.bitangent),
+            dot(vector, &self.axis),
+        )
+    }
+
+    // Distances along the ray where it enters and leaves the cylinder, with the faces it
+    // passes through.
+    fn intersect(&self, ray: &Ray) -> Option<((Float, Face), (Float, Face))> {
+        let origin = self.to_local(&(ray.origin - self.base));
+        let direction = self.to_local(&ray.direction);
+
+        // Within the radius of the axis.
+        let a = direction.x() * direction.x() + direction.y() * direction.y();
+        let half_b = origin.x() * direction.x() + origin.y() * direction.y();
+        let c = origin.x() * origin.x() + origin.y() * origin.y() - self.radius * self.radius;
+        let side = if a == 0.0 {
+            // Parallel to the axis.
+            if c > 0.0 {
+                return None;
+            }
+            (Float::NEG_INFINITY, Float::INFINITY)
+        } else {
+            let discriminant = half_b * half_b - a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            // Both roots without subtracting nearly equal values.
+            let q = -(half_b + discriminant.sqrt().copysign(half_b));
+            if q == 0.0 {
+                return None;
+            }
+            let (near, far) = (q / a, c / q);
+            (near.min(far), near.max(far))
+        };
+
+        // Between the planes of the caps.
+        let (caps, near_cap, far_cap) = if direction.z() == 0.0 {
+            if origin.z() < 0.0 || origin.z() > self.height {
+                return None;
+            }
+            (
+                (Float::NEG_INFINITY, Float::INFINITY),
+                Face::Base,
+                Face::Top,
+            )
+        } else {
+            let t_base = -origin.z() / direction.z();
+            let t_top = (self.height - origin.z()) / direction.z();
+            if t_base <= t_top {
+                ((t_base, t_top), Face::Base, Face::Top)
+            } else {
+                ((t_top, t_base), Face::Top, Face::Base)
+            }
+        };
+
+        let enter = if side.0 > caps.0 {
+            (side.0, Face::Side)
+        } else {
+            (caps.0, near_cap)
+        };
+        let exit = if side.1 < caps.1 {
+            (side.1, Face::Side)
+        } else {
+            (caps.1, far_cap)
+        };
+        if enter.0 > exit.0 || !enter.0.is_finite() || !exit.0.is_finite() {
+            return None;
+        }
+        Some((enter, exit))
+    }
+
+    // Hit on `face` at `t`, projected onto it so the error doesn't grow with the distance
+    // travelled.
+    fn record_at(&self, ray: &Ray, t: Float, face: Face) -> HitRecord<'_> {
+        let local = self.to_local(&(ray.origin + t * ray.direction - self.base));
+        let radial = local.x() * self.tangent + local.y() * self.bitangent;
+        let (point, outward, uv) = match face {
+            Face::Side => {
+                let height = local.z().clamp(0.0, self.height);
+                let outward = radial / radial.length();
+                let angle = Float::atan2(local.y(), local.x());
+                (
+                    self.base + height * self.axis + self.radius * outward,
+                    outward,
+                    [(angle + PI) / (2.0 * PI), height / self.height],
+                )
+            }
+            Face::Base | Face::Top => {
+                let (centre, outward) = match face {
+                    Face::Base => (self.base, -self.axis),
+                    _ => (self.top, self.axis),
+                };
+                let across =
+                    |coordinate: Float| (0.5 * (coordinate / self.radius + 1.0)).clamp(0.0, 1.0);
+                (
+                    centre + radial,
+                    outward,
+                    [across(local.x()), across(local.y())],
+                )
+            }
+        };
+        let error = gamma(7)
+            * ((self.base - Point3::ORIGIN).abs()
+                + (point - self.base).abs()
+                + (point - Point3::ORIGIN).abs());
+        let (is_front_face, normal) = get_face_and_normal_against_ray(ray, Normal3::from(outward));
+
+        HitRecord {
+            origin: point,
+            error,
+            normal,
+            geometric_normal: normal,
+            is_front_face,
+            t,
+            material: self.material.as_ref(),
+            uv,
+            barycentrics: None,
+            tangent: None,
+            primitive: Primitive::Other,
+        }
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let ((enter, enter_face), (exit, exit_face)) = self.intersect(ray)?;
+        if enter > t_min && enter < t_max {
+            Some(self.record_at(ray, enter, enter_face))
+        } else if exit > t_min && exit < t_max {
+            Some(self.record_at(ray, exit, exit_face))
+        } else {
+            None
+        }
+    }
+
+    // Each cap spreads around its centre by the radius times the sine of the axis' angle to
+    // each coordinate axis.
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<Aabb> {
+        let extent = Vec3::new(
+            self.radius * (1.0 - self.axis.x() * self.axis.x()).max(0.0).sqrt(),
+            self.radius * (1.0 - self.axis.y() * self.axis.y()).max(0.0).sqrt(),
+            self.radius * (1.0 - self.axis.z() * self.axis.z()).max(0.0).sqrt(),
+        );
+        Some(
+            Aabb::new(self.base - extent, self.base + extent)
+                .union(&Aabb::new(self.top - extent, self.top + extent)),
+        )
+    }
+
+    fn emits(&self) -> bool {
+        !self.material.emission().is_black()
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        match self.intersect(ray) {
+            Some(((enter, enter_face), (exit, exit_face))) => vec![Span {
+                enter: self.record_at(ray, enter, enter_face),
+                exit: self.record_at(ray, exit, exit_face),
+            }],
+            None => Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,381 @@
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Primitive},
+    triangle::{interpolate, intersect},
+};
+use crate::{
+    error::Error,
+    float::Float,
+    hit_record::{get_face_and_normal_against_ray, HitRecord},
+    image::Image,
+    materials::material::Material,
+    ray::Ray,
+    vector::{cross, Normal3, Point3, Vec3},
+};
+
+// Deep enough for a pending block at every level of any grid that fits in memory.
+const MAX_STACK: usize = 3 * usize::BITS as usize + 1;
+
+// Terrain over a grid of height samples spread evenly over a rectangle, two triangles per cell
+// of four samples. Shading normals are interpolated between the samples, so the terrain looks
+// smooth without more triangles.
+pub struct Heightfield {
+    material: Box<dyn Material>,
+    columns: usize,
+    // World positions of the samples, row by row along x, rows stepping along z.
+    points: Vec<Point3>,
+    normals: Vec<Normal3>,
+    corner: Point3,
+    extent: Vec3,
+    // Lowest and highest height in square blocks of cells, 2^level cells on a side. Whole
+    // blocks below or above a ray are skipped, and the last level is a single block.
+    levels: Vec<HeightRanges>,
+}
+
+struct HeightRanges {
+    columns: usize,
+    rows: usize,
+    ranges: Vec<(Float, Float)>,
+}
+
+// Hit on one of the triangles of a cell, with the samples at its vertices.
+struct CellHit {
+    t: Float,
+    barycentrics: [Float; 3],
+    samples: [(usize, usize); 3],
+}
+
+impl HeightRanges {
+    fn range(&self, column: usize, row: usize) -> (Float, Float) {
+        self.ranges[row * self.columns + column]
+    }
+}
+
+impl Heightfield {
+    // `samples` are `columns` by `rows` heights, row by row, the first at `corner` and the last
+    // at the corner across the `extent` along x and z. Heights are scaled by the extent along y.
+    pub fn new(
+        columns: usize,
+        rows: usize,
+        samples: &[Float],
+        corner: Point3,
+        extent: Vec3,
+        material: Box<dyn Material>,
+    ) -> Result<Heightfield, Error> {
+        if columns < 2 || rows < 2 {
+            return Err(Error::InvalidGeometry(format!(
+                "heightfield needs at least 2 by 2 samples, not {} by {}",
+                columns, rows
+            )));
+        }
+        if samples.len() != columns * rows {
+            return Err(Error::InvalidGeometry(format!(
+                "heightfield of {} by {} samples got {} of them",
+                columns,
+                rows,
+                samples.len()
+            )));
+        }
+        if !samples.iter().all(|sample| sample.is_finite()) || !corner.is_finite() {
+            return Err(Error::InvalidGeometry(String::from(
+                "heightfield samples and corner must be finite",
+            )));
+        }
+        if !(extent.x() > 0.0 && extent.z() > 0.0 && extent.is_finite()) {
+            return Err(Error::InvalidGeometry(String::from(
+                "heightfield must extend along x and z",
+            )));
+        }
+
+        let step_x = extent.x() / (columns - 1) as Float;
+        let step_z = extent.z() / (rows - 1) as Float;
+        let mut points = Vec::with_capacity(samples.len());
+        for row in 0..rows {
+            for column in 0..columns {
+                points.push(
+                    corner
+                        + Vec3::new(
+                            column as Float * step_x,
+                            samples[row * columns + column] * extent.y(),
+                            row as Float * step_z,
+                        ),
+                );
+            }
+        }
+
+        // Slopes by central differences, one sided along the edges.
+        let height = |column: usize, row: usize| points[row * columns + column].y();
+        let mut normals = Vec::with_capacity(points.len());
+        for row in 0..rows {
+            for column in 0..columns {
+                let (left, right) = (column.saturating_sub(1), (column + 1).min(columns - 1));
+                let (back, front) = (row.saturating_sub(1), (row + 1).min(rows - 1));
+                let slope_x =
+                    (height(right, row) - height(left, row)) / ((right - left) as Float * step_x);
+                let slope_z = (height(column, front) - height(column, back))
+                    / ((front - back) as Float * step_z);
+                normals.push(Normal3::from(
+                    Vec3::new(-slope_x, 1.0, -slope_z).normalize(),
+                ));
+            }
+        }
+
+        let mut cells = HeightRanges {
+            columns: columns - 1,
+            rows: rows - 1,
+            ranges: Vec::with_capacity((columns - 1) * (rows - 1)),
+        };
+        for row in 0..rows - 1 {
+            for column in 0..columns - 1 {
+                let corners = [
+                    height(column, row),
+                    height(column + 1, row),
+                    height(column, row + 1),
+                    height(column + 1, row + 1),
+                ];
+                cells.ranges.push((
+                    corners.iter().copied().fold(Float::INFINITY, Float::min),
+                    corners
+                        .iter()
+                        .copied()
+                        .fold(Float::NEG_INFINITY, Float::max),
+                ));
+            }
+        }
+        let mut levels = vec![cells];
+        while levels
+            .last()
+            .is_some_and(|level| level.columns > 1 || level.rows > 1)
+        {
+            let finer = levels.last().unwrap();
+            let mut coarser = HeightRanges {
+                columns: finer.columns.div_ceil(2),
+                rows: finer.rows.div_ceil(2),
+                ranges: Vec::new(),
+            };
+            for row in 0..coarser.rows {
+                for column in 0..coarser.columns {
+                    let mut range = (Float::INFINITY, Float::NEG_INFINITY);
+                    for (child_column, child_row) in children(finer, column, row) {
+                        let (low, high) = finer.range(child_column, child_row);
+                        range = (range.0.min(low), range.1.max(high));
+                    }
+                    coarser.ranges.push(range);
+                }
+            }
+            levels.push(coarser);
+        }
+
+        Ok(Heightfield {
+            material,
+            columns,
+            points,
+            normals,
+            corner,
+            extent,
+            levels,
+        })
+    }
+
+    // One sample per pixel, from their luminance, with the top row of the image at `corner`.
+    // 16 bit PNG or PGM images keep the terraces of 8 bits out of gentle slopes.
+    pub fn from_image(
+        image: &Image,
+        corner: Point3,
+        extent: Vec3,
+        material: Box<dyn Material>,
+    ) -> Result<Heightfield, Error> {
+        let mut samples = Vec::with_capacity(image.width * image.height);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                samples.push(image.luminance(x, y) as Float);
+            }
+        }
+        Heightfield::new(
+            image.width,
+            image.height,
+            &samples,
+            corner,
+            extent,
+            material,
+        )
+    }
+
+    pub fn load(
+        path: &str,
+        corner: Point3,
+        extent: Vec3,
+        material: Box<dyn Material>,
+    ) -> Result<Heightfield, Error> {
+        Heightfield::from_image(&Image::load(path)?, corner, extent, material)
+    }
+
+    pub fn material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    // Box of the block of cells, as tall as the heights in it.
+    fn block_bounds(&self, level: usize, column: usize, row: usize) -> Aabb {
+        let cells = &self.levels[0];
+        let first_column = column << level;
+        let first_row = row << level;
+        let last_column = ((column + 1) << level).min(cells.columns);
+        let last_row = ((row + 1) << level).min(cells.rows);
+        let (low, high) = self.levels[level].range(column, row);
+        let min = self.point(first_column, first_row);
+        let max = self.point(last_column, last_row);
+        Aabb::new(
+            Point3::new(min.x(), low, min.z()),
+            Point3::new(max.x(), high, max.z()),
+        )
+    }
+
+    fn point(&self, column: usize, row: usize) -> Point3 {
+        self.points[row * self.columns + column]
+    }
+
+    fn normal(&self, column: usize, row: usize) -> Normal3 {
+        self.normals[row * self.columns + column]
+    }
+
+    // Closest hit with either triangle of the cell.
+    fn hit_cell(
+        &self,
+        ray: &Ray,
+        column: usize,
+        row: usize,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<CellHit> {
+        // Both wind counterclockwise seen from above.
+        let triangles = [
+            [(column, row), (column, row + 1), (column + 1, row)],
+            [(column + 1, row + 1), (column + 1, row), (column, row + 1)],
+        ];
+        let mut closest = None;
+        let mut t_max = t_max;
+        for samples in triangles {
+            let vertices = samples.map(|(column, row)| self.point(column, row));
+            if let Some((t, barycentrics)) = intersect(&vertices, ray, t_min, t_max) {
+                t_max = t;
+                closest = Some(CellHit {
+                    t,
+                    barycentrics,
+                    samples,
+                });
+            }
+        }
+        closest
+    }
+}
+
+// Blocks of the finer level inside a block of the coarser one.
+fn children(
+    finer: &HeightRanges,
+    column: usize,
+    row: usize,
+) -> impl Iterator<Item = (usize, usize)> + '_ {
+    (2 * row..(2 * row + 2).min(finer.rows)).flat_map(move |child_row| {
+        (2 * column..(2 * column + 2).min(finer.columns))
+            .map(move |child_column| (child_column, child_row))
+    })
+}
+
+impl Hittable for Heightfield {
+    // Walks down the blocks the ray passes, nearest first, into the cells of the ones whose
+    // height range it crosses.
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let inverse_direction = Vec3::new(
+            1.0 / ray.direction.x(),
+            1.0 / ray.direction.y(),
+            1.0 / ray.direction.z(),
+        );
+        let top = self.levels.len() - 1;
+        self.block_bounds(top, 0, 0)
+            .hit_range(&ray.origin, &inverse_direction, t_min, t_max)?;
+
+        let mut closest = None;
+        let mut t_max = t_max;
+        let mut stack = [(0_usize, 0_usize, 0_usize); MAX_STACK];
+        stack[0] = (top, 0, 0);
+        let mut stack_size = 1;
+        while stack_size > 0 {
+            stack_size -= 1;
+            let (level, column, row) = stack[stack_size];
+            if level == 0 {
+                if let Some(hit) = self.hit_cell(ray, column, row, t_min, t_max) {
+                    t_max = hit.t;
+                    closest = Some(hit);
+                }
+                continue;
+            }
+
+            let mut entered = [(0.0, 0, 0); 4];
+            let mut entered_count = 0;
+            for (child_column, child_row) in children(&self.levels[level - 1], column, row) {
+                if let Some((enter, _)) = self
+                    .block_bounds(level - 1, child_column, child_row)
+                    .hit_range(&ray.origin, &inverse_direction, t_min, t_max)
+                {
+                    entered[entered_count] = (enter, child_column, child_row);
+                    entered_count += 1;
+                }
+            }
+            // Farthest first on the stack, so the nearest is taken next.
+            let entered = &mut entered[..entered_count];
+            entered.sort_by(|a, b| b.0.total_cmp(&a.0));
+            for (_, child_column, child_row) in entered.iter() {
+                stack[stack_size] = (level - 1, *child_column, *child_row);
+                stack_size += 1;
+            }
+        }
+
+        let CellHit {
+            t,
+            barycentrics,
+            samples,
+        } = closest?;
+        let vertices = samples.map(|(column, row)| self.point(column, row));
+        let (point, error) = interpolate(&vertices, barycentrics);
+
+        // The side comes from the flat triangle, the smooth normal is turned to match it.
+        let geometric = Normal3::from(
+            cross(&(vertices[1] - vertices[0]), &(vertices[2] - vertices[0])).normalize(),
+        );
+        let (is_front_face, geometric_normal) = get_face_and_normal_against_ray(ray, geometric);
+        let mut smooth = Vec3::default();
+        for (barycentric, (column, row)) in barycentrics.iter().zip(samples) {
+            smooth += *barycentric * Vec3::from(self.normal(column, row));
+        }
+        let smooth = Normal3::from(smooth.normalize());
+        let normal = if is_front_face { smooth } else { -smooth };
+
+        let across =
+            |value: Float, start: Float, length: Float| ((value - start) / length).clamp(0.0, 1.0);
+
+        Some(HitRecord {
+            origin: point,
+            error,
+            normal,
+            geometric_normal,
+            t,
+            is_front_face,
+            material: self.material.as_ref(),
+            uv: [
+                across(point.x(), self.corner.x(), self.extent.x()),
+                across(point.z(), self.corner.z(), self.extent.z()),
+            ],
+            barycentrics: None,
+            tangent: None,
+            primitive: Primitive::Other,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<Aabb> {
+        Some(self.block_bounds(self.levels.len() - 1, 0, 0))
+    }
+
+    fn emits(&self) -> bool {
+        !self.material.emission().is_black()
+    }
+}
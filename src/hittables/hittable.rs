@@ -1,6 +1,73 @@
-use crate::{ray::Ray, hit_record::HitRecord};
+use crate::{
+    float::Float,
+    hit_record::HitRecord,
+    ray::Ray,
+    vector::{Normal3, Point3, Vec3},
+};
 
-pub trait Hittable
-{
-    fn hit(&self, ray : &Ray, t_min : f32, t_max :f32) -> Option<HitRecord>;
-}
\ No newline at end of file
+use super::{aabb::Aabb, sphere::Sphere, triangle::Triangle};
+
+pub trait Hittable {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>>;
+
+    // Box enclosing the object while the shutter is open, None when it is unbounded.
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb>;
+
+    // Bounding boxes and primitives a hit query tests, for the traversal cost heat map.
+    fn traversal_cost(&self, _ray: &Ray, _t_min: Float, _t_max: Float) -> u32 {
+        1
+    }
+
+    // Lets acceleration structures repack simple shapes into packets.
+    fn primitive(&self) -> Primitive<'_> {
+        Primitive::Other
+    }
+
+    // Every primitive it's made of, for finding the emitters of nested objects.
+    fn primitives(&self) -> Vec<Primitive<'_>> {
+        vec![self.primitive()]
+    }
+
+    // Has an emissive material, so it would be an area light.
+    fn emits(&self) -> bool {
+        self.primitives().iter().any(|primitive| match primitive {
+            Primitive::Sphere(sphere) => !sphere.material().emission().is_black(),
+            Primitive::Triangle(triangle) => !triangle.material.emission().is_black(),
+            Primitive::Other => false,
+        })
+    }
+
+    // Encloses a volume, so constructive solid geometry can combine it.
+    fn is_closed(&self) -> bool {
+        false
+    }
+
+    // Stretches of the whole line of the ray, behind its origin too, that are inside a closed
+    // hittable, in ascending order.
+    fn spans(&self, _ray: &Ray) -> Vec<Span<'_>> {
+        Vec::new()
+    }
+}
+
+#[derive(Copy, Clone)]
+pub enum Primitive<'a> {
+    Sphere(&'a Sphere),
+    Triangle(&'a Triangle),
+    Other,
+}
+
+// The ray enters a closed hittable at one hit and leaves it at the other. Front faces are the
+// ones the ray enters through.
+#[derive(Copy, Clone)]
+pub struct Span<'a> {
+    pub enter: HitRecord<'a>,
+    pub exit: HitRecord<'a>,
+}
+
+// Point picked on the surface of a primitive, with the error bounds a hit there would have.
+pub struct SurfaceSample {
+    pub point: Point3,
+    pub error: Vec3,
+    // Points out of the front face.
+    pub normal: Normal3,
+}
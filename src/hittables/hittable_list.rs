@@ -1,27 +1,54 @@
+use crate::float::Float;
 use crate::hit_record::HitRecord;
 
-use super::hittable::Hittable;
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Primitive},
+};
 
 pub struct HittableList {
     pub hittables: Vec<Box<dyn Hittable>>,
 }
 
 impl Hittable for HittableList {
-    fn hit(&self, ray: &crate::ray::Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+    fn hit(&self, ray: &crate::ray::Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
         let mut hit_record: Option<HitRecord> = None;
-        let mut closest_t: f32 = t_max;
+        let mut closest_t: Float = t_max;
 
         for hittable in self.hittables.iter() {
-            match hittable.hit(ray, t_min, t_max) {
-                Some(hit_result) => {
-                    if hit_result.t < closest_t {
-                        closest_t = hit_result.t;
-                        hit_record = Some(hit_result);
-                    }
+            if let Some(hit_result) = hittable.hit(ray, t_min, t_max) {
+                if hit_result.t < closest_t {
+                    closest_t = hit_result.t;
+                    hit_record = Some(hit_result);
                 }
-                None => (),
             }
         }
-        return hit_record;
+        hit_record
+    }
+
+    fn traversal_cost(&self, ray: &crate::ray::Ray, t_min: Float, t_max: Float) -> u32 {
+        self.hittables
+            .iter()
+            .map(|hittable| hittable.traversal_cost(ray, t_min, t_max))
+            .sum()
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        self.hittables
+            .iter()
+            .try_fold(Aabb::empty(), |bounds, hittable| {
+                Some(bounds.union(&hittable.bounding_box(time_start, time_end)?))
+            })
+    }
+
+    fn emits(&self) -> bool {
+        self.hittables.iter().any(|hittable| hittable.emits())
+    }
+
+    fn primitives(&self) -> Vec<Primitive<'_>> {
+        self.hittables
+            .iter()
+            .flat_map(|hittable| hittable.primitives())
+            .collect()
     }
 }
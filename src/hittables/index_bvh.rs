@@ -0,0 +1,148 @@
+use super::aabb::Aabb;
+use crate::{
+    float::Float,
+    ray::Ray,
+    vector::{Point3, Vec3},
+};
+
+// Parts in a leaf of the hierarchy.
+const LEAF_SIZE: usize = 4;
+const MAX_DEPTH: usize = 64;
+
+// Bounding volume hierarchy over the parts of one hittable, like the triangles of a mesh or the
+// segments of curves, which it knows by their index. Built by median splits, it's quick to
+// build for the many parts one object can have.
+pub struct IndexBvh {
+    // Depth first order, the first child of an interior node follows it.
+    nodes: Vec<Node>,
+    // Indices of the parts, each leaf refers to a range of them.
+    order: Vec<usize>,
+}
+
+struct Node {
+    bounds: Aabb,
+    content: NodeContent,
+}
+
+enum NodeContent {
+    Interior { second_child: usize, axis: usize },
+    Leaf { parts: (usize, usize) },
+}
+
+impl IndexBvh {
+    // Over parts with the given bounds, there must be at least one.
+    pub fn new(bounds: &[Aabb]) -> IndexBvh {
+        let mut items: Vec<(usize, Aabb, Point3)> = bounds
+            .iter()
+            .enumerate()
+            .map(|(index, bounds)| (index, *bounds, bounds.centroid()))
+            .collect();
+        let mut bvh = IndexBvh {
+            nodes: Vec::new(),
+            order: Vec::with_capacity(bounds.len()),
+        };
+        bvh.build(&mut items, 0);
+        bvh
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        self.nodes[0].bounds
+    }
+
+    // Splits at the median centroid along the longest axis.
+    fn build(&mut self, items: &mut [(usize, Aabb, Point3)], depth: usize) -> usize {
+        let bounds = items
+            .iter()
+            .fold(Aabb::empty(), |bounds, item| bounds.union(&item.1));
+        let node_index = self.nodes.len();
+
+        if items.len() <= LEAF_SIZE || depth >= MAX_DEPTH {
+            let start = self.order.len();
+            self.order.extend(items.iter().map(|item| item.0));
+            self.nodes.push(Node {
+                bounds,
+                content: NodeContent::Leaf {
+                    parts: (start, self.order.len()),
+                },
+            });
+            return node_index;
+        }
+
+        let axis = items
+            .iter()
+            .fold(Aabb::empty(), |bounds, item| bounds.include(&item.2))
+            .longest_axis();
+        let middle = items.len() / 2;
+        items.select_nth_unstable_by(middle, |a, b| a.2.data[axis].total_cmp(&b.2.data[axis]));
+
+        self.nodes.push(Node {
+            bounds,
+            content: NodeContent::Interior {
+                second_child: 0,
+                axis,
+            },
+        });
+        let (first, second) = items.split_at_mut(middle);
+        self.build(first, depth + 1);
+        let second_index = self.build(second, depth + 1);
+        self.nodes[node_index].content = NodeContent::Interior {
+            second_child: second_index,
+            axis,
+        };
+        node_index
+    }
+
+    // Closest of the hits `hit` finds on the parts whose bounds the ray crosses, nearest ones
+    // first. It's given the index of a part and the distance to beat, and returns the distance
+    // along the ray of a hit with what it wants to keep of it.
+    pub fn closest<T>(
+        &self,
+        ray: &Ray,
+        t_min: Float,
+        t_max: Float,
+        mut hit: impl FnMut(usize, Float) -> Option<(Float, T)>,
+    ) -> Option<T> {
+        let inverse_direction = Vec3 {
+            data: ray.direction.data.map(|value| 1.0 / value),
+        };
+
+        let mut closest = None;
+        let mut t_closest = t_max;
+        let mut stack = [0_usize; MAX_DEPTH + 1];
+        let mut stack_size = 1;
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node_index = stack[stack_size];
+            let node = &self.nodes[node_index];
+            if !node
+                .bounds
+                .hit(&ray.origin, &inverse_direction, t_min, t_closest)
+            {
+                continue;
+            }
+            match node.content {
+                NodeContent::Leaf { parts } => {
+                    for part in &self.order[parts.0..parts.1] {
+                        if let Some((t, found)) = hit(*part, t_closest) {
+                            t_closest = t;
+                            closest = Some(found);
+                        }
+                    }
+                }
+                NodeContent::Interior { second_child, axis } => {
+                    // Nearer child last on the stack, so it is taken first.
+                    let first_child = node_index + 1;
+                    let (near, far) = if ray.direction.data[axis] < 0.0 {
+                        (second_child, first_child)
+                    } else {
+                        (first_child, second_child)
+                    };
+                    stack[stack_size] = far;
+                    stack[stack_size + 1] = near;
+                    stack_size += 2;
+                }
+            }
+        }
+        closest
+    }
+}
@@ -0,0 +1,83 @@
+use std::rc::Rc;
+
+use super::{aabb::Aabb, hittable::Hittable};
+use crate::{float::Float, hit_record::HitRecord, ray::Ray, transform::Transform, vector::Point3};
+
+// Copy of a shared object placed by an affine transform. Rays are taken into the object's
+// space, so a mesh used many times is stored and accelerated once. Front faces stay the ones
+// of the object even when the transform mirrors it.
+pub struct Instance {
+    object: Rc<dyn Hittable>,
+    // From the object's space to the world.
+    transform: Transform,
+    inverse: Transform,
+}
+
+impl Instance {
+    pub fn new(object: Rc<dyn Hittable>, transform: Transform) -> Instance {
+        Instance {
+            object,
+            transform,
+            inverse: transform.inverse(),
+        }
+    }
+}
+
+impl Hittable for Instance {
+    // The direction isn't normalized, so distances along the ray are the same in both spaces.
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let local_ray = Ray {
+            origin: self.inverse.transform_point(&ray.origin),
+            direction: self.inverse.transform_vector(&ray.direction),
+            time: ray.time,
+        };
+        let mut hit_record = self.object.hit(&local_ray, t_min, t_max)?;
+
+        let (origin, error) = self
+            .transform
+            .transform_point_with_error(&hit_record.origin, &hit_record.error);
+        hit_record.origin = origin;
+        hit_record.error = error;
+        hit_record.normal = self
+            .transform
+            .transform_normal(&hit_record.normal)
+            .normalize();
+        hit_record.geometric_normal = self
+            .transform
+            .transform_normal(&hit_record.geometric_normal)
+            .normalize();
+        hit_record.tangent = hit_record
+            .tangent
+            .map(|tangent| self.transform.transform_vector(&tangent).normalize());
+        Some(hit_record)
+    }
+
+    fn traversal_cost(&self, ray: &Ray, t_min: Float, t_max: Float) -> u32 {
+        let local_ray = Ray {
+            origin: self.inverse.transform_point(&ray.origin),
+            direction: self.inverse.transform_vector(&ray.direction),
+            time: ray.time,
+        };
+        self.object.traversal_cost(&local_ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        let bounds = self.object.bounding_box(time_start, time_end)?;
+        let corners = (0..8).map(|corner: usize| {
+            let pick = |axis: usize| {
+                if corner & (1 << axis) == 0 {
+                    bounds.min.data[axis]
+                } else {
+                    bounds.max.data[axis]
+                }
+            };
+            self.transform
+                .transform_point(&Point3::new(pick(0), pick(1), pick(2)))
+        });
+        Some(corners.fold(Aabb::empty(), |bounds, corner| bounds.include(&corner)))
+    }
+
+    fn emits(&self) -> bool {
+        self.object.emits()
+    }
+}
@@ -0,0 +1,180 @@
+use std::rc::Rc;
+
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Primitive},
+    index_bvh::IndexBvh,
+    triangle::{interpolate, intersect, is_degenerate},
+};
+use crate::{
+    error::Error,
+    float::Float,
+    hit_record::{get_face_and_normal_against_ray, HitRecord},
+    materials::material::Material,
+    ray::Ray,
+    vector::{cross, Normal3, Point3, Vec3},
+};
+
+// Triangles sharing their vertices and one material, with their own hierarchy so instances can
+// place copies of them anywhere. Vertex normals shade it smooth and texture coordinates take
+// over the uv of the hits, otherwise it's flat and the uv are barycentrics like on triangles.
+pub struct TriangleMesh {
+    pub material: Rc<dyn Material>,
+    positions: Vec<Point3>,
+    // Vertex indices of each triangle, counterclockwise seen from the front.
+    triangles: Vec<[usize; 3]>,
+    normals: Vec<Normal3>,
+    uvs: Vec<[Float; 2]>,
+    bvh: IndexBvh,
+}
+
+impl TriangleMesh {
+    // Triangles that are degenerate are dropped, but there must be some left.
+    pub fn new(
+        positions: Vec<Point3>,
+        triangles: Vec<[usize; 3]>,
+        material: Rc<dyn Material>,
+    ) -> Result<TriangleMesh, Error> {
+        if !positions.iter().all(Point3::is_finite) {
+            return Err(Error::InvalidGeometry(String::from(
+                "mesh positions must be finite",
+            )));
+        }
+        if let Some(index) = triangles
+            .iter()
+            .flatten()
+            .find(|index| **index >= positions.len())
+        {
+            return Err(Error::InvalidGeometry(format!(
+                "mesh triangle refers to vertex {} of {}",
+                index,
+                positions.len()
+            )));
+        }
+        let triangles: Vec<[usize; 3]> = triangles
+            .into_iter()
+            .filter(|triangle| !is_degenerate(&triangle.map(|index| positions[index])))
+            .collect();
+        if triangles.is_empty() {
+            return Err(Error::InvalidGeometry(String::from(
+                "mesh needs at least one triangle that isn't degenerate",
+            )));
+        }
+
+        let bounds: Vec<Aabb> = triangles
+            .iter()
+            .map(|triangle| {
+                triangle.iter().fold(Aabb::empty(), |bounds, index| {
+                    bounds.include(&positions[*index])
+                })
+            })
+            .collect();
+        Ok(TriangleMesh {
+            material,
+            positions,
+            triangles,
+            normals: Vec::new(),
+            uvs: Vec::new(),
+            bvh: IndexBvh::new(&bounds),
+        })
+    }
+
+    pub fn with_normals(mut self, normals: Vec<Normal3>) -> Result<TriangleMesh, Error> {
+        if normals.len() != self.positions.len() {
+            return Err(Error::InvalidGeometry(format!(
+                "mesh of {} positions got {} normals",
+                self.positions.len(),
+                normals.len()
+            )));
+        }
+        if normals
+            .iter()
+            .any(|normal| !(normal.length() > 0.0 && normal.length().is_finite()))
+        {
+            return Err(Error::InvalidGeometry(String::from(
+                "mesh normals must not be zero",
+            )));
+        }
+        self.normals = normals.iter().map(Normal3::normalize).collect();
+        Ok(self)
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<[Float; 2]>) -> Result<TriangleMesh, Error> {
+        if uvs.len() != self.positions.len() {
+            return Err(Error::InvalidGeometry(format!(
+                "mesh of {} positions got {} uvs",
+                self.positions.len(),
+                uvs.len()
+            )));
+        }
+        self.uvs = uvs;
+        Ok(self)
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, index, barycentrics) =
+            self.bvh.closest(ray, t_min, t_max, |index, t_closest| {
+                let vertices = self.triangles[index].map(|vertex| self.positions[vertex]);
+                let (t, barycentrics) = intersect(&vertices, ray, t_min, t_closest)?;
+                Some((t, (t, index, barycentrics)))
+            })?;
+        let triangle = self.triangles[index];
+        let vertices = triangle.map(|vertex| self.positions[vertex]);
+        let (point, error) = interpolate(&vertices, barycentrics);
+
+        // The side comes from the flat triangle, the smooth normal is turned to match it.
+        let geometric = Normal3::from(
+            cross(&(vertices[1] - vertices[0]), &(vertices[2] - vertices[0])).normalize(),
+        );
+        let (is_front_face, geometric_normal) = get_face_and_normal_against_ray(ray, geometric);
+        let normal = if self.normals.is_empty() {
+            geometric_normal
+        } else {
+            let mut smooth = Vec3::default();
+            for (barycentric, vertex) in barycentrics.iter().zip(triangle) {
+                smooth += *barycentric * Vec3::from(self.normals[vertex]);
+            }
+            let smooth = Normal3::from(smooth.normalize());
+            if is_front_face {
+                smooth
+            } else {
+                -smooth
+            }
+        };
+
+        let uv = if self.uvs.is_empty() {
+            [barycentrics[1], barycentrics[2]]
+        } else {
+            let mut uv = [0.0; 2];
+            for (barycentric, vertex) in barycentrics.iter().zip(triangle) {
+                uv[0] += barycentric * self.uvs[vertex][0];
+                uv[1] += barycentric * self.uvs[vertex][1];
+            }
+            uv
+        };
+
+        Some(HitRecord {
+            origin: point,
+            error,
+            normal,
+            geometric_normal,
+            t,
+            is_front_face,
+            material: self.material.as_ref(),
+            uv,
+            barycentrics: Some(barycentrics),
+            tangent: None,
+            primitive: Primitive::Other,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<Aabb> {
+        Some(self.bvh.bounds())
+    }
+
+    fn emits(&self) -> bool {
+        !self.material.emission().is_black()
+    }
+}
@@ -0,0 +1,247 @@
+use crate::{
+    float::{gamma, Float},
+    ray::Ray,
+    simd::{Lanes, Vec3Lanes},
+    vector::Point3,
+};
+
+use super::{sphere::Sphere, triangle::Triangle};
+
+// Widest supported packet, sizes the stack buffers lanes are stored to.
+pub const MAX_WIDTH: usize = 8;
+
+// Closest lane a packet hits, and the lanes it can't tell apart from a miss.
+pub struct PacketHit {
+    // Distance and hittable index.
+    pub closest: Option<(Float, usize)>,
+    // Bits of the lanes the scalar test has to settle.
+    pub uncertain: u32,
+}
+
+// One ray broadcast to every lane, prepared once per intersection query.
+pub struct RayLanes<L: Lanes> {
+    origin: Vec3Lanes<L>,
+    direction: Vec3Lanes<L>,
+    time: L,
+    direction_squared_length: L,
+    // Part of the sphere tolerance that only depends on the ray.
+    origin_size: L,
+    inverse_direction_length: L,
+    // Axes and shear of the watertight triangle test, see `triangle::intersect`.
+    permutation: [usize; 3],
+    shear: [L; 3],
+}
+
+impl<L: Lanes> RayLanes<L> {
+    pub fn new(ray: &Ray) -> RayLanes<L> {
+        let direction = ray.direction.abs();
+        let kz = if direction.x() > direction.y() {
+            if direction.x() > direction.z() {
+                0
+            } else {
+                2
+            }
+        } else if direction.y() > direction.z() {
+            1
+        } else {
+            2
+        };
+        let permutation = [(kz + 1) % 3, (kz + 2) % 3, kz];
+        let d = permutation.map(|axis| ray.direction.data[axis]);
+        let origin = ray.origin - Point3::ORIGIN;
+
+        RayLanes {
+            origin: Vec3Lanes::splat_point(&ray.origin),
+            direction: Vec3Lanes::splat(&ray.direction),
+            time: L::splat(ray.time),
+            direction_squared_length: L::splat(ray.direction.squared_length()),
+            origin_size: L::splat(origin.x().abs() + origin.y().abs() + origin.z().abs()),
+            inverse_direction_length: L::splat(1.0 / ray.direction.length()),
+            permutation,
+            shear: [-d[0] / d[2], -d[1] / d[2], 1.0 / d[2]].map(L::splat),
+        }
+    }
+}
+
+// Up to `L::WIDTH` spheres stored as structure of arrays, intersected all at once.
+pub struct SpherePacket<L: Lanes> {
+    centre: Vec3Lanes<L>,
+    velocity: Vec3Lanes<L>,
+    radius: L,
+    // Negative in the unused lanes, which can never be hit.
+    radius_squared: L,
+    // Index of the hittable behind every used lane.
+    indices: Vec<usize>,
+}
+
+impl<L: Lanes> SpherePacket<L> {
+    pub fn new(spheres: &[(usize, &Sphere)]) -> SpherePacket<L> {
+        assert!(spheres.len() <= L::WIDTH && L::WIDTH <= MAX_WIDTH);
+
+        let mut components = [[0.0 as Float; MAX_WIDTH]; 8];
+        components[7] = [-1.0; MAX_WIDTH];
+        for (lane, (_, sphere)) in spheres.iter().enumerate() {
+            for axis in 0..3 {
+                components[axis][lane] = sphere.centre().data[axis];
+                components[3 + axis][lane] = sphere.velocity().data[axis];
+            }
+            components[6][lane] = sphere.radius();
+            components[7][lane] = sphere.radius() * sphere.radius();
+        }
+
+        let lanes = |index: usize| &components[index][..L::WIDTH];
+        SpherePacket {
+            centre: Vec3Lanes::load(lanes(0), lanes(1), lanes(2)),
+            velocity: Vec3Lanes::load(lanes(3), lanes(4), lanes(5)),
+            radius: L::load(lanes(6)),
+            radius_squared: L::load(lanes(7)),
+            indices: spheres.iter().map(|(index, _)| *index).collect(),
+        }
+    }
+
+    // Closest hit in [t_min..t_max], with the roots and tolerance of `Sphere::hit`. Whether
+    // roots within the tolerance of t_min count is up to the exact scalar test.
+    pub fn hit(&self, ray: &RayLanes<L>, t_min: Float, t_max: Float) -> Option<PacketHit> {
+        let offset = self.velocity.scale(ray.time);
+        let centre = self.centre.mul_add(ray.time, &self.velocity);
+        let oc = ray.origin - centre;
+        let a = ray.direction_squared_length;
+        let half_b = oc.dot(&ray.direction);
+
+        let scaled_v = oc.scale(a) - ray.direction.scale(half_b);
+        let scaled_radius = a * self.radius;
+        let scaled_discriminant = scaled_radius * scaled_radius - scaled_v.dot(&scaled_v);
+        let zero = L::splat(0.0);
+        let has_roots = scaled_discriminant.ge(zero);
+        if !has_roots.any() {
+            return None;
+        }
+
+        let discriminant_sqrt = (scaled_discriminant.max(zero) / a).sqrt();
+        let q = -(half_b
+            + half_b
+                .ge(zero)
+                .select(discriminant_sqrt, -discriminant_sqrt));
+        let c = oc.dot(&oc) - self.radius_squared;
+        let (first, second) = (q / a, c / q);
+        let (near, far) = (first.min(second), first.max(second));
+
+        let size = ray.origin_size + self.centre.abs_sum() + offset.abs_sum() + self.radius;
+        let tolerance = L::splat(gamma(16).sqrt()) * size * ray.inverse_direction_length;
+        let lowest = L::splat(t_min) - tolerance;
+        let t = near.ge(lowest).select(near, far);
+        let in_range = has_roots & t.ge(lowest) & t.le(L::splat(t_max));
+        let uncertain = in_range & t.le(L::splat(t_min) + tolerance);
+        let missed = L::splat(Float::INFINITY);
+        let t = uncertain.select(missed, in_range.select(t, missed));
+
+        Some(PacketHit {
+            closest: closest_lane(t, &self.indices),
+            uncertain: lane_bits(uncertain, self.indices.len()),
+        })
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+}
+
+// Up to `L::WIDTH` triangles as their three vertices per lane.
+pub struct TrianglePacket<L: Lanes> {
+    // Zero in the unused lanes, the determinant test rejects them.
+    vertices: [Vec3Lanes<L>; 3],
+    indices: Vec<usize>,
+}
+
+impl<L: Lanes> TrianglePacket<L> {
+    pub fn new(triangles: &[(usize, &Triangle)]) -> TrianglePacket<L> {
+        assert!(triangles.len() <= L::WIDTH && L::WIDTH <= MAX_WIDTH);
+
+        let mut components = [[0.0 as Float; MAX_WIDTH]; 9];
+        for (lane, (_, triangle)) in triangles.iter().enumerate() {
+            for (vertex, position) in triangle.vertices.iter().enumerate() {
+                for axis in 0..3 {
+                    components[3 * vertex + axis][lane] = position.data[axis];
+                }
+            }
+        }
+
+        let lanes = |index: usize| &components[index][..L::WIDTH];
+        TrianglePacket {
+            vertices: [0, 3, 6]
+                .map(|first| Vec3Lanes::load(lanes(first), lanes(first + 1), lanes(first + 2))),
+            indices: triangles.iter().map(|(index, _)| *index).collect(),
+        }
+    }
+
+    // The watertight test of `triangle::intersect` with the same conservative bound on t, so
+    // the lanes accept the hits the scalar test does up to the rounding of the edge functions.
+    pub fn hit(&self, ray: &RayLanes<L>, t_min: Float, t_max: Float) -> Option<PacketHit> {
+        let [kx, ky, kz] = ray.permutation;
+        let [shear_x, shear_y, shear_z] = ray.shear;
+        let [p0, p1, p2] = self.vertices.map(|vertex| {
+            let p = vertex - ray.origin;
+            let (x, y, z) = (p.axis(kx), p.axis(ky), p.axis(kz));
+            [x + shear_x * z, y + shear_y * z, z * shear_z]
+        });
+
+        let e0 = p1[0] * p2[1] - p1[1] * p2[0];
+        let e1 = p2[0] * p0[1] - p2[1] * p0[0];
+        let e2 = p0[0] * p1[1] - p0[1] * p1[0];
+        let zero = L::splat(0.0);
+        let inside = e0.min(e1).min(e2).ge(zero) | e0.max(e1).max(e2).le(zero);
+        if !inside.any() {
+            return None;
+        }
+        // A zero determinant makes t NaN, which fails the range test below.
+        let inverse_determinant = L::splat(1.0) / (e0 + e1 + e2);
+        let t = (e0 * p0[2] + e1 * p1[2] + e2 * p2[2]) * inverse_determinant;
+
+        let max_abs = |axis: usize| p0[axis].abs().max(p1[axis].abs()).max(p2[axis].abs());
+        let (max_x, max_y, max_z) = (max_abs(0), max_abs(1), max_abs(2));
+        let max_e = e0.abs().max(e1.abs()).max(e2.abs());
+        let delta_x = L::splat(gamma(5)) * (max_x + max_z);
+        let delta_y = L::splat(gamma(5)) * (max_y + max_z);
+        let delta_z = L::splat(gamma(3)) * max_z;
+        let delta_e = L::splat(2.0)
+            * (L::splat(gamma(2)) * max_x * max_y + delta_y * max_x + delta_x * max_y);
+        let delta_t = L::splat(3.0)
+            * (L::splat(gamma(3)) * max_e * max_z + delta_e * max_z + delta_z * max_e)
+            * inverse_determinant.abs();
+
+        let hit = inside & (t - delta_t).ge(L::splat(t_min)) & t.le(L::splat(t_max));
+        let t = hit.select(t, L::splat(Float::INFINITY));
+
+        Some(PacketHit {
+            closest: closest_lane(t, &self.indices),
+            uncertain: 0,
+        })
+    }
+
+    pub fn indices(&self) -> &[usize] {
+        &self.indices
+    }
+}
+
+fn lane_bits<L: Lanes>(mask: L, used: usize) -> u32 {
+    let mut values = [0.0 as Float; MAX_WIDTH];
+    mask.store(&mut values[..L::WIDTH]);
+
+    values[..used]
+        .iter()
+        .enumerate()
+        .filter(|(_, value)| value.to_bits() != 0)
+        .fold(0, |bits, (lane, _)| bits | 1 << lane)
+}
+
+fn closest_lane<L: Lanes>(t: L, indices: &[usize]) -> Option<(Float, usize)> {
+    let mut values = [0.0 as Float; MAX_WIDTH];
+    t.store(&mut values[..L::WIDTH]);
+
+    values[..indices.len()]
+        .iter()
+        .zip(indices.iter())
+        .filter(|(t, _)| t.is_finite())
+        .min_by(|a, b| a.0.total_cmp(b.0))
+        .map(|(t, index)| (*t, *index))
+}
@@ -0,0 +1,542 @@
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Primitive},
+};
+use crate::{
+    error::Error,
+    float::Float,
+    hit_record::{get_face_and_normal_against_ray, HitRecord},
+    materials::material::Material,
+    ray::Ray,
+    vector::{dot, Normal3, Point3, Vec3},
+};
+
+// Steps before a ray marching along a surface without reaching it gives up.
+const MAX_STEPS: usize = 512;
+
+// Surfaces are found to within this fraction of the diagonal of the bounds.
+const RELATIVE_PRECISION: Float = 1e-4;
+
+// Signed distance to a surface, negative inside. Built from primitives centred at the origin
+// and operations on them, or from any function a caller supplies.
+pub enum SdfShape {
+    Sphere {
+        radius: Float,
+    },
+    // Box with its corners rounded off by `rounding`, which it stays within.
+    Cuboid {
+        half_extents: Vec3,
+        rounding: Float,
+    },
+    // Ring around the y axis.
+    Torus {
+        major_radius: Float,
+        minor_radius: Float,
+    },
+    // Segment between two points thickened by the radius.
+    Capsule {
+        start: Point3,
+        end: Point3,
+        radius: Float,
+    },
+    Translate {
+        offset: Vec3,
+        shape: Box<SdfShape>,
+    },
+    // Blends the two surfaces where they are within `smoothness` of each other.
+    SmoothUnion {
+        first: Box<SdfShape>,
+        second: Box<SdfShape>,
+        smoothness: Float,
+    },
+    // Carves the second shape out of the first with a blended edge.
+    SmoothSubtraction {
+        first: Box<SdfShape>,
+        second: Box<SdfShape>,
+        smoothness: Float,
+    },
+    // Copies of the shape `period` apart, `count` on each side of the original along every
+    // axis. The shape must fit in its cell for the distance to hold.
+    Repeat {
+        period: Vec3,
+        count: [u32; 3],
+        shape: Box<SdfShape>,
+    },
+    // Rotates each slice of the shape around the y axis by `rate` radians per unit of height.
+    Twist {
+        rate: Float,
+        shape: Box<SdfShape>,
+    },
+    // A distance function of the caller's within `bounds`. Distances may overestimate by up to
+    // the `lipschitz` factor, marching divides its steps by it.
+    Custom {
+        distance: Box<dyn Fn(&Point3) -> Float>,
+        bounds: Aabb,
+        lipschitz: Float,
+    },
+}
+
+impl SdfShape {
+    pub fn sphere(radius: Float) -> SdfShape {
+        SdfShape::Sphere { radius }
+    }
+
+    pub fn cuboid(half_extents: Vec3, rounding: Float) -> SdfShape {
+        SdfShape::Cuboid {
+            half_extents,
+            rounding,
+        }
+    }
+
+    pub fn torus(major_radius: Float, minor_radius: Float) -> SdfShape {
+        SdfShape::Torus {
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    pub fn capsule(start: Point3, end: Point3, radius: Float) -> SdfShape {
+        SdfShape::Capsule { start, end, radius }
+    }
+
+    pub fn custom(
+        distance: impl Fn(&Point3) -> Float + 'static,
+        bounds: Aabb,
+        lipschitz: Float,
+    ) -> SdfShape {
+        SdfShape::Custom {
+            distance: Box::new(distance),
+            bounds,
+            lipschitz,
+        }
+    }
+
+    pub fn translate(self, offset: Vec3) -> SdfShape {
+        SdfShape::Translate {
+            offset,
+            shape: Box::new(self),
+        }
+    }
+
+    pub fn smooth_union(self, other: SdfShape, smoothness: Float) -> SdfShape {
+        SdfShape::SmoothUnion {
+            first: Box::new(self),
+            second: Box::new(other),
+            smoothness,
+        }
+    }
+
+    pub fn smooth_subtraction(self, other: SdfShape, smoothness: Float) -> SdfShape {
+        SdfShape::SmoothSubtraction {
+            first: Box::new(self),
+            second: Box::new(other),
+            smoothness,
+        }
+    }
+
+    pub fn repeat(self, period: Vec3, count: [u32; 3]) -> SdfShape {
+        SdfShape::Repeat {
+            period,
+            count,
+            shape: Box::new(self),
+        }
+    }
+
+    pub fn twist(self, rate: Float) -> SdfShape {
+        SdfShape::Twist {
+            rate,
+            shape: Box::new(self),
+        }
+    }
+
+    pub fn distance(&self, point: &Point3) -> Float {
+        match self {
+            SdfShape::Sphere { radius } => (*point - Point3::ORIGIN).length() - radius,
+            SdfShape::Cuboid {
+                half_extents,
+                rounding,
+            } => {
+                let q = (*point - Point3::ORIGIN).abs() - *half_extents
+                    + Vec3::new(*rounding, *rounding, *rounding);
+                let outside = Vec3::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0));
+                outside.length() + q.x().max(q.y()).max(q.z()).min(0.0) - rounding
+            }
+            SdfShape::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let ring = point.x().hypot(point.z()) - major_radius;
+                ring.hypot(point.y()) - minor_radius
+            }
+            SdfShape::Capsule { start, end, radius } => {
+                let along = *end - *start;
+                let offset = *point - *start;
+                let t = (dot(&offset, &along) / along.squared_length()).clamp(0.0, 1.0);
+                (offset - t * along).length() - radius
+            }
+            SdfShape::Translate { offset, shape } => shape.distance(&(*point - *offset)),
+            SdfShape::SmoothUnion {
+                first,
+                second,
+                smoothness,
+            } => {
+                let a = first.distance(point);
+                let b = second.distance(point);
+                if *smoothness <= 0.0 {
+                    return a.min(b);
+                }
+                let h = (0.5 + 0.5 * (b - a) / smoothness).clamp(0.0, 1.0);
+                b + (a - b) * h - smoothness * h * (1.0 - h)
+            }
+            SdfShape::SmoothSubtraction {
+                first,
+                second,
+                smoothness,
+            } => {
+                let a = first.distance(point);
+                let b = second.distance(point);
+                if *smoothness <= 0.0 {
+                    return a.max(-b);
+                }
+                let h = (0.5 - 0.5 * (a + b) / smoothness).clamp(0.0, 1.0);
+                a + (-b - a) * h + smoothness * h * (1.0 - h)
+            }
+            SdfShape::Repeat {
+                period,
+                count,
+                shape,
+            } => {
+                // Into the cell of the nearest copy.
+                let mut local = *point;
+                for (axis, count) in count.iter().enumerate() {
+                    if *count > 0 && period.data[axis] > 0.0 {
+                        let limit = *count as Float;
+                        let cell = (point.data[axis] / period.data[axis])
+                            .round()
+                            .clamp(-limit, limit);
+                        local.data[axis] -= period.data[axis] * cell;
+                    }
+                }
+                shape.distance(&local)
+            }
+            SdfShape::Twist { rate, shape } => {
+                let angle = rate * point.y();
+                let (sin, cos) = angle.sin_cos();
+                shape.distance(&Point3::new(
+                    cos * point.x() + sin * point.z(),
+                    point.y(),
+                    -sin * point.x() + cos * point.z(),
+                ))
+            }
+            SdfShape::Custom { distance, .. } => distance(point),
+        }
+    }
+
+    // Box the surface stays within.
+    pub fn bounds(&self) -> Aabb {
+        let centred = |half: Vec3| Aabb::new(Point3::ORIGIN - half, Point3::ORIGIN + half);
+        match self {
+            SdfShape::Sphere { radius } => centred(Vec3::new(*radius, *radius, *radius)),
+            SdfShape::Cuboid { half_extents, .. } => centred(*half_extents),
+            SdfShape::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                let across = major_radius + minor_radius;
+                centred(Vec3::new(across, *minor_radius, across))
+            }
+            SdfShape::Capsule { start, end, radius } => {
+                let radius = Vec3::new(*radius, *radius, *radius);
+                Aabb::new(*start - radius, *start + radius)
+                    .union(&Aabb::new(*end - radius, *end + radius))
+            }
+            SdfShape::Translate { offset, shape } => {
+                let bounds = shape.bounds();
+                Aabb::new(bounds.min + *offset, bounds.max + *offset)
+            }
+            // The blend bulges out by at most a quarter of the smoothness.
+            SdfShape::SmoothUnion {
+                first,
+                second,
+                smoothness,
+            } => {
+                let bulge = 0.25 * smoothness.max(0.0);
+                let bounds = first.bounds().union(&second.bounds());
+                let bulge = Vec3::new(bulge, bulge, bulge);
+                Aabb::new(bounds.min - bulge, bounds.max + bulge)
+            }
+            SdfShape::SmoothSubtraction { first, .. } => first.bounds(),
+            SdfShape::Repeat {
+                period,
+                count,
+                shape,
+            } => {
+                let bounds = shape.bounds();
+                let mut reach = Vec3::default();
+                for (axis, count) in count.iter().enumerate() {
+                    reach.data[axis] = *count as Float * period.data[axis].max(0.0);
+                }
+                Aabb::new(bounds.min - reach, bounds.max + reach)
+            }
+            // Any slice turns within the circle around the y axis through the farthest corner.
+            SdfShape::Twist { shape, .. } => {
+                let bounds = shape.bounds();
+                let radius = twist_radius(&bounds);
+                Aabb::new(
+                    Point3::new(-radius, bounds.min.y(), -radius),
+                    Point3::new(radius, bounds.max.y(), radius),
+                )
+            }
+            SdfShape::Custom { bounds, .. } => *bounds,
+        }
+    }
+
+    // How much the distances may overestimate the true ones.
+    pub fn lipschitz(&self) -> Float {
+        match self {
+            SdfShape::Sphere { .. }
+            | SdfShape::Cuboid { .. }
+            | SdfShape::Torus { .. }
+            | SdfShape::Capsule { .. } => 1.0,
+            SdfShape::Translate { shape, .. } | SdfShape::Repeat { shape, .. } => shape.lipschitz(),
+            SdfShape::SmoothUnion { first, second, .. }
+            | SdfShape::SmoothSubtraction { first, second, .. } => {
+                first.lipschitz().max(second.lipschitz())
+            }
+            // Points at the rim move sideways by the rate times their radius for every step
+            // up.
+            SdfShape::Twist { rate, shape } => {
+                let stretch = rate * twist_radius(&shape.bounds());
+                shape.lipschitz() * (1.0 + stretch * stretch).sqrt()
+            }
+            SdfShape::Custom { lipschitz, .. } => *lipschitz,
+        }
+    }
+
+    fn validate(&self) -> Result<(), Error> {
+        let positive = |value: Float, what: &str| {
+            if value > 0.0 && value.is_finite() {
+                Ok(())
+            } else {
+                Err(Error::InvalidGeometry(format!(
+                    "SDF {} {} must be positive",
+                    what, value
+                )))
+            }
+        };
+        let not_negative = |value: Float, what: &str| {
+            if value >= 0.0 && value.is_finite() {
+                Ok(())
+            } else {
+                Err(Error::InvalidGeometry(format!(
+                    "SDF {} {} must not be negative",
+                    what, value
+                )))
+            }
+        };
+        match self {
+            SdfShape::Sphere { radius } => positive(*radius, "sphere radius"),
+            SdfShape::Cuboid {
+                half_extents,
+                rounding,
+            } => {
+                for extent in half_extents.data {
+                    positive(extent, "box half extent")?;
+                }
+                not_negative(*rounding, "box rounding")?;
+                if half_extents.data.iter().any(|extent| *rounding > *extent) {
+                    return Err(Error::InvalidGeometry(String::from(
+                        "SDF box rounding must not exceed its half extents",
+                    )));
+                }
+                Ok(())
+            }
+            SdfShape::Torus {
+                major_radius,
+                minor_radius,
+            } => {
+                positive(*major_radius, "torus radius")?;
+                positive(*minor_radius, "torus tube radius")
+            }
+            SdfShape::Capsule { start, end, radius } => {
+                if !start.is_finite() || !end.is_finite() || start.distance(end) == 0.0 {
+                    return Err(Error::InvalidGeometry(String::from(
+                        "SDF capsule needs two distinct finite end points",
+                    )));
+                }
+                positive(*radius, "capsule radius")
+            }
+            SdfShape::Translate { offset, shape } => {
+                if !offset.is_finite() {
+                    return Err(Error::InvalidGeometry(String::from(
+                        "SDF translation must be finite",
+                    )));
+                }
+                shape.validate()
+            }
+            SdfShape::SmoothUnion {
+                first,
+                second,
+                smoothness,
+            }
+            | SdfShape::SmoothSubtraction {
+                first,
+                second,
+                smoothness,
+            } => {
+                not_negative(*smoothness, "smoothness")?;
+                first.validate()?;
+                second.validate()
+            }
+            SdfShape::Repeat { period, shape, .. } => {
+                for length in period.data {
+                    not_negative(length, "repetition period")?;
+                }
+                shape.validate()
+            }
+            SdfShape::Twist { rate, shape } => {
+                if !rate.is_finite() {
+                    return Err(Error::InvalidGeometry(String::from(
+                        "SDF twist rate must be finite",
+                    )));
+                }
+                shape.validate()
+            }
+            SdfShape::Custom {
+                bounds, lipschitz, ..
+            } => {
+                if !bounds.min.is_finite()
+                    || !bounds.max.is_finite()
+                    || (0..3).any(|axis| bounds.min.data[axis] > bounds.max.data[axis])
+                {
+                    return Err(Error::InvalidGeometry(String::from(
+                        "custom SDF needs finite bounds",
+                    )));
+                }
+                positive(*lipschitz, "Lipschitz factor")
+            }
+        }
+    }
+}
+
+fn twist_radius(bounds: &Aabb) -> Float {
+    let x = bounds.min.x().abs().max(bounds.max.x().abs());
+    let z = bounds.min.z().abs().max(bounds.max.z().abs());
+    x.hypot(z)
+}
+
+// Surface of a signed distance field, found by sphere tracing: the distance at a point is a
+// step the ray can take without passing through the surface.
+pub struct Sdf {
+    shape: SdfShape,
+    material: Box<dyn Material>,
+    bounds: Aabb,
+    // Steps are divided by it.
+    lipschitz: Float,
+    // Distance counting as on the surface, and the step of the normal's central differences.
+    precision: Float,
+}
+
+impl Sdf {
+    pub fn new(shape: SdfShape, material: Box<dyn Material>) -> Result<Sdf, Error> {
+        shape.validate()?;
+        let bounds = shape.bounds();
+        let lipschitz = shape.lipschitz();
+        let precision = RELATIVE_PRECISION * bounds.diagonal().length();
+        // Room for the surface to be found just outside of the bounds.
+        let margin = Vec3::new(precision, precision, precision) * 2.0;
+
+        Ok(Sdf {
+            shape,
+            material,
+            bounds: Aabb::new(bounds.min - margin, bounds.max + margin),
+            lipschitz,
+            precision,
+        })
+    }
+
+    pub fn material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    // Outward unit normal from the gradient of the distance by central differences.
+    fn normal(&self, point: &Point3) -> Normal3 {
+        let step = self.precision;
+        let mut gradient = Vec3::default();
+        for axis in 0..3 {
+            let mut offset = Vec3::default();
+            offset.data[axis] = step;
+            gradient.data[axis] =
+                self.shape.distance(&(*point + offset)) - self.shape.distance(&(*point - offset));
+        }
+        if gradient.is_near_zero() {
+            return Normal3::new(0.0, 1.0, 0.0);
+        }
+        Normal3::from(gradient.normalize())
+    }
+}
+
+impl Hittable for Sdf {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let inverse_direction = Vec3::new(
+            1.0 / ray.direction.x(),
+            1.0 / ray.direction.y(),
+            1.0 / ray.direction.z(),
+        );
+        let (start, end) = self
+            .bounds
+            .hit_range(&ray.origin, &inverse_direction, t_min, t_max)?;
+        let speed = ray.direction.length();
+
+        // Rays leaving the surface start close to it, and refracted ones start inside. Marching
+        // on the distance with the sign it has at the start finds the surface either way.
+        let mut t = start;
+        let sign = if self.shape.distance(&ray.point_at_parameter(t)) < 0.0 {
+            -1.0
+        } else {
+            1.0
+        };
+        let mut found = false;
+        for _ in 0..MAX_STEPS {
+            let distance = sign * self.shape.distance(&ray.point_at_parameter(t));
+            if distance < self.precision {
+                found = true;
+                break;
+            }
+            t += distance / (self.lipschitz * speed);
+            if t > end {
+                return None;
+            }
+        }
+        if !found || t <= t_min {
+            return None;
+        }
+
+        let point = ray.point_at_parameter(t);
+        let (is_front_face, normal) = get_face_and_normal_against_ray(ray, self.normal(&point));
+        // Within the precision of the surface, which keeps spawned rays from finding it again.
+        let error = 2.0 * Vec3::new(self.precision, self.precision, self.precision);
+
+        Some(HitRecord {
+            origin: point,
+            error,
+            normal,
+            geometric_normal: normal,
+            is_front_face,
+            t,
+            material: self.material.as_ref(),
+            uv: [0.0, 0.0],
+            barycentrics: None,
+            tangent: None,
+            primitive: Primitive::Other,
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<Aabb> {
+        Some(self.bounds)
+    }
+
+    fn emits(&self) -> bool {
+        !self.material.emission().is_black()
+    }
+}
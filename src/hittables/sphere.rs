@@ -1,52 +1,276 @@
-use super::hittable::Hittable;
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Primitive, Span, SurfaceSample},
+};
 use crate::{
+    error::Error,
+    float::{consts::PI, gamma, Float},
     hit_record::{get_face_and_normal_against_ray, HitRecord},
+    interval::Interval,
     materials::material::Material,
     ray::Ray,
-    vector::{dot, Vector},
+    vector::{dot, random_on_unit_sphere, Normal3, Point3, Vec3},
 };
 
+// Fields are private so only `new` can make one, with a positive radius.
 pub struct Sphere {
-    pub centre: Vector,
-    pub radius: f32,
-    pub material: Box<dyn Material>,
+    centre: Point3,
+    radius: Float,
+    material: Box<dyn Material>,
+    // Distance the centre moves per second while the shutter is open.
+    velocity: Vec3,
 }
 
-impl Hittable for Sphere {
-    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
-        let oc: Vector = (*ray).origin - (self.centre);
-        let a: f32 = (*ray).direction.squared_length();
-        let half_b: f32 = dot(&oc, &(*ray).direction);
-        let c: f32 = oc.squared_length() - self.radius * self.radius;
+impl Sphere {
+    pub fn new(
+        centre: Point3,
+        radius: Float,
+        material: Box<dyn Material>,
+    ) -> Result<Sphere, Error> {
+        if !centre.is_finite() {
+            return Err(Error::InvalidGeometry(String::from(
+                "sphere centre must be finite",
+            )));
+        }
+        if !(radius > 0.0 && radius.is_finite()) {
+            return Err(Error::InvalidGeometry(format!(
+                "sphere radius {} must be positive",
+                radius
+            )));
+        }
 
-        let discriminant: f32 = half_b * half_b - a * c;
+        Ok(Sphere {
+            centre,
+            radius,
+            material,
+            velocity: Vec3::default(),
+        })
+    }
 
-        if discriminant < 0.0 {
-            return None;
+    pub fn with_velocity(mut self, velocity: Vec3) -> Sphere {
+        self.velocity = velocity;
+        self
+    }
+
+    pub fn centre(&self) -> Point3 {
+        self.centre
+    }
+
+    pub fn radius(&self) -> Float {
+        self.radius
+    }
+
+    pub fn material(&self) -> &dyn Material {
+        self.material.as_ref()
+    }
+
+    pub fn velocity(&self) -> Vec3 {
+        self.velocity
+    }
+
+    pub fn centre_at(&self, time: Float) -> Point3 {
+        self.centre + time * self.velocity
+    }
+
+    pub fn area(&self) -> Float {
+        4.0 * PI * self.radius * self.radius
+    }
+
+    // Uniformly distributed point on the surface at `time`.
+    pub fn sample_surface(&self, time: Float) -> SurfaceSample {
+        let centre = self.centre_at(time);
+        let outward = random_on_unit_sphere();
+        let offset = self.radius * outward;
+        let point = centre + offset;
+
+        SurfaceSample {
+            point,
+            error: gamma(5) * offset.abs()
+                + gamma(1) * (point - Point3::ORIGIN).abs()
+                + self.centre_error(time),
+            normal: Normal3::from(outward),
         }
+    }
+}
 
-        let discriminant_sqrt = discriminant.sqrt();
+impl Sphere {
+    // Nearest root of the ray's quadratic in [t_min..t_max], in plain floats. Near t_min their
+    // rounding can't tell a real hit from the surface the ray starts on, the interval version
+    // decides there.
+    fn nearest_root(&self, ray: &Ray, oc: &Vec3, t_min: Float, t_max: Float) -> Option<Float> {
+        let (near, far) = self.roots(ray, oc)?;
 
-        // Find the nearest root of quadratic equation that lies in the acceptable range.
-        let mut root: f32 = (-half_b - discriminant_sqrt) / a;
-        if root < t_min || root > t_max {
-            root = (-half_b + discriminant_sqrt) / a;
-            if root < t_min || root > t_max {
+        // Roots move by up to √ε of the scene scale when the ray grazes the sphere, far less
+        // otherwise.
+        let sum = |vector: Vec3| vector.x().abs() + vector.y().abs() + vector.z().abs();
+        let scale = sum(ray.origin - Point3::ORIGIN)
+            + sum(self.centre - Point3::ORIGIN)
+            + sum(ray.time * self.velocity)
+            + self.radius;
+        let tolerance = gamma(16).sqrt() * scale / ray.direction.length();
+
+        for root in [near, far] {
+            if root > t_max {
                 return None;
             }
+            if root > t_min + tolerance {
+                return Some(root);
+            }
+            if root >= t_min - tolerance {
+                return self.nearest_root_exact(ray, oc, t_min, t_max);
+            }
+        }
+        None
+    }
+
+    // Both roots of the ray's quadratic in ascending order, None when the ray misses.
+    fn roots(&self, ray: &Ray, oc: &Vec3) -> Option<(Float, Float)> {
+        let a = ray.direction.squared_length();
+        let half_b = dot(oc, &ray.direction);
+
+        // a(r² - |v|²), with v the offset of the closest approach from the centre, equals
+        // half_b² - ac but cancels far less for distant spheres. Scaled by a to spare a division
+        // for the misses.
+        let scaled_v = a * *oc - half_b * ray.direction;
+        let scaled_discriminant = (a * self.radius).powi(2) - scaled_v.squared_length();
+        if scaled_discriminant < 0.0 {
+            return None;
+        }
+        let discriminant = scaled_discriminant / a;
+
+        // Both roots without subtracting nearly equal values.
+        let q = -(half_b + discriminant.sqrt().copysign(half_b));
+        if q == 0.0 {
+            return None;
         }
+        let c = oc.squared_length() - self.radius * self.radius;
+        let (near, far) = (q / a, c / q);
+        Some((near.min(far), near.max(far)))
+    }
+
+    // Same as `nearest_root` in interval arithmetic, a root only counts when its whole interval
+    // is in range.
+    fn nearest_root_exact(
+        &self,
+        ray: &Ray,
+        oc: &Vec3,
+        t_min: Float,
+        t_max: Float,
+    ) -> Option<Float> {
+        let oc_error = gamma(1) * oc.abs() + self.centre_error(ray.time);
+        let o = [0, 1, 2].map(|axis| Interval::with_error(oc.data[axis], oc_error.data[axis]));
+        let d = ray.direction.data.map(Interval::new);
+
+        let a = d[0].square() + d[1].square() + d[2].square();
+        let b = 2.0 * (d[0] * o[0] + d[1] * o[1] + d[2] * o[2]);
+        let c = o[0].square() + o[1].square() + o[2].square() - Interval::new(self.radius).square();
+
+        let scale = b / (2.0 * a);
+        let v = [0, 1, 2].map(|axis| o[axis] - scale * d[axis]);
+        let length = (v[0].square() + v[1].square() + v[2].square()).sqrt();
+        let radius = Interval::new(self.radius);
+        let discriminant = 4.0 * a * (radius + length) * (radius - length);
+        if discriminant.low() < 0.0 {
+            return None;
+        }
+        let discriminant_sqrt = discriminant.sqrt();
+
+        let q = if b.midpoint() < 0.0 {
+            -0.5 * (b - discriminant_sqrt)
+        } else {
+            -0.5 * (b + discriminant_sqrt)
+        };
+        let (mut near, mut far) = (q / a, c / q);
+        if near.low() > far.low() {
+            (near, far) = (far, near);
+        }
+
+        if near.high() > t_max || far.low() <= t_min {
+            return None;
+        }
+        if near.low() > t_min {
+            Some(near.midpoint())
+        } else if far.high() <= t_max {
+            Some(far.midpoint())
+        } else {
+            None
+        }
+    }
+
+    // Hit of the ray at the root `t`.
+    fn record_at(&self, ray: &Ray, t: Float) -> HitRecord<'_> {
+        let centre = self.centre_at(ray.time);
+
+        // Projecting onto the surface bounds the error by the radius alone, not by the
+        // distance travelled.
+        let offset = ray.origin + t * ray.direction - centre;
+        let outward = offset * (1.0 / offset.length());
+        let offset = outward * self.radius;
+        let hit_position = centre + offset;
+        let error = gamma(5) * offset.abs()
+            + gamma(1) * (hit_position - Point3::ORIGIN).abs()
+            + self.centre_error(ray.time);
 
-        let hit_position: Vector = (*ray).point_at_parameter(root);
-        let hit_noraml: Vector = ((hit_position - self.centre) / self.radius).normalize();
+        let (face, normal) = get_face_and_normal_against_ray(ray, Normal3::from(outward));
 
-        let (face, normal) = get_face_and_normal_against_ray(ray, hit_noraml);
+        // Longitude around +y from -x, latitude from the bottom.
+        let u = (Float::atan2(-outward.z(), outward.x()) + PI) / (2.0 * PI);
+        let v = (-outward.y()).clamp(-1.0, 1.0).acos() / PI;
 
-        return Some(HitRecord {
+        HitRecord {
             origin: hit_position,
-            normal: normal,
+            error,
+            normal,
+            geometric_normal: normal,
             is_front_face: face,
-            t: root,
+            t,
             material: self.material.as_ref(),
-        });
+            uv: [u, v],
+            barycentrics: None,
+            tangent: None,
+            primitive: Primitive::Sphere(self),
+        }
+    }
+
+    // Bounds the rounding of `centre_at`.
+    fn centre_error(&self, time: Float) -> Vec3 {
+        gamma(2) * ((self.centre - Point3::ORIGIN).abs() + (time * self.velocity).abs())
+    }
+}
+
+impl Hittable for Sphere {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let oc: Vec3 = ray.origin - self.centre_at(ray.time);
+        let t = self.nearest_root(ray, &oc, t_min, t_max)?;
+        Some(self.record_at(ray, t))
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        let radius = Vec3::new(self.radius, self.radius, self.radius);
+        let bounds_at = |time: Float| {
+            let centre = self.centre_at(time);
+            Aabb::new(centre - radius, centre + radius)
+        };
+
+        Some(bounds_at(time_start).union(&bounds_at(time_end)))
+    }
+
+    fn primitive(&self) -> Primitive<'_> {
+        Primitive::Sphere(self)
+    }
+
+    fn is_closed(&self) -> bool {
+        true
+    }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span<'_>> {
+        let oc = ray.origin - self.centre_at(ray.time);
+        match self.roots(ray, &oc) {
+            Some((near, far)) => vec![Span {
+                enter: self.record_at(ray, near),
+                exit: self.record_at(ray, far),
+            }],
+            None => Vec::new(),
+        }
     }
 }
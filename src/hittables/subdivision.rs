@@ -0,0 +1,477 @@
+use std::collections::HashMap;
+
+use super::triangle::Triangle;
+use crate::{
+    error::Error,
+    float::Float,
+    image::Image,
+    materials::material::Material,
+    vector::{cross, dot, orthonormal_basis, Normal3, Point3, Vec3},
+};
+
+// Catmull-Clark control cage of polygons sharing their edges, at most two to an edge. Each
+// level splits every face into quads around its centre, after which the surface is made of
+// triangles to be put in the scene. Edges can be creased: a sharpness of n keeps them sharp
+// for n levels and then smooths them, fractions blend the two, and infinite ones stay sharp.
+// Edges with one face are always sharp, and so are the corners of a single face.
+pub struct SubdivisionSurface {
+    pub positions: Vec<Point3>,
+    // Vertex indices of each face, counterclockwise seen from outside.
+    pub faces: Vec<Vec<usize>>,
+    // Texture coordinates of every position, or none. They are interpolated linearly.
+    pub uvs: Vec<[Float; 2]>,
+    // Sharpness of creased edges, by their vertices in increasing order.
+    creases: HashMap<(usize, usize), Float>,
+}
+
+// Offsets along the surface read from an image at its texture coordinates. Mid gray leaves
+// the surface where it is and `scale` is the distance from black to white.
+pub enum Displacement {
+    // Along the normal by luminance.
+    Scalar { image: Image, scale: Float },
+    // Red along the tangent following u, green along the normal crossed with it and blue
+    // along the normal.
+    Vector { image: Image, scale: Float },
+}
+
+// Edge of the cage with the faces on either side.
+struct Edge {
+    vertices: (usize, usize),
+    faces: Vec<usize>,
+    sharpness: Float,
+}
+
+impl SubdivisionSurface {
+    pub fn new(
+        positions: Vec<Point3>,
+        faces: Vec<Vec<usize>>,
+    ) -> Result<SubdivisionSurface, Error> {
+        if !positions.iter().all(Point3::is_finite) {
+            return Err(Error::InvalidGeometry(String::from(
+                "subdivision surface positions must be finite",
+            )));
+        }
+        if faces.is_empty() {
+            return Err(Error::InvalidGeometry(String::from(
+                "subdivision surface needs at least one face",
+            )));
+        }
+        for face in &faces {
+            if face.len() < 3 {
+                return Err(Error::InvalidGeometry(format!(
+                    "subdivision surface face needs at least 3 vertices, not {}",
+                    face.len()
+                )));
+            }
+            if let Some(index) = face.iter().find(|index| **index >= positions.len()) {
+                return Err(Error::InvalidGeometry(format!(
+                    "subdivision surface face refers to vertex {} of {}",
+                    index,
+                    positions.len()
+                )));
+            }
+        }
+
+        let surface = SubdivisionSurface {
+            positions,
+            faces,
+            uvs: Vec::new(),
+            creases: HashMap::new(),
+        };
+        // Shared edges must run opposite ways in their two faces, so the faces agree on
+        // which side is outside.
+        let mut directed = HashMap::new();
+        for (face_index, face) in surface.faces.iter().enumerate() {
+            for (start, end) in face_edges(face) {
+                if start == end || directed.insert((start, end), face_index).is_some() {
+                    return Err(Error::InvalidGeometry(format!(
+                        "subdivision surface edge from vertex {} to {} is degenerate, shared by \
+                         more than two faces or by faces wound opposite ways",
+                        start, end
+                    )));
+                }
+            }
+        }
+        Ok(surface)
+    }
+
+    pub fn with_uvs(mut self, uvs: Vec<[Float; 2]>) -> Result<SubdivisionSurface, Error> {
+        if uvs.len() != self.positions.len() {
+            return Err(Error::InvalidGeometry(format!(
+                "subdivision surface of {} positions got {} uvs",
+                self.positions.len(),
+                uvs.len()
+            )));
+        }
+        self.uvs = uvs;
+        Ok(self)
+    }
+
+    // Creases the edge between the two vertices, which has to be one of the cage's.
+    pub fn with_crease(
+        mut self,
+        start: usize,
+        end: usize,
+        sharpness: Float,
+    ) -> Result<SubdivisionSurface, Error> {
+        if sharpness.is_nan() || sharpness < 0.0 {
+            return Err(Error::InvalidGeometry(format!(
+                "crease sharpness {} must not be negative",
+                sharpness
+            )));
+        }
+        let key = edge_key(start, end);
+        let is_edge = self
+            .faces
+            .iter()
+            .any(|face| face_edges(face).any(|(a, b)| edge_key(a, b) == key));
+        if !is_edge {
+            return Err(Error::InvalidGeometry(format!(
+                "crease between vertices {} and {} isn't an edge of the cage",
+                start, end
+            )));
+        }
+        self.creases.insert(key, sharpness);
+        Ok(self)
+    }
+
+    // Subdivided `levels` times, each one turning a face of n vertices into n quads.
+    pub fn refine(self, levels: u32) -> SubdivisionSurface {
+        (0..levels).fold(self, |surface, _| surface.subdivide())
+    }
+
+    fn subdivide(&self) -> SubdivisionSurface {
+        let edges = self.edges();
+        let edge_index: HashMap<(usize, usize), usize> = edges
+            .iter()
+            .enumerate()
+            .map(|(index, edge)| (edge.vertices, index))
+            .collect();
+
+        let face_points: Vec<Point3> = self
+            .faces
+            .iter()
+            .map(|face| centroid(face.iter().map(|index| self.positions[*index])))
+            .collect();
+
+        let edge_points: Vec<Point3> = edges
+            .iter()
+            .map(|edge| {
+                let (a, b) = edge.vertices;
+                let middle = self.positions[a].lerp(&self.positions[b], 0.5);
+                if edge.faces.len() != 2 || edge.sharpness >= 1.0 {
+                    return middle;
+                }
+                let smooth = centroid(
+                    [self.positions[a], self.positions[b]]
+                        .into_iter()
+                        .chain(edge.faces.iter().map(|face| face_points[*face])),
+                );
+                smooth.lerp(&middle, edge.sharpness)
+            })
+            .collect();
+
+        // Edges and faces around each vertex.
+        let mut vertex_edges = vec![Vec::new(); self.positions.len()];
+        for (index, edge) in edges.iter().enumerate() {
+            vertex_edges[edge.vertices.0].push(index);
+            vertex_edges[edge.vertices.1].push(index);
+        }
+        let mut vertex_faces = vec![Vec::new(); self.positions.len()];
+        for (index, face) in self.faces.iter().enumerate() {
+            for vertex in face {
+                vertex_faces[*vertex].push(index);
+            }
+        }
+
+        let vertex_points: Vec<Point3> = (0..self.positions.len())
+            .map(|vertex| {
+                let position = self.positions[vertex];
+                let around = &vertex_edges[vertex];
+                if around.is_empty() {
+                    return position;
+                }
+                let creased: Vec<&Edge> = around
+                    .iter()
+                    .map(|index| &edges[*index])
+                    .filter(|edge| edge.faces.len() != 2 || edge.sharpness > 0.0)
+                    .collect();
+                let is_corner = vertex_faces[vertex].len() == 1 && around.len() == 2;
+                let smooth = || {
+                    smooth_vertex(
+                        position,
+                        around.iter().map(|index| {
+                            let (a, b) = edges[*index].vertices;
+                            self.positions[a].lerp(&self.positions[b], 0.5)
+                        }),
+                        vertex_faces[vertex].iter().map(|face| face_points[*face]),
+                    )
+                };
+
+                // Corners stay, vertices on a crease slide along it and darts are smooth.
+                let sharp = if is_corner || creased.len() > 2 {
+                    position
+                } else if creased.len() == 2 {
+                    let other = |edge: &Edge| {
+                        let (a, b) = edge.vertices;
+                        self.positions[if a == vertex { b } else { a }] - Point3::ORIGIN
+                    };
+                    position.lerp(
+                        &(Point3::ORIGIN + 0.5 * (other(creased[0]) + other(creased[1]))),
+                        0.25,
+                    )
+                } else {
+                    return smooth();
+                };
+
+                // Boundaries count as infinitely sharp, and so do vertices on them.
+                let sharpness = creased
+                    .iter()
+                    .map(|edge| {
+                        if edge.faces.len() != 2 {
+                            Float::INFINITY
+                        } else {
+                            edge.sharpness
+                        }
+                    })
+                    .sum::<Float>()
+                    / creased.len() as Float;
+                if is_corner || sharpness >= 1.0 || vertex_faces[vertex].len() != around.len() {
+                    return sharp;
+                }
+                smooth().lerp(&sharp, sharpness)
+            })
+            .collect();
+
+        // Vertex points first, then edge points, then face points.
+        let edge_offset = vertex_points.len();
+        let face_offset = edge_offset + edge_points.len();
+        let mut positions = vertex_points;
+        positions.extend(edge_points);
+        positions.extend(face_points);
+
+        let edge_vertex = |a: usize, b: usize| edge_offset + edge_index[&edge_key(a, b)];
+        let mut faces = Vec::new();
+        for (face_index, face) in self.faces.iter().enumerate() {
+            let count = face.len();
+            for corner in 0..count {
+                let previous = face[(corner + count - 1) % count];
+                let next = face[(corner + 1) % count];
+                faces.push(vec![
+                    face[corner],
+                    edge_vertex(face[corner], next),
+                    face_offset + face_index,
+                    edge_vertex(previous, face[corner]),
+                ]);
+            }
+        }
+
+        let uvs = if self.uvs.is_empty() {
+            Vec::new()
+        } else {
+            let middle = |a: [Float; 2], b: [Float; 2]| [0.5 * (a[0] + b[0]), 0.5 * (a[1] + b[1])];
+            let mut uvs = self.uvs.clone();
+            uvs.extend(
+                edges
+                    .iter()
+                    .map(|edge| middle(self.uvs[edge.vertices.0], self.uvs[edge.vertices.1])),
+            );
+            uvs.extend(self.faces.iter().map(|face| {
+                let sum = face.iter().fold([0.0, 0.0], |sum, index| {
+                    [sum[0] + self.uvs[*index][0], sum[1] + self.uvs[*index][1]]
+                });
+                [sum[0] / face.len() as Float, sum[1] / face.len() as Float]
+            }));
+            uvs
+        };
+
+        // Both halves of a crease are one level less sharp.
+        let mut creases = HashMap::new();
+        for edge in &edges {
+            if edge.sharpness > 1.0 {
+                let (a, b) = edge.vertices;
+                let middle = edge_vertex(a, b);
+                creases.insert(edge_key(a, middle), edge.sharpness - 1.0);
+                creases.insert(edge_key(middle, b), edge.sharpness - 1.0);
+            }
+        }
+
+        SubdivisionSurface {
+            positions,
+            faces,
+            uvs,
+            creases,
+        }
+    }
+
+    fn edges(&self) -> Vec<Edge> {
+        let mut index = HashMap::new();
+        let mut edges: Vec<Edge> = Vec::new();
+        for (face_index, face) in self.faces.iter().enumerate() {
+            for (a, b) in face_edges(face) {
+                let key = edge_key(a, b);
+                let edge = *index.entry(key).or_insert_with(|| {
+                    edges.push(Edge {
+                        vertices: key,
+                        faces: Vec::new(),
+                        sharpness: self.creases.get(&key).copied().unwrap_or(0.0),
+                    });
+                    edges.len() - 1
+                });
+                edges[edge].faces.push(face_index);
+            }
+        }
+        edges
+    }
+
+    // Moves every vertex by the displacement at its texture coordinates, in the frame of the
+    // surface around it. Refine first, displacement only moves the vertices there are.
+    pub fn displace(mut self, displacement: &Displacement) -> Result<SubdivisionSurface, Error> {
+        if self.uvs.is_empty() {
+            return Err(Error::InvalidGeometry(String::from(
+                "displacement needs texture coordinates on the surface",
+            )));
+        }
+
+        // Tangents weighted by how fast u runs across the faces around.
+        let normals = self.area_weighted_normals();
+        let mut tangents = vec![Vec3::default(); self.positions.len()];
+        for face in &self.faces {
+            for index in 1..face.len() - 1 {
+                let corners = [face[0], face[index], face[index + 1]];
+                let [p0, p1, p2] = corners.map(|vertex| self.positions[vertex]);
+                let [uv0, uv1, uv2] = corners.map(|vertex| self.uvs[vertex]);
+                let (edge1, edge2) = (p1 - p0, p2 - p0);
+                let (du1, dv1, du2, dv2) = (
+                    uv1[0] - uv0[0],
+                    uv1[1] - uv0[1],
+                    uv2[0] - uv0[0],
+                    uv2[1] - uv0[1],
+                );
+                let determinant = du1 * dv2 - du2 * dv1;
+                let tangent = if determinant.abs() > 1e-12 {
+                    (dv2 * edge1 - dv1 * edge2) / determinant
+                } else {
+                    Vec3::default()
+                };
+                for vertex in corners {
+                    tangents[vertex] += tangent;
+                }
+            }
+        }
+
+        for (vertex, position) in self.positions.iter_mut().enumerate() {
+            if normals[vertex].is_near_zero() {
+                continue;
+            }
+            let normal = normals[vertex].normalize();
+            let [u, v] = self.uvs[vertex];
+            *position += match displacement {
+                Displacement::Scalar { image, scale } => {
+                    let pixel = image.sample(u, v);
+                    let height = (pixel.r() + pixel.g() + pixel.b()) / 3.0 - 0.5;
+                    (*scale * height as Float) * normal
+                }
+                Displacement::Vector { image, scale } => {
+                    let along = tangents[vertex] - dot(&tangents[vertex], &normal) * normal;
+                    let tangent = if along.is_near_zero() {
+                        orthonormal_basis(&normal).0
+                    } else {
+                        along.normalize()
+                    };
+                    let bitangent = cross(&normal, &tangent);
+                    let pixel = image.sample(u, v);
+                    *scale
+                        * ((pixel.r() - 0.5) as Float * tangent
+                            + (pixel.g() - 0.5) as Float * bitangent
+                            + (pixel.b() - 0.5) as Float * normal)
+                }
+            };
+        }
+        if !self.positions.iter().all(Point3::is_finite) {
+            return Err(Error::InvalidGeometry(String::from(
+                "displaced positions must be finite",
+            )));
+        }
+        Ok(self)
+    }
+
+    // Normals of the vertices averaged over the faces around, for smooth shading. Vertices
+    // without area around them, on no face or folded by displacement, point up.
+    pub fn vertex_normals(&self) -> Vec<Normal3> {
+        self.area_weighted_normals()
+            .into_iter()
+            .map(|normal| {
+                if normal.is_near_zero() {
+                    Normal3::new(0.0, 1.0, 0.0)
+                } else {
+                    Normal3::from(normal.normalize())
+                }
+            })
+            .collect()
+    }
+
+    // Sums of the normals of the faces around every vertex, as long as their areas.
+    fn area_weighted_normals(&self) -> Vec<Vec3> {
+        let mut normals = vec![Vec3::default(); self.positions.len()];
+        for triangle in self.triangle_indices() {
+            let [p0, p1, p2] = triangle.map(|vertex| self.positions[vertex]);
+            let normal = cross(&(p1 - p0), &(p2 - p0));
+            for vertex in triangle {
+                normals[vertex] += normal;
+            }
+        }
+        normals
+    }
+
+    // Fans of triangles over the faces, each with a material of its own from `material`.
+    // Faces displacement folded to nothing are left out.
+    pub fn triangles(&self, material: impl Fn() -> Box<dyn Material>) -> Vec<Triangle> {
+        self.triangle_indices()
+            .into_iter()
+            .filter_map(|triangle| {
+                Triangle::new(triangle.map(|vertex| self.positions[vertex]), material()).ok()
+            })
+            .collect()
+    }
+
+    // Faces fanned into triangles of vertex indices, for a `TriangleMesh`.
+    pub fn triangle_indices(&self) -> Vec<[usize; 3]> {
+        self.faces
+            .iter()
+            .flat_map(|face| {
+                (1..face.len() - 1).map(|index| [face[0], face[index], face[index + 1]])
+            })
+            .collect()
+    }
+}
+
+fn edge_key(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn face_edges(face: &[usize]) -> impl Iterator<Item = (usize, usize)> + '_ {
+    (0..face.len()).map(move |corner| (face[corner], face[(corner + 1) % face.len()]))
+}
+
+fn centroid(points: impl Iterator<Item = Point3>) -> Point3 {
+    let (sum, count) = points.fold((Vec3::default(), 0), |(sum, count), point| {
+        (sum + (point - Point3::ORIGIN), count + 1)
+    });
+    Point3::ORIGIN + sum / count as Float
+}
+
+// Interior vertex rule, (Q + 2R + (n - 3)S) / n with the average face point Q, the average
+// edge midpoint R and the old position S for n edges.
+fn smooth_vertex(
+    position: Point3,
+    edge_middles: impl Iterator<Item = Point3>,
+    face_points: impl Iterator<Item = Point3>,
+) -> Point3 {
+    let edge_middles: Vec<Point3> = edge_middles.collect();
+    let valence = edge_middles.len() as Float;
+    let r = centroid(edge_middles.into_iter()) - Point3::ORIGIN;
+    let q = centroid(face_points) - Point3::ORIGIN;
+    let s = position - Point3::ORIGIN;
+    Point3::ORIGIN + (q + 2.0 * r + (valence - 3.0) * s) / valence
+}
@@ -0,0 +1,189 @@
+use rand::Rng;
+
+use super::{
+    aabb::Aabb,
+    hittable::{Hittable, Primitive, SurfaceSample},
+};
+use crate::{
+    error::Error,
+    float::{difference_of_products, gamma, Float},
+    hit_record::{get_face_and_normal_against_ray, HitRecord},
+    materials::material::Material,
+    ray::Ray,
+    sampler,
+    vector::{cross, Normal3, Point3, Vec3},
+};
+
+pub struct Triangle {
+    pub vertices: [Point3; 3],
+    pub material: Box<dyn Material>,
+}
+
+impl Triangle {
+    pub fn new(vertices: [Point3; 3], material: Box<dyn Material>) -> Result<Triangle, Error> {
+        if !vertices.iter().all(Point3::is_finite) {
+            return Err(Error::InvalidGeometry(String::from(
+                "triangle vertices must be finite",
+            )));
+        }
+        if is_degenerate(&vertices) {
+            return Err(Error::InvalidGeometry(String::from(
+                "triangle must not be degenerate",
+            )));
+        }
+
+        Ok(Triangle { vertices, material })
+    }
+
+    pub fn area(&self) -> Float {
+        0.5 * cross(&self.edge(1), &self.edge(2)).length()
+    }
+
+    // Uniformly distributed point on the triangle, the square root keeps the barycentrics from
+    // bunching up at the first vertex.
+    pub fn sample_surface(&self) -> SurfaceSample {
+        let mut random = sampler::rng();
+        let root = random.gen::<Float>().sqrt();
+        let b0 = 1.0 - root;
+        let b1 = random.gen::<Float>() * root;
+        let (point, error) = interpolate(&self.vertices, [b0, b1, 1.0 - b0 - b1]);
+
+        SurfaceSample {
+            point,
+            error,
+            normal: self.normal(),
+        }
+    }
+
+    fn edge(&self, index: usize) -> Vec3 {
+        self.vertices[index] - self.vertices[0]
+    }
+
+    // Faces the side the vertices wind counterclockwise around.
+    pub fn normal(&self) -> Normal3 {
+        Normal3::from(cross(&self.edge(1), &self.edge(2)).normalize())
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let (t, barycentrics) = intersect(&self.vertices, ray, t_min, t_max)?;
+        let (hit_position, error) = interpolate(&self.vertices, barycentrics);
+        let (is_front_face, normal) = get_face_and_normal_against_ray(ray, self.normal());
+
+        Some(HitRecord {
+            origin: hit_position,
+            error,
+            normal,
+            geometric_normal: normal,
+            t,
+            is_front_face,
+            material: self.material.as_ref(),
+            uv: [barycentrics[1], barycentrics[2]],
+            barycentrics: Some(barycentrics),
+            tangent: None,
+            primitive: Primitive::Triangle(self),
+        })
+    }
+
+    fn bounding_box(&self, _time_start: Float, _time_end: Float) -> Option<Aabb> {
+        Some(
+            self.vertices
+                .iter()
+                .fold(Aabb::empty(), |bounds, vertex| bounds.include(vertex)),
+        )
+    }
+
+    fn primitive(&self) -> Primitive<'_> {
+        Primitive::Triangle(self)
+    }
+}
+
+// The watertight test of pbrt: in a frame where the ray runs along +z from the origin,
+// the hit is inside when the 2D edge functions agree in sign. It neither misses along
+// shared edges nor accepts a distance rounding could have pushed past zero.
+pub fn intersect(
+    vertices: &[Point3; 3],
+    ray: &Ray,
+    t_min: Float,
+    t_max: Float,
+) -> Option<(Float, [Float; 3])> {
+    // Permute the axes so the largest component of the direction becomes z.
+    let direction = ray.direction.abs();
+    let kz = if direction.x() > direction.y() {
+        if direction.x() > direction.z() {
+            0
+        } else {
+            2
+        }
+    } else if direction.y() > direction.z() {
+        1
+    } else {
+        2
+    };
+    let kx = (kz + 1) % 3;
+    let ky = (kx + 1) % 3;
+    let permute = |vector: Vec3| Vec3::new(vector.data[kx], vector.data[ky], vector.data[kz]);
+
+    let d = permute(ray.direction);
+    let shear_x = -d.x() / d.z();
+    let shear_y = -d.y() / d.z();
+    let shear_z = 1.0 / d.z();
+
+    let [p0, p1, p2] = vertices.map(|vertex| {
+        let p = permute(vertex - ray.origin);
+        Vec3::new(p.x() + shear_x * p.z(), p.y() + shear_y * p.z(), p.z())
+    });
+
+    let e0 = difference_of_products(p1.x(), p2.y(), p1.y(), p2.x());
+    let e1 = difference_of_products(p2.x(), p0.y(), p2.y(), p0.x());
+    let e2 = difference_of_products(p0.x(), p1.y(), p0.y(), p1.x());
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+    let determinant = e0 + e1 + e2;
+    if determinant == 0.0 {
+        return None;
+    }
+
+    let [z0, z1, z2] = [p0.z(), p1.z(), p2.z()].map(|z| z * shear_z);
+    let inverse_determinant = 1.0 / determinant;
+    let t = (e0 * z0 + e1 * z1 + e2 * z2) * inverse_determinant;
+
+    // Conservative bound on the error of t, pbrt 3.9.6.
+    let max_x = p0.x().abs().max(p1.x().abs()).max(p2.x().abs());
+    let max_y = p0.y().abs().max(p1.y().abs()).max(p2.y().abs());
+    let max_z = z0.abs().max(z1.abs()).max(z2.abs());
+    let max_e = e0.abs().max(e1.abs()).max(e2.abs());
+    let delta_x = gamma(5) * (max_x + max_z);
+    let delta_y = gamma(5) * (max_y + max_z);
+    let delta_z = gamma(3) * max_z;
+    let delta_e = 2.0 * (gamma(2) * max_x * max_y + delta_y * max_x + delta_x * max_y);
+    let delta_t = 3.0
+        * (gamma(3) * max_e * max_z + delta_e * max_z + delta_z * max_e)
+        * inverse_determinant.abs();
+    if t - delta_t <= t_min || t > t_max {
+        return None;
+    }
+
+    Some((t, [e0, e1, e2].map(|e| e * inverse_determinant)))
+}
+
+// Whether the edges are too close to parallel, or too short, to tell the side of the plane
+// from rounding. The test is relative to the edges so it holds at any scale.
+pub fn is_degenerate(vertices: &[Point3; 3]) -> bool {
+    let (edge1, edge2) = (vertices[1] - vertices[0], vertices[2] - vertices[0]);
+    cross(&edge1, &edge2).length() <= gamma(5) * edge1.length() * edge2.length()
+}
+
+// Point at the barycentrics of the vertices, exact up to a few roundings, and its error.
+pub fn interpolate(vertices: &[Point3; 3], barycentrics: [Float; 3]) -> (Point3, Vec3) {
+    let mut point = Point3::ORIGIN;
+    let mut error = Vec3::default();
+    for (barycentric, vertex) in barycentrics.iter().zip(vertices.iter()) {
+        let weighted = *barycentric * (*vertex - Point3::ORIGIN);
+        point += weighted;
+        error += gamma(7) * weighted.abs();
+    }
+    (point, error)
+}
@@ -0,0 +1,213 @@
+use std::fs;
+
+use crate::{
+    color::Color,
+    error::Error,
+    float::{to_f32, Float},
+};
+
+// Decoded image with channels normalized to [0..1], stored row by row from the top-left corner.
+#[derive(Clone)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Color>,
+}
+
+impl Image {
+    // Supports the ASCII and binary PGM/PPM formats (P2, P3, P5 and P6) and PNG, with 8 or 16
+    // bits. Alpha is dropped.
+    pub fn load(path: &str) -> Result<Image, Error> {
+        let bytes = fs::read(path).map_err(|error| match error.kind() {
+            std::io::ErrorKind::NotFound => Error::MissingTexture(path.to_string()),
+            _ => Error::io(format!("can't read {}", path), error),
+        })?;
+        Image::decode(&bytes, path)
+    }
+
+    // Image in one of the formats of `load` already in memory, `name` says where it's from in
+    // errors.
+    pub fn decode(bytes: &[u8], name: &str) -> Result<Image, Error> {
+        let decoded = if bytes.starts_with(PNG_SIGNATURE) {
+            parse_png(bytes)
+        } else {
+            parse_pnm(bytes)
+        };
+        decoded.map_err(|message| {
+            Error::io(
+                format!("can't decode {}", name),
+                std::io::Error::new(std::io::ErrorKind::InvalidData, message),
+            )
+        })
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> Color {
+        self.pixels[y * self.width + x]
+    }
+
+    pub fn luminance(&self, x: usize, y: usize) -> f32 {
+        let pixel = self.pixel(x, y);
+        (pixel.r() + pixel.g() + pixel.b()) / 3.0
+    }
+
+    // Bilinear lookup at texture coordinates with v = 0 along the top row, repeating beyond
+    // [0, 1] like a tiled texture.
+    pub fn sample(&self, u: Float, v: Float) -> Color {
+        let x = u.rem_euclid(1.0) * self.width as Float - 0.5;
+        let y = v.rem_euclid(1.0) * self.height as Float - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (to_f32(x - x0), to_f32(y - y0));
+        let wrap = |value: Float, size: usize| (value as isize).rem_euclid(size as isize) as usize;
+        let (x0, x1) = (wrap(x0, self.width), wrap(x0 + 1.0, self.width));
+        let (y0, y1) = (wrap(y0, self.height), wrap(y0 + 1.0, self.height));
+        let top = (1.0 - fx) * self.pixel(x0, y0) + fx * self.pixel(x1, y0);
+        let bottom = (1.0 - fx) * self.pixel(x0, y1) + fx * self.pixel(x1, y1);
+        (1.0 - fy) * top + fy * bottom
+    }
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+fn parse_png(bytes: &[u8]) -> Result<Image, String> {
+    let mut decoder = png::Decoder::new(bytes);
+    // Palettes become RGB and fewer than 8 bits become 8, 16 bits stay.
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|error| error.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader
+        .next_frame(&mut buffer)
+        .map_err(|error| error.to_string())?;
+
+    let channels = frame.color_type.samples();
+    let (bytes_per_sample, max_value) = match frame.bit_depth {
+        png::BitDepth::Sixteen => (2, 65535.0),
+        _ => (1, 255.0),
+    };
+    let (width, height) = (frame.width as usize, frame.height as usize);
+    let mut pixels: Vec<Color> = Vec::with_capacity(width * height);
+    for row in buffer.chunks(frame.line_size).take(height) {
+        for pixel in row.chunks(channels * bytes_per_sample).take(width) {
+            let sample = |channel: usize| {
+                let start = channel * bytes_per_sample;
+                let value = if bytes_per_sample == 2 {
+                    u16::from_be_bytes([pixel[start], pixel[start + 1]]) as f32
+                } else {
+                    pixel[start] as f32
+                };
+                value / max_value
+            };
+            pixels.push(if channels < 3 {
+                Color::new(sample(0), sample(0), sample(0))
+            } else {
+                Color::new(sample(0), sample(1), sample(2))
+            });
+        }
+    }
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+fn parse_pnm(bytes: &[u8]) -> Result<Image, String> {
+    let mut position = 0;
+    let magic = next_token(bytes, &mut position).ok_or("missing header")?;
+    let (channels, binary) = match magic.as_str() {
+        "P2" => (1, false),
+        "P3" => (3, false),
+        "P5" => (1, true),
+        "P6" => (3, true),
+        _ => return Err(format!("unsupported format {}", magic)),
+    };
+
+    let width = next_number(bytes, &mut position)?;
+    let height = next_number(bytes, &mut position)?;
+    let max_value = next_number(bytes, &mut position)?;
+    if width == 0 || height == 0 || max_value == 0 || max_value > 65535 {
+        return Err(String::from("invalid header"));
+    }
+
+    let sample_count = width
+        .checked_mul(height)
+        .and_then(|count| count.checked_mul(channels))
+        .ok_or("image is too large")?;
+    let samples: Vec<f32> = if binary {
+        // Exactly one whitespace separates the header from the raster.
+        position += 1;
+        let bytes_per_sample = if max_value > 255 { 2 } else { 1 };
+        // The raster has to be there before anything gets allocated for it.
+        let raster = sample_count
+            .checked_mul(bytes_per_sample)
+            .and_then(|length| bytes.get(position..position.checked_add(length)?))
+            .ok_or("raster is too short")?;
+        raster
+            .chunks(bytes_per_sample)
+            .map(|sample| {
+                let value = if bytes_per_sample == 2 {
+                    u16::from_be_bytes([sample[0], sample[1]]) as usize
+                } else {
+                    sample[0] as usize
+                };
+                value as f32 / max_value as f32
+            })
+            .collect()
+    } else {
+        // Every sample takes at least one digit and a separator.
+        if sample_count > bytes.len().saturating_sub(position) / 2 + 1 {
+            return Err(String::from("raster is too short"));
+        }
+        let mut samples = Vec::with_capacity(sample_count);
+        for _ in 0..sample_count {
+            samples.push(next_number(bytes, &mut position)? as f32 / max_value as f32);
+        }
+        samples
+    };
+
+    let pixels = samples
+        .chunks(channels)
+        .map(|sample| match channels {
+            1 => Color::new(sample[0], sample[0], sample[0]),
+            _ => Color::new(sample[0], sample[1], sample[2]),
+        })
+        .collect();
+
+    Ok(Image {
+        width,
+        height,
+        pixels,
+    })
+}
+
+// Header tokens are separated by whitespace, '#' starts a comment until the end of the line.
+fn next_token(bytes: &[u8], position: &mut usize) -> Option<String> {
+    while *position < bytes.len() {
+        if bytes[*position] == b'#' {
+            while *position < bytes.len() && bytes[*position] != b'\n' {
+                *position += 1;
+            }
+        } else if bytes[*position].is_ascii_whitespace() {
+            *position += 1;
+        } else {
+            break;
+        }
+    }
+
+    let start = *position;
+    while *position < bytes.len() && !bytes[*position].is_ascii_whitespace() {
+        *position += 1;
+    }
+
+    if start == *position {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&bytes[start..*position]).into_owned())
+}
+
+fn next_number(bytes: &[u8], position: &mut usize) -> Result<usize, String> {
+    let token = next_token(bytes, position).ok_or("unexpected end of file")?;
+    token
+        .parse()
+        .map_err(|_| format!("expected a number, found {}", token))
+}
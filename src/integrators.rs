@@ -0,0 +1,6 @@
+pub mod bdpt;
+pub mod debug;
+pub mod direct;
+pub mod mlt;
+pub mod photon_map;
+pub mod sppm;
@@ -0,0 +1,699 @@
+use crate::{
+    cameras::camera::CameraImportance,
+    color::Color,
+    error::Error,
+    float::{consts::PI, to_f32, Float},
+    hit_record::HitRecord,
+    hittables::hittable::Hittable,
+    lights::{light_sampling::LightSampling, light_set::LightSet},
+    ray::{spawn_ray_to, Ray},
+    scene::Scene,
+    vector::{dot, Normal3, Point3, Vec3},
+};
+
+// Radiance a light path brought to the image point (u, v) by connecting to the camera.
+pub struct Splat {
+    pub u: Float,
+    pub v: Float,
+    pub color: Color,
+}
+
+// Nearly every vertex is on a surface, boxing those would only add allocations.
+#[allow(clippy::large_enum_variant)]
+#[derive(Copy, Clone)]
+enum VertexKind<'h> {
+    Camera,
+    // Start of a light path, a point sampled on a light, or the sky a camera path escaped to.
+    Light(usize),
+    Surface(HitRecord<'h>),
+}
+
+// Vertex of a camera or light subpath. The densities are by area, except towards the sky,
+// where they stay by solid angle.
+#[derive(Copy, Clone)]
+struct Vertex<'h> {
+    kind: VertexKind<'h>,
+    point: Point3,
+    // None away from surfaces, on the camera and on the sky.
+    normal: Option<Normal3>,
+    error: Vec3,
+    // Towards the previous vertex of the subpath, on surfaces.
+    incoming: Vec3,
+    // Product of the subpath's contributions up to here, divided by their densities.
+    beta: Color,
+    // Density of the subpath sampling this vertex.
+    pdf_forward: Float,
+    // Density of the same vertex sampled from the other end of the path.
+    pdf_reverse: Float,
+    // Scattered by a specular surface, no connection can go through it.
+    delta: bool,
+}
+
+impl<'h> Vertex<'h> {
+    fn new(kind: VertexKind<'h>, point: Point3, normal: Option<Normal3>, beta: Color) -> Self {
+        Vertex {
+            kind,
+            point,
+            normal,
+            error: Vec3::default(),
+            incoming: Vec3::default(),
+            beta,
+            pdf_forward: 0.0,
+            pdf_reverse: 0.0,
+            delta: false,
+        }
+    }
+
+    fn surface(hit_record: HitRecord<'h>, incoming: Vec3, beta: Color) -> Self {
+        Vertex {
+            error: hit_record.error,
+            incoming,
+            ..Vertex::new(
+                VertexKind::Surface(hit_record),
+                hit_record.origin,
+                Some(hit_record.normal),
+                beta,
+            )
+        }
+    }
+
+    // Bidirectional methods can connect to it.
+    fn is_connectible(&self) -> bool {
+        match self.kind {
+            VertexKind::Surface(hit_record) => !hit_record.material.is_specular(),
+            VertexKind::Camera | VertexKind::Light(_) => true,
+        }
+    }
+
+    // Normal of the front face, the emitting side of area lights.
+    fn front_normal(&self) -> Option<Normal3> {
+        match self.kind {
+            VertexKind::Surface(hit_record) if !hit_record.is_front_face => {
+                Some(-hit_record.normal)
+            }
+            _ => self.normal,
+        }
+    }
+}
+
+// Radiance of transport, or importance for paths coming from the lights.
+#[derive(Copy, Clone)]
+enum Transport {
+    Radiance,
+    Importance,
+}
+
+// Bidirectional path tracing (Veach's thesis, chapter 10, as laid out by pbrt-v3 16.3). Every
+// camera sample traces a subpath from the camera and one from a light, then connects every
+// prefix of one to every prefix of the other. Multiple importance sampling weighs the
+// strategies by how likely each was to make the same path, so caustics come from the light
+// side and the rest mostly from the camera side. Connections straight to the camera land
+// elsewhere on the image and are returned as splats.
+pub struct BidirectionalPathTracer<'a> {
+    world: &'a dyn Hittable,
+    camera: &'a dyn CameraImportance,
+    lights: LightSet<'a>,
+    max_depth: u32,
+}
+
+impl<'a> BidirectionalPathTracer<'a> {
+    // `world` is an acceleration structure over the hittables of `scene`, whose emissive
+    // spheres and triangles become area lights. Paths have at most `max_depth` bounces.
+    pub fn new(
+        world: &'a dyn Hittable,
+        scene: &'a Scene,
+        max_depth: u32,
+        light_sampling: LightSampling,
+        time_start: Float,
+        time_end: Float,
+    ) -> Result<BidirectionalPathTracer<'a>, Error> {
+        let camera = scene.camera.importance().ok_or_else(|| {
+            Error::InvalidSettings(String::from(
+                "bidirectional path tracing needs a perspective camera with a circular aperture",
+            ))
+        })?;
+        let bounds = world.bounding_box(time_start, time_end).ok_or_else(|| {
+            Error::InvalidGeometry(String::from(
+                "bidirectional path tracing needs a bounded scene",
+            ))
+        })?;
+
+        Ok(BidirectionalPathTracer {
+            world,
+            camera,
+            lights: LightSet::new(scene, &bounds, light_sampling, time_start, time_end),
+            max_depth,
+        })
+    }
+
+    // Radiance along the camera ray `ray`, contributions of the light path to other image
+    // points go to `splats`.
+    pub fn radiance(&self, ray: &Ray, splats: &mut Vec<Splat>) -> Color {
+        let camera_path = self.camera_subpath(ray);
+        let light_path = self.light_subpath(ray.time);
+
+        let mut radiance = Color::BLACK;
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                let depth = (s + t) as i64 - 2;
+                if (s == 1 && t == 1) || depth < 0 || depth > self.max_depth as i64 {
+                    continue;
+                }
+
+                match self.connect(&light_path, &camera_path, s, t, ray.time) {
+                    Some((color, Some((u, v)))) => splats.push(Splat { u, v, color }),
+                    Some((color, None)) => radiance += color,
+                    None => {}
+                }
+            }
+        }
+        radiance
+    }
+
+    fn camera_subpath(&self, ray: &Ray) -> Vec<Vertex<'a>> {
+        let mut path = vec![Vertex::new(
+            VertexKind::Camera,
+            ray.origin,
+            None,
+            Color::WHITE,
+        )];
+        let (_, pdf_direction) = self.camera.ray_pdf(ray);
+        if pdf_direction > 0.0 {
+            self.random_walk(
+                *ray,
+                Color::WHITE,
+                pdf_direction,
+                Transport::Radiance,
+                self.max_depth + 1,
+                &mut path,
+            );
+        }
+        path
+    }
+
+    fn light_subpath(&self, time: Float) -> Vec<Vertex<'a>> {
+        let mut path = Vec::new();
+        let (index, selection_pdf) = match self.lights.pick() {
+            Some(picked) => picked,
+            None => return path,
+        };
+        let light = self.lights.get(index);
+        let emission = match light.sample_emission(time) {
+            Some(emission) => emission,
+            None => return path,
+        };
+        if emission.pdf_position == 0.0
+            || emission.pdf_direction == 0.0
+            || emission.radiance.is_black()
+        {
+            return path;
+        }
+
+        let mut start = Vertex::new(
+            VertexKind::Light(index),
+            emission.point,
+            emission.normal,
+            emission.radiance,
+        );
+        start.error = emission.error;
+        start.pdf_forward = emission.pdf_position * selection_pdf;
+        path.push(start);
+
+        let direction = emission.ray.direction.normalize();
+        let cos_theta = emission
+            .normal
+            .map_or(1.0, |normal| dot(&normal, &direction).abs());
+        let beta =
+            to_f32(cos_theta / (selection_pdf * emission.pdf_position * emission.pdf_direction))
+                * emission.radiance;
+        self.random_walk(
+            emission.ray,
+            beta,
+            emission.pdf_direction,
+            Transport::Importance,
+            self.max_depth,
+            &mut path,
+        );
+
+        // Light from infinitely far away starts on a disc, the first hit's density is the
+        // disc's by area, and the sky itself is sampled by direction.
+        if light.is_infinite() {
+            if let Some(first) = path.get_mut(1) {
+                first.pdf_forward = emission.pdf_position;
+                if let Some(normal) = &first.normal {
+                    first.pdf_forward *= dot(normal, &direction).abs();
+                }
+            }
+            path[0].pdf_forward = light.incident_pdf(&-direction) * selection_pdf;
+        }
+        path
+    }
+
+    // Extends `path` by up to `max_vertices` vertices, starting with `ray` sampled with the
+    // density `pdf` by solid angle.
+    fn random_walk(
+        &self,
+        mut ray: Ray,
+        mut beta: Color,
+        mut pdf: Float,
+        transport: Transport,
+        max_vertices: u32,
+        path: &mut Vec<Vertex<'a>>,
+    ) {
+        for _ in 0..max_vertices {
+            let hit_record = match self.world.hit(&ray, 0.0, Float::MAX) {
+                Some(hit_record) => hit_record,
+                None => {
+                    // Only camera paths see the sky, light paths leave the scene.
+                    // Escaped paths reach the sky at twice the radius of the scene.
+                    if let (Transport::Radiance, Some(sky)) = (transport, self.lights.sky()) {
+                        let direction = ray.direction.normalize();
+                        let mut sky = Vertex::new(
+                            VertexKind::Light(sky),
+                            ray.origin + 2.0 * self.lights.scene_radius() * direction,
+                            None,
+                            beta,
+                        );
+                        sky.pdf_forward = pdf;
+                        path.push(sky);
+                    }
+                    return;
+                }
+            };
+
+            let incoming = -ray.direction.normalize();
+            let mut vertex = Vertex::surface(hit_record, incoming, beta);
+            let previous = path.len() - 1;
+            vertex.pdf_forward = self.convert_density(pdf, &path[previous], &vertex);
+            path.push(vertex);
+            let current = previous + 1;
+
+            let material = hit_record.material;
+            let (attenuation, scattered_ray) = match material.scatter(&ray, &hit_record) {
+                Some(scattered) => scattered,
+                None => return,
+            };
+            let outgoing = scattered_ray.direction.normalize();
+
+            // Specular scattering has no density to weigh strategies with, nor can it be
+            // reversed by a connection.
+            let pdf_reverse = if material.is_specular() {
+                path[current].delta = true;
+                pdf = 0.0;
+                0.0
+            } else {
+                pdf = material.pdf(&hit_record, &incoming, &outgoing);
+                material.pdf(&hit_record, &outgoing, &incoming)
+            };
+            path[previous].pdf_reverse =
+                self.convert_density(pdf_reverse, &path[current], &path[previous]);
+
+            beta *= attenuation;
+            ray = scattered_ray;
+        }
+    }
+
+    // Contribution of the path made of the first `s` vertices of the light subpath and the
+    // first `t` of the camera subpath, and the image point it goes to when it isn't the one of
+    // the camera ray.
+    fn connect(
+        &self,
+        light_path: &[Vertex<'a>],
+        camera_path: &[Vertex<'a>],
+        s: usize,
+        t: usize,
+        time: Float,
+    ) -> Option<(Color, Option<(Float, Float)>)> {
+        let pt = &camera_path[t - 1];
+        // A camera path that escaped to the sky has nothing to connect from.
+        if t > 1 && s != 0 && matches!(pt.kind, VertexKind::Light(_)) {
+            return None;
+        }
+
+        let mut sampled = None;
+        let mut image_point = None;
+        let contribution = if s == 0 {
+            // The camera path found a light by itself.
+            pt.beta * self.emitted(pt, &camera_path[t - 2])
+        } else if t == 1 {
+            // The light path connects to a point sampled on the lens.
+            let qs = &light_path[s - 1];
+            if !qs.is_connectible() {
+                return None;
+            }
+            let lens = self.camera.sample_lens(&qs.point)?;
+            if lens.pdf <= 0.0 || lens.importance <= 0.0 {
+                return None;
+            }
+            let camera = Vertex::new(
+                VertexKind::Camera,
+                lens.point,
+                None,
+                to_f32(lens.importance / lens.pdf) * Color::WHITE,
+            );
+            let mut contribution = qs.beta * self.eval(qs, &camera) * camera.beta;
+            if let Some(normal) = &qs.normal {
+                let direction = (lens.point - qs.point).normalize();
+                contribution = to_f32(dot(normal, &direction).abs()) * contribution;
+            }
+            if !contribution.is_black() && !self.unoccluded(qs, &camera, time) {
+                return None;
+            }
+            sampled = Some(camera);
+            image_point = Some((lens.u, lens.v));
+            contribution
+        } else if s == 1 {
+            // The camera path connects to a point sampled on a light, next event estimation.
+            if !pt.is_connectible() {
+                return None;
+            }
+            let (index, selection_pdf) = self.lights.pick_towards(&pt.point, pt.normal.as_ref())?;
+            let sample = self.lights.get(index).sample_incident(&pt.point, time)?;
+            if sample.pdf <= 0.0 || sample.radiance.is_black() {
+                return None;
+            }
+            let mut light = Vertex::new(
+                VertexKind::Light(index),
+                sample.point,
+                sample.normal,
+                to_f32(1.0 / (sample.pdf * selection_pdf)) * sample.radiance,
+            );
+            light.error = sample.error;
+            light.pdf_forward = self.pdf_light_origin(&light, pt);
+            let mut contribution = pt.beta * self.eval(pt, &light) * light.beta;
+            if let Some(normal) = &pt.normal {
+                let direction = (sample.point - pt.point).normalize();
+                contribution = to_f32(dot(normal, &direction).abs()) * contribution;
+            }
+            if !contribution.is_black() && !self.unoccluded(pt, &light, time) {
+                return None;
+            }
+            sampled = Some(light);
+            contribution
+        } else {
+            let qs = &light_path[s - 1];
+            if !qs.is_connectible() || !pt.is_connectible() {
+                return None;
+            }
+            let contribution = qs.beta * self.eval(qs, pt) * self.eval(pt, qs) * pt.beta;
+            if contribution.is_black() {
+                return None;
+            }
+            to_f32(self.geometry(qs, pt, time)) * contribution
+        };
+        if contribution.is_black() {
+            return None;
+        }
+
+        let weight = self.mis_weight(light_path, camera_path, sampled, s, t);
+        Some((to_f32(weight) * contribution, image_point))
+    }
+
+    // Balance heuristic weight of the strategy (s, t) among all that could make the same path,
+    // from the ratios of their densities along the path (pbrt-v3 16.3.4).
+    fn mis_weight(
+        &self,
+        light_path: &[Vertex<'a>],
+        camera_path: &[Vertex<'a>],
+        sampled: Option<Vertex<'a>>,
+        s: usize,
+        t: usize,
+    ) -> Float {
+        if s + t == 2 {
+            return 1.0;
+        }
+
+        // The connection changes the endpoints, weigh a copy of the joined path.
+        let mut light = light_path[..s].to_vec();
+        let mut camera = camera_path[..t].to_vec();
+        if let Some(sampled) = sampled {
+            if s == 1 {
+                light[0] = sampled;
+            } else if t == 1 {
+                camera[0] = sampled;
+            }
+        }
+        camera[t - 1].delta = false;
+        if s > 0 {
+            light[s - 1].delta = false;
+        }
+
+        // Densities of the endpoints and their neighbours sampled from the other side.
+        camera[t - 1].pdf_reverse = if s > 0 {
+            self.pdf(
+                &light[s - 1],
+                s.checked_sub(2).map(|i| &light[i]),
+                &camera[t - 1],
+            )
+        } else {
+            self.pdf_light_origin(&camera[t - 1], &camera[t - 2])
+        };
+        if t > 1 {
+            camera[t - 2].pdf_reverse = if s > 0 {
+                self.pdf(&camera[t - 1], Some(&light[s - 1]), &camera[t - 2])
+            } else {
+                self.pdf_light(&camera[t - 1], &camera[t - 2])
+            };
+        }
+        if s > 0 {
+            light[s - 1].pdf_reverse = self.pdf(
+                &camera[t - 1],
+                t.checked_sub(2).map(|i| &camera[i]),
+                &light[s - 1],
+            );
+        }
+        if s > 1 {
+            light[s - 2].pdf_reverse = self.pdf(&light[s - 1], Some(&camera[t - 1]), &light[s - 2]);
+        }
+
+        // The densities above pick the light by power, as light paths do. Next event
+        // estimation, the strategy with one light vertex, picks it for the point it lights.
+        let (endpoint, neighbour) = if s > 0 {
+            (&light[0], if s > 1 { &light[1] } else { &camera[t - 1] })
+        } else {
+            (&camera[t - 1], &camera[t - 2])
+        };
+        let next_event = self.next_event_pick_ratio(endpoint, neighbour);
+        let scale = |strategy: usize| if strategy == 1 { next_event } else { 1.0 };
+
+        // Zero densities mark specular vertices, which the delta flags already exclude.
+        let remap = |pdf: Float| if pdf != 0.0 { pdf } else { 1.0 };
+        let mut sum = 0.0;
+        let mut ratio = 1.0;
+        for i in (1..t).rev() {
+            ratio *= remap(camera[i].pdf_reverse) / remap(camera[i].pdf_forward);
+            if !camera[i].delta && !camera[i - 1].delta {
+                sum += scale(s + t - i) * ratio;
+            }
+        }
+        ratio = 1.0;
+        for i in (0..s).rev() {
+            ratio *= remap(light[i].pdf_reverse) / remap(light[i].pdf_forward);
+            // Without light vertices the camera path has to hit the light, which it never
+            // does for punctual lights.
+            let previous_delta = if i > 0 {
+                light[i - 1].delta
+            } else {
+                self.is_delta_light(&light[0])
+            };
+            if !light[i].delta && !previous_delta {
+                sum += scale(i) * ratio;
+            }
+        }
+        scale(s) / (scale(s) + sum)
+    }
+
+    // How much likelier next event estimation from `neighbour` picks the light at `endpoint`
+    // than a pick by power does.
+    fn next_event_pick_ratio(&self, endpoint: &Vertex, neighbour: &Vertex) -> Float {
+        let index = match self.light_at(endpoint) {
+            Some(index) => index,
+            None => return 1.0,
+        };
+        let selection_pdf = self.lights.selection_pdf(index);
+        if selection_pdf == 0.0 {
+            return 1.0;
+        }
+        self.lights
+            .pmf_towards(&neighbour.point, neighbour.normal.as_ref(), index)
+            / selection_pdf
+    }
+
+    // Light the vertex is on, if any.
+    fn light_at(&self, vertex: &Vertex) -> Option<usize> {
+        match vertex.kind {
+            VertexKind::Light(index) => Some(index),
+            VertexKind::Surface(hit_record) => self.lights.find(&hit_record.primitive),
+            VertexKind::Camera => None,
+        }
+    }
+
+    fn is_infinite_light(&self, vertex: &Vertex) -> bool {
+        matches!(vertex.kind, VertexKind::Light(index) if self.lights.get(index).is_infinite())
+    }
+
+    fn is_delta_light(&self, vertex: &Vertex) -> bool {
+        matches!(vertex.kind, VertexKind::Light(index) if self.lights.get(index).is_delta())
+    }
+
+    // Radiance the light at `vertex` sends towards `target`.
+    fn emitted(&self, vertex: &Vertex, target: &Vertex) -> Color {
+        let light = match self.light_at(vertex) {
+            Some(light) => light,
+            None => return Color::BLACK,
+        };
+        let direction = target.point - vertex.point;
+        if direction.is_near_zero() {
+            return Color::BLACK;
+        }
+        self.lights
+            .get(light)
+            .radiance(vertex.front_normal().as_ref(), &direction.normalize())
+    }
+
+    // BSDF at `vertex` between its incoming direction and the one towards `next`.
+    fn eval(&self, vertex: &Vertex, next: &Vertex) -> Color {
+        match vertex.kind {
+            VertexKind::Surface(hit_record) => {
+                let direction = (next.point - vertex.point).normalize();
+                hit_record
+                    .material
+                    .eval(&hit_record, &vertex.incoming, &direction)
+            }
+            VertexKind::Camera | VertexKind::Light(_) => Color::BLACK,
+        }
+    }
+
+    // Turns a density by solid angle at `from` into one by area at `to`.
+    fn convert_density(&self, pdf: Float, from: &Vertex, to: &Vertex) -> Float {
+        if self.is_infinite_light(to) {
+            return pdf;
+        }
+        let direction = to.point - from.point;
+        let squared_distance = direction.squared_length();
+        if squared_distance == 0.0 {
+            return 0.0;
+        }
+        let mut pdf = pdf / squared_distance;
+        if let Some(normal) = &to.normal {
+            pdf *= dot(normal, &direction).abs() / squared_distance.sqrt();
+        }
+        pdf
+    }
+
+    // Density by area of sampling `next` from `vertex`, reached from `previous`.
+    fn pdf(&self, vertex: &Vertex, previous: Option<&Vertex>, next: &Vertex) -> Float {
+        let direction = next.point - vertex.point;
+        if direction.is_near_zero() {
+            return 0.0;
+        }
+        let direction = direction.normalize();
+
+        let pdf = match vertex.kind {
+            VertexKind::Light(_) => return self.pdf_light(vertex, next),
+            VertexKind::Camera => {
+                self.camera
+                    .ray_pdf(&Ray {
+                        origin: vertex.point,
+                        direction,
+                        time: 0.0,
+                    })
+                    .1
+            }
+            VertexKind::Surface(hit_record) => {
+                let previous = match previous {
+                    Some(previous) => previous,
+                    None => return 0.0,
+                };
+                let incoming = previous.point - vertex.point;
+                if incoming.is_near_zero() {
+                    return 0.0;
+                }
+                hit_record
+                    .material
+                    .pdf(&hit_record, &incoming.normalize(), &direction)
+            }
+        };
+        self.convert_density(pdf, vertex, next)
+    }
+
+    // Density by area of a light path starting at the light at `vertex` sampling `next`.
+    fn pdf_light(&self, vertex: &Vertex, next: &Vertex) -> Float {
+        let light = match self.light_at(vertex) {
+            Some(light) => self.lights.get(light),
+            None => return 0.0,
+        };
+        let direction = next.point - vertex.point;
+        let squared_distance = direction.squared_length();
+        if squared_distance == 0.0 {
+            return 0.0;
+        }
+        let direction = direction / squared_distance.sqrt();
+
+        let mut pdf = if light.is_infinite() {
+            // Light paths leave a disc as wide as the scene.
+            let radius = self.lights.scene_radius();
+            1.0 / (PI * radius * radius)
+        } else {
+            let (_, pdf_direction) = light.emission_pdf(vertex.front_normal().as_ref(), &direction);
+            pdf_direction / squared_distance
+        };
+        if let Some(normal) = &next.normal {
+            pdf *= dot(normal, &direction).abs();
+        }
+        pdf
+    }
+
+    // Density of a light path starting at `vertex`, the light at it seen from `next`.
+    fn pdf_light_origin(&self, vertex: &Vertex, next: &Vertex) -> Float {
+        let index = match self.light_at(vertex) {
+            Some(index) => index,
+            None => return 0.0,
+        };
+        let light = self.lights.get(index);
+        let direction = next.point - vertex.point;
+        if direction.is_near_zero() {
+            return 0.0;
+        }
+        let direction = direction.normalize();
+        let selection_pdf = self.lights.selection_pdf(index);
+
+        if light.is_infinite() {
+            light.incident_pdf(&-direction) * selection_pdf
+        } else {
+            let (pdf_position, _) = light.emission_pdf(vertex.front_normal().as_ref(), &direction);
+            pdf_position * selection_pdf
+        }
+    }
+
+    // Geometry term of the connection between two vertices, zero when it's blocked.
+    fn geometry(&self, a: &Vertex, b: &Vertex, time: Float) -> Float {
+        let direction = b.point - a.point;
+        let squared_distance = direction.squared_length();
+        if squared_distance == 0.0 {
+            return 0.0;
+        }
+        let direction = direction / squared_distance.sqrt();
+        let mut geometry = 1.0 / squared_distance;
+        for normal in [&a.normal, &b.normal].into_iter().flatten() {
+            geometry *= dot(normal, &direction).abs();
+        }
+        if geometry == 0.0 || !self.unoccluded(a, b, time) {
+            return 0.0;
+        }
+        geometry
+    }
+
+    fn unoccluded(&self, a: &Vertex, b: &Vertex, time: Float) -> bool {
+        let ray = spawn_ray_to(
+            &a.point,
+            &a.error,
+            a.normal.as_ref(),
+            &b.point,
+            &b.error,
+            b.normal.as_ref(),
+            time,
+        );
+        self.world.hit(&ray, 0.0, 1.0).is_none()
+    }
+}
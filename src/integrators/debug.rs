@@ -0,0 +1,128 @@
+use crate::{
+    color::Color,
+    float::{to_f32, Float},
+    hit_record::HitRecord,
+    hittables::hittable::Hittable,
+    lights::sky_light::Sky,
+    ray::Ray,
+    vector::{dot, random_on_unit_sphere, Vec3},
+};
+
+// Cheap views of a scene for finding out what's wrong with it, each from the first hit of the
+// camera ray only.
+
+// Triangle edges are drawn where the smallest barycentric is below it.
+const WIREFRAME_WIDTH: Float = 0.02;
+
+// Boxes and primitives tested that show up red in the heat map.
+const HEATMAP_MAX_COST: f32 = 100.0;
+
+// White where nothing lies within `radius` of the hit in a cosine-distributed direction, black
+// where something does, so averaged over samples it darkens creases and contacts.
+pub fn ambient_occlusion(ray: &Ray, world: &dyn Hittable, radius: Float) -> Color {
+    let hit_record = match world.hit(ray, 0.0, Float::MAX) {
+        Some(hit_record) => hit_record,
+        None => return Color::BLACK,
+    };
+
+    let normal = Vec3::from(hit_record.normal);
+    let direction = normal + random_on_unit_sphere();
+    let direction = if direction.is_near_zero() {
+        normal
+    } else {
+        direction.normalize()
+    };
+    let occlusion_ray = hit_record.spawn_ray(direction, ray.time);
+    match world.hit(&occlusion_ray, 0.0, radius) {
+        Some(_) => Color::BLACK,
+        None => Color::WHITE,
+    }
+}
+
+// The outward normal with its axes mapped from [-1, 1] to [0, 1].
+pub fn normals(ray: &Ray, world: &dyn Hittable) -> Color {
+    match world.hit(ray, 0.0, Float::MAX) {
+        Some(hit_record) => {
+            let normal = outward_normal(&hit_record);
+            Color::new(
+                to_f32(0.5 * (normal.x() + 1.0)),
+                to_f32(0.5 * (normal.y() + 1.0)),
+                to_f32(0.5 * (normal.z() + 1.0)),
+            )
+        }
+        None => Color::BLACK,
+    }
+}
+
+// Surface coordinates as red and green.
+pub fn uv(ray: &Ray, world: &dyn Hittable) -> Color {
+    match world.hit(ray, 0.0, Float::MAX) {
+        Some(hit_record) => Color::new(to_f32(hit_record.uv[0]), to_f32(hit_record.uv[1]), 0.0),
+        None => Color::BLACK,
+    }
+}
+
+// The weights of the three vertices as red, green and blue on triangles, grey elsewhere.
+pub fn barycentrics(ray: &Ray, world: &dyn Hittable) -> Color {
+    match world.hit(ray, 0.0, Float::MAX) {
+        Some(hit_record) => match hit_record.barycentrics {
+            Some([b0, b1, b2]) => Color::new(to_f32(b0), to_f32(b1), to_f32(b2)),
+            None => Color::new(0.5, 0.5, 0.5),
+        },
+        None => Color::BLACK,
+    }
+}
+
+// Albedo of the first hit, darkened where the surface turns away from the camera. Lights show
+// their color.
+pub fn flat(ray: &Ray, world: &dyn Hittable, sky: &Sky) -> Color {
+    match world.hit(ray, 0.0, Float::MAX) {
+        Some(hit_record) => flat_shade(ray, &hit_record),
+        None => sky.radiance(&ray.direction),
+    }
+}
+
+// The flat shade with the edges of triangles drawn black over it.
+pub fn wireframe(ray: &Ray, world: &dyn Hittable, sky: &Sky) -> Color {
+    match world.hit(ray, 0.0, Float::MAX) {
+        Some(hit_record) => match hit_record.barycentrics {
+            Some(barycentrics) if barycentrics.iter().any(|b| *b < WIREFRAME_WIDTH) => Color::BLACK,
+            _ => flat_shade(ray, &hit_record),
+        },
+        None => sky.radiance(&ray.direction),
+    }
+}
+
+// Blue where the acceleration structure finds the hit quickly, through green to red where it
+// tests `HEATMAP_MAX_COST` boxes and primitives or more.
+pub fn traversal_cost(ray: &Ray, world: &dyn Hittable) -> Color {
+    let cost = world.traversal_cost(ray, 0.0, Float::MAX) as f32;
+    let heat = (cost / HEATMAP_MAX_COST).min(1.0);
+
+    let blue = Color::new(0.0, 0.0, 1.0);
+    let green = Color::new(0.0, 1.0, 0.0);
+    let red = Color::new(1.0, 0.0, 0.0);
+    if heat < 0.5 {
+        blue.lerp(&green, 2.0 * heat)
+    } else {
+        green.lerp(&red, 2.0 * heat - 1.0)
+    }
+}
+
+fn flat_shade(ray: &Ray, hit_record: &HitRecord) -> Color {
+    let emission = hit_record.material.emission();
+    if !emission.is_black() {
+        return emission / emission.max_component();
+    }
+    let facing = dot(&hit_record.normal, &ray.direction.normalize()).abs();
+    to_f32(facing) * hit_record.material.albedo()
+}
+
+fn outward_normal(hit_record: &HitRecord) -> Vec3 {
+    let normal = Vec3::from(hit_record.normal);
+    if hit_record.is_front_face {
+        normal
+    } else {
+        -normal
+    }
+}
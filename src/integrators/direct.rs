@@ -0,0 +1,129 @@
+use crate::{
+    color::Color,
+    error::Error,
+    float::{to_f32, Float},
+    hit_record::HitRecord,
+    hittables::hittable::Hittable,
+    lights::{light::LightSample, light_sampling::LightSampling, light_set::LightSet},
+    ray::{spawn_ray_to, Ray},
+    scene::Scene,
+    vector::{dot, Vec3},
+};
+
+// Light only straight from the emitters: what the camera sees of them directly or through
+// mirrors and glass, and one light sample at the first diffuse hit. Fast, and shows what the
+// lights alone do to a scene.
+pub struct DirectLighting<'a> {
+    world: &'a dyn Hittable,
+    lights: LightSet<'a>,
+    max_depth: u32,
+}
+
+impl<'a> DirectLighting<'a> {
+    // `world` is an acceleration structure over the hittables of `scene`. Camera paths follow
+    // at most `max_depth` specular bounces.
+    pub fn new(
+        world: &'a dyn Hittable,
+        scene: &'a Scene,
+        max_depth: u32,
+        light_sampling: LightSampling,
+        time_start: Float,
+        time_end: Float,
+    ) -> Result<DirectLighting<'a>, Error> {
+        let bounds = world.bounding_box(time_start, time_end).ok_or_else(|| {
+            Error::InvalidGeometry(String::from("direct lighting needs a bounded scene"))
+        })?;
+
+        Ok(DirectLighting {
+            world,
+            lights: LightSet::new(scene, &bounds, light_sampling, time_start, time_end),
+            max_depth,
+        })
+    }
+
+    pub fn radiance(&self, ray: &Ray) -> Color {
+        let mut ray = *ray;
+        let mut radiance = Color::BLACK;
+        let mut beta = Color::WHITE;
+
+        for _ in 0..=self.max_depth {
+            let hit_record = match self.world.hit(&ray, 0.0, Float::MAX) {
+                Some(hit_record) => hit_record,
+                None => {
+                    if let Some(sky) = self.lights.sky() {
+                        let direction = ray.direction.normalize();
+                        radiance += beta * self.lights.get(sky).radiance(None, &-direction);
+                    }
+                    return radiance;
+                }
+            };
+            let outgoing = -ray.direction.normalize();
+            if hit_record.is_front_face {
+                radiance += beta
+                    * hit_record
+                        .material
+                        .emission_towards(&hit_record.normal, &outgoing);
+            }
+
+            if !hit_record.material.is_specular() {
+                return radiance
+                    + beta
+                        * sample_light(self.world, &self.lights, &hit_record, &outgoing, ray.time);
+            }
+
+            let (attenuation, scattered_ray) = match hit_record.material.scatter(&ray, &hit_record)
+            {
+                Some(scattered) => scattered,
+                None => return radiance,
+            };
+            beta *= attenuation;
+            ray = scattered_ray;
+        }
+        radiance
+    }
+}
+
+// Light arriving at the hit from one light sample towards `outgoing`, next event estimation.
+pub fn sample_light(
+    world: &dyn Hittable,
+    lights: &LightSet,
+    hit_record: &HitRecord,
+    outgoing: &Vec3,
+    time: Float,
+) -> Color {
+    let (index, selection_pdf) =
+        match lights.pick_towards(&hit_record.origin, Some(&hit_record.normal)) {
+            Some(picked) => picked,
+            None => return Color::BLACK,
+        };
+    let sample = match lights.get(index).sample_incident(&hit_record.origin, time) {
+        Some(sample) if sample.pdf > 0.0 && !sample.radiance.is_black() => sample,
+        _ => return Color::BLACK,
+    };
+
+    let incoming = (sample.point - hit_record.origin).normalize();
+    let bsdf = hit_record.material.eval(hit_record, outgoing, &incoming);
+    if bsdf.is_black() || !unoccluded(world, hit_record, &sample, time) {
+        return Color::BLACK;
+    }
+    let cos_theta = dot(&hit_record.normal, &incoming).abs();
+    to_f32(cos_theta / (sample.pdf * selection_pdf)) * (bsdf * sample.radiance)
+}
+
+fn unoccluded(
+    world: &dyn Hittable,
+    hit_record: &HitRecord,
+    sample: &LightSample,
+    time: Float,
+) -> bool {
+    let ray = spawn_ray_to(
+        &hit_record.origin,
+        &hit_record.error,
+        Some(&hit_record.geometric_normal),
+        &sample.point,
+        &sample.error,
+        sample.normal.as_ref(),
+        time,
+    );
+    world.hit(&ray, 0.0, 1.0).is_none()
+}
@@ -0,0 +1,309 @@
+use std::{cell::RefCell, rc::Rc};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    cameras::camera::Camera,
+    color::Color,
+    error::Error,
+    float::{consts::PI, to_f32, Float},
+    hittables::hittable::Hittable,
+    lights::sky_light::Sky,
+    renderer::{calculate_color, PathDepths},
+    sampler::{self, Sampler},
+};
+
+// How the chains start and how far their mutations move.
+#[derive(Copy, Clone, Debug)]
+pub struct MetropolisSettings {
+    // Independent paths traced to estimate the brightness of the image and seed the chains.
+    pub bootstrap_samples: usize,
+    pub chains: usize,
+    // Chance a mutation draws a whole new path instead of perturbing the current one.
+    pub large_step_probability: Float,
+    // Standard deviation of the small perturbations in primary sample space.
+    pub sigma: Float,
+}
+
+impl Default for MetropolisSettings {
+    fn default() -> Self {
+        MetropolisSettings {
+            bootstrap_samples: 100_000,
+            chains: 1000,
+            large_step_probability: 0.3,
+            sigma: 0.01,
+        }
+    }
+}
+
+// A path sample of the chain: where it lands on the image and what it carries there.
+#[derive(Copy, Clone)]
+struct PathSample {
+    u: Float,
+    v: Float,
+    radiance: Color,
+}
+
+impl PathSample {
+    // The brightness the chains visit paths in proportion to.
+    fn contribution(&self) -> Float {
+        self.radiance.luminance() as Float
+    }
+}
+
+#[derive(Copy, Clone)]
+struct PrimarySample {
+    value: Float,
+    // Iteration that last set the value, smaller steps are only applied when it's asked for.
+    last_modification: u64,
+    value_backup: Float,
+    modification_backup: u64,
+}
+
+// The numbers of a path as a point in the unit hypercube, mutated lazily: a number is only
+// perturbed when the path asks for it, by all the small steps it missed at once (pbrt-v3 16.4).
+pub struct PrimarySampleSpace {
+    random: StdRng,
+    samples: Vec<PrimarySample>,
+    index: usize,
+    iteration: u64,
+    large_step: bool,
+    last_large_step: u64,
+    large_step_probability: Float,
+    sigma: Float,
+}
+
+impl PrimarySampleSpace {
+    // The same seed replays the same first path, which is how the chains start from the
+    // bootstrap paths.
+    pub fn new(seed: u64, settings: &MetropolisSettings) -> PrimarySampleSpace {
+        PrimarySampleSpace {
+            random: StdRng::seed_from_u64(seed),
+            samples: Vec::new(),
+            index: 0,
+            iteration: 0,
+            large_step: true,
+            last_large_step: 0,
+            large_step_probability: settings.large_step_probability,
+            sigma: settings.sigma,
+        }
+    }
+
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = self.random.gen::<Float>() < self.large_step_probability;
+        self.index = 0;
+    }
+
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    pub fn reject(&mut self) {
+        for sample in &mut self.samples {
+            if sample.last_modification == self.iteration {
+                sample.value = sample.value_backup;
+                sample.last_modification = sample.modification_backup;
+            }
+        }
+        self.iteration -= 1;
+    }
+
+    fn ensure_ready(&mut self, index: usize) {
+        // Numbers the chain never asked for are as random as after the last large step, small
+        // steps from zero would stall the rejection loops of sphere and disc sampling.
+        while self.samples.len() <= index {
+            let value = self.random.gen();
+            self.samples.push(PrimarySample {
+                value,
+                last_modification: self.last_large_step,
+                value_backup: value,
+                modification_backup: self.last_large_step,
+            });
+        }
+        let sample = &mut self.samples[index];
+
+        // A large step after the last change replaced everything with new numbers.
+        if sample.last_modification < self.last_large_step {
+            sample.value = self.random.gen();
+            sample.last_modification = self.last_large_step;
+        }
+
+        sample.value_backup = sample.value;
+        sample.modification_backup = sample.last_modification;
+        if self.large_step {
+            sample.value = self.random.gen();
+        } else {
+            // The missed steps add up to one normal step with their summed variance.
+            let steps = (self.iteration - sample.last_modification) as Float;
+            let radius = (-2.0 * (1.0 - self.random.gen::<Float>()).ln()).sqrt();
+            let normal = radius * (2.0 * PI * self.random.gen::<Float>()).cos();
+            sample.value += normal * self.sigma * steps.sqrt();
+            sample.value -= sample.value.floor();
+            // Rounding may wrap a tiny negative value to exactly one.
+            if sample.value >= 1.0 {
+                sample.value = 0.0;
+            }
+        }
+        sample.last_modification = self.iteration;
+    }
+}
+
+impl Sampler for PrimarySampleSpace {
+    fn next_sample(&mut self) -> Float {
+        let index = self.index;
+        self.index += 1;
+        self.ensure_ready(index);
+        self.samples[index].value
+    }
+}
+
+// Primary sample space Metropolis light transport (Kelemen et al. 2002, pbrt-v3 16.4). Markov
+// chains wander through the random numbers the path tracer builds its paths from, staying on
+// paths in proportion to their brightness, so once a chain finds light through a difficult
+// route it keeps exploring the paths around it. Every mutation splats both the proposed and
+// the current path weighed by the chance of accepting, and the bootstrap estimate of the mean
+// brightness scales the visit counts back to radiance.
+pub struct MetropolisLightTransport<'a> {
+    world: &'a dyn Hittable,
+    camera: &'a dyn Camera,
+    sky: &'a Sky,
+    depths: PathDepths,
+    shutter_time: Float,
+    settings: MetropolisSettings,
+}
+
+impl<'a> MetropolisLightTransport<'a> {
+    pub fn new(
+        world: &'a dyn Hittable,
+        camera: &'a dyn Camera,
+        sky: &'a Sky,
+        depths: PathDepths,
+        shutter_time: Float,
+        settings: MetropolisSettings,
+    ) -> Result<MetropolisLightTransport<'a>, Error> {
+        if settings.bootstrap_samples == 0 || settings.chains == 0 {
+            return Err(Error::InvalidSettings(String::from(
+                "bootstrap samples and chains must be positive",
+            )));
+        }
+        if !(0.0..=1.0).contains(&settings.large_step_probability) {
+            return Err(Error::InvalidSettings(format!(
+                "large step probability {} must be in [0, 1]",
+                settings.large_step_probability
+            )));
+        }
+        if !(settings.sigma > 0.0 && settings.sigma.is_finite()) {
+            return Err(Error::InvalidSettings(format!(
+                "mutation size {} must be positive",
+                settings.sigma
+            )));
+        }
+
+        Ok(MetropolisLightTransport {
+            world,
+            camera,
+            sky,
+            depths,
+            shutter_time,
+            settings,
+        })
+    }
+
+    pub fn settings(&self) -> &MetropolisSettings {
+        &self.settings
+    }
+
+    // Traces one path from the current numbers of the sampler.
+    fn evaluate(&self, space: &Rc<RefCell<PrimarySampleSpace>>) -> PathSample {
+        sampler::with_sampler(space.clone(), || {
+            let mut random = sampler::rng();
+            let u = random.gen::<Float>();
+            let v = random.gen::<Float>();
+            let time = random.gen::<Float>() * self.shutter_time;
+
+            let radiance = match self.camera.get_ray(u, v) {
+                Some(mut ray) => {
+                    ray.time = time;
+                    calculate_color(&ray, self.world, self.sky, &self.depths)
+                }
+                None => Color::BLACK,
+            };
+            // A broken path would poison the whole chain.
+            let radiance = if radiance.luminance().is_finite() {
+                radiance
+            } else {
+                Color::BLACK
+            };
+            PathSample { u, v, radiance }
+        })
+    }
+
+    // Mean brightness of the image and the cumulative brightness of the bootstrap paths, which
+    // are replayed from their index as seed.
+    pub fn bootstrap(&self) -> (Float, Vec<Float>) {
+        let mut cumulative = Vec::with_capacity(self.settings.bootstrap_samples);
+        let mut total = 0.0;
+        for seed in 0..self.settings.bootstrap_samples {
+            let space = Rc::new(RefCell::new(PrimarySampleSpace::new(
+                seed as u64,
+                &self.settings,
+            )));
+            total += self.evaluate(&space).contribution();
+            cumulative.push(total);
+        }
+        (total / self.settings.bootstrap_samples as Float, cumulative)
+    }
+
+    // Runs one chain from a bootstrap path picked by brightness, splatting `mutations` times.
+    pub fn run_chain(
+        &self,
+        cumulative: &[Float],
+        mutations: usize,
+        splat: &mut impl FnMut(Float, Float, Color),
+    ) {
+        let total = match cumulative.last() {
+            Some(total) if *total > 0.0 => *total,
+            _ => return,
+        };
+        let target = sampler::rng().gen::<Float>() * total;
+        let seed = cumulative
+            .partition_point(|sum| *sum <= target)
+            .min(cumulative.len() - 1);
+
+        let space = Rc::new(RefCell::new(PrimarySampleSpace::new(
+            seed as u64,
+            &self.settings,
+        )));
+        let mut current = self.evaluate(&space);
+        let mut random = sampler::rng();
+
+        for _ in 0..mutations {
+            space.borrow_mut().start_iteration();
+            let proposed = self.evaluate(&space);
+
+            let acceptance = if current.contribution() > 0.0 {
+                (proposed.contribution() / current.contribution()).min(1.0)
+            } else {
+                1.0
+            };
+            if acceptance > 0.0 {
+                let weight = acceptance / proposed.contribution();
+                splat(proposed.u, proposed.v, to_f32(weight) * proposed.radiance);
+            }
+            if current.contribution() > 0.0 {
+                let weight = (1.0 - acceptance) / current.contribution();
+                splat(current.u, current.v, to_f32(weight) * current.radiance);
+            }
+
+            if random.gen::<Float>() < acceptance {
+                current = proposed;
+                space.borrow_mut().accept();
+            } else {
+                space.borrow_mut().reject();
+            }
+        }
+    }
+}
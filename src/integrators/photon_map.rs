@@ -0,0 +1,100 @@
+use crate::{
+    color::Color,
+    float::Float,
+    hittables::aabb::Aabb,
+    vector::{Point3, Vec3},
+};
+
+// Light deposited on a surface by a photon path.
+pub struct Photon {
+    pub position: Point3,
+    // Towards where the photon came from.
+    pub incoming: Vec3,
+    pub power: Color,
+}
+
+// Balanced kd-tree over photons, stored implicitly: the photon in the middle of every range
+// splits it along `axes` at its index, with the photons on either side in the halves.
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>) -> PhotonMap {
+        let mut axes = vec![0; photons.len()];
+        build(&mut photons, &mut axes);
+        PhotonMap { photons, axes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    // Calls `visit` with every photon closer to `centre` than `radius`.
+    pub fn for_each_within(&self, centre: &Point3, radius: Float, mut visit: impl FnMut(&Photon)) {
+        search(&self.photons, &self.axes, centre, radius, &mut visit);
+    }
+}
+
+fn build(photons: &mut [Photon], axes: &mut [usize]) {
+    if photons.is_empty() {
+        return;
+    }
+
+    // Split along the widest extent, at the median.
+    let bounds = photons.iter().fold(Aabb::empty(), |bounds, photon| {
+        bounds.include(&photon.position)
+    });
+    let extent = bounds.diagonal();
+    let axis = (0..3)
+        .max_by(|a, b| extent.data[*a].total_cmp(&extent.data[*b]))
+        .unwrap_or(0);
+    let middle = photons.len() / 2;
+    photons.select_nth_unstable_by(middle, |a, b| {
+        a.position.data[axis].total_cmp(&b.position.data[axis])
+    });
+    axes[middle] = axis;
+
+    let (photons_below, photons_above) = photons.split_at_mut(middle);
+    let (axes_below, axes_above) = axes.split_at_mut(middle);
+    build(photons_below, axes_below);
+    build(&mut photons_above[1..], &mut axes_above[1..]);
+}
+
+fn search(
+    photons: &[Photon],
+    axes: &[usize],
+    centre: &Point3,
+    radius: Float,
+    visit: &mut impl FnMut(&Photon),
+) {
+    if photons.is_empty() {
+        return;
+    }
+
+    let middle = photons.len() / 2;
+    let photon = &photons[middle];
+    if (photon.position - *centre).squared_length() <= radius * radius {
+        visit(photon);
+    }
+
+    let axis = axes[middle];
+    let offset = centre.data[axis] - photon.position.data[axis];
+    if offset <= radius {
+        search(&photons[..middle], &axes[..middle], centre, radius, visit);
+    }
+    if offset >= -radius {
+        search(
+            &photons[middle + 1..],
+            &axes[middle + 1..],
+            centre,
+            radius,
+            visit,
+        );
+    }
+}
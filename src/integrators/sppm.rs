@@ -0,0 +1,284 @@
+use rand::Rng;
+
+use crate::{
+    color::Color,
+    error::Error,
+    float::{consts::PI, to_f32, Float},
+    hit_record::HitRecord,
+    hittables::hittable::Hittable,
+    lights::{light_sampling::LightSampling, light_set::LightSet},
+    ray::Ray,
+    sampler,
+    scene::Scene,
+    vector::{dot, Vec3},
+};
+
+use super::{
+    direct::sample_light,
+    photon_map::{Photon, PhotonMap},
+};
+
+// Photon paths may end at random from this bounce on.
+const ROULETTE_DEPTH: u32 = 3;
+
+// How many photons every iteration traces and how far apart they may land to be gathered.
+#[derive(Copy, Clone, Debug)]
+pub struct PhotonSettings {
+    pub photons_per_iteration: usize,
+    // Gather radius of the first iteration, in scene units. It shrinks as photons pile up.
+    pub initial_radius: Float,
+    // Fraction of the new photons kept every iteration, alpha in the paper.
+    pub alpha: Float,
+}
+
+impl Default for PhotonSettings {
+    fn default() -> Self {
+        PhotonSettings {
+            photons_per_iteration: 200_000,
+            initial_radius: 0.05,
+            alpha: 2.0 / 3.0,
+        }
+    }
+}
+
+// Running estimate of one pixel over the iterations.
+#[derive(Clone)]
+pub struct PixelEstimate {
+    // Light that reached the camera without photons, emitters seen directly or through
+    // specular bounces and lights sampled from the visible points, summed.
+    direct: Color,
+    radius: Float,
+    // Photons gathered so far, N in the paper, fractional once the radius shrank.
+    photon_count: Float,
+    // Their flux, tau in the paper.
+    flux: Color,
+}
+
+// Where a camera path of this iteration gathers photons, its first non-specular hit.
+pub struct VisiblePoint<'h> {
+    hit_record: HitRecord<'h>,
+    // Towards the camera.
+    outgoing: Vec3,
+    // Attenuation of the specular bounces before it.
+    beta: Color,
+}
+
+// Stochastic progressive photon mapping (Hachisuka and Jensen 2009, pbrt-v3 16.2). Every
+// iteration traces one camera path per pixel to a visible point, then shoots photons from the
+// lights and stores where they land in a kd-tree. Visible points gather the photons within
+// their radius, which shrinks every iteration, so the blur of the density estimate vanishes
+// while its noise averages out. Caustics come out sharp, as photons reach the ground through
+// glass just as easily as anywhere else.
+pub struct PhotonMapper<'a> {
+    world: &'a dyn Hittable,
+    lights: LightSet<'a>,
+    max_depth: u32,
+    settings: PhotonSettings,
+}
+
+impl<'a> PhotonMapper<'a> {
+    // `world` is an acceleration structure over the hittables of `scene`. Camera and photon
+    // paths have at most `max_depth` bounces.
+    pub fn new(
+        world: &'a dyn Hittable,
+        scene: &'a Scene,
+        max_depth: u32,
+        settings: PhotonSettings,
+        light_sampling: LightSampling,
+        time_start: Float,
+        time_end: Float,
+    ) -> Result<PhotonMapper<'a>, Error> {
+        if settings.photons_per_iteration == 0 {
+            return Err(Error::InvalidSettings(String::from(
+                "photons per iteration must be positive",
+            )));
+        }
+        if !(settings.initial_radius > 0.0 && settings.initial_radius.is_finite()) {
+            return Err(Error::InvalidSettings(format!(
+                "photon radius {} must be positive",
+                settings.initial_radius
+            )));
+        }
+        if !(settings.alpha > 0.0 && settings.alpha <= 1.0) {
+            return Err(Error::InvalidSettings(format!(
+                "photon alpha {} must be in (0, 1]",
+                settings.alpha
+            )));
+        }
+        let bounds = world.bounding_box(time_start, time_end).ok_or_else(|| {
+            Error::InvalidGeometry(String::from("photon mapping needs a bounded scene"))
+        })?;
+
+        Ok(PhotonMapper {
+            world,
+            lights: LightSet::new(scene, &bounds, light_sampling, time_start, time_end),
+            max_depth,
+            settings,
+        })
+    }
+
+    pub fn pixel_estimates(&self, count: usize) -> Vec<PixelEstimate> {
+        vec![
+            PixelEstimate {
+                direct: Color::BLACK,
+                radius: self.settings.initial_radius,
+                photon_count: 0.0,
+                flux: Color::BLACK,
+            };
+            count
+        ]
+    }
+
+    // Follows the camera ray through specular bounces to its visible point, adding the light
+    // it finds on the way to `estimate`.
+    pub fn trace_camera(
+        &self,
+        ray: &Ray,
+        estimate: &mut PixelEstimate,
+    ) -> Option<VisiblePoint<'a>> {
+        let mut ray = *ray;
+        let mut beta = Color::WHITE;
+
+        for _ in 0..=self.max_depth {
+            let hit_record = match self.world.hit(&ray, 0.0, Float::MAX) {
+                Some(hit_record) => hit_record,
+                None => {
+                    if let Some(sky) = self.lights.sky() {
+                        let direction = ray.direction.normalize();
+                        estimate.direct += beta * self.lights.get(sky).radiance(None, &-direction);
+                    }
+                    return None;
+                }
+            };
+            let outgoing = -ray.direction.normalize();
+            if hit_record.is_front_face {
+                estimate.direct += beta
+                    * hit_record
+                        .material
+                        .emission_towards(&hit_record.normal, &outgoing);
+            }
+            if !hit_record.material.is_specular() {
+                estimate.direct +=
+                    beta * sample_light(self.world, &self.lights, &hit_record, &outgoing, ray.time);
+                return Some(VisiblePoint {
+                    hit_record,
+                    outgoing,
+                    beta,
+                });
+            }
+
+            let (attenuation, scattered_ray) = hit_record.material.scatter(&ray, &hit_record)?;
+            beta *= attenuation;
+            ray = scattered_ray;
+        }
+        None
+    }
+
+    // Shoots the photons of one iteration at random times in [0..time_end]. Where they land
+    // right from the light isn't stored, the visible points sample that light directly.
+    pub fn trace_photons(&self, time_end: Float) -> PhotonMap {
+        let mut random = sampler::rng();
+        let mut photons = Vec::new();
+
+        for _ in 0..self.settings.photons_per_iteration {
+            let (index, selection_pdf) = match self.lights.pick() {
+                Some(picked) => picked,
+                None => break,
+            };
+            let emission = match self
+                .lights
+                .get(index)
+                .sample_emission(random.gen::<Float>() * time_end)
+            {
+                Some(emission) => emission,
+                None => continue,
+            };
+            if emission.pdf_position == 0.0 || emission.pdf_direction == 0.0 {
+                continue;
+            }
+
+            let mut ray = emission.ray;
+            let cos_theta = emission
+                .normal
+                .map_or(1.0, |normal| dot(&normal, &ray.direction.normalize()).abs());
+            let mut power = to_f32(
+                cos_theta / (selection_pdf * emission.pdf_position * emission.pdf_direction),
+            ) * emission.radiance;
+
+            for depth in 0..self.max_depth {
+                let hit_record = match self.world.hit(&ray, 0.0, Float::MAX) {
+                    Some(hit_record) => hit_record,
+                    None => break,
+                };
+                if depth > 0 && !hit_record.material.is_specular() {
+                    photons.push(Photon {
+                        position: hit_record.origin,
+                        incoming: -ray.direction.normalize(),
+                        power,
+                    });
+                }
+
+                let (attenuation, scattered_ray) =
+                    match hit_record.material.scatter(&ray, &hit_record) {
+                        Some(scattered) => scattered,
+                        None => break,
+                    };
+                let scattered_power = power * attenuation;
+                if scattered_power.is_black() {
+                    break;
+                }
+
+                // Survivors keep the power they had, the rest ends in proportion to what the
+                // bounce absorbed.
+                if depth + 1 >= ROULETTE_DEPTH {
+                    let survival = scattered_power.max_component() / power.max_component();
+                    if survival < 1.0 {
+                        if random.gen::<f32>() >= survival {
+                            break;
+                        }
+                        power = scattered_power / survival;
+                    } else {
+                        power = scattered_power;
+                    }
+                } else {
+                    power = scattered_power;
+                }
+                ray = scattered_ray;
+            }
+        }
+
+        PhotonMap::new(photons)
+    }
+
+    // Adds the photons around the visible point to the pixel and shrinks its radius, progressive
+    // radiance estimation of the paper.
+    pub fn gather(&self, map: &PhotonMap, visible: &VisiblePoint, estimate: &mut PixelEstimate) {
+        let hit_record = &visible.hit_record;
+        let mut flux = Color::BLACK;
+        let mut count = 0.0;
+        map.for_each_within(&hit_record.origin, estimate.radius, |photon| {
+            let bsdf = hit_record
+                .material
+                .eval(hit_record, &visible.outgoing, &photon.incoming);
+            flux += bsdf * photon.power;
+            count += 1.0;
+        });
+        if count == 0.0 {
+            return;
+        }
+
+        let photon_count = estimate.photon_count + self.settings.alpha * count;
+        let radius = estimate.radius * (photon_count / (estimate.photon_count + count)).sqrt();
+        let shrink = to_f32((radius * radius) / (estimate.radius * estimate.radius));
+        estimate.flux = shrink * (estimate.flux + visible.beta * flux);
+        estimate.photon_count = photon_count;
+        estimate.radius = radius;
+    }
+
+    // Radiance of the pixel after `iterations` iterations.
+    pub fn radiance(&self, estimate: &PixelEstimate, iterations: u32) -> Color {
+        let photons = iterations as Float * self.settings.photons_per_iteration as Float;
+        let area = PI * estimate.radius * estimate.radius;
+        estimate.direct / (iterations as f32) + estimate.flux / to_f32(photons * area)
+    }
+}
@@ -0,0 +1,161 @@
+use std::ops;
+
+use crate::float::Float;
+
+// Range certain to hold the exact result of the operations that produced it. Every operation
+// rounds its bounds outwards, so the range grows with the rounding error of the computation.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Interval {
+    low: Float,
+    high: Float,
+}
+
+impl Interval {
+    pub fn new(value: Float) -> Interval {
+        Interval {
+            low: value,
+            high: value,
+        }
+    }
+
+    // `value` give or take `error`.
+    pub fn with_error(value: Float, error: Float) -> Interval {
+        if error == 0.0 {
+            return Interval::new(value);
+        }
+        Interval {
+            low: (value - error).next_down(),
+            high: (value + error).next_up(),
+        }
+    }
+
+    pub fn low(&self) -> Float {
+        self.low
+    }
+
+    pub fn high(&self) -> Float {
+        self.high
+    }
+
+    pub fn midpoint(&self) -> Float {
+        (self.low + self.high) / 2.0
+    }
+
+    pub fn contains_zero(&self) -> bool {
+        self.low <= 0.0 && self.high >= 0.0
+    }
+
+    // Tighter than `self * self`, which can't know both factors are the same value.
+    pub fn square(self) -> Interval {
+        let (low, high) = (self.low.abs(), self.high.abs());
+        let (low, high) = (low.min(high), low.max(high));
+        Interval {
+            low: if self.contains_zero() {
+                0.0
+            } else {
+                (low * low).next_down()
+            },
+            high: (high * high).next_up(),
+        }
+    }
+
+    pub fn sqrt(self) -> Interval {
+        Interval {
+            low: self.low.max(0.0).sqrt().next_down().max(0.0),
+            high: self.high.max(0.0).sqrt().next_up(),
+        }
+    }
+
+    fn from_products(products: [Float; 4]) -> Interval {
+        Interval {
+            low: products
+                .into_iter()
+                .fold(Float::INFINITY, Float::min)
+                .next_down(),
+            high: products
+                .into_iter()
+                .fold(Float::NEG_INFINITY, Float::max)
+                .next_up(),
+        }
+    }
+}
+
+impl From<Float> for Interval {
+    fn from(value: Float) -> Self {
+        Interval::new(value)
+    }
+}
+
+impl ops::Add for Interval {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Interval {
+            low: (self.low + rhs.low).next_down(),
+            high: (self.high + rhs.high).next_up(),
+        }
+    }
+}
+
+impl ops::Sub for Interval {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Interval {
+            low: (self.low - rhs.high).next_down(),
+            high: (self.high - rhs.low).next_up(),
+        }
+    }
+}
+
+impl ops::Mul for Interval {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Interval::from_products([
+            self.low * rhs.low,
+            self.low * rhs.high,
+            self.high * rhs.low,
+            self.high * rhs.high,
+        ])
+    }
+}
+
+impl ops::Mul<Interval> for Float {
+    type Output = Interval;
+
+    fn mul(self, rhs: Interval) -> Self::Output {
+        Interval::new(self) * rhs
+    }
+}
+
+impl ops::Div for Interval {
+    type Output = Self;
+
+    // Unbounded when the divisor may be zero.
+    fn div(self, rhs: Self) -> Self::Output {
+        if rhs.contains_zero() {
+            return Interval {
+                low: Float::NEG_INFINITY,
+                high: Float::INFINITY,
+            };
+        }
+        Interval::from_products([
+            self.low / rhs.low,
+            self.low / rhs.high,
+            self.high / rhs.low,
+            self.high / rhs.high,
+        ])
+    }
+}
+
+impl ops::Neg for Interval {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Interval {
+            low: -self.high,
+            high: -self.low,
+        }
+    }
+}
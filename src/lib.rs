@@ -0,0 +1,28 @@
+pub mod cameras;
+pub mod color;
+pub mod denoiser;
+pub mod error;
+pub mod exposure;
+pub mod float;
+pub mod framebuffer;
+pub mod gltf_scene;
+pub mod hit_record;
+pub mod hittables;
+pub mod image;
+pub mod integrators;
+pub mod interval;
+pub mod lights;
+pub mod materials;
+pub mod ray;
+pub mod renderer;
+pub mod sampler;
+pub mod scene;
+pub mod simd;
+pub mod spectrum;
+pub mod transform;
+pub mod vector;
+
+pub use error::Error;
+pub use framebuffer::Framebuffer;
+pub use renderer::{CancellationToken, Progress, RenderSettings, Renderer};
+pub use scene::Scene;
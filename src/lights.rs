@@ -0,0 +1,8 @@
+pub mod area_light;
+pub mod ies;
+pub mod light;
+pub mod light_bvh;
+pub mod light_sampling;
+pub mod light_set;
+pub mod punctual_light;
+pub mod sky_light;
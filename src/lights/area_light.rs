@@ -0,0 +1,176 @@
+use crate::{
+    color::Color,
+    float::{
+        consts::{FRAC_1_PI, PI},
+        Float,
+    },
+    hittables::{
+        aabb::Aabb,
+        hittable::{Hittable, Primitive, SurfaceSample},
+    },
+    materials::material::Material,
+    ray::{offset_ray_origin, Ray},
+    vector::{dot, random_on_unit_sphere, Normal3, Point3, Vec3},
+};
+
+use super::{
+    light::{EmissionSample, Light, LightSample},
+    light_bvh::LightBounds,
+};
+
+// Sphere or triangle with an emissive material, lit on its front face.
+pub struct AreaLight<'a> {
+    shape: Primitive<'a>,
+    area: Float,
+    // Covers the motion while the shutter is open.
+    bounds: Aabb,
+}
+
+impl<'a> AreaLight<'a> {
+    // None unless the primitive is a sphere or a triangle whose material emits.
+    pub fn new(shape: Primitive<'a>, time_start: Float, time_end: Float) -> Option<AreaLight<'a>> {
+        let (emission, area, bounds) = match shape {
+            Primitive::Sphere(sphere) => (
+                sphere.material().emission(),
+                sphere.area(),
+                sphere.bounding_box(time_start, time_end),
+            ),
+            Primitive::Triangle(triangle) => (
+                triangle.material.emission(),
+                triangle.area(),
+                triangle.bounding_box(time_start, time_end),
+            ),
+            Primitive::Other => return None,
+        };
+        if emission.is_black() {
+            return None;
+        }
+
+        Some(AreaLight {
+            shape,
+            area,
+            bounds: bounds?,
+        })
+    }
+
+    pub fn material(&self) -> &'a dyn Material {
+        match self.shape {
+            Primitive::Sphere(sphere) => sphere.material(),
+            Primitive::Triangle(triangle) => triangle.material.as_ref(),
+            Primitive::Other => {
+                unreachable!("area lights are only made from spheres and triangles")
+            }
+        }
+    }
+
+    fn sample_surface(&self, time: Float) -> SurfaceSample {
+        match self.shape {
+            Primitive::Sphere(sphere) => sphere.sample_surface(time),
+            Primitive::Triangle(triangle) => triangle.sample_surface(),
+            Primitive::Other => {
+                unreachable!("area lights are only made from spheres and triangles")
+            }
+        }
+    }
+}
+
+impl Light for AreaLight<'_> {
+    fn sample_incident(&self, reference: &Point3, time: Float) -> Option<LightSample> {
+        let surface = self.sample_surface(time);
+        let to_light = surface.point - *reference;
+        let squared_distance = to_light.squared_length();
+        if squared_distance == 0.0 {
+            return None;
+        }
+
+        // The area density by solid angle, the back face doesn't emit.
+        let towards_reference = -to_light / squared_distance.sqrt();
+        let cos_theta = dot(&surface.normal, &towards_reference);
+        if cos_theta <= 0.0 {
+            return None;
+        }
+
+        Some(LightSample {
+            point: surface.point,
+            normal: Some(surface.normal),
+            error: surface.error,
+            radiance: self
+                .material()
+                .emission_towards(&surface.normal, &towards_reference),
+            pdf: squared_distance / (cos_theta * self.area),
+        })
+    }
+
+    fn sample_emission(&self, time: Float) -> Option<EmissionSample> {
+        let surface = self.sample_surface(time);
+
+        // Cosine distributed around the normal, like the diffuse material scatters.
+        let direction = Vec3::from(surface.normal) + random_on_unit_sphere();
+        if direction.is_near_zero() {
+            return None;
+        }
+        let direction = direction.normalize();
+        let (pdf_position, pdf_direction) = self.emission_pdf(Some(&surface.normal), &direction);
+
+        Some(EmissionSample {
+            point: surface.point,
+            normal: Some(surface.normal),
+            error: surface.error,
+            ray: Ray {
+                origin: offset_ray_origin(
+                    &surface.point,
+                    &surface.error,
+                    &surface.normal,
+                    &direction,
+                ),
+                direction,
+                time,
+            },
+            radiance: self
+                .material()
+                .emission_towards(&surface.normal, &direction),
+            pdf_position,
+            pdf_direction,
+        })
+    }
+
+    fn emission_pdf(&self, normal: Option<&Normal3>, direction: &Vec3) -> (Float, Float) {
+        match normal {
+            Some(normal) => (1.0 / self.area, dot(normal, direction).max(0.0) * FRAC_1_PI),
+            None => (0.0, 0.0),
+        }
+    }
+
+    fn radiance(&self, normal: Option<&Normal3>, direction: &Vec3) -> Color {
+        match normal {
+            Some(normal) if dot(normal, direction) > 0.0 => {
+                self.material().emission_towards(normal, direction)
+            }
+            _ => Color::BLACK,
+        }
+    }
+
+    // A profile only dims the emission away from its peak.
+    fn power(&self) -> Float {
+        PI * self.area * self.material().emission().luminance() as Float
+    }
+
+    // Triangles emit into the hemisphere of their normal, spheres everywhere.
+    fn bounds(&self) -> Option<LightBounds> {
+        let (axis, cos_theta_o) = match self.shape {
+            Primitive::Sphere(_) => (Vec3::new(0.0, 0.0, 1.0), -1.0),
+            Primitive::Triangle(triangle) => (Vec3::from(triangle.normal()), 1.0),
+            Primitive::Other => {
+                unreachable!("area lights are only made from spheres and triangles")
+            }
+        };
+
+        Some(LightBounds {
+            bounds: self.bounds,
+            power: self.power(),
+            axis,
+            cos_theta_o,
+            cos_theta_e: 0.0,
+        })
+    }
+}
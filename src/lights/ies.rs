@@ -0,0 +1,357 @@
+use std::{fs, sync::Arc};
+
+use crate::{
+    error::Error,
+    float::{consts::PI, to_f32, Float},
+    vector::{cross, dot, Vec3},
+};
+
+// Type C photometry, the one of architectural fixtures: vertical angles from the nadir of the
+// fixture, horizontal angles around its vertical axis.
+const PHOTOMETRIC_TYPE_C: usize = 1;
+
+// Steps per degree of the numerical integration of the flux.
+const FLUX_STEPS_PER_DEGREE: usize = 1;
+
+// Candela distribution of a light fixture measured by a goniophotometer, from an IES LM-63
+// file (the 1986, 1991, 1995 and 2002 revisions).
+#[derive(Clone, Debug)]
+pub struct IesProfile {
+    // Ascending, in degrees.
+    vertical_angles: Vec<Float>,
+    horizontal_angles: Vec<Float>,
+    // For each horizontal angle the values at all vertical angles, multipliers applied.
+    candela: Vec<Float>,
+    max_candela: Float,
+    flux: Float,
+}
+
+impl IesProfile {
+    pub fn load(path: &str) -> Result<IesProfile, Error> {
+        let text = fs::read_to_string(path)
+            .map_err(|error| Error::io(format!("can't read IES file {}", path), error))?;
+        IesProfile::parse(&text, path)
+    }
+
+    // `path` only names the file in errors.
+    pub fn parse(text: &str, path: &str) -> Result<IesProfile, Error> {
+        let invalid = |message: String| invalid(path, message);
+
+        // Keywords come before the tilt line and the numbers after it.
+        let mut lines = text.lines();
+        let tilt = loop {
+            match lines.next() {
+                Some(line) => {
+                    if let Some(tilt) = line.trim().strip_prefix("TILT=") {
+                        break tilt.trim().to_string();
+                    }
+                }
+                None => return Err(invalid(String::from("missing TILT line"))),
+            }
+        };
+        let mut tokens = Tokens {
+            tokens: lines
+                .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+                .filter(|token| !token.is_empty()),
+            path,
+        };
+
+        // How the output changes as the lamp tilts only matters for fixtures mounted at
+        // another angle than measured.
+        if tilt == "INCLUDE" {
+            tokens.number("lamp to luminaire geometry")?;
+            let count = tokens.count("number of tilt angles")?;
+            for _ in 0..2 * count {
+                tokens.number("tilt angles and multipliers")?;
+            }
+        }
+
+        let _lamps = tokens.number("number of lamps")?;
+        let _lumens_per_lamp = tokens.number("lumens per lamp")?;
+        let multiplier = tokens.number("candela multiplier")?;
+        let vertical_count = tokens.count("number of vertical angles")?;
+        let horizontal_count = tokens.count("number of horizontal angles")?;
+        let photometric_type = tokens.count("photometric type")?;
+        let _units = tokens.number("units type")?;
+        let _width = tokens.number("luminaire width")?;
+        let _length = tokens.number("luminaire length")?;
+        let _height = tokens.number("luminaire height")?;
+        let ballast_factor = tokens.number("ballast factor")?;
+        let ballast_lamp_factor = tokens.number("ballast lamp photometric factor")?;
+        let _watts = tokens.number("input watts")?;
+
+        if photometric_type != PHOTOMETRIC_TYPE_C {
+            return Err(invalid(format!(
+                "photometric type {} isn't supported, only type C (1)",
+                photometric_type
+            )));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(invalid(String::from("no angles measured")));
+        }
+
+        let vertical_angles = (0..vertical_count)
+            .map(|_| tokens.number("vertical angle"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let horizontal_angles = (0..horizontal_count)
+            .map(|_| tokens.number("horizontal angle"))
+            .collect::<Result<Vec<_>, _>>()?;
+        let value_count = vertical_count
+            .checked_mul(horizontal_count)
+            .ok_or_else(|| invalid(String::from("too many angles")))?;
+        let scale = multiplier * ballast_factor * ballast_lamp_factor;
+        let candela = (0..value_count)
+            .map(|_| tokens.number("candela value").map(|value| value * scale))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        for angles in [&vertical_angles, &horizontal_angles] {
+            if angles.windows(2).any(|pair| pair[0] >= pair[1]) {
+                return Err(invalid(String::from("angles must be ascending")));
+            }
+        }
+        if vertical_angles[0] < 0.0 || vertical_angles[vertical_count - 1] > 180.0 {
+            return Err(invalid(String::from("vertical angles must be in [0, 180]")));
+        }
+        if horizontal_angles[0] < 0.0 || horizontal_angles[horizontal_count - 1] > 360.0 {
+            return Err(invalid(String::from(
+                "horizontal angles must be in [0, 360]",
+            )));
+        }
+        if candela
+            .iter()
+            .any(|value| !(*value >= 0.0 && value.is_finite()))
+        {
+            return Err(invalid(String::from(
+                "candela values must be finite and not negative",
+            )));
+        }
+        let max_candela = candela
+            .iter()
+            .fold(0.0, |max: Float, value| max.max(*value));
+        if max_candela == 0.0 {
+            return Err(invalid(String::from("fixture emits no light")));
+        }
+
+        let mut profile = IesProfile {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+            max_candela,
+            flux: 0.0,
+        };
+        profile.flux = profile.integrate_flux();
+        Ok(profile)
+    }
+
+    pub fn max_candela(&self) -> Float {
+        self.max_candela
+    }
+
+    // Luminous intensity at the `vertical` and `horizontal` angles in degrees, interpolated
+    // between the measurements, and zero where the file has none.
+    pub fn candela(&self, vertical: Float, horizontal: Float) -> Float {
+        let vertical_count = self.vertical_angles.len();
+        let (v0, v1, tv) = match interpolation(&self.vertical_angles, vertical) {
+            Some(found) => found,
+            None => return 0.0,
+        };
+
+        let horizontal = self.fold_horizontal(horizontal);
+        let (h0, h1, th) = match interpolation(&self.horizontal_angles, horizontal) {
+            Some(found) => found,
+            // Past the last angle of a full circle, between it and the first.
+            None => {
+                let last = self.horizontal_angles.len() - 1;
+                let first_angle = self.horizontal_angles[0] + 360.0;
+                let gap = first_angle - self.horizontal_angles[last];
+                let angle = if horizontal < self.horizontal_angles[0] {
+                    horizontal + 360.0
+                } else {
+                    horizontal
+                };
+                (last, 0, (angle - self.horizontal_angles[last]) / gap)
+            }
+        };
+
+        let value = |h: usize, v: usize| self.candela[h * vertical_count + v];
+        let lower = value(h0, v0) * (1.0 - tv) + value(h0, v1) * tv;
+        let upper = value(h1, v0) * (1.0 - tv) + value(h1, v1) * tv;
+        lower * (1.0 - th) + upper * th
+    }
+
+    // Intensity towards the unit `direction`, for the fixture hanging with its nadir along the
+    // unit vector `nadir` and horizontal angle 0 along the unit vector `reference`
+    // perpendicular to it. Horizontal angle 90 is along `cross(nadir, reference)`.
+    pub fn candela_towards(&self, direction: &Vec3, nadir: &Vec3, reference: &Vec3) -> Float {
+        let vertical = dot(direction, nadir).clamp(-1.0, 1.0).acos().to_degrees();
+        let side = cross(nadir, reference);
+        let horizontal = dot(direction, &side)
+            .atan2(dot(direction, reference))
+            .to_degrees();
+        self.candela(vertical, horizontal.rem_euclid(360.0))
+    }
+
+    // Luminous flux of the fixture in lumens, the intensity integrated over all directions.
+    pub fn flux(&self) -> Float {
+        self.flux
+    }
+
+    fn integrate_flux(&self) -> Float {
+        let vertical_steps = 180 * FLUX_STEPS_PER_DEGREE;
+        let horizontal_steps = 360 * FLUX_STEPS_PER_DEGREE;
+        let step = 1.0 / FLUX_STEPS_PER_DEGREE as Float;
+        let solid_angle = step.to_radians() * step.to_radians();
+
+        let mut flux = 0.0;
+        for i in 0..vertical_steps {
+            let vertical = (i as Float + 0.5) * step;
+            let sin_vertical = vertical.to_radians().sin();
+            for j in 0..horizontal_steps {
+                let horizontal = (j as Float + 0.5) * step;
+                flux += self.candela(vertical, horizontal) * sin_vertical * solid_angle;
+            }
+        }
+        flux
+    }
+
+    // The horizontal angle in [0, 360) of the measured ones the file's symmetry repeats.
+    fn fold_horizontal(&self, horizontal: Float) -> Float {
+        let first = self.horizontal_angles[0];
+        let last = self.horizontal_angles[self.horizontal_angles.len() - 1];
+        if self.horizontal_angles.len() == 1 {
+            // Rotationally symmetric.
+            first
+        } else if first == 0.0 && last == 90.0 {
+            // Symmetric in each quadrant.
+            let horizontal = horizontal % 180.0;
+            if horizontal > 90.0 {
+                180.0 - horizontal
+            } else {
+                horizontal
+            }
+        } else if first == 0.0 && last == 180.0 {
+            // Symmetric about the 0-180 plane.
+            if horizontal > 180.0 {
+                360.0 - horizontal
+            } else {
+                horizontal
+            }
+        } else if first == 90.0 && last == 270.0 {
+            // Symmetric about the 90-270 plane.
+            if horizontal < 90.0 {
+                180.0 - horizontal
+            } else if horizontal > 270.0 {
+                540.0 - horizontal
+            } else {
+                horizontal
+            }
+        } else {
+            horizontal
+        }
+    }
+}
+
+fn invalid(path: &str, message: String) -> Error {
+    Error::InvalidGeometry(format!("{}: {}", path, message))
+}
+
+// The numbers after the tilt line, `what` says which one is expected in errors.
+struct Tokens<'a, I> {
+    tokens: I,
+    path: &'a str,
+}
+
+impl<'a, I: Iterator<Item = &'a str>> Tokens<'a, I> {
+    fn next(&mut self, what: &str) -> Result<&'a str, Error> {
+        self.tokens
+            .next()
+            .ok_or_else(|| invalid(self.path, format!("file ends before {}", what)))
+    }
+
+    fn number(&mut self, what: &str) -> Result<Float, Error> {
+        let token = self.next(what)?;
+        token
+            .parse()
+            .map_err(|_| invalid(self.path, format!("expected {}, found \"{}\"", what, token)))
+    }
+
+    // Counts are whole numbers small enough to allocate for.
+    fn count(&mut self, what: &str) -> Result<usize, Error> {
+        let token = self.next(what)?;
+        token
+            .parse::<u32>()
+            .map(|count| count as usize)
+            .map_err(|_| {
+                invalid(
+                    self.path,
+                    format!("expected a whole {}, found \"{}\"", what, token),
+                )
+            })
+    }
+}
+
+// Indices of the angles around `angle` and how far it is between them, None outside of them.
+fn interpolation(angles: &[Float], angle: Float) -> Option<(usize, usize, Float)> {
+    let last = angles.len() - 1;
+    if angle < angles[0] || angle > angles[last] {
+        return None;
+    }
+    if last == 0 {
+        return Some((0, 0, 0.0));
+    }
+    let upper = angles.partition_point(|a| *a <= angle).clamp(1, last);
+    let lower = upper - 1;
+    let t = (angle - angles[lower]) / (angles[upper] - angles[lower]);
+    Some((lower, upper, t.clamp(0.0, 1.0)))
+}
+
+// An IES profile attached to a light, shaping how much it emits in each direction.
+#[derive(Clone, Debug)]
+pub struct LightProfile {
+    profile: Arc<IesProfile>,
+    // Turns candela into the factor the light's color is multiplied with.
+    scale: Float,
+    lumens: Option<Float>,
+}
+
+impl LightProfile {
+    // The light keeps its color at the peak of the distribution and is dimmer elsewhere.
+    pub fn relative(profile: Arc<IesProfile>) -> LightProfile {
+        LightProfile {
+            scale: 1.0 / profile.max_candela(),
+            profile,
+            lumens: None,
+        }
+    }
+
+    // The light emits `lumens` in total, taking one unit of intensity as one candela.
+    pub fn with_lumens(profile: Arc<IesProfile>, lumens: Float) -> Result<LightProfile, Error> {
+        if !(lumens > 0.0 && lumens.is_finite()) {
+            return Err(Error::InvalidGeometry(format!(
+                "luminous flux {} must be positive",
+                lumens
+            )));
+        }
+
+        Ok(LightProfile {
+            scale: lumens / profile.flux(),
+            profile,
+            lumens: Some(lumens),
+        })
+    }
+
+    pub fn lumens(&self) -> Option<Float> {
+        self.lumens
+    }
+
+    // Average factor over all directions, for the power of the light.
+    pub fn mean_factor(&self) -> f32 {
+        to_f32(self.scale * self.profile.flux() / (4.0 * PI))
+    }
+
+    // Factor of the light's color towards the unit `direction`, see
+    // `IesProfile::candela_towards` for the orientation.
+    pub fn factor(&self, direction: &Vec3, nadir: &Vec3, reference: &Vec3) -> f32 {
+        to_f32(self.scale * self.profile.candela_towards(direction, nadir, reference))
+    }
+}
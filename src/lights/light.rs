@@ -0,0 +1,73 @@
+use crate::{
+    color::Color,
+    float::Float,
+    ray::Ray,
+    vector::{Normal3, Point3, Vec3},
+};
+
+use super::light_bvh::LightBounds;
+
+// Point on a light picked for connecting a point in the scene to it.
+pub struct LightSample {
+    pub point: Point3,
+    // None for lights without a surface, like the sky.
+    pub normal: Option<Normal3>,
+    pub error: Vec3,
+    // Arriving at the reference point unless something is in the way.
+    pub radiance: Color,
+    // Density by solid angle at the reference point.
+    pub pdf: Float,
+}
+
+// Ray leaving a light, the start of a light path.
+pub struct EmissionSample {
+    pub point: Point3,
+    pub normal: Option<Normal3>,
+    pub error: Vec3,
+    pub ray: Ray,
+    pub radiance: Color,
+    // Density of the origin by area.
+    pub pdf_position: Float,
+    // Density of the direction by solid angle.
+    pub pdf_direction: Float,
+}
+
+pub trait Light {
+    // Samples a point on the light as seen from `reference`, None when it contributes nothing.
+    fn sample_incident(&self, reference: &Point3, time: Float) -> Option<LightSample>;
+
+    fn sample_emission(&self, time: Float) -> Option<EmissionSample>;
+
+    // Densities of `sample_emission` giving a ray that leaves a point with `normal` along the
+    // unit vector `direction`, by area and by solid angle.
+    fn emission_pdf(&self, normal: Option<&Normal3>, direction: &Vec3) -> (Float, Float);
+
+    // Radiance leaving a point with `normal` along the unit vector `direction`.
+    fn radiance(&self, normal: Option<&Normal3>, direction: &Vec3) -> Color;
+
+    // Infinitely far away, its samples are placed just outside of the scene.
+    fn is_infinite(&self) -> bool {
+        false
+    }
+
+    // Emits from a single point or in a single direction, so paths can only reach it by
+    // sampling it and never by hitting it.
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    // Luminance of the total power emitted, for picking bright lights more often. An upper
+    // bound is good enough.
+    fn power(&self) -> Float;
+
+    // Where the light is and which way it emits, None for infinite lights.
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+
+    // Density by solid angle of `sample_incident` picking the unit vector `direction` towards
+    // the light, only defined for infinite lights whose samples have no real position.
+    fn incident_pdf(&self, _direction: &Vec3) -> Float {
+        0.0
+    }
+}
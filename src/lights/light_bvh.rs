@@ -0,0 +1,375 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use crate::{
+    float::{consts::PI, Float},
+    hittables::aabb::Aabb,
+    sampler,
+    vector::{cross, dot, Normal3, Point3, Vec3},
+};
+
+// Buckets along the split axis when building the tree.
+const BUCKETS: usize = 12;
+
+// Where a light emits and how much, loose enough to hold for any point on it. Emission
+// leaves within `cos_theta_o` of `axis` plus `cos_theta_e` around that.
+#[derive(Copy, Clone, Debug)]
+pub struct LightBounds {
+    pub bounds: Aabb,
+    // Luminance of the emitted power.
+    pub power: Float,
+    // Unit vector the normals are spread around.
+    pub axis: Vec3,
+    // Cosine of the widest angle between `axis` and a normal of the light.
+    pub cos_theta_o: Float,
+    // Cosine of the widest angle the light emits at from one of its normals.
+    pub cos_theta_e: Float,
+}
+
+impl LightBounds {
+    pub fn union(&self, other: &LightBounds) -> LightBounds {
+        if self.power == 0.0 {
+            return *other;
+        }
+        if other.power == 0.0 {
+            return *self;
+        }
+
+        let (axis, cos_theta_o) =
+            union_cones(&self.axis, self.cos_theta_o, &other.axis, other.cos_theta_o);
+        LightBounds {
+            bounds: self.bounds.union(&other.bounds),
+            power: self.power + other.power,
+            axis,
+            cos_theta_o,
+            cos_theta_e: self.cos_theta_e.min(other.cos_theta_e),
+        }
+    }
+
+    // Estimate of the light reaching `point` from anywhere within the bounds, zero only when
+    // none can. The normal of a surface at the point weighs in the cosine towards the bounds.
+    pub fn importance(&self, point: &Point3, normal: Option<&Normal3>) -> Float {
+        let centre = self.bounds.centroid();
+        let offset = *point - centre;
+        // Not closer than half the diagonal, points inside the bounds would blow it up.
+        let squared_distance = offset
+            .squared_length()
+            .max(0.5 * self.bounds.diagonal().length());
+        if squared_distance == 0.0 {
+            return 0.0;
+        }
+        let outgoing = offset.normalize();
+
+        // Angle from the closest normal of the light to the point, less the angle the bounds
+        // subtend.
+        let cos_theta_w = dot(&self.axis, &outgoing);
+        let sin_theta_w = safe_sqrt(1.0 - cos_theta_w * cos_theta_w);
+        let sin_theta_o = safe_sqrt(1.0 - self.cos_theta_o * self.cos_theta_o);
+        let cos_theta_x = cos_sub_clamped(sin_theta_w, cos_theta_w, sin_theta_o, self.cos_theta_o);
+        let sin_theta_x = safe_sqrt(1.0 - cos_theta_x * cos_theta_x);
+        let cos_theta_b = bound_subtended_cos(&self.bounds, point);
+        let sin_theta_b = safe_sqrt(1.0 - cos_theta_b * cos_theta_b);
+        let cos_theta_p = cos_sub_clamped(sin_theta_x, cos_theta_x, sin_theta_b, cos_theta_b);
+        if cos_theta_p <= self.cos_theta_e {
+            return 0.0;
+        }
+
+        let mut importance = self.power * cos_theta_p / squared_distance;
+        if let Some(normal) = normal {
+            let cos_theta_i = dot(&outgoing, normal).abs();
+            let sin_theta_i = safe_sqrt(1.0 - cos_theta_i * cos_theta_i);
+            importance *= cos_sub_clamped(sin_theta_i, cos_theta_i, sin_theta_b, cos_theta_b);
+        }
+        importance.max(0.0)
+    }
+}
+
+// Bounding volume hierarchy over the lights with bounds, the many-lights tree. Picks lights
+// in proportion to an estimate of what they contribute at a point by walking down from the
+// root, choosing between the children by their importance.
+pub struct LightBvh {
+    // Depth first order, the first child of an interior node follows it.
+    nodes: Vec<Node>,
+    // Leaf node of every light, for walking back up to the root.
+    leaves: HashMap<usize, usize>,
+}
+
+struct Node {
+    bounds: LightBounds,
+    content: NodeContent,
+    // The root is its own parent.
+    parent: usize,
+}
+
+enum NodeContent {
+    Interior { second_child: usize },
+    Leaf { light: usize },
+}
+
+impl LightBvh {
+    // Lights as their index in the light set and their bounds. Lights without power are left
+    // out, they can't contribute.
+    pub fn new(lights: &[(usize, LightBounds)]) -> LightBvh {
+        let mut items: Vec<(usize, LightBounds)> = lights
+            .iter()
+            .filter(|(_, bounds)| bounds.power > 0.0)
+            .copied()
+            .collect();
+        let mut bvh = LightBvh {
+            nodes: Vec::new(),
+            leaves: HashMap::new(),
+        };
+        if !items.is_empty() {
+            bvh.build(&mut items, 0);
+        }
+        bvh
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    // Light picked for `point` and the chance of picking it, None when no light reaches it.
+    pub fn pick(&self, point: &Point3, normal: Option<&Normal3>) -> Option<(usize, Float)> {
+        let root = self.nodes.first()?;
+        if root.bounds.importance(point, normal) == 0.0 {
+            return None;
+        }
+
+        let mut random = sampler::rng();
+        let mut node_index = 0;
+        let mut probability = 1.0;
+        loop {
+            match self.nodes[node_index].content {
+                NodeContent::Leaf { light } => return Some((light, probability)),
+                NodeContent::Interior { second_child } => {
+                    let first = self.nodes[node_index + 1].bounds.importance(point, normal);
+                    let second = self.nodes[second_child].bounds.importance(point, normal);
+                    if first == 0.0 && second == 0.0 {
+                        return None;
+                    }
+                    let first_probability = first / (first + second);
+                    if random.gen::<Float>() < first_probability {
+                        probability *= first_probability;
+                        node_index += 1;
+                    } else {
+                        probability *= 1.0 - first_probability;
+                        node_index = second_child;
+                    }
+                }
+            }
+        }
+    }
+
+    // Chance of `pick` returning the light for `point`, zero for the lights it leaves out.
+    pub fn pmf(&self, point: &Point3, normal: Option<&Normal3>, light: usize) -> Float {
+        let mut node_index = match self.leaves.get(&light) {
+            Some(leaf) => *leaf,
+            None => return 0.0,
+        };
+        if self.nodes[0].bounds.importance(point, normal) == 0.0 {
+            return 0.0;
+        }
+
+        let mut probability = 1.0;
+        while node_index != 0 {
+            let parent = self.nodes[node_index].parent;
+            let second_child = match self.nodes[parent].content {
+                NodeContent::Interior { second_child } => second_child,
+                NodeContent::Leaf { .. } => unreachable!("leaves have no children"),
+            };
+            let first = self.nodes[parent + 1].bounds.importance(point, normal);
+            let second = self.nodes[second_child].bounds.importance(point, normal);
+            if first == 0.0 && second == 0.0 {
+                return 0.0;
+            }
+            let first_probability = first / (first + second);
+            probability *= if node_index == parent + 1 {
+                first_probability
+            } else {
+                1.0 - first_probability
+            };
+            node_index = parent;
+        }
+        probability
+    }
+
+    fn build(&mut self, items: &mut [(usize, LightBounds)], parent: usize) -> usize {
+        let node_index = self.nodes.len();
+        if let [(light, bounds)] = items {
+            self.nodes.push(Node {
+                bounds: *bounds,
+                content: NodeContent::Leaf { light: *light },
+                parent,
+            });
+            self.leaves.insert(*light, node_index);
+            return node_index;
+        }
+
+        let bounds = items
+            .iter()
+            .skip(1)
+            .fold(items[0].1, |bounds, (_, item)| bounds.union(item));
+        let split = saoh_split(items).unwrap_or(items.len() / 2);
+
+        self.nodes.push(Node {
+            bounds,
+            content: NodeContent::Interior { second_child: 0 },
+            parent,
+        });
+        let (first, second) = items.split_at_mut(split);
+        self.build(first, node_index);
+        let second_index = self.build(second, node_index);
+        self.nodes[node_index].content = NodeContent::Interior {
+            second_child: second_index,
+        };
+
+        node_index
+    }
+}
+
+// Partitions the lights at the bucket border with the lowest surface area orientation
+// heuristic cost over all axes, returns the count of lights on the first side. None when
+// all centroids coincide.
+fn saoh_split(items: &mut [(usize, LightBounds)]) -> Option<usize> {
+    let node_bounds = items.iter().fold(Aabb::empty(), |bounds, (_, item)| {
+        bounds.union(&item.bounds)
+    });
+    let centroid_bounds = items.iter().fold(Aabb::empty(), |bounds, (_, item)| {
+        bounds.include(&item.bounds.centroid())
+    });
+    let bucket_of = |bounds: &LightBounds, axis: usize| {
+        let start = centroid_bounds.min.data[axis];
+        let extent = centroid_bounds.max.data[axis] - start;
+        let offset = (bounds.bounds.centroid().data[axis] - start) / extent;
+        ((offset * BUCKETS as Float) as usize).min(BUCKETS - 1)
+    };
+
+    // Axis and the last bucket on the first side.
+    let mut best: Option<(Float, usize, usize)> = None;
+    for axis in 0..3 {
+        if centroid_bounds.max.data[axis] <= centroid_bounds.min.data[axis] {
+            continue;
+        }
+
+        let mut buckets: [Option<LightBounds>; BUCKETS] = [None; BUCKETS];
+        for (_, bounds) in items.iter() {
+            let bucket = &mut buckets[bucket_of(bounds, axis)];
+            *bucket = Some(match bucket {
+                Some(merged) => merged.union(bounds),
+                None => *bounds,
+            });
+        }
+
+        for border in 0..BUCKETS - 1 {
+            let merge = |buckets: &[Option<LightBounds>]| {
+                buckets.iter().flatten().fold(None, |merged, bounds| {
+                    Some(match merged {
+                        Some(merged) => bounds.union(&merged),
+                        None => *bounds,
+                    })
+                })
+            };
+            let (first, second) = match (merge(&buckets[..=border]), merge(&buckets[border + 1..]))
+            {
+                (Some(first), Some(second)) => (first, second),
+                // Splits leaving one side empty don't divide anything.
+                _ => continue,
+            };
+            let cost =
+                saoh_cost(&first, &node_bounds, axis) + saoh_cost(&second, &node_bounds, axis);
+            if best.is_none_or(|(best_cost, _, _)| cost < best_cost) {
+                best = Some((cost, axis, border));
+            }
+        }
+    }
+
+    let (_, axis, border) = best?;
+    let mut split = 0;
+    for index in 0..items.len() {
+        if bucket_of(&items[index].1, axis) <= border {
+            items.swap(index, split);
+            split += 1;
+        }
+    }
+    Some(split)
+}
+
+// Power times the surface area of the bounds times the solid angle measure of the cone of
+// directions, stretched for bounds thin along the split axis of wide nodes.
+fn saoh_cost(bounds: &LightBounds, node_bounds: &Aabb, axis: usize) -> Float {
+    let theta_o = bounds.cos_theta_o.clamp(-1.0, 1.0).acos();
+    let theta_e = bounds.cos_theta_e.clamp(-1.0, 1.0).acos();
+    let theta_w = (theta_o + theta_e).min(PI);
+    let sin_theta_o = safe_sqrt(1.0 - bounds.cos_theta_o * bounds.cos_theta_o);
+    let orientation = 2.0 * PI * (1.0 - bounds.cos_theta_o)
+        + PI / 2.0
+            * (2.0 * theta_w * sin_theta_o
+                - (theta_o - 2.0 * theta_w).cos()
+                - 2.0 * theta_o * sin_theta_o
+                + bounds.cos_theta_o);
+
+    let diagonal = node_bounds.diagonal();
+    let longest = diagonal.x().max(diagonal.y()).max(diagonal.z());
+    let regularity = if diagonal.data[axis] > 0.0 {
+        longest / diagonal.data[axis]
+    } else {
+        1.0
+    };
+
+    bounds.power * orientation * regularity * bounds.bounds.surface_area().max(Float::MIN_POSITIVE)
+}
+
+// Smallest cone holding the cones around the unit axes with the cosines of their half angles.
+fn union_cones(a: &Vec3, cos_a: Float, b: &Vec3, cos_b: Float) -> (Vec3, Float) {
+    let theta_a = cos_a.clamp(-1.0, 1.0).acos();
+    let theta_b = cos_b.clamp(-1.0, 1.0).acos();
+    let theta_d = dot(a, b).clamp(-1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return (*a, cos_a);
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return (*b, cos_b);
+    }
+
+    let theta_o = 0.5 * (theta_a + theta_d + theta_b);
+    if theta_o >= PI {
+        return (*a, -1.0);
+    }
+    // Rotates `a` towards `b` until the cone touches both.
+    let rotation_axis = cross(a, b);
+    if rotation_axis.squared_length() == 0.0 {
+        return (*a, -1.0);
+    }
+    let rotation_axis = rotation_axis.normalize();
+    let theta_r = theta_o - theta_a;
+    let axis = theta_r.cos() * *a
+        + theta_r.sin() * cross(&rotation_axis, a)
+        + ((1.0 - theta_r.cos()) * dot(&rotation_axis, a)) * rotation_axis;
+    (axis.normalize(), theta_o.cos())
+}
+
+// Cosine of the half angle of the cone from `point` around the bounding sphere of the bounds,
+// -1 inside of it.
+fn bound_subtended_cos(bounds: &Aabb, point: &Point3) -> Float {
+    let centre = bounds.centroid();
+    let squared_radius = (bounds.max - centre).squared_length();
+    let squared_distance = (*point - centre).squared_length();
+    if squared_distance < squared_radius {
+        return -1.0;
+    }
+    safe_sqrt(1.0 - squared_radius / squared_distance)
+}
+
+// Cosine of the difference of the angles a and b, one when b is the larger.
+fn cos_sub_clamped(sin_a: Float, cos_a: Float, sin_b: Float, cos_b: Float) -> Float {
+    if cos_a > cos_b {
+        return 1.0;
+    }
+    cos_a * cos_b + sin_a * sin_b
+}
+
+fn safe_sqrt(value: Float) -> Float {
+    value.max(0.0).sqrt()
+}
@@ -0,0 +1,122 @@
+use std::str::FromStr;
+
+use rand::Rng;
+
+use crate::{error::Error, float::Float, sampler};
+
+// How integrators pick the light to sample among all of a scene.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum LightSampling {
+    // Every light equally often.
+    Uniform,
+    // In proportion to the power of the light.
+    #[default]
+    Power,
+    // In proportion to an estimate of the light reaching the point to light, from a bounding
+    // volume hierarchy over the lights. Light paths still start by power.
+    Bvh,
+}
+
+impl FromStr for LightSampling {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "uniform" => Ok(LightSampling::Uniform),
+            "power" => Ok(LightSampling::Power),
+            "bvh" => Ok(LightSampling::Bvh),
+            _ => Err(Error::InvalidSettings(format!(
+                "light sampling {} must be uniform, power or bvh",
+                value
+            ))),
+        }
+    }
+}
+
+// Picks indices in proportion to their weights in constant time, with Vose's alias method.
+pub struct AliasTable {
+    probabilities: Vec<Float>,
+    bins: Vec<AliasBin>,
+}
+
+struct AliasBin {
+    // Chance of keeping the bin's own index rather than its alias.
+    threshold: Float,
+    alias: usize,
+}
+
+impl AliasTable {
+    // Weights must not be negative. All zero gives every index the same chance.
+    pub fn new(weights: &[Float]) -> AliasTable {
+        let count = weights.len();
+        let total: Float = weights.iter().sum();
+        let probabilities: Vec<Float> = if total > 0.0 {
+            weights.iter().map(|weight| weight / total).collect()
+        } else {
+            vec![1.0 / count as Float; count]
+        };
+
+        let mut bins: Vec<AliasBin> = (0..count)
+            .map(|index| AliasBin {
+                threshold: 1.0,
+                alias: index,
+            })
+            .collect();
+        // Scaled so that the average is one.
+        let mut under = Vec::new();
+        let mut over = Vec::new();
+        for (index, probability) in probabilities.iter().enumerate() {
+            let weight = probability * count as Float;
+            if weight < 1.0 {
+                under.push((index, weight));
+            } else {
+                over.push((index, weight));
+            }
+        }
+
+        // Every bin below one is topped up from one above, which may drop below one itself.
+        while let (Some((small, small_weight)), Some((large, large_weight))) =
+            (under.pop(), over.pop())
+        {
+            bins[small] = AliasBin {
+                threshold: small_weight,
+                alias: large,
+            };
+            let remaining = large_weight - (1.0 - small_weight);
+            if remaining < 1.0 {
+                under.push((large, remaining));
+            } else {
+                over.push((large, remaining));
+            }
+        }
+        // What's left is one up to rounding.
+
+        AliasTable {
+            probabilities,
+            bins,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bins.is_empty()
+    }
+
+    // Index and its chance.
+    pub fn sample(&self) -> Option<(usize, Float)> {
+        if self.bins.is_empty() {
+            return None;
+        }
+        let mut random = sampler::rng();
+        let bin = random.gen_range(0..self.bins.len());
+        let index = if random.gen::<Float>() < self.bins[bin].threshold {
+            bin
+        } else {
+            self.bins[bin].alias
+        };
+        Some((index, self.probabilities[index]))
+    }
+
+    pub fn probability(&self, index: usize) -> Float {
+        self.probabilities[index]
+    }
+}
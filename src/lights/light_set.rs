@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use crate::{
+    float::Float,
+    hittables::{aabb::Aabb, hittable::Primitive},
+    sampler,
+    scene::Scene,
+    vector::{Normal3, Point3},
+};
+
+use super::{
+    area_light::AreaLight,
+    light::Light,
+    light_bvh::LightBvh,
+    light_sampling::{AliasTable, LightSampling},
+    punctual_light::PunctualLightSource,
+    sky_light::SkyLight,
+};
+
+// Everything that emits in a scene: the spheres and triangles with an emissive material, the
+// punctual lights, and the sky unless it's black.
+pub struct LightSet<'a> {
+    lights: Vec<Box<dyn Light + 'a>>,
+    // By the address of their primitive, which tells which light a path hit.
+    by_primitive: HashMap<usize, usize>,
+    sky: Option<usize>,
+    // Of the sphere around the scene bounds, the sky light surrounds it.
+    radius: Float,
+    // By power, unless sampling is uniform.
+    power: Option<AliasTable>,
+    // Over the bounded lights when sampling with the light BVH.
+    bvh: Option<LightBvh>,
+    // The ones the BVH can't hold.
+    infinite: Vec<usize>,
+}
+
+impl<'a> LightSet<'a> {
+    // Emitters of `scene` within `bounds`, positioned for the shutter open between the times.
+    pub fn new(
+        scene: &'a Scene,
+        bounds: &Aabb,
+        sampling: LightSampling,
+        time_start: Float,
+        time_end: Float,
+    ) -> LightSet<'a> {
+        let radius = (0.5 * bounds.diagonal().length()).max(Float::MIN_POSITIVE);
+
+        let mut lights: Vec<Box<dyn Light + 'a>> = Vec::new();
+        let mut by_primitive = HashMap::new();
+        // Meshes are made of triangles, each one is a light of its own picked by its power,
+        // that is by area for an even emission.
+        for primitive in scene
+            .world
+            .hittables
+            .iter()
+            .flat_map(|hittable| hittable.primitives())
+        {
+            if let Some(light) = AreaLight::new(primitive, time_start, time_end) {
+                if let Some(address) = primitive_address(&primitive) {
+                    by_primitive.insert(address, lights.len());
+                }
+                lights.push(Box::new(light));
+            }
+        }
+        for light in &scene.lights {
+            lights.push(Box::new(PunctualLightSource::new(
+                light,
+                bounds.centroid(),
+                radius,
+            )));
+        }
+        let sky = if scene.sky.is_black() {
+            None
+        } else {
+            lights.push(Box::new(SkyLight::new(
+                &scene.sky,
+                bounds.centroid(),
+                radius,
+            )));
+            Some(lights.len() - 1)
+        };
+
+        let power = match sampling {
+            LightSampling::Uniform => None,
+            LightSampling::Power | LightSampling::Bvh => {
+                let powers: Vec<Float> = lights.iter().map(|light| light.power()).collect();
+                Some(AliasTable::new(&powers))
+            }
+        };
+        let (bvh, infinite) = match sampling {
+            LightSampling::Bvh => {
+                let mut bounded = Vec::new();
+                let mut infinite = Vec::new();
+                for (index, light) in lights.iter().enumerate() {
+                    match light.bounds() {
+                        Some(bounds) => bounded.push((index, bounds)),
+                        None => infinite.push(index),
+                    }
+                }
+                (Some(LightBvh::new(&bounded)), infinite)
+            }
+            _ => (None, Vec::new()),
+        };
+
+        LightSet {
+            lights,
+            by_primitive,
+            sky,
+            radius,
+            power,
+            bvh,
+            infinite,
+        }
+    }
+
+    pub fn get(&self, index: usize) -> &(dyn Light + 'a) {
+        self.lights[index].as_ref()
+    }
+
+    pub fn sky(&self) -> Option<usize> {
+        self.sky
+    }
+
+    pub fn scene_radius(&self) -> Float {
+        self.radius
+    }
+
+    // Light picked without knowing where it will light, for starting light paths, and the
+    // chance of picking it. Only uniform sampling leaves out the power.
+    pub fn pick(&self) -> Option<(usize, Float)> {
+        if self.lights.is_empty() {
+            return None;
+        }
+        match &self.power {
+            Some(power) => power.sample(),
+            None => {
+                let index = sampler::rng().gen_range(0..self.lights.len());
+                Some((index, self.selection_pdf(index)))
+            }
+        }
+    }
+
+    // Chance of `pick` returning the light.
+    pub fn selection_pdf(&self, index: usize) -> Float {
+        match &self.power {
+            Some(power) => power.probability(index),
+            None => 1.0 / self.lights.len() as Float,
+        }
+    }
+
+    // Light picked for lighting `point`, on a surface with `normal` if there's one, and the
+    // chance of picking it. Only the light BVH makes use of the point, infinite lights get as
+    // much of a chance as the whole tree.
+    pub fn pick_towards(&self, point: &Point3, normal: Option<&Normal3>) -> Option<(usize, Float)> {
+        let bvh = match &self.bvh {
+            Some(bvh) => bvh,
+            None => return self.pick(),
+        };
+
+        let branches = self.infinite.len() + usize::from(!bvh.is_empty());
+        if branches == 0 {
+            return None;
+        }
+        let branch_probability = 1.0 / branches as Float;
+        let mut random = sampler::rng();
+        let branch = random.gen_range(0..branches);
+        if branch < self.infinite.len() {
+            return Some((self.infinite[branch], branch_probability));
+        }
+        let (index, probability) = bvh.pick(point, normal)?;
+        Some((index, branch_probability * probability))
+    }
+
+    // Chance of `pick_towards` returning the light for `point`.
+    pub fn pmf_towards(&self, point: &Point3, normal: Option<&Normal3>, index: usize) -> Float {
+        let bvh = match &self.bvh {
+            Some(bvh) => bvh,
+            None => return self.selection_pdf(index),
+        };
+
+        let branches = self.infinite.len() + usize::from(!bvh.is_empty());
+        if branches == 0 {
+            return 0.0;
+        }
+        let branch_probability = 1.0 / branches as Float;
+        if self.infinite.contains(&index) {
+            branch_probability
+        } else {
+            branch_probability * bvh.pmf(point, normal, index)
+        }
+    }
+
+    // Light the primitive is, if it emits.
+    pub fn find(&self, primitive: &Primitive) -> Option<usize> {
+        self.by_primitive
+            .get(&primitive_address(primitive)?)
+            .copied()
+    }
+}
+
+// Spheres and triangles are never zero-sized, so no two of them share an address.
+fn primitive_address(primitive: &Primitive) -> Option<usize> {
+    match primitive {
+        Primitive::Sphere(sphere) => Some(*sphere as *const _ as usize),
+        Primitive::Triangle(triangle) => Some(*triangle as *const _ as usize),
+        Primitive::Other => None,
+    }
+}
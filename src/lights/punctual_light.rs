@@ -0,0 +1,409 @@
+use rand::Rng;
+
+use crate::{
+    color::Color,
+    error::Error,
+    float::{consts::PI, to_f32, Float},
+    hittables::aabb::Aabb,
+    ray::Ray,
+    sampler,
+    vector::{
+        dot, orthonormal_basis, random_in_unit_disc, random_on_unit_sphere, Normal3, Point3, Vec3,
+    },
+};
+
+use super::{
+    ies::LightProfile,
+    light::{EmissionSample, Light, LightSample},
+    light_bvh::LightBounds,
+};
+
+// Fixtures hang straight down unless they're spot lights, with horizontal angle 0 along x.
+const POINT_NADIR: Vec3 = Vec3::new(0.0, -1.0, 0.0);
+const POINT_REFERENCE: Vec3 = Vec3::new(1.0, 0.0, 0.0);
+
+// Light from a single point or from a single direction, for quick lighting setups. Nothing in
+// the scene can hit it, integrators reach it only by sampling it with a shadow ray.
+#[derive(Clone, Debug)]
+pub enum PunctualLight {
+    // The same intensity in every direction, unless a profile shapes it.
+    Point {
+        position: Point3,
+        intensity: Color,
+        profile: Option<LightProfile>,
+    },
+    // Full intensity within `cos_falloff_start` of its unit `direction`, fading smoothly to
+    // nothing at `cos_total_width`. A profile hangs with its nadir along the direction.
+    Spot {
+        position: Point3,
+        direction: Vec3,
+        intensity: Color,
+        cos_total_width: Float,
+        cos_falloff_start: Float,
+        profile: Option<LightProfile>,
+    },
+    // Light travelling along the unit `direction` from infinitely far away, like sunlight,
+    // with `irradiance` on surfaces facing it.
+    Directional {
+        direction: Vec3,
+        irradiance: Color,
+    },
+}
+
+impl PunctualLight {
+    pub fn point(position: Point3, intensity: Color) -> Result<PunctualLight, Error> {
+        validate_position(&position)?;
+        validate_power(&intensity)?;
+
+        Ok(PunctualLight::Point {
+            position,
+            intensity,
+            profile: None,
+        })
+    }
+
+    // Points from `position` to `target`. The cone angles are measured from its axis in
+    // degrees, the light fades out between `falloff_start` and `cone_angle`.
+    pub fn spot(
+        position: Point3,
+        target: Point3,
+        intensity: Color,
+        cone_angle: Float,
+        falloff_start: Float,
+    ) -> Result<PunctualLight, Error> {
+        validate_position(&position)?;
+        validate_position(&target)?;
+        validate_power(&intensity)?;
+        let direction = target - position;
+        if direction.is_near_zero() {
+            return Err(Error::InvalidGeometry(String::from(
+                "spot light must not point at itself",
+            )));
+        }
+        if !(cone_angle > 0.0 && cone_angle < 180.0) {
+            return Err(Error::InvalidGeometry(format!(
+                "spot light cone angle {} must be in (0, 180) degrees",
+                cone_angle
+            )));
+        }
+        if !(0.0..=cone_angle).contains(&falloff_start) {
+            return Err(Error::InvalidGeometry(format!(
+                "spot light falloff start {} must be in [0, {}] degrees",
+                falloff_start, cone_angle
+            )));
+        }
+
+        Ok(PunctualLight::Spot {
+            position,
+            direction: direction.normalize(),
+            intensity,
+            cos_total_width: cone_angle.to_radians().cos(),
+            cos_falloff_start: falloff_start.to_radians().cos(),
+            profile: None,
+        })
+    }
+
+    // `direction` is the one the light travels in, from the light into the scene.
+    pub fn directional(direction: Vec3, irradiance: Color) -> Result<PunctualLight, Error> {
+        if !direction.is_finite() || direction.is_near_zero() {
+            return Err(Error::InvalidGeometry(String::from(
+                "directional light needs a direction",
+            )));
+        }
+        validate_power(&irradiance)?;
+
+        Ok(PunctualLight::Directional {
+            direction: direction.normalize(),
+            irradiance,
+        })
+    }
+
+    // Shapes the emission of a point or spot light with a measured distribution. A profile
+    // scaled to lumens sets the brightness, the light's intensity only tints it.
+    pub fn with_profile(mut self, light_profile: LightProfile) -> Result<PunctualLight, Error> {
+        match &mut self {
+            PunctualLight::Point {
+                intensity, profile, ..
+            }
+            | PunctualLight::Spot {
+                intensity, profile, ..
+            } => {
+                if light_profile.lumens().is_some() && intensity.luminance() > 0.0 {
+                    *intensity = *intensity / intensity.luminance();
+                }
+                *profile = Some(light_profile);
+                Ok(self)
+            }
+            PunctualLight::Directional { .. } => Err(Error::InvalidGeometry(String::from(
+                "directional lights can't have a profile",
+            ))),
+        }
+    }
+
+    // Intensity of a point or spot light along the unit `direction` leaving it, the
+    // irradiance of a directional light.
+    fn intensity_towards(&self, direction: &Vec3) -> Color {
+        match self {
+            PunctualLight::Point {
+                intensity, profile, ..
+            } => match profile {
+                Some(profile) => {
+                    profile.factor(direction, &POINT_NADIR, &POINT_REFERENCE) * *intensity
+                }
+                None => *intensity,
+            },
+            PunctualLight::Spot {
+                direction: axis,
+                intensity,
+                cos_total_width,
+                cos_falloff_start,
+                profile,
+                ..
+            } => {
+                let falloff =
+                    spot_falloff(dot(axis, direction), *cos_total_width, *cos_falloff_start);
+                match profile {
+                    Some(profile) => {
+                        let (reference, _) = orthonormal_basis(axis);
+                        (falloff * profile.factor(direction, axis, &reference)) * *intensity
+                    }
+                    None => falloff * *intensity,
+                }
+            }
+            PunctualLight::Directional { irradiance, .. } => *irradiance,
+        }
+    }
+}
+
+fn validate_position(position: &Point3) -> Result<(), Error> {
+    if !position.is_finite() {
+        return Err(Error::InvalidGeometry(String::from(
+            "light position must be finite",
+        )));
+    }
+    Ok(())
+}
+
+fn validate_power(color: &Color) -> Result<(), Error> {
+    let components = [color.r(), color.g(), color.b()];
+    if !components.iter().all(|c| *c >= 0.0 && c.is_finite()) {
+        return Err(Error::InvalidGeometry(format!(
+            "light power {:?} must be finite and not negative",
+            components
+        )));
+    }
+    Ok(())
+}
+
+// A punctual light as the integrators sample it. Directional light starts its light paths on
+// a disc as wide as the scene, like the sky.
+pub struct PunctualLightSource<'a> {
+    light: &'a PunctualLight,
+    centre: Point3,
+    radius: Float,
+}
+
+impl<'a> PunctualLightSource<'a> {
+    pub fn new(light: &'a PunctualLight, centre: Point3, radius: Float) -> PunctualLightSource<'a> {
+        PunctualLightSource {
+            light,
+            centre,
+            radius,
+        }
+    }
+}
+
+// Smooth step of the spot light from the edge of its cone to the start of the falloff.
+fn spot_falloff(cos_theta: Float, cos_total_width: Float, cos_falloff_start: Float) -> f32 {
+    if cos_theta <= cos_total_width {
+        return 0.0;
+    }
+    if cos_theta >= cos_falloff_start {
+        return 1.0;
+    }
+    let t = (cos_theta - cos_total_width) / (cos_falloff_start - cos_total_width);
+    to_f32(t * t * (3.0 - 2.0 * t))
+}
+
+impl Light for PunctualLightSource<'_> {
+    fn sample_incident(&self, reference: &Point3, _time: Float) -> Option<LightSample> {
+        let (point, radiance) = match self.light {
+            PunctualLight::Point { position, .. } | PunctualLight::Spot { position, .. } => {
+                let offset = *reference - *position;
+                let squared_distance = offset.squared_length();
+                let intensity = self
+                    .light
+                    .intensity_towards(&(offset / squared_distance.sqrt()));
+                (*position, intensity / to_f32(squared_distance))
+            }
+            PunctualLight::Directional {
+                direction,
+                irradiance,
+            } => (*reference - 2.0 * self.radius * *direction, *irradiance),
+        };
+        if radiance.is_black() || !radiance.r().is_finite() {
+            return None;
+        }
+
+        Some(LightSample {
+            point,
+            normal: None,
+            error: Vec3::default(),
+            radiance,
+            // Delta distributions, the radiance is already divided by them.
+            pdf: 1.0,
+        })
+    }
+
+    fn sample_emission(&self, time: Float) -> Option<EmissionSample> {
+        let (origin, direction, radiance, pdf_position) = match self.light {
+            PunctualLight::Point { position, .. } => {
+                let direction = random_on_unit_sphere();
+                let intensity = self.light.intensity_towards(&direction);
+                (*position, direction, intensity, 1.0)
+            }
+            PunctualLight::Spot {
+                position,
+                direction: axis,
+                cos_total_width,
+                ..
+            } => {
+                // Uniform within the cone.
+                let mut random = sampler::rng();
+                let cos_theta = 1.0 - random.gen::<Float>() * (1.0 - cos_total_width);
+                let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+                let phi = 2.0 * PI * random.gen::<Float>();
+                let (tangent, bitangent) = orthonormal_basis(axis);
+                let direction = cos_theta * *axis
+                    + sin_theta * phi.cos() * tangent
+                    + sin_theta * phi.sin() * bitangent;
+                let intensity = self.light.intensity_towards(&direction);
+                (*position, direction, intensity, 1.0)
+            }
+            PunctualLight::Directional {
+                direction,
+                irradiance,
+            } => {
+                let (tangent, bitangent) = orthonormal_basis(direction);
+                let disc = self.radius * random_in_unit_disc();
+                let origin = self.centre - self.radius * *direction
+                    + disc.x() * tangent
+                    + disc.y() * bitangent;
+                (
+                    origin,
+                    *direction,
+                    *irradiance,
+                    1.0 / (PI * self.radius * self.radius),
+                )
+            }
+        };
+        let (_, pdf_direction) = self.emission_pdf(None, &direction);
+
+        Some(EmissionSample {
+            point: origin,
+            normal: None,
+            error: Vec3::default(),
+            ray: Ray {
+                origin,
+                direction,
+                time,
+            },
+            radiance,
+            pdf_position,
+            pdf_direction: match self.light {
+                PunctualLight::Directional { .. } => 1.0,
+                _ => pdf_direction,
+            },
+        })
+    }
+
+    // Positions and the directional light's direction are delta distributions, which have
+    // no density to weigh with.
+    fn emission_pdf(&self, _normal: Option<&Normal3>, direction: &Vec3) -> (Float, Float) {
+        match self.light {
+            PunctualLight::Point { .. } => (0.0, 1.0 / (4.0 * PI)),
+            PunctualLight::Spot {
+                direction: axis,
+                cos_total_width,
+                ..
+            } => {
+                if dot(axis, direction) > *cos_total_width {
+                    (0.0, 1.0 / (2.0 * PI * (1.0 - cos_total_width)))
+                } else {
+                    (0.0, 0.0)
+                }
+            }
+            PunctualLight::Directional { .. } => (1.0 / (PI * self.radius * self.radius), 0.0),
+        }
+    }
+
+    fn radiance(&self, _normal: Option<&Normal3>, _direction: &Vec3) -> Color {
+        Color::BLACK
+    }
+
+    fn is_infinite(&self) -> bool {
+        matches!(self.light, PunctualLight::Directional { .. })
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    // The spot light's falloff is taken halfway, the directional light's power is what falls
+    // on the scene.
+    fn power(&self) -> Float {
+        let profile_factor = |profile: &Option<LightProfile>| {
+            profile
+                .as_ref()
+                .map_or(1.0, |profile| profile.mean_factor())
+        };
+        match self.light {
+            PunctualLight::Point {
+                intensity, profile, ..
+            } => 4.0 * PI * (profile_factor(profile) * intensity.luminance()) as Float,
+            PunctualLight::Spot {
+                intensity,
+                cos_total_width,
+                cos_falloff_start,
+                profile,
+                ..
+            } => {
+                2.0 * PI
+                    * (1.0 - 0.5 * (cos_falloff_start + cos_total_width))
+                    * (profile_factor(profile) * intensity.luminance()) as Float
+            }
+            PunctualLight::Directional { irradiance, .. } => {
+                PI * self.radius * self.radius * irradiance.luminance() as Float
+            }
+        }
+    }
+
+    fn bounds(&self) -> Option<LightBounds> {
+        let (position, axis, cos_theta_o, cos_theta_e) = match self.light {
+            PunctualLight::Point { position, .. } => {
+                (*position, Vec3::new(0.0, 0.0, 1.0), -1.0, 0.0)
+            }
+            PunctualLight::Spot {
+                position,
+                direction,
+                cos_total_width,
+                cos_falloff_start,
+                ..
+            } => (
+                *position,
+                *direction,
+                *cos_falloff_start,
+                (cos_total_width.acos() - cos_falloff_start.acos()).cos(),
+            ),
+            PunctualLight::Directional { .. } => return None,
+        };
+
+        Some(LightBounds {
+            bounds: Aabb::new(position, position),
+            power: self.power(),
+            axis,
+            cos_theta_o,
+            cos_theta_e,
+        })
+    }
+}
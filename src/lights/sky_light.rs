@@ -0,0 +1,139 @@
+use crate::{
+    color::Color,
+    float::{consts::PI, to_f32, Float},
+    ray::Ray,
+    spectrum::{RgbSpectrum, SampledSpectrum, SampledWavelengths},
+    vector::{
+        orthonormal_basis, random_in_unit_disc, random_on_unit_sphere, Normal3, Point3, Vec3,
+    },
+};
+
+use super::light::{EmissionSample, Light, LightSample};
+
+// Background that rays escaping the scene see, blended from the horizon to straight up.
+#[derive(Copy, Clone, Debug)]
+pub struct Sky {
+    pub horizon: Color,
+    pub zenith: Color,
+}
+
+impl Default for Sky {
+    fn default() -> Self {
+        Sky {
+            horizon: Color::new(1.0, 1.0, 1.0),
+            zenith: Color::new(0.5, 0.7, 1.0),
+        }
+    }
+}
+
+impl Sky {
+    // For scenes lit by their emitters alone.
+    pub const BLACK: Sky = Sky {
+        horizon: Color::BLACK,
+        zenith: Color::BLACK,
+    };
+
+    pub fn is_black(&self) -> bool {
+        self.horizon.is_black() && self.zenith.is_black()
+    }
+
+    // Remap y = [-1..1] to [0..1] range.
+    fn blend(direction: &Vec3) -> f32 {
+        let direction_normalized: Vec3 = direction.normalize();
+        0.5 * (to_f32(direction_normalized.y()) + 1.0)
+    }
+
+    // Seen looking along `direction`.
+    pub fn radiance(&self, direction: &Vec3) -> Color {
+        self.horizon.lerp(&self.zenith, Sky::blend(direction))
+    }
+
+    // Blending the spectra gives the same color as blending the RGB values, since the
+    // conversion from spectra to RGB is linear.
+    pub fn spectrum(&self, direction: &Vec3, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        let t = Sky::blend(direction);
+        (1.0 - t) * RgbSpectrum::unbounded(&self.horizon).sample(wavelengths)
+            + t * RgbSpectrum::unbounded(&self.zenith).sample(wavelengths)
+    }
+}
+
+// The sky as a light around a scene bounded by the sphere at `centre`. Light paths start on a
+// disc facing the scene, just outside of it.
+pub struct SkyLight<'a> {
+    sky: &'a Sky,
+    centre: Point3,
+    radius: Float,
+}
+
+impl<'a> SkyLight<'a> {
+    pub fn new(sky: &'a Sky, centre: Point3, radius: Float) -> SkyLight<'a> {
+        SkyLight {
+            sky,
+            centre,
+            radius,
+        }
+    }
+}
+
+impl Light for SkyLight<'_> {
+    fn sample_incident(&self, reference: &Point3, _time: Float) -> Option<LightSample> {
+        let direction = random_on_unit_sphere();
+
+        Some(LightSample {
+            point: *reference + 2.0 * self.radius * direction,
+            normal: None,
+            error: Vec3::default(),
+            radiance: self.sky.radiance(&direction),
+            pdf: self.incident_pdf(&direction),
+        })
+    }
+
+    fn sample_emission(&self, time: Float) -> Option<EmissionSample> {
+        let towards_sky = random_on_unit_sphere();
+        let (tangent, bitangent) = orthonormal_basis(&towards_sky);
+        let disc = self.radius * random_in_unit_disc();
+        let origin =
+            self.centre + self.radius * towards_sky + disc.x() * tangent + disc.y() * bitangent;
+        let (pdf_position, pdf_direction) = self.emission_pdf(None, &-towards_sky);
+
+        Some(EmissionSample {
+            point: origin,
+            normal: None,
+            error: Vec3::default(),
+            ray: Ray {
+                origin,
+                direction: -towards_sky,
+                time,
+            },
+            radiance: self.sky.radiance(&towards_sky),
+            pdf_position,
+            pdf_direction,
+        })
+    }
+
+    fn emission_pdf(&self, _normal: Option<&Normal3>, direction: &Vec3) -> (Float, Float) {
+        (
+            1.0 / (PI * self.radius * self.radius),
+            self.incident_pdf(&-*direction),
+        )
+    }
+
+    fn radiance(&self, _normal: Option<&Normal3>, direction: &Vec3) -> Color {
+        self.sky.radiance(&-*direction)
+    }
+
+    fn is_infinite(&self) -> bool {
+        true
+    }
+
+    fn incident_pdf(&self, _direction: &Vec3) -> Float {
+        1.0 / (4.0 * PI)
+    }
+
+    // Falling on the sphere around the scene, the blend averages to halfway over all
+    // directions.
+    fn power(&self) -> Float {
+        let average = self.sky.horizon.lerp(&self.sky.zenith, 0.5);
+        4.0 * PI * PI * self.radius * self.radius * average.luminance() as Float
+    }
+}
@@ -1,269 +1,467 @@
-use materials::dielectric_material::DielectricMaterial;
-use materials::diffuse_material::DiffuseMaterial;
-use materials::metal_material::MetalMaterial;
-use rand::Rng;
-use std::fs::File;
-use std::io::{Error, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Arc;
 use std::time::Instant;
 
-pub mod camera;
-pub mod hit_record;
-pub mod hittables;
-pub mod materials;
-pub mod ray;
-pub mod vector;
-use camera::Camera;
-use hittables::hittable::Hittable;
-use hittables::hittable_list::HittableList;
-use hittables::sphere::Sphere;
-use ray::Ray;
-use vector::{lerp, Vector};
-
-fn main() -> Result<(), Error> {
-    // Image.
-    let aspect_ratio: f32 = 3.0 / 2.0;
-    let width: i32 = 1200;
-    let height: i32 = (width as f32 / aspect_ratio) as i32;
-    let samples_per_pixel: i32 = 100;
-    let max_depth: i32 = 50;
+use learning_rust_with_ray_tracing::{
+    cameras::{
+        aperture::{Aperture, ApertureMask},
+        camera::Camera,
+        fisheye_camera::{FisheyeCamera, FisheyeProjection},
+        orthographic_camera::OrthographicCamera,
+        panoramic_camera::{PanoramicCamera, PanoramicProjection},
+        perspective_camera::PerspectiveCamera,
+        realistic_camera::RealisticCamera,
+    },
+    denoiser::DenoiserSettings,
+    exposure::{focal_length, Exposure, FULL_FRAME_SENSOR_HEIGHT},
+    float::Float,
+    gltf_scene::GltfScene,
+    hittables::bvh::PacketWidth,
+    integrators::{mlt::MetropolisSettings, sppm::PhotonSettings},
+    lights::{
+        ies::{IesProfile, LightProfile},
+        light_sampling::LightSampling,
+        sky_light::Sky,
+    },
+    renderer::{Integrator, PathDepths},
+    scene::{
+        generate_caustics_scene, generate_csg_scene, generate_hair_scene, generate_lamps_scene,
+        generate_many_lights_scene, generate_random_scene, generate_sdf_scene,
+        generate_subdivision_scene, generate_terrain_scene,
+    },
+    vector::{Point3, Vec3},
+    CancellationToken, Error, RenderSettings, Renderer, Scene,
+};
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {}", error);
+            ExitCode::from(error.exit_code())
+        }
+    }
+}
 
-    // World.
-    let world = generate_random_scene();
+fn run() -> Result<(), Error> {
+    let options = parse_options(std::env::args().skip(1).collect())?;
 
-    // Camera.
-    let camera_origin = Vector {
-        data: [13.0, 2.0, -3.0],
+    // Image, panoramas need their own aspect ratio.
+    let width: usize = 1200;
+    let height: usize = match options.camera.as_str() {
+        "equirectangular" => width / 2,
+        "cubemap" => width / 6,
+        _ => 800,
     };
-
-    let camera_target = Vector {
-        data: [0.0, 0.0, 0.0],
+    let exposure = physical_exposure(&options);
+    let settings = RenderSettings {
+        width,
+        height,
+        samples_per_pixel: options.samples_per_pixel,
+        integrator: options.integrator,
+        depths: options.depths,
+        photons: options.photons,
+        metropolis: options.metropolis,
+        occlusion_radius: options.occlusion_radius,
+        feature_buffers: options.denoise,
+        exposure,
+        spectral: options.spectral,
+        packet_width: options.packet_width,
+        light_sampling: options.light_sampling,
     };
 
-    let focus_distance = 10.0; //Not hardcoded way: (camera_target - camera_origin).length();
+    // Camera.
+    let camera_origin = Point3::new(13.0, 2.0, -3.0);
 
-    let camera: Camera = Camera::new(
-        &camera_origin,
-        &camera_target,
-        &Vector {
-            data: [0.0, 1.0, 0.0],
-        },
-        aspect_ratio,
-        20.0,
-        0.1,
-        focus_distance,
-    );
+    let camera_target = Point3::ORIGIN;
 
-    // Render.
-    let path = "image.ppm";
-    let mut output = File::create(path)?;
-    write!(output, "P3\n{} {}\n255\n", width, height)?;
+    let up = Vec3::new(0.0, 1.0, 0.0);
 
-    let mut random = rand::thread_rng();
-    let timer = Instant::now();
+    let focus_distance = 10.0; //Not hardcoded way: (camera_target - camera_origin).length();
 
-    for y in 0..height {
-        print!("Lines left {}\n", height - y);
-        for x in 0..width {
-            let u: f32 = x as f32 / width as f32;
-            let v: f32 = (height as f32 - y as f32) / height as f32;
+    let aperture = match (&options.aperture_mask, options.aperture_blades) {
+        (Some(path), _) => Aperture::Mask(ApertureMask::load(path)?),
+        (None, Some(blades)) => Aperture::polygon(blades, options.aperture_rotation)?,
+        (None, None) => Aperture::Circle,
+    };
 
-            let mut result_color: Vector = Vector {
-                data: [0.0, 0.0, 0.0],
-            };
+    // The f-number sizes the lens when the exposure is physical.
+    let f_number = settings
+        .exposure
+        .as_ref()
+        .map(|exposure| exposure.f_number as Float);
+    let aperture_diameter = match &settings.exposure {
+        Some(exposure) => {
+            exposure.aperture_diameter(focal_length(20.0, FULL_FRAME_SENSOR_HEIGHT)) as Float
+        }
+        None => 0.1,
+    };
 
-            for _i in 0..samples_per_pixel {
-                let u_with_offset: f32 = u + random.gen::<f32>() / (width as f32);
-                let v_with_offset: f32 = v + random.gen::<f32>() / (height as f32);
+    let aspect_ratio = settings.aspect_ratio();
+    let camera: Box<dyn Camera> = match options.camera.as_str() {
+        "perspective" => Box::new(
+            PerspectiveCamera::new(
+                &camera_origin,
+                &camera_target,
+                &up,
+                aspect_ratio,
+                20.0,
+                aperture_diameter,
+                focus_distance,
+            )?
+            .with_aperture(aperture)
+            .with_cat_eye_vignetting(options.cat_eye),
+        ),
+        "realistic" => Box::new(RealisticCamera::new(
+            &camera_origin,
+            &camera_target,
+            &up,
+            aspect_ratio,
+            &options.lens,
+            35.0,
+            f_number,
+            focus_distance,
+            aperture,
+        )?),
+        "orthographic" => Box::new(OrthographicCamera::new(
+            &camera_origin,
+            &camera_target,
+            &up,
+            aspect_ratio,
+            4.0,
+        )?),
+        "fisheye-equidistant" => Box::new(FisheyeCamera::new(
+            &camera_origin,
+            &camera_target,
+            &up,
+            aspect_ratio,
+            180.0,
+            FisheyeProjection::Equidistant,
+        )?),
+        "fisheye-equisolid" => Box::new(FisheyeCamera::new(
+            &camera_origin,
+            &camera_target,
+            &up,
+            aspect_ratio,
+            180.0,
+            FisheyeProjection::Equisolid,
+        )?),
+        "equirectangular" => Box::new(PanoramicCamera::new(
+            &camera_origin,
+            &camera_target,
+            &up,
+            PanoramicProjection::Equirectangular,
+        )?),
+        "cubemap" => Box::new(PanoramicCamera::new(
+            &camera_origin,
+            &camera_target,
+            &up,
+            PanoramicProjection::Cubemap,
+        )?),
+        other => return Err(Error::InvalidSettings(format!("unknown camera {}", other))),
+    };
 
-                let ray = camera.get_ray(u_with_offset, v_with_offset);
-                result_color += calculate_color(&ray, &world, max_depth);
-            }
+    let profile = match &options.ies {
+        Some(path) => {
+            let ies = Arc::new(IesProfile::load(path)?);
+            Some(match options.lumens {
+                Some(lumens) => LightProfile::with_lumens(ies, lumens)?,
+                None => LightProfile::relative(ies),
+            })
+        }
+        None if options.lumens.is_some() => {
+            return Err(Error::InvalidSettings(String::from(
+                "--lumens scales an IES profile, pass one with --ies",
+            )))
+        }
+        None => None,
+    };
 
-            match write_average_color(output, result_color, samples_per_pixel) {
-                Ok(file) => output = file,
-                Err(error) => {
-                    print!("Error during file writing: {}", error);
-                    return Err(error);
-                }
-            }
+    if options.heightmap.is_some() && options.scene != "terrain" {
+        return Err(Error::InvalidSettings(String::from(
+            "--heightmap is for the terrain scene",
+        )));
+    }
+    if options.displacement.is_some() && options.scene != "subdivision" {
+        return Err(Error::InvalidSettings(String::from(
+            "--displacement is for the subdivision scene",
+        )));
+    }
+    if options.gltf.is_some() != (options.scene == "gltf") {
+        return Err(Error::InvalidSettings(String::from(
+            "--gltf names the file of the gltf scene",
+        )));
+    }
+
+    let scene = match options.scene.as_str() {
+        "random" | "many-lights" | "csg" | "sdf" | "terrain" | "hair" | "subdivision" | "gltf"
+            if profile.is_some() =>
+        {
+            return Err(Error::InvalidSettings(format!(
+                "the {} scene has no lamps for an IES profile",
+                options.scene
+            )))
+        }
+        "random" => Scene::new(generate_random_scene()?, camera),
+        "caustics" => {
+            Scene::new(generate_caustics_scene(profile.as_ref())?, camera).with_sky(Sky::BLACK)
         }
+        "csg" => Scene::new(generate_csg_scene()?, camera),
+        "sdf" => Scene::new(generate_sdf_scene()?, camera),
+        "terrain" => Scene::new(
+            generate_terrain_scene(options.heightmap.as_deref())?,
+            camera,
+        ),
+        "hair" => Scene::new(generate_hair_scene()?, camera),
+        "subdivision" => Scene::new(
+            generate_subdivision_scene(options.displacement.as_deref())?,
+            camera,
+        ),
+        "many-lights" => Scene::new(generate_many_lights_scene()?, camera).with_sky(Sky::BLACK),
+        // The file's own camera, if it has one, takes over.
+        "gltf" => {
+            let path = options.gltf.as_deref().unwrap_or_default();
+            let gltf = GltfScene::load(path, aspect_ratio)?;
+            gltf.lights.into_iter().fold(
+                Scene::new(gltf.world, gltf.camera.unwrap_or(camera)),
+                Scene::with_light,
+            )
+        }
+        "lamps" => {
+            let (world, lights) = generate_lamps_scene(profile.as_ref())?;
+            lights.into_iter().fold(
+                Scene::new(world, camera).with_sky(Sky::BLACK),
+                Scene::with_light,
+            )
+        }
+        other => return Err(Error::InvalidSettings(format!("unknown scene {}", other))),
+    };
+
+    // Render.
+    let timer = Instant::now();
+    let renderer = Renderer::new(settings);
+    let framebuffer = renderer.render_with_progress(
+        &scene,
+        &mut |progress| println!("Lines left {}", progress.lines_total - progress.lines_done),
+        &CancellationToken::new(),
+    )?;
+
+    framebuffer.write_ppm(&options.output)?;
+
+    if options.denoise {
+        println!("Denoising");
+        let denoised = framebuffer
+            .denoise(&DenoiserSettings::default())
+            .expect("Feature buffers are gathered when denoising is requested");
+        denoised.write_ppm(&denoised_path(&options.output))?;
     }
 
-    print!("Done in {} sec!", timer.elapsed().as_secs());
+    println!("Done in {} sec!", timer.elapsed().as_secs());
     Ok(())
 }
 
-fn generate_random_scene() -> HittableList {
-    let mut world: HittableList = HittableList {
-        hittables: Vec::new(),
-    };
-
-    world.hittables.push(Box::new(Sphere {
-        centre: Vector {
-            data: [0.0, -1000.0, 0.0],
-        },
-        radius: 1000.0,
-        material: Box::new(DiffuseMaterial {
-            albedo: Vector {
-                data: [0.5, 0.5, 0.5],
-            },
-        }),
-    }));
+struct Options {
+    output: String,
+    scene: String,
+    camera: String,
+    lens: String,
+    aperture_blades: Option<u32>,
+    aperture_rotation: Float,
+    aperture_mask: Option<String>,
+    cat_eye: Float,
+    f_number: Option<f32>,
+    shutter_time: Option<f32>,
+    iso: Option<f32>,
+    auto_exposure: bool,
+    white_balance: Option<f32>,
+    samples_per_pixel: u32,
+    integrator: Integrator,
+    depths: PathDepths,
+    photons: PhotonSettings,
+    metropolis: MetropolisSettings,
+    occlusion_radius: Float,
+    ies: Option<String>,
+    lumens: Option<Float>,
+    heightmap: Option<String>,
+    displacement: Option<String>,
+    gltf: Option<String>,
+    denoise: bool,
+    spectral: bool,
+    packet_width: PacketWidth,
+    light_sampling: LightSampling,
+}
 
-    let mut random = rand::thread_rng();
-    for x in -11..11 {
-        for z in -11..11 {
-            let sphere_origin = Vector {
-                data: [
-                    x as f32 + 0.9 * random.gen::<f32>(),
-                    0.2,
-                    z as f32 + 0.9 * random.gen::<f32>(),
-                ],
-            };
+fn parse_options(arguments: Vec<String>) -> Result<Options, Error> {
+    let mut options = Options {
+        output: String::from("image.ppm"),
+        scene: String::from("random"),
+        camera: String::from("perspective"),
+        lens: String::from("lenses/dgauss.50mm.dat"),
+        aperture_blades: None,
+        aperture_rotation: 0.0,
+        aperture_mask: None,
+        cat_eye: 0.0,
+        f_number: None,
+        shutter_time: None,
+        iso: None,
+        auto_exposure: false,
+        white_balance: None,
+        samples_per_pixel: 100,
+        integrator: Integrator::Path,
+        depths: PathDepths::default(),
+        photons: PhotonSettings::default(),
+        metropolis: MetropolisSettings::default(),
+        occlusion_radius: 1.0,
+        ies: None,
+        lumens: None,
+        heightmap: None,
+        displacement: None,
+        gltf: None,
+        denoise: false,
+        spectral: false,
+        packet_width: PacketWidth::Four,
+        light_sampling: LightSampling::Power,
+    };
 
-            if (sphere_origin
-                - Vector {
-                    data: [4.0, 0.2, 0.0],
-                })
-            .length()
-                <= 0.9
-            {
-                continue;
+    let mut arguments = arguments.into_iter();
+    while let Some(argument) = arguments.next() {
+        match argument.as_str() {
+            "--denoise" => options.denoise = true,
+            "--output" => options.output = next_value(&mut arguments, &argument)?,
+            "--scene" => options.scene = next_value(&mut arguments, &argument)?,
+            "--camera" => options.camera = next_value(&mut arguments, &argument)?,
+            "--lens" => options.lens = next_value(&mut arguments, &argument)?,
+            "--aperture-blades" => {
+                options.aperture_blades = Some(parse_value(&mut arguments, &argument)?)
             }
-
-            let random_material = random.gen::<f32>();
-
-            if random_material < 0.8 {
-                // Diffuse.
-                let albedo = Vector {
-                    data: [random.gen(), random.gen(), random.gen()],
-                };
-
-                world.hittables.push(Box::new(Sphere {
-                    centre: sphere_origin,
-                    radius: 0.2,
-                    material: Box::new(DiffuseMaterial {
-                        albedo: albedo * albedo,
-                    }),
-                }));
-            } else if random_material < 0.95 {
-                // Metal.
-                let albedo = Vector {
-                    data: [
-                        random.gen_range(0.5..=1.0),
-                        random.gen_range(0.5..=1.0),
-                        random.gen_range(0.5..=1.0),
-                    ],
-                };
-
-                let fuzziness = random.gen_range(0.0..=0.5);
-
-                world.hittables.push(Box::new(Sphere {
-                    centre: sphere_origin,
-                    radius: 0.2,
-                    material: Box::new(MetalMaterial {
-                        albedo: albedo,
-                        fuzziness: fuzziness,
-                    }),
-                }));
-            } else {
-                // Glass.
-                world.hittables.push(Box::new(Sphere {
-                    centre: sphere_origin,
-                    radius: 0.2,
-                    material: Box::new(DielectricMaterial {
-                        refraction_index: 1.5,
-                    }),
-                }));
+            "--aperture-rotation" => {
+                options.aperture_rotation = parse_value(&mut arguments, &argument)?
+            }
+            "--aperture-mask" => {
+                options.aperture_mask = Some(next_value(&mut arguments, &argument)?)
+            }
+            "--cat-eye" => options.cat_eye = parse_value(&mut arguments, &argument)?,
+            "--f-stop" => options.f_number = Some(parse_value(&mut arguments, &argument)?),
+            "--shutter" => {
+                options.shutter_time = Some(parse_shutter_time(&mut arguments, &argument)?)
+            }
+            "--iso" => options.iso = Some(parse_value(&mut arguments, &argument)?),
+            "--auto-exposure" => options.auto_exposure = true,
+            "--spectral" => options.spectral = true,
+            "--white-balance" => {
+                options.white_balance = Some(parse_value(&mut arguments, &argument)?)
+            }
+            "--packet-width" => {
+                options.packet_width = next_value(&mut arguments, &argument)?.parse()?
+            }
+            "--light-sampling" => {
+                options.light_sampling = next_value(&mut arguments, &argument)?.parse()?
+            }
+            "--spp" => options.samples_per_pixel = parse_value(&mut arguments, &argument)?,
+            "--integrator" => {
+                options.integrator = next_value(&mut arguments, &argument)?.parse()?
+            }
+            "--diffuse-depth" => options.depths.diffuse = parse_value(&mut arguments, &argument)?,
+            "--specular-depth" => options.depths.specular = parse_value(&mut arguments, &argument)?,
+            "--transmission-depth" => {
+                options.depths.transmission = parse_value(&mut arguments, &argument)?
+            }
+            "--roulette-depth" => options.depths.roulette = parse_value(&mut arguments, &argument)?,
+            "--photons" => {
+                options.photons.photons_per_iteration = parse_value(&mut arguments, &argument)?
+            }
+            "--photon-radius" => {
+                options.photons.initial_radius = parse_value(&mut arguments, &argument)?
+            }
+            "--bootstrap" => {
+                options.metropolis.bootstrap_samples = parse_value(&mut arguments, &argument)?
+            }
+            "--ao-radius" => options.occlusion_radius = parse_value(&mut arguments, &argument)?,
+            "--ies" => options.ies = Some(next_value(&mut arguments, &argument)?),
+            "--lumens" => options.lumens = Some(parse_value(&mut arguments, &argument)?),
+            "--heightmap" => options.heightmap = Some(next_value(&mut arguments, &argument)?),
+            "--displacement" => options.displacement = Some(next_value(&mut arguments, &argument)?),
+            "--gltf" => options.gltf = Some(next_value(&mut arguments, &argument)?),
+            "--chains" => options.metropolis.chains = parse_value(&mut arguments, &argument)?,
+            "--large-step" => {
+                options.metropolis.large_step_probability = parse_value(&mut arguments, &argument)?
+            }
+            _ => {
+                return Err(Error::InvalidSettings(format!(
+                    "unknown argument {}",
+                    argument
+                )))
             }
         }
     }
 
-    world.hittables.push(Box::new(Sphere {
-        centre: Vector {
-            data: [0.0, 1.0, 0.0],
-        },
-        radius: 1.0,
-        material: Box::new(DielectricMaterial {
-            refraction_index: 1.5,
-        }),
-    }));
-
-    world.hittables.push(Box::new(Sphere {
-        centre: Vector {
-            data: [-4.0, 1.0, 0.0],
-        },
-        radius: 1.0,
-        material: Box::new(DiffuseMaterial {
-            albedo: Vector {
-                data: [0.4, 0.2, 0.1],
-            },
-        }),
-    }));
-
-    world.hittables.push(Box::new(Sphere {
-        centre: Vector {
-            data: [4.0, 1.0, 0.0],
-        },
-        radius: 1.0,
-        material: Box::new(MetalMaterial {
-            albedo: Vector {
-                data: [0.7, 0.6, 0.5],
-            },
-            fuzziness: 0.0,
-        }),
-    }));
-    return world;
+    Ok(options)
 }
 
-fn calculate_color(ray: &Ray, world: &HittableList, depth: i32) -> Vector {
-    if depth <= 0 {
-        return Vector::default();
-    }
-
-    // Try hit something in the world.
-    match world.hit(ray, 0.001, f32::MAX) {
-        // Try scatter ray from the hit geometry.
-        Some(hit_result) => match hit_result.material.scatter(ray, &hit_result) {
-            // Cast scattered ray.
-            Some((attenuation, scattered_ray)) => {
-                return attenuation * calculate_color(&scattered_ray, world, depth - 1);
-            }
-            None => return Vector::default(),
-        },
-        None => (),
-    }
+fn next_value(arguments: &mut impl Iterator<Item = String>, name: &str) -> Result<String, Error> {
+    arguments
+        .next()
+        .ok_or_else(|| Error::InvalidSettings(format!("{} expects a value", name)))
+}
 
-    let direction_normalized: Vector = ray.direction.normalize();
+fn parse_value<T: std::str::FromStr>(
+    arguments: &mut impl Iterator<Item = String>,
+    name: &str,
+) -> Result<T, Error> {
+    let value = next_value(arguments, name)?;
+    value
+        .parse()
+        .map_err(|_| Error::InvalidSettings(format!("{} can't parse value {}", name, value)))
+}
 
-    // Remap y = [-1..1] to [0..1] range.
-    let t: f32 = 0.5 * (direction_normalized.y() + 1.0);
+// Accepts both seconds and photographic fractions like 1/125.
+fn parse_shutter_time(
+    arguments: &mut impl Iterator<Item = String>,
+    name: &str,
+) -> Result<f32, Error> {
+    let value = next_value(arguments, name)?;
+    let parsed = match value.split_once('/') {
+        Some((numerator, denominator)) => numerator
+            .parse::<f32>()
+            .ok()
+            .zip(denominator.parse::<f32>().ok())
+            .map(|(numerator, denominator)| numerator / denominator),
+        None => value.parse().ok(),
+    };
 
-    return lerp(
-        &Vector {
-            data: [1.0, 1.0, 1.0],
-        },
-        &Vector {
-            data: [0.5, 0.7, 1.0],
-        },
-        t,
-    );
+    parsed.ok_or_else(|| Error::InvalidSettings(format!("{} can't parse value {}", name, value)))
 }
 
-fn write_average_color(
-    mut file: File,
-    color: Vector,
-    samples_per_pixel: i32,
-) -> Result<File, Error> {
-    let average_color = color / (samples_per_pixel as f32);
+// Any exposure option switches to physical exposure, the rest default to "sunny 16".
+fn physical_exposure(options: &Options) -> Option<Exposure> {
+    if options.f_number.is_none()
+        && options.shutter_time.is_none()
+        && options.iso.is_none()
+        && !options.auto_exposure
+        && options.white_balance.is_none()
+    {
+        return None;
+    }
 
-    let gamma: f32 = 1.0 / 2.2;
+    let default = Exposure::default();
+    Some(Exposure {
+        f_number: options.f_number.unwrap_or(default.f_number),
+        shutter_time: options.shutter_time.unwrap_or(default.shutter_time),
+        iso: options.iso.unwrap_or(default.iso),
+        auto_exposure: options.auto_exposure,
+        white_balance: options.white_balance,
+    })
+}
 
-    let r: u8 = (average_color.r().powf(gamma) * 255.99) as u8;
-    let g: u8 = (average_color.g().powf(gamma) * 255.99) as u8;
-    let b: u8 = (average_color.b().powf(gamma) * 255.99) as u8;
-    match write!(file, "{} {} {}\n", r, g, b) {
-        Ok(_it) => return Ok(file),
-        Err(err) => return Err(err),
-    };
+// "image.ppm" -> "image_denoised.ppm", so the filtered image lands next to the raw one.
+fn denoised_path(path: &str) -> String {
+    let path = Path::new(path);
+    let mut name = path.file_stem().unwrap_or_default().to_os_string();
+    name.push("_denoised");
+    if let Some(extension) = path.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    path.with_file_name(name).to_string_lossy().into_owned()
 }
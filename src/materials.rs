@@ -1,4 +1,7 @@
-pub mod material;
+pub mod dielectric_material;
 pub mod diffuse_material;
+pub mod emissive_material;
+pub mod hair_material;
+pub mod material;
 pub mod metal_material;
-pub mod dielectric_material;
\ No newline at end of file
+pub mod principled_material;
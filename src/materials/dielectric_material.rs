@@ -1,25 +1,119 @@
+// Sellmeier coefficients are kept exactly as published.
+#![allow(clippy::excessive_precision)]
+
 use rand::Rng;
 
 use crate::{
+    color::Color,
+    error::Error,
+    float::Float,
     hit_record::HitRecord,
     ray::Ray,
-    vector::{dot, Vector},
+    sampler,
+    spectrum::{SampledSpectrum, SampledWavelengths},
+    vector::dot,
 };
 
-use super::material::Material;
+use super::material::{Material, ScatterKind};
 
 pub struct DielectricMaterial {
-    pub refraction_index: f32,
+    // Used by the RGB renderer and by the spectral one without dispersion.
+    refraction_index: Float,
+    dispersion: Dispersion,
 }
 
-impl Material for DielectricMaterial {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Vector, Ray)> {
+// Wavelength dependent index of refraction, wavelengths are in micrometres.
+#[derive(Copy, Clone)]
+pub enum Dispersion {
+    None,
+    // n(λ) = a + b / λ².
+    Cauchy { a: Float, b: Float },
+    // n²(λ) = 1 + Σ b_i λ² / (λ² - c_i).
+    Sellmeier { b: [Float; 3], c: [Float; 3] },
+}
+
+impl Dispersion {
+    // Cauchy dispersion of a BK7-like crown glass scaled to `refraction_index` at 589 nm.
+    pub fn crown_glass(refraction_index: Float) -> Dispersion {
+        let b = 0.00420;
+        Dispersion::Cauchy {
+            a: refraction_index - b / (0.589 * 0.589),
+            b,
+        }
+    }
+
+    // Schott N-BK7.
+    pub fn bk7() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [1.03961212, 0.231792344, 1.01046945],
+            c: [0.00600069867, 0.0200179144, 103.560653],
+        }
+    }
+
+    // Schott SF11 dense flint, strongly dispersive.
+    pub fn sf11() -> Dispersion {
+        Dispersion::Sellmeier {
+            b: [1.73759695, 0.313747346, 1.89878101],
+            c: [0.013188707, 0.0623068142, 155.23629],
+        }
+    }
+
+    pub fn refraction_index(&self, lambda_nm: Float) -> Option<Float> {
+        let lambda = lambda_nm / 1000.0;
+        let lambda_squared = lambda * lambda;
+        match self {
+            Dispersion::None => None,
+            Dispersion::Cauchy { a, b } => Some(a + b / lambda_squared),
+            Dispersion::Sellmeier { b, c } => Some(
+                (1.0 + (0..3)
+                    .map(|index| b[index] * lambda_squared / (lambda_squared - c[index]))
+                    .sum::<Float>())
+                .sqrt(),
+            ),
+        }
+    }
+}
+
+impl DielectricMaterial {
+    pub fn new(refraction_index: Float) -> Result<DielectricMaterial, Error> {
+        if !(refraction_index > 0.0 && refraction_index.is_finite()) {
+            return Err(Error::InvalidMaterial(format!(
+                "refraction index {} must be positive",
+                refraction_index
+            )));
+        }
+
+        Ok(DielectricMaterial {
+            refraction_index,
+            dispersion: Dispersion::None,
+        })
+    }
+
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> DielectricMaterial {
+        self.dispersion = dispersion;
+        self
+    }
+
+    pub fn refraction_index(&self) -> Float {
+        self.refraction_index
+    }
+
+    pub fn dispersion(&self) -> Dispersion {
+        self.dispersion
+    }
+
+    fn scatter_with_index(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        refraction_index: Float,
+    ) -> Ray {
         let refraction_ratio = if hit_record.is_front_face {
             // From air to this material.
-            1.0 / self.refraction_index
+            1.0 / refraction_index
         } else {
             // From this material to air.
-            self.refraction_index
+            refraction_index
         };
 
         let direction_normalized = ray.direction.normalize();
@@ -27,32 +121,70 @@ impl Material for DielectricMaterial {
         let cos_theta = dot(&-direction_normalized, &hit_record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
 
-        let mut random = rand::thread_rng();
+        let mut random = sampler::rng();
 
-        let scattered_ray_direction = if refraction_ratio * sin_theta > 1.0 || reflectance(cos_theta, refraction_ratio) > random.gen() {
+        let scattered_ray_direction = if refraction_ratio * sin_theta > 1.0
+            || reflectance(cos_theta, refraction_ratio) > random.gen()
+        {
             direction_normalized.reflect(&hit_record.normal)
         } else {
             direction_normalized.refract(&hit_record.normal, refraction_ratio)
         };
 
-        let scattered_ray: Ray = Ray {
-            origin: hit_record.origin,
-            direction: scattered_ray_direction,
+        hit_record.spawn_ray(scattered_ray_direction, ray.time)
+    }
+}
+
+impl Material for DielectricMaterial {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        Some((
+            Color::WHITE,
+            self.scatter_with_index(ray, hit_record, self.refraction_index),
+        ))
+    }
+
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        let refraction_index = match self
+            .dispersion
+            .refraction_index(wavelengths.hero() as Float)
+        {
+            Some(refraction_index) => {
+                // Every wavelength bends differently, only the hero one can follow this path.
+                wavelengths.terminate_secondary();
+                refraction_index
+            }
+            None => self.refraction_index,
         };
 
         Some((
-            Vector {
-                data: [1.0, 1.0, 1.0],
-            },
-            scattered_ray,
+            SampledSpectrum::constant(1.0),
+            self.scatter_with_index(ray, hit_record, refraction_index),
         ))
     }
+
+    fn scatter_kind(&self, hit_record: &HitRecord, scattered_ray: &Ray) -> ScatterKind {
+        // The normal faces the incoming ray, refracted rays leave on its other side.
+        if dot(&scattered_ray.direction, &hit_record.normal) < 0.0 {
+            ScatterKind::Transmission
+        } else {
+            ScatterKind::Specular
+        }
+    }
+
+    fn albedo(&self) -> Color {
+        Color::WHITE
+    }
 }
 
-fn reflectance(cosine: f32, refraction_ratio: f32) -> f32 {
+fn reflectance(cosine: Float, refraction_ratio: Float) -> Float {
     // Use Schlick's approximation for reflectance.
     let mut r_0 = (1.0 - refraction_ratio) / (1.0 + refraction_ratio);
     r_0 = r_0 * r_0;
 
     r_0 + (1.0 - r_0) * (1.0 - cosine).powf(5.0)
-}
\ No newline at end of file
+}
@@ -1,27 +1,68 @@
 use crate::{
+    color::Color,
+    error::Error,
+    float::{consts::FRAC_1_PI, to_f32, Float},
     hit_record::HitRecord,
     ray::Ray,
-    vector::{random_on_unit_sphere, Vector},
+    vector::{dot, random_on_unit_sphere, Vec3},
 };
 
 use super::material::Material;
 
+#[derive(Clone)]
 pub struct DiffuseMaterial {
-    pub albedo: Vector,
+    albedo: Color,
+}
+
+impl DiffuseMaterial {
+    pub fn new(albedo: Color) -> Result<DiffuseMaterial, Error> {
+        if !albedo.data.iter().all(|value| (0.0..=1.0).contains(value)) {
+            return Err(Error::InvalidMaterial(format!(
+                "diffuse albedo {:?} must be within [0, 1]",
+                albedo.data
+            )));
+        }
+
+        Ok(DiffuseMaterial { albedo })
+    }
 }
 
 impl Material for DiffuseMaterial {
-    fn scatter(&self, _ray: &Ray, hit_record: &HitRecord) -> Option<(Vector, Ray)> {
-        let mut scatter_direction = hit_record.normal + random_on_unit_sphere();
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let mut scatter_direction = Vec3::from(hit_record.normal) + random_on_unit_sphere();
         if scatter_direction.is_near_zero() {
-            scatter_direction = hit_record.normal;
+            scatter_direction = Vec3::from(hit_record.normal);
         }
 
-        let scattered_ray: Ray = Ray {
-            origin: hit_record.origin,
-            direction: scatter_direction,
-        };
+        let scattered_ray: Ray = hit_record.spawn_ray(scatter_direction, ray.time);
 
         Some((self.albedo, scattered_ray))
     }
+
+    fn albedo(&self) -> Color {
+        self.albedo
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    // Lambertian on whichever side the path arrived, the surface is two-sided.
+    fn eval(&self, hit_record: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        if dot(wo, &hit_record.normal) * dot(wi, &hit_record.normal) > 0.0 {
+            to_f32(FRAC_1_PI) * self.albedo
+        } else {
+            Color::BLACK
+        }
+    }
+
+    // A random point on the unit sphere around the tip of the normal is cosine distributed.
+    fn pdf(&self, hit_record: &HitRecord, wo: &Vec3, wi: &Vec3) -> Float {
+        let cos_theta = dot(wi, &hit_record.normal);
+        if dot(wo, &hit_record.normal) * cos_theta > 0.0 {
+            cos_theta.abs() * FRAC_1_PI
+        } else {
+            0.0
+        }
+    }
 }
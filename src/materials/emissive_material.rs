@@ -0,0 +1,63 @@
+use crate::{
+    color::Color,
+    error::Error,
+    hit_record::HitRecord,
+    lights::ies::LightProfile,
+    ray::Ray,
+    vector::{orthonormal_basis, Normal3, Vec3},
+};
+
+use super::material::Material;
+
+// Turns the primitive it's on into an area light. The front face emits `radiance` evenly in
+// all directions, nothing is reflected.
+pub struct EmissiveMaterial {
+    pub radiance: Color,
+    profile: Option<LightProfile>,
+}
+
+impl EmissiveMaterial {
+    pub fn new(radiance: Color) -> EmissiveMaterial {
+        EmissiveMaterial {
+            radiance,
+            profile: None,
+        }
+    }
+
+    // Shapes the emission with a profile whose nadir points along the normal, `radiance` is
+    // then its peak. It can't be scaled to lumens, what a surface emits depends on its area.
+    pub fn with_profile(mut self, profile: LightProfile) -> Result<EmissiveMaterial, Error> {
+        if profile.lumens().is_some() {
+            return Err(Error::InvalidMaterial(String::from(
+                "only point and spot lights can be scaled to lumens",
+            )));
+        }
+        self.profile = Some(profile);
+        Ok(self)
+    }
+}
+
+impl Material for EmissiveMaterial {
+    fn scatter(&self, _ray: &Ray, _hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        None
+    }
+
+    fn albedo(&self) -> Color {
+        Color::BLACK
+    }
+
+    fn emission(&self) -> Color {
+        self.radiance
+    }
+
+    fn emission_towards(&self, normal: &Normal3, direction: &Vec3) -> Color {
+        match &self.profile {
+            Some(profile) => {
+                let nadir = Vec3::from(*normal);
+                let (reference, _) = orthonormal_basis(&nadir);
+                profile.factor(direction, &nadir, &reference) * self.radiance
+            }
+            None => self.radiance,
+        }
+    }
+}
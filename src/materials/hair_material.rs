@@ -0,0 +1,538 @@
+use rand::Rng;
+
+use crate::{
+    color::Color,
+    error::Error,
+    float::{
+        consts::{LN_2, PI},
+        to_f32, Float,
+    },
+    hit_record::HitRecord,
+    ray::Ray,
+    sampler,
+    vector::{cross, dot, Vec3},
+};
+
+use super::material::{Material, ScatterKind};
+
+// Lobes modelled separately: reflection R, transmission TT, the internal reflection TRT, and
+// one lobe for all longer paths.
+const P_MAX: usize = 3;
+
+// Cuticle refraction index of human hair.
+const HAIR_ETA: Float = 1.55;
+
+// Absorption of a unit diameter fiber for the two melanin pigments at unit concentration.
+const EUMELANIN_ABSORPTION: [f32; 3] = [0.419, 0.697, 1.37];
+const PHEOMELANIN_ABSORPTION: [f32; 3] = [0.187, 0.4, 1.05];
+
+// What colors a fiber: the light it absorbs crossing its diameter once.
+#[derive(Copy, Clone, Debug)]
+pub enum HairPigment {
+    Absorption(Color),
+    // Concentrations of the dark brown eumelanin and the red pheomelanin. Eumelanin around 0.3
+    // gives blond hair, 1.3 brown and 8 black.
+    Melanin { eumelanin: f32, pheomelanin: f32 },
+    // Absorption that makes hair of the given color after many bounces, for its roughness.
+    Reflectance(Color),
+}
+
+// Chiang et al. 2016 hair BSDF, as in pbrt: longitudinal lobes spread by rough cuticle scales
+// tilted toward the root, azimuthal ones from a smooth cylinder at the offset `h` of the hit
+// across the fiber. Curves put `h` in the second uv coordinate and the fiber direction in the
+// tangent. Other hittables have no fibers to scatter off.
+pub struct HairMaterial {
+    pigment: HairPigment,
+    // Longitudinal and azimuthal roughness in (0, 1].
+    longitudinal_roughness: Float,
+    azimuthal_roughness: Float,
+    // Tilt of the cuticle scales in degrees.
+    scale_tilt: Float,
+    absorption: Color,
+    // Longitudinal variance of every lobe.
+    variances: [Float; P_MAX + 1],
+    // Scale of the azimuthal logistic distribution.
+    logistic_scale: Float,
+    // Sines and cosines of 2^k times the scale tilt, shifting the lobes apart.
+    sin_2k_alpha: [Float; 3],
+    cos_2k_alpha: [Float; 3],
+}
+
+impl HairMaterial {
+    pub fn new(pigment: HairPigment) -> Result<HairMaterial, Error> {
+        HairMaterial::build(pigment, 0.3, 0.3, 2.0)
+    }
+
+    pub fn from_melanin(eumelanin: f32, pheomelanin: f32) -> Result<HairMaterial, Error> {
+        HairMaterial::new(HairPigment::Melanin {
+            eumelanin,
+            pheomelanin,
+        })
+    }
+
+    pub fn with_roughness(
+        self,
+        longitudinal: Float,
+        azimuthal: Float,
+    ) -> Result<HairMaterial, Error> {
+        HairMaterial::build(self.pigment, longitudinal, azimuthal, self.scale_tilt)
+    }
+
+    pub fn with_scale_tilt(self, degrees: Float) -> Result<HairMaterial, Error> {
+        HairMaterial::build(
+            self.pigment,
+            self.longitudinal_roughness,
+            self.azimuthal_roughness,
+            degrees,
+        )
+    }
+
+    pub fn pigment(&self) -> HairPigment {
+        self.pigment
+    }
+
+    pub fn longitudinal_roughness(&self) -> Float {
+        self.longitudinal_roughness
+    }
+
+    pub fn azimuthal_roughness(&self) -> Float {
+        self.azimuthal_roughness
+    }
+
+    pub fn scale_tilt(&self) -> Float {
+        self.scale_tilt
+    }
+
+    fn build(
+        pigment: HairPigment,
+        longitudinal_roughness: Float,
+        azimuthal_roughness: Float,
+        scale_tilt: Float,
+    ) -> Result<HairMaterial, Error> {
+        for roughness in [longitudinal_roughness, azimuthal_roughness] {
+            if !(roughness > 0.0 && roughness <= 1.0) {
+                return Err(Error::InvalidMaterial(format!(
+                    "hair roughness {} must be in (0, 1]",
+                    roughness
+                )));
+            }
+        }
+        if !(-90.0 < scale_tilt && scale_tilt < 90.0) {
+            return Err(Error::InvalidMaterial(format!(
+                "hair scale tilt {} must be within 90 degrees",
+                scale_tilt
+            )));
+        }
+        let absorption = match pigment {
+            HairPigment::Absorption(absorption) => absorption,
+            HairPigment::Melanin {
+                eumelanin,
+                pheomelanin,
+            } => {
+                if !(eumelanin >= 0.0
+                    && pheomelanin >= 0.0
+                    && eumelanin.is_finite()
+                    && pheomelanin.is_finite())
+                {
+                    return Err(Error::InvalidMaterial(String::from(
+                        "melanin concentrations must not be negative",
+                    )));
+                }
+                let channel = |i: usize| {
+                    eumelanin * EUMELANIN_ABSORPTION[i] + pheomelanin * PHEOMELANIN_ABSORPTION[i]
+                };
+                Color::new(channel(0), channel(1), channel(2))
+            }
+            HairPigment::Reflectance(color) => {
+                let scale = reflectance_scale(azimuthal_roughness);
+                let channel = |value: f32| {
+                    let log = value.max(1e-4).ln();
+                    (log / scale) * (log / scale)
+                };
+                Color::new(channel(color.r()), channel(color.g()), channel(color.b()))
+            }
+        };
+        if ![absorption.r(), absorption.g(), absorption.b()]
+            .iter()
+            .all(|value| *value >= 0.0 && value.is_finite())
+        {
+            return Err(Error::InvalidMaterial(String::from(
+                "hair absorption must not be negative",
+            )));
+        }
+
+        let beta_m = longitudinal_roughness;
+        let beta_n = azimuthal_roughness;
+        let r_variance = {
+            let deviation = 0.726 * beta_m + 0.812 * beta_m * beta_m + 3.7 * beta_m.powi(20);
+            deviation * deviation
+        };
+        let variances = [
+            r_variance,
+            0.25 * r_variance,
+            4.0 * r_variance,
+            4.0 * r_variance,
+        ];
+        let logistic_scale = (PI / 8.0).sqrt()
+            * (0.265 * beta_n + 1.194 * beta_n * beta_n + 5.372 * beta_n.powi(22));
+
+        let mut sin_2k_alpha = [scale_tilt.to_radians().sin(), 0.0, 0.0];
+        let mut cos_2k_alpha = [
+            (1.0 - sin_2k_alpha[0] * sin_2k_alpha[0]).max(0.0).sqrt(),
+            0.0,
+            0.0,
+        ];
+        for i in 1..3 {
+            sin_2k_alpha[i] = 2.0 * cos_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+            cos_2k_alpha[i] = cos_2k_alpha[i - 1] * cos_2k_alpha[i - 1]
+                - sin_2k_alpha[i - 1] * sin_2k_alpha[i - 1];
+        }
+
+        Ok(HairMaterial {
+            pigment,
+            longitudinal_roughness,
+            azimuthal_roughness,
+            scale_tilt,
+            absorption,
+            variances,
+            logistic_scale,
+            sin_2k_alpha,
+            cos_2k_alpha,
+        })
+    }
+
+    // Sine and cosine of the outgoing elevation shifted for lobe `p`: R by minus twice the scale
+    // tilt, TT by the tilt and TRT by four times it.
+    fn tilted(&self, p: usize, sin_theta_o: Float, cos_theta_o: Float) -> (Float, Float) {
+        let (sin_theta, cos_theta) = match p {
+            0 => (
+                sin_theta_o * self.cos_2k_alpha[1] - cos_theta_o * self.sin_2k_alpha[1],
+                cos_theta_o * self.cos_2k_alpha[1] + sin_theta_o * self.sin_2k_alpha[1],
+            ),
+            1 => (
+                sin_theta_o * self.cos_2k_alpha[0] + cos_theta_o * self.sin_2k_alpha[0],
+                cos_theta_o * self.cos_2k_alpha[0] - sin_theta_o * self.sin_2k_alpha[0],
+            ),
+            2 => (
+                sin_theta_o * self.cos_2k_alpha[2] + cos_theta_o * self.sin_2k_alpha[2],
+                cos_theta_o * self.cos_2k_alpha[2] - sin_theta_o * self.sin_2k_alpha[2],
+            ),
+            _ => (sin_theta_o, cos_theta_o),
+        };
+        (sin_theta, cos_theta.abs())
+    }
+
+    // Attenuation of every lobe for light leaving at elevation `sin_theta_o` from offset `h`,
+    // with the angle the refracted ray makes across the fiber.
+    fn attenuations(&self, sin_theta_o: Float, h: Float) -> ([Color; P_MAX + 1], Float) {
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let sin_theta_t = sin_theta_o / HAIR_ETA;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+        // Modified index for the projection onto the cross section.
+        let eta_p = safe_sqrt(HAIR_ETA * HAIR_ETA - sin_theta_o * sin_theta_o) / cos_theta_o;
+        let sin_gamma_t = (h / eta_p).clamp(-1.0, 1.0);
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t * sin_gamma_t);
+        let gamma_t = sin_gamma_t.asin();
+
+        // Transmittance of one crossing of the interior.
+        let length = to_f32(2.0 * cos_gamma_t / cos_theta_t);
+        let transmittance = Color::new(
+            (-self.absorption.r() * length).exp(),
+            (-self.absorption.g() * length).exp(),
+            (-self.absorption.b() * length).exp(),
+        );
+
+        let cos_gamma_o = safe_sqrt(1.0 - h * h);
+        let fresnel = to_f32(fresnel_dielectric(cos_theta_o * cos_gamma_o, HAIR_ETA));
+        let mut attenuations = [Color::BLACK; P_MAX + 1];
+        attenuations[0] = Color::new(fresnel, fresnel, fresnel);
+        attenuations[1] = (1.0 - fresnel) * (1.0 - fresnel) * transmittance;
+        for p in 2..P_MAX {
+            attenuations[p] = fresnel * attenuations[p - 1] * transmittance;
+        }
+        // Geometric series of everything longer.
+        let bounce = fresnel * transmittance;
+        attenuations[P_MAX] = Color::new(
+            attenuations[P_MAX - 1].r() * bounce.r() / (1.0 - bounce.r()),
+            attenuations[P_MAX - 1].g() * bounce.g() / (1.0 - bounce.g()),
+            attenuations[P_MAX - 1].b() * bounce.b() / (1.0 - bounce.b()),
+        );
+        (attenuations, gamma_t)
+    }
+
+    // Probability of sampling each lobe, by the luminance of its attenuation.
+    fn lobe_probabilities(&self, sin_theta_o: Float, h: Float) -> [Float; P_MAX + 1] {
+        let (attenuations, _) = self.attenuations(sin_theta_o, h);
+        let total: f32 = attenuations.iter().map(Color::luminance).sum();
+        if total <= 0.0 {
+            return [1.0, 0.0, 0.0, 0.0];
+        }
+        attenuations.map(|attenuation| (attenuation.luminance() / total) as Float)
+    }
+
+    // BSDF and sampling density together, with the directions in the frame of the fiber.
+    fn evaluate(&self, h: Float, wo: &Vec3, wi: &Vec3) -> (Color, Float) {
+        let sin_theta_o = wo.x().clamp(-1.0, 1.0);
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let sin_theta_i = wi.x().clamp(-1.0, 1.0);
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+        let phi = wi.z().atan2(wi.y()) - wo.z().atan2(wo.y());
+        let gamma_o = h.asin();
+
+        let (attenuations, gamma_t) = self.attenuations(sin_theta_o, h);
+        let probabilities = self.lobe_probabilities(sin_theta_o, h);
+        let mut value = Color::BLACK;
+        let mut pdf = 0.0;
+        for p in 0..P_MAX {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+            let density = longitudinal(
+                cos_theta_i,
+                cos_theta_op,
+                sin_theta_i,
+                sin_theta_op,
+                self.variances[p],
+            ) * azimuthal(phi, p, self.logistic_scale, gamma_o, gamma_t);
+            value += to_f32(density) * attenuations[p];
+            pdf += density * probabilities[p];
+        }
+        let density = longitudinal(
+            cos_theta_i,
+            cos_theta_o,
+            sin_theta_i,
+            sin_theta_o,
+            self.variances[P_MAX],
+        ) / (2.0 * PI);
+        value += to_f32(density) * attenuations[P_MAX];
+        pdf += density * probabilities[P_MAX];
+
+        // The model is a density over the sphere that includes the cosine.
+        let cos_normal = wi.z().abs();
+        if cos_normal > 0.0 {
+            value = value / to_f32(cos_normal);
+        }
+        (value, pdf)
+    }
+
+    fn sample(&self, h: Float, wo: &Vec3) -> Vec3 {
+        let mut random = sampler::rng();
+        let sin_theta_o = wo.x().clamp(-1.0, 1.0);
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o * sin_theta_o);
+        let phi_o = wo.z().atan2(wo.y());
+
+        let probabilities = self.lobe_probabilities(sin_theta_o, h);
+        let mut choice = random.gen::<Float>();
+        let mut p = 0;
+        while p < P_MAX && choice >= probabilities[p] {
+            choice -= probabilities[p];
+            p += 1;
+        }
+
+        // Elevation from the longitudinal lobe around the tilted mirror direction.
+        let (sin_theta_op, cos_theta_op) = self.tilted(p, sin_theta_o, cos_theta_o);
+        let variance = self.variances[p];
+        let u = random.gen::<Float>().max(1e-5);
+        let cos_theta = 1.0 + variance * (u + (1.0 - u) * (-2.0 / variance).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta * cos_theta);
+        let cos_phi = (2.0 * PI * random.gen::<Float>()).cos();
+        let sin_theta_i =
+            (-cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op).clamp(-1.0, 1.0);
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i * sin_theta_i);
+
+        // Azimuth around the deflection of the lobe.
+        let eta_p = safe_sqrt(HAIR_ETA * HAIR_ETA - sin_theta_o * sin_theta_o) / cos_theta_o;
+        let gamma_t = (h / eta_p).clamp(-1.0, 1.0).asin();
+        let delta_phi = if p < P_MAX {
+            deflection(p, h.asin(), gamma_t)
+                + sample_trimmed_logistic(random.gen::<Float>(), self.logistic_scale)
+        } else {
+            2.0 * PI * random.gen::<Float>()
+        };
+        let phi_i = phi_o + delta_phi;
+
+        Vec3::new(
+            sin_theta_i,
+            cos_theta_i * phi_i.cos(),
+            cos_theta_i * phi_i.sin(),
+        )
+    }
+}
+
+// Frame of the fiber at the hit: x along it, z the normal, and the offset across it.
+fn fiber_frame(hit_record: &HitRecord) -> Option<(Vec3, Vec3, Vec3, Float)> {
+    let tangent = hit_record.tangent?;
+    let normal = Vec3::from(hit_record.normal);
+    let z = (normal - dot(&normal, &tangent) * tangent).normalize();
+    let y = cross(&z, &tangent);
+    let h = (2.0 * hit_record.uv[1] - 1.0).clamp(-1.0 + 1e-5, 1.0 - 1e-5);
+    Some((tangent, y, z, h))
+}
+
+fn to_frame(x: &Vec3, y: &Vec3, z: &Vec3, direction: &Vec3) -> Vec3 {
+    Vec3::new(dot(direction, x), dot(direction, y), dot(direction, z))
+}
+
+impl Material for HairMaterial {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let (x, y, z, h) = fiber_frame(hit_record)?;
+        let wo = -ray.direction.normalize();
+        let local_wo = to_frame(&x, &y, &z, &wo);
+        let local_wi = self.sample(h, &local_wo);
+        let (value, pdf) = self.evaluate(h, &local_wo, &local_wi);
+        if pdf <= 0.0 || !pdf.is_finite() {
+            return None;
+        }
+        let wi = local_wi.x() * x + local_wi.y() * y + local_wi.z() * z;
+        let attenuation = value * to_f32(local_wi.z().abs() / pdf);
+
+        Some((attenuation, hit_record.spawn_ray(wi, ray.time)))
+    }
+
+    // Light passing through the fiber comes out on the far side.
+    fn scatter_kind(&self, hit_record: &HitRecord, scattered_ray: &Ray) -> ScatterKind {
+        if dot(&scattered_ray.direction, &hit_record.normal) < 0.0 {
+            ScatterKind::Transmission
+        } else {
+            ScatterKind::Diffuse
+        }
+    }
+
+    // Color of a fiber seen in a bundle of them, inverting the reflectance fit.
+    fn albedo(&self) -> Color {
+        if let HairPigment::Reflectance(color) = self.pigment {
+            return color;
+        }
+        let scale = reflectance_scale(self.azimuthal_roughness);
+        let channel = |absorption: f32| (-absorption.sqrt() * scale).exp();
+        Color::new(
+            channel(self.absorption.r()),
+            channel(self.absorption.g()),
+            channel(self.absorption.b()),
+        )
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, hit_record: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        match fiber_frame(hit_record) {
+            Some((x, y, z, h)) => {
+                self.evaluate(h, &to_frame(&x, &y, &z, wo), &to_frame(&x, &y, &z, wi))
+                    .0
+            }
+            None => Color::BLACK,
+        }
+    }
+
+    fn pdf(&self, hit_record: &HitRecord, wo: &Vec3, wi: &Vec3) -> Float {
+        match fiber_frame(hit_record) {
+            Some((x, y, z, h)) => {
+                self.evaluate(h, &to_frame(&x, &y, &z, wo), &to_frame(&x, &y, &z, wi))
+                    .1
+            }
+            None => 0.0,
+        }
+    }
+}
+
+// Divisor of the fit from reflectance to absorption, for the azimuthal roughness.
+fn reflectance_scale(beta_n: Float) -> f32 {
+    to_f32(
+        5.969 - 0.215 * beta_n + 2.532 * beta_n.powi(2) - 10.73 * beta_n.powi(3)
+            + 5.574 * beta_n.powi(4)
+            + 0.245 * beta_n.powi(5),
+    )
+}
+
+fn safe_sqrt(value: Float) -> Float {
+    value.max(0.0).sqrt()
+}
+
+// Unpolarized Fresnel reflectance entering a dielectric of index `eta` from outside.
+fn fresnel_dielectric(cos_theta_i: Float, eta: Float) -> Float {
+    let cos_theta_i = cos_theta_i.clamp(0.0, 1.0);
+    let sin_theta_t = safe_sqrt(1.0 - cos_theta_i * cos_theta_i) / eta;
+    if sin_theta_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_theta_t = safe_sqrt(1.0 - sin_theta_t * sin_theta_t);
+    let parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+    0.5 * (parallel * parallel + perpendicular * perpendicular)
+}
+
+// Modified Bessel function of the first kind and order zero, by its series.
+fn bessel_i0(x: Float) -> Float {
+    let mut value = 0.0;
+    let mut x_2i = 1.0;
+    let mut factorial = 1.0;
+    let mut four_i = 1.0;
+    for i in 0..10 {
+        if i > 1 {
+            factorial *= i as Float;
+        }
+        value += x_2i / (four_i * factorial * factorial);
+        x_2i *= x * x;
+        four_i *= 4.0;
+    }
+    value
+}
+
+fn log_bessel_i0(x: Float) -> Float {
+    if x > 12.0 {
+        x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x))
+    } else {
+        bessel_i0(x).ln()
+    }
+}
+
+// Longitudinal scattering of d'Eon et al., in log space for small variances where the terms
+// overflow.
+fn longitudinal(
+    cos_theta_i: Float,
+    cos_theta_o: Float,
+    sin_theta_i: Float,
+    sin_theta_o: Float,
+    variance: Float,
+) -> Float {
+    let a = cos_theta_i * cos_theta_o / variance;
+    let b = sin_theta_i * sin_theta_o / variance;
+    if variance <= 0.1 {
+        (log_bessel_i0(a) - b - 1.0 / variance + LN_2 + (1.0 / (2.0 * variance)).ln()).exp()
+    } else {
+        (-b).exp() * bessel_i0(a) / ((1.0 / variance).sinh() * 2.0 * variance)
+    }
+}
+
+// Azimuth lobe `p` leaves at, relative to the incoming one.
+fn deflection(p: usize, gamma_o: Float, gamma_t: Float) -> Float {
+    let p = p as Float;
+    2.0 * p * gamma_t - 2.0 * gamma_o + p * PI
+}
+
+fn azimuthal(phi: Float, p: usize, scale: Float, gamma_o: Float, gamma_t: Float) -> Float {
+    let mut delta = phi - deflection(p, gamma_o, gamma_t);
+    delta = (delta + PI).rem_euclid(2.0 * PI) - PI;
+    trimmed_logistic(delta, scale)
+}
+
+fn logistic(x: Float, scale: Float) -> Float {
+    let e = (-x.abs() / scale).exp();
+    e / (scale * (1.0 + e) * (1.0 + e))
+}
+
+fn logistic_cdf(x: Float, scale: Float) -> Float {
+    1.0 / (1.0 + (-x / scale).exp())
+}
+
+// Logistic distribution cut down to [-π, π].
+fn trimmed_logistic(x: Float, scale: Float) -> Float {
+    logistic(x, scale) / (logistic_cdf(PI, scale) - logistic_cdf(-PI, scale))
+}
+
+fn sample_trimmed_logistic(u: Float, scale: Float) -> Float {
+    let low = logistic_cdf(-PI, scale);
+    let k = logistic_cdf(PI, scale) - low;
+    let x = -scale * (1.0 / (u * k + low) - 1.0).ln();
+    x.clamp(-PI, PI)
+}
@@ -1,5 +1,72 @@
-use crate::{hit_record::HitRecord, ray::Ray, vector::Vector};
+use crate::{
+    color::Color,
+    float::Float,
+    hit_record::HitRecord,
+    ray::Ray,
+    spectrum::{RgbSpectrum, SampledSpectrum, SampledWavelengths},
+    vector::{Normal3, Vec3},
+};
+
+// How a scattered ray leaves the surface, paths limit their bounces of every kind separately.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ScatterKind {
+    Diffuse,
+    Specular,
+    Transmission,
+}
 
 pub trait Material {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Vector, Ray)>;
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)>;
+
+    // Kind of a ray returned by `scatter`.
+    fn scatter_kind(&self, _hit_record: &HitRecord, _scattered_ray: &Ray) -> ScatterKind {
+        ScatterKind::Diffuse
+    }
+
+    // Spectral version of `scatter`, by default the RGB attenuation is upsampled to a spectrum.
+    // Wavelength dependent materials may terminate the secondary wavelengths.
+    fn scatter_spectral(
+        &self,
+        ray: &Ray,
+        hit_record: &HitRecord,
+        wavelengths: &mut SampledWavelengths,
+    ) -> Option<(SampledSpectrum, Ray)> {
+        let (attenuation, scattered_ray) = self.scatter(ray, hit_record)?;
+        Some((
+            RgbSpectrum::albedo(&attenuation).sample(wavelengths),
+            scattered_ray,
+        ))
+    }
+
+    // Surface color used for the albedo feature buffer of the denoiser.
+    fn albedo(&self) -> Color;
+
+    // Radiance leaving the front face, black unless the material is a light. Lights with a
+    // profile give their brightest.
+    fn emission(&self) -> Color {
+        Color::BLACK
+    }
+
+    // Radiance leaving the front face with `normal` along the unit vector `direction`.
+    fn emission_towards(&self, _normal: &Normal3, _direction: &Vec3) -> Color {
+        self.emission()
+    }
+
+    // Whether `scatter` picks directions from a delta distribution, like mirrors and glass.
+    // Bidirectional methods can't connect paths through such surfaces, so it's also the safe
+    // answer for materials without `eval` and `pdf`.
+    fn is_specular(&self) -> bool {
+        true
+    }
+
+    // BSDF for light going between the unit directions `wo` and `wi`, both pointing away from
+    // the hit. Unlike the attenuation of `scatter` it doesn't include the cosine.
+    fn eval(&self, _hit_record: &HitRecord, _wo: &Vec3, _wi: &Vec3) -> Color {
+        Color::BLACK
+    }
+
+    // Density by solid angle of `scatter` picking `wi` for a path arriving from `wo`.
+    fn pdf(&self, _hit_record: &HitRecord, _wo: &Vec3, _wi: &Vec3) -> Float {
+        0.0
+    }
 }
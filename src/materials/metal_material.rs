@@ -1,29 +1,65 @@
 use crate::{
+    color::Color,
+    error::Error,
+    float::Float,
     hit_record::HitRecord,
     ray::Ray,
-    vector::{dot, Vector, random_on_unit_sphere},
+    vector::{dot, random_on_unit_sphere},
 };
 
-use super::material::Material;
+use super::material::{Material, ScatterKind};
 
+#[derive(Clone)]
 pub struct MetalMaterial {
-    pub albedo: Vector,
-    pub fuzziness : f32
+    albedo: Color,
+    // Radius of the sphere the reflected direction is jittered within.
+    fuzziness: Float,
+}
+
+impl MetalMaterial {
+    pub fn new(albedo: Color, fuzziness: Float) -> Result<MetalMaterial, Error> {
+        if !albedo.data.iter().all(|value| (0.0..=1.0).contains(value)) {
+            return Err(Error::InvalidMaterial(format!(
+                "metal albedo {:?} must be within [0, 1]",
+                albedo.data
+            )));
+        }
+        if !(0.0..=1.0).contains(&fuzziness) {
+            return Err(Error::InvalidMaterial(format!(
+                "metal fuzziness {} must be in [0, 1]",
+                fuzziness
+            )));
+        }
+
+        Ok(MetalMaterial { albedo, fuzziness })
+    }
+
+    pub fn fuzziness(&self) -> Float {
+        self.fuzziness
+    }
 }
 
 impl Material for MetalMaterial {
-    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Vector, Ray)> {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
         let reflected_direction = ray.direction.reflect(&hit_record.normal);
 
-        let scattered_ray: Ray = Ray {
-            origin: hit_record.origin,
-            direction: reflected_direction + self.fuzziness * random_on_unit_sphere(),
-        };
+        let scattered_ray: Ray = hit_record.spawn_ray(
+            reflected_direction + self.fuzziness * random_on_unit_sphere(),
+            ray.time,
+        );
 
         if dot(&scattered_ray.direction, &hit_record.normal) > 0.0 {
-           return Some((self.albedo, scattered_ray))
+            return Some((self.albedo, scattered_ray));
         }
 
         None
     }
+
+    fn scatter_kind(&self, _hit_record: &HitRecord, _scattered_ray: &Ray) -> ScatterKind {
+        ScatterKind::Specular
+    }
+
+    fn albedo(&self) -> Color {
+        self.albedo
+    }
 }
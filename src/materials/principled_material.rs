@@ -0,0 +1,294 @@
+use std::rc::Rc;
+
+use rand::Rng;
+
+use crate::{
+    color::Color,
+    error::Error,
+    float::{
+        consts::{FRAC_1_PI, PI},
+        to_f32, Float,
+    },
+    hit_record::HitRecord,
+    image::Image,
+    ray::Ray,
+    sampler,
+    vector::{cross, dot, orthonormal_basis, Vec3},
+};
+
+use super::material::Material;
+
+// Reflectance of dielectrics at normal incidence, an index of refraction of 1.5.
+const DIELECTRIC_F0: f32 = 0.04;
+
+// Below this GGX width the highlights get too narrow to sample reliably.
+const MIN_ALPHA: Float = 1e-3;
+
+// Metallic-roughness material of glTF: GGX reflection with Smith shadowing and Schlick's
+// Fresnel over a Lambertian base. Metals tint their reflection with the base color and have no
+// diffuse part. Textures hold linear values and multiply the factors at the uv of the hits.
+pub struct PrincipledMaterial {
+    base_color: Color,
+    metallic: Float,
+    // Perceptual roughness, GGX width is its square.
+    roughness: Float,
+    base_color_texture: Option<Rc<Image>>,
+    // Roughness in the green channel, metalness in the blue one.
+    metallic_roughness_texture: Option<Rc<Image>>,
+}
+
+// Surface at one hit, with the textures applied.
+struct Surface {
+    base_color: Color,
+    metallic: f32,
+    alpha: Float,
+    // Probability of sampling the specular lobe rather than the diffuse one.
+    specular_probability: Float,
+}
+
+impl PrincipledMaterial {
+    pub fn new(
+        base_color: Color,
+        metallic: Float,
+        roughness: Float,
+    ) -> Result<PrincipledMaterial, Error> {
+        if !base_color
+            .data
+            .iter()
+            .all(|value| (0.0..=1.0).contains(value))
+        {
+            return Err(Error::InvalidMaterial(format!(
+                "base color {:?} must be within [0, 1]",
+                base_color.data
+            )));
+        }
+        for (name, value) in [("metallic", metallic), ("roughness", roughness)] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(Error::InvalidMaterial(format!(
+                    "{} {} must be in [0, 1]",
+                    name, value
+                )));
+            }
+        }
+
+        Ok(PrincipledMaterial {
+            base_color,
+            metallic,
+            roughness,
+            base_color_texture: None,
+            metallic_roughness_texture: None,
+        })
+    }
+
+    pub fn with_base_color_texture(mut self, texture: Rc<Image>) -> PrincipledMaterial {
+        self.base_color_texture = Some(texture);
+        self
+    }
+
+    pub fn with_metallic_roughness_texture(mut self, texture: Rc<Image>) -> PrincipledMaterial {
+        self.metallic_roughness_texture = Some(texture);
+        self
+    }
+
+    pub fn base_color(&self) -> Color {
+        self.base_color
+    }
+
+    pub fn metallic(&self) -> Float {
+        self.metallic
+    }
+
+    pub fn roughness(&self) -> Float {
+        self.roughness
+    }
+
+    pub fn base_color_texture(&self) -> Option<&Rc<Image>> {
+        self.base_color_texture.as_ref()
+    }
+
+    pub fn metallic_roughness_texture(&self) -> Option<&Rc<Image>> {
+        self.metallic_roughness_texture.as_ref()
+    }
+
+    fn surface(&self, hit_record: &HitRecord, wo_z: Float) -> Surface {
+        let [u, v] = hit_record.uv;
+        let mut base_color = self.base_color;
+        if let Some(texture) = &self.base_color_texture {
+            base_color *= texture.sample(u, v);
+        }
+        let (mut metallic, mut roughness) = (self.metallic, self.roughness);
+        if let Some(texture) = &self.metallic_roughness_texture {
+            let texel = texture.sample(u, v);
+            roughness *= texel.g() as Float;
+            metallic *= texel.b() as Float;
+        }
+        let metallic = to_f32(metallic);
+
+        // Lobes weighted by their reflectance seen from `wo`.
+        let specular = fresnel(&specular_f0(&base_color, metallic), wo_z).luminance();
+        let diffuse = (1.0 - metallic) * (1.0 - specular) * base_color.luminance();
+        let specular_probability = if specular + diffuse > 0.0 {
+            (specular / (specular + diffuse)) as Float
+        } else {
+            1.0
+        };
+
+        Surface {
+            base_color,
+            metallic,
+            alpha: (roughness * roughness).max(MIN_ALPHA),
+            specular_probability,
+        }
+    }
+
+    // Directions in a frame with the normal of the side the path arrived from along z.
+    fn frame(hit_record: &HitRecord) -> [Vec3; 3] {
+        let normal = Vec3::from(hit_record.normal);
+        let (tangent, bitangent) = orthonormal_basis(&normal);
+        [tangent, bitangent, normal]
+    }
+
+    fn eval_local(surface: &Surface, wo: &Vec3, wi: &Vec3) -> Color {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return Color::BLACK;
+        }
+        let half = (*wo + *wi).normalize();
+        let reflectance = fresnel(
+            &specular_f0(&surface.base_color, surface.metallic),
+            dot(wi, &half),
+        );
+        let specular = to_f32(
+            ggx(surface.alpha, half.z())
+                * smith_g1(surface.alpha, wo.z())
+                * smith_g1(surface.alpha, wi.z())
+                / (4.0 * wo.z() * wi.z()),
+        );
+        // The base only gets the light the dielectric coating doesn't reflect.
+        let coating = fresnel(&specular_f0(&Color::BLACK, 0.0), dot(wi, &half)).r();
+        let diffuse =
+            (1.0 - surface.metallic) * (1.0 - coating) * to_f32(FRAC_1_PI) * surface.base_color;
+
+        specular * reflectance + diffuse
+    }
+
+    fn pdf_local(surface: &Surface, wo: &Vec3, wi: &Vec3) -> Float {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 {
+            return 0.0;
+        }
+        let half = (*wo + *wi).normalize();
+        // Visible normals: G1(wo) D(h) max(0, wo.h) / wo.z over the 4 wo.h of the reflection.
+        let specular =
+            smith_g1(surface.alpha, wo.z()) * ggx(surface.alpha, half.z()) / (4.0 * wo.z());
+        let diffuse = wi.z() * FRAC_1_PI;
+
+        surface.specular_probability * specular + (1.0 - surface.specular_probability) * diffuse
+    }
+}
+
+impl Material for PrincipledMaterial {
+    fn scatter(&self, ray: &Ray, hit_record: &HitRecord) -> Option<(Color, Ray)> {
+        let frame = PrincipledMaterial::frame(hit_record);
+        let wo = to_local(&frame, &(-ray.direction.normalize()));
+        if wo.z() <= 0.0 {
+            return None;
+        }
+        let surface = self.surface(hit_record, wo.z());
+
+        let mut random = sampler::rng();
+        let choice = random.gen::<Float>();
+        let (u1, u2) = (random.gen::<Float>(), random.gen::<Float>());
+        let wi = if choice < surface.specular_probability {
+            let half = sample_visible_normal(surface.alpha, &wo, u1, u2);
+            2.0 * dot(&wo, &half) * half - wo
+        } else {
+            let radius = u1.sqrt();
+            let (sin, cos) = (2.0 * PI * u2).sin_cos();
+            Vec3::new(radius * cos, radius * sin, (1.0 - u1).max(0.0).sqrt())
+        };
+
+        let pdf = PrincipledMaterial::pdf_local(&surface, &wo, &wi);
+        if pdf <= 0.0 {
+            return None;
+        }
+        let attenuation = to_f32(wi.z() / pdf) * PrincipledMaterial::eval_local(&surface, &wo, &wi);
+        let direction = wi.x() * frame[0] + wi.y() * frame[1] + wi.z() * frame[2];
+
+        Some((attenuation, hit_record.spawn_ray(direction, ray.time)))
+    }
+
+    fn albedo(&self) -> Color {
+        self.base_color
+    }
+
+    fn is_specular(&self) -> bool {
+        false
+    }
+
+    fn eval(&self, hit_record: &HitRecord, wo: &Vec3, wi: &Vec3) -> Color {
+        let frame = PrincipledMaterial::frame(hit_record);
+        let (wo, wi) = (to_local(&frame, wo), to_local(&frame, wi));
+        let surface = self.surface(hit_record, wo.z());
+        PrincipledMaterial::eval_local(&surface, &wo, &wi)
+    }
+
+    fn pdf(&self, hit_record: &HitRecord, wo: &Vec3, wi: &Vec3) -> Float {
+        let frame = PrincipledMaterial::frame(hit_record);
+        let (wo, wi) = (to_local(&frame, wo), to_local(&frame, wi));
+        let surface = self.surface(hit_record, wo.z());
+        PrincipledMaterial::pdf_local(&surface, &wo, &wi)
+    }
+}
+
+fn to_local(frame: &[Vec3; 3], direction: &Vec3) -> Vec3 {
+    Vec3 {
+        data: frame.map(|axis| dot(direction, &axis)),
+    }
+}
+
+fn specular_f0(base_color: &Color, metallic: f32) -> Color {
+    Color::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0).lerp(base_color, metallic)
+}
+
+// Schlick's approximation.
+fn fresnel(f0: &Color, cos_theta: Float) -> Color {
+    let weight = to_f32((1.0 - cos_theta.clamp(0.0, 1.0)).powi(5));
+    f0.lerp(&Color::WHITE, weight)
+}
+
+// GGX distribution of microfacet normals at `cos_theta` from the normal.
+fn ggx(alpha: Float, cos_theta: Float) -> Float {
+    if cos_theta <= 0.0 {
+        return 0.0;
+    }
+    let alpha2 = alpha * alpha;
+    let denominator = cos_theta * cos_theta * (alpha2 - 1.0) + 1.0;
+    alpha2 / (PI * denominator * denominator)
+}
+
+// Smith masking of GGX for a direction at `cos_theta` from the normal.
+fn smith_g1(alpha: Float, cos_theta: Float) -> Float {
+    let alpha2 = alpha * alpha;
+    2.0 * cos_theta / (cos_theta + (alpha2 + (1.0 - alpha2) * cos_theta * cos_theta).sqrt())
+}
+
+// Microfacet normal seen from `wo`, in proportion to its visible area (Heitz 2018).
+fn sample_visible_normal(alpha: Float, wo: &Vec3, u1: Float, u2: Float) -> Vec3 {
+    let stretched = Vec3::new(alpha * wo.x(), alpha * wo.y(), wo.z()).normalize();
+    let length_squared = stretched.x() * stretched.x() + stretched.y() * stretched.y();
+    let t1 = if length_squared > 0.0 {
+        Vec3::new(-stretched.y(), stretched.x(), 0.0) / length_squared.sqrt()
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let t2 = cross(&stretched, &t1);
+
+    let radius = u1.sqrt();
+    let (sin, cos) = (2.0 * PI * u2).sin_cos();
+    let p1 = radius * cos;
+    let s = 0.5 * (1.0 + stretched.z());
+    let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * radius * sin;
+    let p3 = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+    let normal = p1 * t1 + p2 * t2 + p3 * stretched;
+
+    Vec3::new(alpha * normal.x(), alpha * normal.y(), normal.z().max(0.0)).normalize()
+}
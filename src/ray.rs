@@ -1,13 +1,71 @@
+use crate::float::Float;
+use crate::vector::{dot, Normal3, Point3, Vec3};
 
-use crate::vector::Vector;
-
+#[derive(Copy, Clone, Debug)]
 pub struct Ray {
-    pub origin: Vector,
-    pub direction: Vector,
+    pub origin: Point3,
+    pub direction: Vec3,
+    // Moment within the shutter interval, in seconds since it opened.
+    pub time: Float,
 }
 
 impl Ray {
-    pub fn point_at_parameter(&self, t: f32) -> Vector {
+    pub fn point_at_parameter(&self, t: Float) -> Point3 {
         self.origin + t * self.direction
     }
 }
+
+// Moves a hit point along the normal, to the side `direction` leaves towards, just far enough
+// to clear the box its rounding `error` spans. A ray from there can't hit the same surface
+// again, at any scene scale, so intersection tests need no minimum distance.
+pub fn offset_ray_origin(
+    point: &Point3,
+    error: &Vec3,
+    normal: &Normal3,
+    direction: &Vec3,
+) -> Point3 {
+    let distance = dot(&normal.abs(), error);
+    let mut offset = distance * Vec3::from(*normal);
+    if dot(direction, normal) < 0.0 {
+        offset = -offset;
+    }
+
+    let mut origin = *point + offset;
+    // The addition rounds too, round once more away from the point.
+    for axis in 0..3 {
+        if offset.data[axis] > 0.0 {
+            origin.data[axis] = origin.data[axis].next_up();
+        } else if offset.data[axis] < 0.0 {
+            origin.data[axis] = origin.data[axis].next_down();
+        }
+    }
+    origin
+}
+
+// Ray between points on two surfaces, pbrt's SpawnRayTo: both ends are moved off their
+// surfaces like `offset_ray_origin`, towards each other, and the ray reaches the far one at
+// t = 1. A hit in (0, 1) is something in between, never either surface. Points without a
+// normal, like those on the lens or a point light, stay where they are.
+pub fn spawn_ray_to(
+    from: &Point3,
+    from_error: &Vec3,
+    from_normal: Option<&Normal3>,
+    to: &Point3,
+    to_error: &Vec3,
+    to_normal: Option<&Normal3>,
+    time: Float,
+) -> Ray {
+    let origin = match from_normal {
+        Some(normal) => offset_ray_origin(from, from_error, normal, &(*to - *from)),
+        None => *from,
+    };
+    let target = match to_normal {
+        Some(normal) => offset_ray_origin(to, to_error, normal, &(origin - *to)),
+        None => *to,
+    };
+    Ray {
+        origin,
+        direction: target - origin,
+        time,
+    }
+}
@@ -0,0 +1,806 @@
+use std::{
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use rand::Rng;
+
+use crate::{
+    color::Color,
+    denoiser::FeatureBuffers,
+    error::Error,
+    exposure::Exposure,
+    float::{to_f32, Float},
+    framebuffer::Framebuffer,
+    hittables::{
+        bvh::{build_bvh, PacketWidth},
+        hittable::Hittable,
+    },
+    integrators::{
+        bdpt::BidirectionalPathTracer,
+        debug,
+        direct::DirectLighting,
+        mlt::{MetropolisLightTransport, MetropolisSettings},
+        sppm::{PhotonMapper, PhotonSettings},
+    },
+    lights::{light_sampling::LightSampling, sky_light::Sky},
+    materials::material::ScatterKind,
+    ray::Ray,
+    sampler,
+    scene::Scene,
+    spectrum::{self, RgbSpectrum, SampledSpectrum, SampledWavelengths},
+    vector::{Normal3, Vec3},
+};
+
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: u32,
+    pub integrator: Integrator,
+    pub depths: PathDepths,
+    // Only used by photon mapping.
+    pub photons: PhotonSettings,
+    // Only used by Metropolis light transport.
+    pub metropolis: MetropolisSettings,
+    // Only used by ambient occlusion, how far away geometry still occludes.
+    pub occlusion_radius: Float,
+    // Gather albedo, normal and depth buffers for the denoiser.
+    pub feature_buffers: bool,
+    // Physical camera exposure, without it the radiance is written as is.
+    pub exposure: Option<Exposure>,
+    // Trace wavelengths instead of RGB, needed for dispersion.
+    pub spectral: bool,
+    // Primitives intersected at once in the leaves of the BVH.
+    pub packet_width: PacketWidth,
+    // How the integrators sampling lights pick one of them.
+    pub light_sampling: LightSampling,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            width: 1200,
+            height: 800,
+            samples_per_pixel: 100,
+            integrator: Integrator::Path,
+            depths: PathDepths::default(),
+            photons: PhotonSettings::default(),
+            metropolis: MetropolisSettings::default(),
+            occlusion_radius: 1.0,
+            feature_buffers: false,
+            exposure: None,
+            spectral: false,
+            packet_width: PacketWidth::Four,
+            light_sampling: LightSampling::Power,
+        }
+    }
+}
+
+// Algorithm that estimates the light arriving along the camera rays.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Integrator {
+    Path,
+    // Bidirectional path tracing, for light that camera paths rarely find by themselves, like
+    // caustics and lamps seen through small openings.
+    Bidirectional,
+    // Stochastic progressive photon mapping, for sharp caustics. Every sample per pixel is
+    // one iteration.
+    PhotonMapping,
+    // Primary sample space Metropolis light transport over the path tracer, for light that
+    // only few paths find. Samples per pixel set the number of mutations.
+    Metropolis,
+    // Cheap views for debugging scenes, see `integrators::debug`.
+    AmbientOcclusion,
+    DirectLighting,
+    Normals,
+    TraversalCost,
+    Uv,
+    Barycentrics,
+    Flat,
+    Wireframe,
+}
+
+impl FromStr for Integrator {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "path" => Ok(Integrator::Path),
+            "bdpt" => Ok(Integrator::Bidirectional),
+            "sppm" => Ok(Integrator::PhotonMapping),
+            "mlt" => Ok(Integrator::Metropolis),
+            "ao" => Ok(Integrator::AmbientOcclusion),
+            "direct" => Ok(Integrator::DirectLighting),
+            "normals" => Ok(Integrator::Normals),
+            "heatmap" => Ok(Integrator::TraversalCost),
+            "uv" => Ok(Integrator::Uv),
+            "barycentrics" => Ok(Integrator::Barycentrics),
+            "flat" => Ok(Integrator::Flat),
+            "wireframe" => Ok(Integrator::Wireframe),
+            _ => Err(Error::InvalidSettings(format!(
+                "integrator {} must be path, bdpt, sppm, mlt, ao, direct, normals, heatmap, uv, \
+                 barycentrics, flat or wireframe",
+                value
+            ))),
+        }
+    }
+}
+
+// Bounces of every kind a path may take, and the bounce from which Russian roulette may end it
+// early.
+#[derive(Copy, Clone, Debug)]
+pub struct PathDepths {
+    pub diffuse: u32,
+    pub specular: u32,
+    pub transmission: u32,
+    pub roulette: u32,
+}
+
+impl Default for PathDepths {
+    fn default() -> Self {
+        PathDepths {
+            diffuse: 50,
+            specular: 50,
+            transmission: 50,
+            roulette: 3,
+        }
+    }
+}
+
+impl PathDepths {
+    // Bidirectional paths don't tell the kinds of their bounces apart.
+    pub fn max(&self) -> u32 {
+        self.diffuse.max(self.specular).max(self.transmission)
+    }
+}
+
+impl RenderSettings {
+    pub fn aspect_ratio(&self) -> Float {
+        self.width as Float / self.height as Float
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.width == 0 || self.height == 0 {
+            return Err(Error::InvalidSettings(format!(
+                "image size {}x{} must not be empty",
+                self.width, self.height
+            )));
+        }
+        if self.samples_per_pixel == 0 {
+            return Err(Error::InvalidSettings(String::from(
+                "samples per pixel must be positive",
+            )));
+        }
+        if let Some(exposure) = &self.exposure {
+            exposure.validate()?;
+        }
+        if self.occlusion_radius.is_nan() || self.occlusion_radius <= 0.0 {
+            return Err(Error::InvalidSettings(format!(
+                "occlusion radius {} must be positive",
+                self.occlusion_radius
+            )));
+        }
+        if self.spectral && self.integrator != Integrator::Path {
+            return Err(Error::InvalidSettings(String::from(
+                "spectral rendering needs the path integrator",
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Copy, Clone)]
+pub struct Progress {
+    pub lines_done: usize,
+    pub lines_total: usize,
+}
+
+// Shared flag that lets another thread stop a render between two lines.
+#[derive(Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+pub struct Renderer {
+    pub settings: RenderSettings,
+}
+
+impl Renderer {
+    pub fn new(settings: RenderSettings) -> Renderer {
+        Renderer { settings }
+    }
+
+    pub fn render(&self, scene: &Scene) -> Result<Framebuffer, Error> {
+        self.render_with_progress(scene, &mut |_| {}, &CancellationToken::new())
+    }
+
+    // Fails with `Error::Cancelled` if the render was cancelled through the token.
+    pub fn render_with_progress(
+        &self,
+        scene: &Scene,
+        progress: &mut dyn FnMut(Progress),
+        cancellation: &CancellationToken,
+    ) -> Result<Framebuffer, Error> {
+        self.settings.validate()?;
+        // Camera paths never hit punctual lights, only integrators sampling lights see them.
+        if !scene.lights.is_empty()
+            && matches!(
+                self.settings.integrator,
+                Integrator::Path | Integrator::Metropolis
+            )
+        {
+            return Err(Error::InvalidSettings(String::from(
+                "point, spot and directional lights need the bdpt, sppm or direct integrator",
+            )));
+        }
+
+        let shutter_time = self
+            .settings
+            .exposure
+            .as_ref()
+            .map_or(0.0, |exposure| exposure.shutter_time as Float);
+
+        let world = build_bvh(
+            &scene.world.hittables,
+            self.settings.packet_width,
+            0.0,
+            shutter_time,
+        );
+
+        let mut framebuffer = Framebuffer::new(self.settings.width, self.settings.height);
+        match self.settings.integrator {
+            Integrator::PhotonMapping => self.render_photon_mapping(
+                scene,
+                world.as_ref(),
+                shutter_time,
+                &mut framebuffer,
+                progress,
+                cancellation,
+            )?,
+            Integrator::Metropolis => self.render_metropolis(
+                scene,
+                world.as_ref(),
+                shutter_time,
+                &mut framebuffer,
+                progress,
+                cancellation,
+            )?,
+            _ => self.render_camera_samples(
+                scene,
+                world.as_ref(),
+                shutter_time,
+                &mut framebuffer,
+                progress,
+                cancellation,
+            )?,
+        }
+
+        if let Some(exposure) = &self.settings.exposure {
+            exposure.apply(&mut framebuffer.pixels);
+        }
+
+        Ok(framebuffer)
+    }
+
+    // Traces the samples of every pixel one after another, with the integrators that estimate
+    // the color along a camera ray.
+    fn render_camera_samples(
+        &self,
+        scene: &Scene,
+        world: &dyn Hittable,
+        shutter_time: Float,
+        framebuffer: &mut Framebuffer,
+        progress: &mut dyn FnMut(Progress),
+        cancellation: &CancellationToken,
+    ) -> Result<(), Error> {
+        let width = self.settings.width;
+        let height = self.settings.height;
+        let samples_per_pixel = self.settings.samples_per_pixel;
+        let gather_features = self.settings.feature_buffers;
+
+        let bidirectional = match self.settings.integrator {
+            Integrator::Bidirectional => Some(BidirectionalPathTracer::new(
+                world,
+                scene,
+                self.settings.depths.max(),
+                self.settings.light_sampling,
+                0.0,
+                shutter_time,
+            )?),
+            _ => None,
+        };
+        let direct = match self.settings.integrator {
+            Integrator::DirectLighting => Some(DirectLighting::new(
+                world,
+                scene,
+                self.settings.depths.max(),
+                self.settings.light_sampling,
+                0.0,
+                shutter_time,
+            )?),
+            _ => None,
+        };
+        // Light paths reaching the camera, they land anywhere on the image.
+        let mut splats = Vec::new();
+        let mut splat_pixels = vec![Color::BLACK; width * height];
+
+        let mut features = FeatureBuffers::new(width * height);
+
+        let mut random = sampler::rng();
+
+        for y in 0..height {
+            if cancellation.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+
+            for x in 0..width {
+                let u: Float = x as Float / width as Float;
+                let v: Float = (height - 1 - y) as Float / height as Float;
+
+                let mut result_color = Color::BLACK;
+                let mut albedo = Color::BLACK;
+                let mut normal = Vec3::default();
+                let mut depth: f32 = 0.0;
+
+                for _i in 0..samples_per_pixel {
+                    let u_with_offset: Float = u + random.gen::<Float>() / (width as Float);
+                    let v_with_offset: Float = v + random.gen::<Float>() / (height as Float);
+
+                    let mut ray = match scene.camera.get_ray(u_with_offset, v_with_offset) {
+                        Some(ray) => ray,
+                        // Outside of the projection, stays black.
+                        None => continue,
+                    };
+                    ray.time = random.gen::<Float>() * shutter_time;
+                    result_color += if let Some(bidirectional) = &bidirectional {
+                        let radiance = bidirectional.radiance(&ray, &mut splats);
+                        for splat in splats.drain(..) {
+                            let splat_x = ((splat.u * width as Float) as usize).min(width - 1);
+                            let splat_y = ((splat.v * height as Float) as usize).min(height - 1);
+                            splat_pixels[(height - 1 - splat_y) * width + splat_x] += splat.color;
+                        }
+                        radiance
+                    } else if let Some(direct) = &direct {
+                        direct.radiance(&ray)
+                    } else if self.settings.spectral {
+                        let mut wavelengths = SampledWavelengths::sample_uniform();
+                        let radiance = calculate_color_spectral(
+                            &ray,
+                            world,
+                            &scene.sky,
+                            &self.settings.depths,
+                            &mut wavelengths,
+                        );
+                        spectrum::to_rgb(&radiance, &wavelengths)
+                    } else {
+                        match self.settings.integrator {
+                            Integrator::AmbientOcclusion => debug::ambient_occlusion(
+                                &ray,
+                                world,
+                                self.settings.occlusion_radius,
+                            ),
+                            Integrator::Normals => debug::normals(&ray, world),
+                            Integrator::TraversalCost => debug::traversal_cost(&ray, world),
+                            Integrator::Uv => debug::uv(&ray, world),
+                            Integrator::Barycentrics => debug::barycentrics(&ray, world),
+                            Integrator::Flat => debug::flat(&ray, world, &scene.sky),
+                            Integrator::Wireframe => debug::wireframe(&ray, world, &scene.sky),
+                            _ => calculate_color(&ray, world, &scene.sky, &self.settings.depths),
+                        }
+                    };
+
+                    if gather_features {
+                        let (sample_albedo, sample_normal, sample_depth) =
+                            calculate_features(&ray, world, &scene.sky);
+                        albedo += sample_albedo;
+                        normal += Vec3::from(sample_normal);
+                        depth += sample_depth;
+                    }
+                }
+
+                let index = y * width + x;
+                framebuffer.pixels[index] = result_color / (samples_per_pixel as f32);
+                if gather_features {
+                    features.albedo[index] = albedo / (samples_per_pixel as f32);
+                    features.normal[index] = Normal3::from(if normal.is_near_zero() {
+                        normal
+                    } else {
+                        normal.normalize()
+                    });
+                    features.depth[index] = depth / (samples_per_pixel as f32);
+                }
+            }
+
+            progress(Progress {
+                lines_done: y + 1,
+                lines_total: height,
+            });
+        }
+
+        // Every camera sample traced one light path, so the splats average over as many.
+        for (pixel, splat) in framebuffer.pixels.iter_mut().zip(splat_pixels.iter()) {
+            *pixel += *splat / (samples_per_pixel as f32);
+        }
+
+        if gather_features {
+            framebuffer.features = Some(features);
+        }
+
+        Ok(())
+    }
+
+    // The chains run one after another, each splatting its share of the mutations, samples
+    // per pixel times the number of pixels in total.
+    fn render_metropolis(
+        &self,
+        scene: &Scene,
+        world: &dyn Hittable,
+        shutter_time: Float,
+        framebuffer: &mut Framebuffer,
+        progress: &mut dyn FnMut(Progress),
+        cancellation: &CancellationToken,
+    ) -> Result<(), Error> {
+        let width = self.settings.width;
+        let height = self.settings.height;
+        let samples_per_pixel = self.settings.samples_per_pixel;
+
+        let metropolis = MetropolisLightTransport::new(
+            world,
+            scene.camera.as_ref(),
+            &scene.sky,
+            self.settings.depths,
+            shutter_time,
+            self.settings.metropolis,
+        )?;
+        let (brightness, cumulative) = metropolis.bootstrap();
+
+        let total_mutations = samples_per_pixel as usize * width * height;
+        let chains = metropolis.settings().chains.min(total_mutations);
+        let mut splat = |u: Float, v: Float, color: Color| {
+            let x = ((u * width as Float) as usize).min(width - 1);
+            let y = ((v * height as Float) as usize).min(height - 1);
+            framebuffer.pixels[(height - 1 - y) * width + x] += color;
+        };
+        for chain in 0..chains {
+            if cancellation.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+
+            let mutations =
+                total_mutations / chains + usize::from(chain < total_mutations % chains);
+            metropolis.run_chain(&cumulative, mutations, &mut splat);
+
+            progress(Progress {
+                lines_done: (chain + 1) * height / chains,
+                lines_total: height,
+            });
+        }
+
+        // Splats count visits, the mean brightness turns them back into radiance.
+        let scale = to_f32(brightness) / samples_per_pixel as f32;
+        for pixel in framebuffer.pixels.iter_mut() {
+            *pixel = scale * *pixel;
+        }
+
+        if self.settings.feature_buffers {
+            framebuffer.features = Some(self.render_features(scene, world, shutter_time));
+        }
+
+        Ok(())
+    }
+
+    // Feature buffers from camera rays of their own, for integrators that don't trace per pixel.
+    fn render_features(
+        &self,
+        scene: &Scene,
+        world: &dyn Hittable,
+        shutter_time: Float,
+    ) -> FeatureBuffers {
+        let width = self.settings.width;
+        let height = self.settings.height;
+        let samples_per_pixel = self.settings.samples_per_pixel;
+        let mut features = FeatureBuffers::new(width * height);
+        let mut random = sampler::rng();
+
+        for y in 0..height {
+            for x in 0..width {
+                let index = y * width + x;
+                let mut normal = Vec3::default();
+                for _ in 0..samples_per_pixel {
+                    let u = (x as Float + random.gen::<Float>()) / width as Float;
+                    let v = ((height - 1 - y) as Float + random.gen::<Float>()) / height as Float;
+                    let mut ray = match scene.camera.get_ray(u, v) {
+                        Some(ray) => ray,
+                        None => continue,
+                    };
+                    ray.time = random.gen::<Float>() * shutter_time;
+
+                    let (albedo, sample_normal, depth) =
+                        calculate_features(&ray, world, &scene.sky);
+                    features.albedo[index] += albedo;
+                    normal += Vec3::from(sample_normal);
+                    features.depth[index] += depth;
+                }
+
+                features.albedo[index] = features.albedo[index] / (samples_per_pixel as f32);
+                features.normal[index] = Normal3::from(if normal.is_near_zero() {
+                    normal
+                } else {
+                    normal.normalize()
+                });
+                features.depth[index] /= samples_per_pixel as f32;
+            }
+        }
+
+        features
+    }
+
+    // Every sample per pixel is an iteration of progressive photon mapping: one camera ray per
+    // pixel, then a batch of photons for all of them.
+    fn render_photon_mapping(
+        &self,
+        scene: &Scene,
+        world: &dyn Hittable,
+        shutter_time: Float,
+        framebuffer: &mut Framebuffer,
+        progress: &mut dyn FnMut(Progress),
+        cancellation: &CancellationToken,
+    ) -> Result<(), Error> {
+        let width = self.settings.width;
+        let height = self.settings.height;
+        let iterations = self.settings.samples_per_pixel;
+        let gather_features = self.settings.feature_buffers;
+
+        let photon_mapper = PhotonMapper::new(
+            world,
+            scene,
+            self.settings.depths.max(),
+            self.settings.photons,
+            self.settings.light_sampling,
+            0.0,
+            shutter_time,
+        )?;
+        let mut estimates = photon_mapper.pixel_estimates(width * height);
+        let mut features = FeatureBuffers::new(width * height);
+
+        let mut random = sampler::rng();
+
+        for iteration in 0..iterations {
+            if cancellation.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+
+            let mut visible_points = Vec::with_capacity(width * height);
+            for y in 0..height {
+                for x in 0..width {
+                    let u = (x as Float + random.gen::<Float>()) / width as Float;
+                    let v = ((height - 1 - y) as Float + random.gen::<Float>()) / height as Float;
+                    let index = y * width + x;
+
+                    let mut ray = match scene.camera.get_ray(u, v) {
+                        Some(ray) => ray,
+                        None => {
+                            visible_points.push(None);
+                            continue;
+                        }
+                    };
+                    ray.time = random.gen::<Float>() * shutter_time;
+                    visible_points.push(photon_mapper.trace_camera(&ray, &mut estimates[index]));
+
+                    if gather_features {
+                        let (albedo, normal, depth) = calculate_features(&ray, world, &scene.sky);
+                        features.albedo[index] += albedo;
+                        features.normal[index] += normal;
+                        features.depth[index] += depth;
+                    }
+                }
+            }
+
+            let photon_map = photon_mapper.trace_photons(shutter_time);
+            for (visible_point, estimate) in visible_points.iter().zip(estimates.iter_mut()) {
+                if let Some(visible_point) = visible_point {
+                    photon_mapper.gather(&photon_map, visible_point, estimate);
+                }
+            }
+
+            progress(Progress {
+                lines_done: (iteration as usize + 1) * height / iterations as usize,
+                lines_total: height,
+            });
+        }
+
+        for (pixel, estimate) in framebuffer.pixels.iter_mut().zip(estimates.iter()) {
+            *pixel = photon_mapper.radiance(estimate, iterations);
+        }
+        if gather_features {
+            for index in 0..width * height {
+                features.albedo[index] = features.albedo[index] / (iterations as f32);
+                let normal = Vec3::from(features.normal[index]);
+                if !normal.is_near_zero() {
+                    features.normal[index] = Normal3::from(normal.normalize());
+                }
+                features.depth[index] /= iterations as f32;
+            }
+            framebuffer.features = Some(features);
+        }
+
+        Ok(())
+    }
+}
+
+// Bounces a path took so far, by kind.
+#[derive(Default)]
+struct Bounces {
+    diffuse: u32,
+    specular: u32,
+    transmission: u32,
+}
+
+impl Bounces {
+    // Counts one more bounce, false when it's past the limit of its kind.
+    fn add(&mut self, kind: ScatterKind, depths: &PathDepths) -> bool {
+        let (count, limit) = match kind {
+            ScatterKind::Diffuse => (&mut self.diffuse, depths.diffuse),
+            ScatterKind::Specular => (&mut self.specular, depths.specular),
+            ScatterKind::Transmission => (&mut self.transmission, depths.transmission),
+        };
+        *count += 1;
+        *count <= limit
+    }
+
+    fn total(&self) -> u32 {
+        self.diffuse + self.specular + self.transmission
+    }
+}
+
+// Chance a path with the given throughput continues. Russian roulette ends dim paths once
+// they are deep enough, survivors divide their throughput by it, which keeps the estimate
+// unbiased.
+fn survival_probability(max_throughput: f32, bounces: &Bounces, depths: &PathDepths) -> f32 {
+    if bounces.total() < depths.roulette {
+        1.0
+    } else {
+        max_throughput.min(1.0)
+    }
+}
+
+// Follows a path from the camera, carrying the product of the attenuations along it, until it
+// escapes to the sky or ends. Lights it hits on the way add their emission.
+pub fn calculate_color(ray: &Ray, world: &dyn Hittable, sky: &Sky, depths: &PathDepths) -> Color {
+    let mut random = sampler::rng();
+    let mut ray = *ray;
+    let mut radiance = Color::BLACK;
+    let mut throughput = Color::WHITE;
+    let mut bounces = Bounces::default();
+
+    loop {
+        let hit_result = match world.hit(&ray, 0.0, Float::MAX) {
+            Some(hit_result) => hit_result,
+            None => return radiance + throughput * sky.radiance(&ray.direction),
+        };
+        if hit_result.is_front_face {
+            let outgoing = -ray.direction.normalize();
+            radiance += throughput
+                * hit_result
+                    .material
+                    .emission_towards(&hit_result.normal, &outgoing);
+        }
+        let (attenuation, scattered_ray) = match hit_result.material.scatter(&ray, &hit_result) {
+            Some(scattered) => scattered,
+            None => return radiance,
+        };
+        let kind = hit_result
+            .material
+            .scatter_kind(&hit_result, &scattered_ray);
+        if !bounces.add(kind, depths) {
+            return radiance;
+        }
+
+        throughput *= attenuation;
+        // Nothing it meets from here on can add to the color.
+        if throughput.is_black() {
+            return radiance;
+        }
+        let survival = survival_probability(throughput.max_component(), &bounces, depths);
+        if survival < 1.0 {
+            if random.gen::<f32>() >= survival {
+                return radiance;
+            }
+            throughput = throughput / survival;
+        }
+        ray = scattered_ray;
+    }
+}
+
+// Same as `calculate_color`, but the path carries radiance for a few sampled wavelengths.
+pub fn calculate_color_spectral(
+    ray: &Ray,
+    world: &dyn Hittable,
+    sky: &Sky,
+    depths: &PathDepths,
+    wavelengths: &mut SampledWavelengths,
+) -> SampledSpectrum {
+    let mut random = sampler::rng();
+    let mut ray = *ray;
+    let mut radiance = SampledSpectrum::default();
+    let mut throughput = SampledSpectrum::constant(1.0);
+    let mut bounces = Bounces::default();
+
+    loop {
+        let hit_result = match world.hit(&ray, 0.0, Float::MAX) {
+            Some(hit_result) => hit_result,
+            None => return radiance + throughput * sky.spectrum(&ray.direction, wavelengths),
+        };
+        if hit_result.is_front_face {
+            let outgoing = -ray.direction.normalize();
+            let emission = hit_result
+                .material
+                .emission_towards(&hit_result.normal, &outgoing);
+            if !emission.is_black() {
+                radiance =
+                    radiance + throughput * RgbSpectrum::unbounded(&emission).sample(wavelengths);
+            }
+        }
+        let (attenuation, scattered_ray) =
+            match hit_result
+                .material
+                .scatter_spectral(&ray, &hit_result, wavelengths)
+            {
+                Some(scattered) => scattered,
+                None => return radiance,
+            };
+        let kind = hit_result
+            .material
+            .scatter_kind(&hit_result, &scattered_ray);
+        if !bounces.add(kind, depths) {
+            return radiance;
+        }
+
+        throughput = throughput * attenuation;
+        if throughput.is_black() {
+            return radiance;
+        }
+        let survival = survival_probability(throughput.max_component(), &bounces, depths);
+        if survival < 1.0 {
+            if random.gen::<f32>() >= survival {
+                return radiance;
+            }
+            throughput = (1.0 / survival) * throughput;
+        }
+        ray = scattered_ray;
+    }
+}
+
+// Albedo, shading normal and distance of the first hit, used to guide the denoiser.
+fn calculate_features(ray: &Ray, world: &dyn Hittable, sky: &Sky) -> (Color, Normal3, f32) {
+    match world.hit(ray, 0.0, Float::MAX) {
+        Some(hit_result) => (
+            hit_result.material.albedo(),
+            hit_result.normal,
+            to_f32(hit_result.t * ray.direction.length()),
+        ),
+        None => (
+            sky.radiance(&ray.direction),
+            Normal3::from(-ray.direction.normalize()),
+            0.0,
+        ),
+    }
+}
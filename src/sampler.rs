@@ -0,0 +1,74 @@
+use std::{cell::RefCell, rc::Rc};
+
+use rand::{rngs::ThreadRng, RngCore};
+
+use crate::float::Float;
+
+// Source of the uniform numbers a sample is built from: the film position, the time, every
+// scattering decision and light sample along its path. Rendering code draws them through
+// `rng()`, in the order it needs them, so a sampler that hands out chosen numbers controls the
+// whole path.
+pub trait Sampler {
+    // Next number in [0, 1) of the current sample.
+    fn next_sample(&mut self) -> Float;
+}
+
+thread_local! {
+    static ACTIVE: RefCell<Option<Rc<RefCell<dyn Sampler>>>> = const { RefCell::new(None) };
+}
+
+// Runs `draw` with the generators `rng()` creates on this thread drawing from `sampler`.
+pub fn with_sampler<R>(sampler: Rc<RefCell<dyn Sampler>>, draw: impl FnOnce() -> R) -> R {
+    let previous = ACTIVE.with(|active| active.replace(Some(sampler)));
+    let result = draw();
+    ACTIVE.with(|active| active.replace(previous));
+    result
+}
+
+// Random number generator of rendering code, independent uniform numbers unless a sampler was
+// installed with `with_sampler` when it was created.
+pub fn rng() -> SampleRng {
+    match ACTIVE.with(|active| active.borrow().clone()) {
+        Some(sampler) => SampleRng::Sampler(sampler),
+        None => SampleRng::Independent(rand::thread_rng()),
+    }
+}
+
+pub enum SampleRng {
+    Independent(ThreadRng),
+    Sampler(Rc<RefCell<dyn Sampler>>),
+}
+
+// Samples are scaled to the full range of the integers, so `gen::<Float>()` gives them back.
+#[allow(clippy::unnecessary_cast)]
+impl RngCore for SampleRng {
+    fn next_u32(&mut self) -> u32 {
+        match self {
+            SampleRng::Independent(random) => random.next_u32(),
+            SampleRng::Sampler(sampler) => {
+                (sampler.borrow_mut().next_sample() as f64 * 4_294_967_296.0) as u32
+            }
+        }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        match self {
+            SampleRng::Independent(random) => random.next_u64(),
+            SampleRng::Sampler(sampler) => {
+                (sampler.borrow_mut().next_sample() as f64 * 18_446_744_073_709_551_616.0) as u64
+            }
+        }
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
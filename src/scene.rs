@@ -0,0 +1,771 @@
+use rand::Rng;
+
+use crate::{
+    cameras::camera::Camera,
+    color::Color,
+    error::Error,
+    float::{consts::PI, to_f32, Float},
+    hittables::{
+        aabb::Aabb,
+        csg::Csg,
+        cuboid::Cuboid,
+        curve::{CurveShape, Curves, Strand},
+        cylinder::Cylinder,
+        heightfield::Heightfield,
+        hittable_list::HittableList,
+        sdf::{Sdf, SdfShape},
+        sphere::Sphere,
+        subdivision::{Displacement, SubdivisionSurface},
+        triangle::Triangle,
+    },
+    image::Image,
+    lights::{ies::LightProfile, punctual_light::PunctualLight, sky_light::Sky},
+    materials::{
+        dielectric_material::{DielectricMaterial, Dispersion},
+        diffuse_material::DiffuseMaterial,
+        emissive_material::EmissiveMaterial,
+        hair_material::HairMaterial,
+        material::Material,
+        metal_material::MetalMaterial,
+    },
+    vector::{random_on_unit_sphere, Normal3, Point3, Vec3},
+};
+
+// Everything needed to render an image: the geometry, the point of view, the lights that aren't
+// geometry and the sky around them.
+pub struct Scene {
+    pub world: HittableList,
+    pub camera: Box<dyn Camera>,
+    pub sky: Sky,
+    pub lights: Vec<PunctualLight>,
+}
+
+impl Scene {
+    pub fn new(world: HittableList, camera: Box<dyn Camera>) -> Scene {
+        Scene {
+            world,
+            camera,
+            sky: Sky::default(),
+            lights: Vec::new(),
+        }
+    }
+
+    pub fn with_sky(mut self, sky: Sky) -> Scene {
+        self.sky = sky;
+        self
+    }
+
+    pub fn with_light(mut self, light: PunctualLight) -> Scene {
+        self.lights.push(light);
+        self
+    }
+}
+
+pub fn generate_random_scene() -> Result<HittableList, Error> {
+    let mut world: HittableList = HittableList {
+        hittables: Vec::new(),
+    };
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5))?),
+    )?));
+
+    let mut random = rand::thread_rng();
+    for x in -11..11 {
+        for z in -11..11 {
+            let sphere_origin = Point3::new(
+                x as Float + 0.9 * random.gen::<Float>(),
+                0.2,
+                z as Float + 0.9 * random.gen::<Float>(),
+            );
+
+            if sphere_origin.distance(&Point3::new(4.0, 0.2, 0.0)) <= 0.9 {
+                continue;
+            }
+
+            let random_material = random.gen::<Float>();
+            let mut velocity = Vec3::default();
+
+            let material: Box<dyn Material> = if random_material < 0.8 {
+                // Diffuse.
+                let albedo = Color::new(random.gen(), random.gen(), random.gen());
+
+                // Diffuse spheres bounce up, visible as motion blur with a long shutter time.
+                velocity = Vec3::new(0.0, random.gen_range(0.0..=50.0), 0.0);
+
+                Box::new(DiffuseMaterial::new(albedo * albedo)?)
+            } else if random_material < 0.95 {
+                // Metal.
+                let albedo = Color::new(
+                    random.gen_range(0.5..=1.0),
+                    random.gen_range(0.5..=1.0),
+                    random.gen_range(0.5..=1.0),
+                );
+
+                let fuzziness = random.gen_range(0.0..=0.5);
+
+                Box::new(MetalMaterial::new(albedo, fuzziness)?)
+            } else {
+                // Glass.
+                Box::new(
+                    DielectricMaterial::new(1.5)?.with_dispersion(Dispersion::crown_glass(1.5)),
+                )
+            };
+
+            world.hittables.push(Box::new(
+                Sphere::new(sphere_origin, 0.2, material)?.with_velocity(velocity),
+            ));
+        }
+    }
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Box::new(DielectricMaterial::new(1.5)?.with_dispersion(Dispersion::crown_glass(1.5))),
+    )?));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.4, 0.2, 0.1))?),
+    )?));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        Box::new(MetalMaterial::new(Color::new(0.7, 0.6, 0.5), 0.0)?),
+    )?));
+
+    Ok(world)
+}
+
+// Glass ball lit by a small lamp above it and out of view, meant to be rendered without the sky.
+// The caustic it focuses onto the ground is light that camera paths hardly ever find. The lamp
+// emits along its normals as `profile` describes, if given.
+pub fn generate_caustics_scene(profile: Option<&LightProfile>) -> Result<HittableList, Error> {
+    let mut world: HittableList = HittableList {
+        hittables: Vec::new(),
+    };
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5))?),
+    )?));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Box::new(DielectricMaterial::new(1.5)?),
+    )?));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.4, 0.2, 0.1))?),
+    )?));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        Box::new(MetalMaterial::new(Color::new(0.7, 0.6, 0.5), 0.0)?),
+    )?));
+
+    let lamp = EmissiveMaterial::new(Color::new(100.0, 90.0, 80.0));
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(-3.0, 6.0, 1.5),
+        0.5,
+        Box::new(match profile {
+            Some(profile) => lamp.with_profile(profile.clone())?,
+            None => lamp,
+        }),
+    )?));
+
+    Ok(world)
+}
+
+// The balls of the other scenes lit only by punctual lights: a lamp above the glass ball, a
+// spot light on the brown one and a dim evening sun, meant to be rendered without the sky.
+// `profile` shapes the lamp and the spot light.
+pub fn generate_lamps_scene(
+    profile: Option<&LightProfile>,
+) -> Result<(HittableList, Vec<PunctualLight>), Error> {
+    let mut world: HittableList = HittableList {
+        hittables: Vec::new(),
+    };
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5))?),
+    )?));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Box::new(DielectricMaterial::new(1.5)?),
+    )?));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.4, 0.2, 0.1))?),
+    )?));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        Box::new(MetalMaterial::new(Color::new(0.7, 0.6, 0.5), 0.0)?),
+    )?));
+
+    let mut lights = vec![
+        PunctualLight::point(Point3::new(1.0, 4.0, 1.0), Color::new(20.0, 18.0, 15.0))?,
+        PunctualLight::spot(
+            Point3::new(-4.0, 5.0, 2.0),
+            Point3::new(-4.0, 0.0, 0.0),
+            Color::new(30.0, 35.0, 50.0),
+            25.0,
+            15.0,
+        )?,
+    ];
+    if let Some(profile) = profile {
+        lights = lights
+            .into_iter()
+            .map(|lamp| lamp.with_profile(profile.clone()))
+            .collect::<Result<_, _>>()?;
+    }
+    lights.push(PunctualLight::directional(
+        Vec3::new(-1.0, -1.0, -2.0),
+        Color::new(0.4, 0.3, 0.2),
+    )?);
+
+    Ok((world, lights))
+}
+
+// The balls of the other scenes among hundreds of small glowing beads a hundred times apart in
+// brightness, under a light panel made of triangles, meant to be rendered without the sky.
+// Shows how the light sampling strategies cope with many lights.
+pub fn generate_many_lights_scene() -> Result<HittableList, Error> {
+    let mut world: HittableList = HittableList {
+        hittables: Vec::new(),
+    };
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5))?),
+    )?));
+
+    let mut random = rand::thread_rng();
+    for x in -11..11 {
+        for z in -11..11 {
+            let bead_origin = Point3::new(
+                x as Float + 0.9 * random.gen::<Float>(),
+                0.1,
+                z as Float + 0.9 * random.gen::<Float>(),
+            );
+            let hue = Color::new(random.gen(), random.gen(), random.gen());
+            let brightness = 10.0_f32.powf(random.gen_range(0.0..=2.0));
+            world.hittables.push(Box::new(Sphere::new(
+                bead_origin,
+                0.1,
+                Box::new(EmissiveMaterial::new(brightness * (hue * hue))),
+            )?));
+        }
+    }
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, 1.0, 0.0),
+        1.0,
+        Box::new(DielectricMaterial::new(1.5)?),
+    )?));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(-4.0, 1.0, 0.0),
+        1.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.4, 0.2, 0.1))?),
+    )?));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(4.0, 1.0, 0.0),
+        1.0,
+        Box::new(MetalMaterial::new(Color::new(0.7, 0.6, 0.5), 0.0)?),
+    )?));
+
+    // Facing down, two triangles for each of its cells.
+    let mut panel = HittableList {
+        hittables: Vec::new(),
+    };
+    let height = 4.0;
+    let radiance = Color::new(4.0, 4.0, 3.5);
+    for cell in 0..4 {
+        let x0 = -6.0 + cell as Float;
+        let x1 = x0 + 1.0;
+        let (z0, z1) = (-1.0, 1.0);
+        panel.hittables.push(Box::new(Triangle::new(
+            [
+                Point3::new(x0, height, z0),
+                Point3::new(x1, height, z0),
+                Point3::new(x0, height, z1),
+            ],
+            Box::new(EmissiveMaterial::new(radiance)),
+        )?));
+        panel.hittables.push(Box::new(Triangle::new(
+            [
+                Point3::new(x1, height, z0),
+                Point3::new(x1, height, z1),
+                Point3::new(x0, height, z1),
+            ],
+            Box::new(EmissiveMaterial::new(radiance)),
+        )?));
+    }
+    world.hittables.push(Box::new(panel));
+
+    Ok(world)
+}
+
+// Solids modelled with constructive solid geometry where the big balls usually are: a part
+// machined from the overlap of a box and a ball with three bores through it, a glass lens
+// from the overlap of two balls, and a metal bowl from a hollowed ball with its top cut off.
+// The bores show the material of the cylinders that carved them.
+pub fn generate_csg_scene() -> Result<HittableList, Error> {
+    let mut world: HittableList = HittableList {
+        hittables: Vec::new(),
+    };
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5))?),
+    )?));
+
+    let centre = Point3::new(0.0, 1.0, 0.0);
+    let red = DiffuseMaterial::new(Color::new(0.7, 0.15, 0.1))?;
+    let bore = |axis: Vec3| -> Result<Box<Cylinder>, Error> {
+        Ok(Box::new(Cylinder::new(
+            centre - 1.2 * axis,
+            centre + 1.2 * axis,
+            0.45,
+            Box::new(DiffuseMaterial::new(Color::new(0.8, 0.8, 0.8))?),
+        )?))
+    };
+    let half = Vec3::new(0.8, 0.8, 0.8);
+    let rounded_box = Csg::intersection(
+        Box::new(Cuboid::new(
+            centre - half,
+            centre + half,
+            Box::new(red.clone()),
+        )?),
+        Box::new(Sphere::new(centre, 1.05, Box::new(red))?),
+    )?;
+    let bores = Csg::union(
+        Box::new(Csg::union(
+            bore(Vec3::new(1.0, 0.0, 0.0))?,
+            bore(Vec3::new(0.0, 1.0, 0.0))?,
+        )?),
+        bore(Vec3::new(0.0, 0.0, 1.0))?,
+    )?;
+    world.hittables.push(Box::new(Csg::difference(
+        Box::new(rounded_box),
+        Box::new(bores),
+    )?));
+
+    let lens_centre = Point3::new(-4.0, 1.1, 0.0);
+    let lens_offset = Vec3::new(1.7, 0.0, 0.0);
+    world.hittables.push(Box::new(Csg::intersection(
+        Box::new(Sphere::new(
+            lens_centre - lens_offset,
+            2.0,
+            Box::new(DielectricMaterial::new(1.5)?),
+        )?),
+        Box::new(Sphere::new(
+            lens_centre + lens_offset,
+            2.0,
+            Box::new(DielectricMaterial::new(1.5)?),
+        )?),
+    )?));
+
+    let bowl_centre = Point3::new(4.0, 1.0, 0.0);
+    let metal = MetalMaterial::new(Color::new(0.7, 0.6, 0.5), 0.1)?;
+    let shell = Csg::difference(
+        Box::new(Sphere::new(bowl_centre, 1.0, Box::new(metal.clone()))?),
+        Box::new(Sphere::new(bowl_centre, 0.9, Box::new(metal.clone()))?),
+    )?;
+    let lid = Cuboid::new(
+        bowl_centre + Vec3::new(-1.1, 0.0, -1.1),
+        bowl_centre + Vec3::new(1.1, 1.1, 1.1),
+        Box::new(metal),
+    )?;
+    world
+        .hittables
+        .push(Box::new(Csg::difference(Box::new(shell), Box::new(lid))?));
+
+    Ok(world)
+}
+
+pub fn generate_sdf_scene() -> Result<HittableList, Error> {
+    let mut world: HittableList = HittableList {
+        hittables: Vec::new(),
+    };
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5))?),
+    )?));
+
+    // Rounded box turned through half a turn from bottom to top.
+    let column = SdfShape::cuboid(Vec3::new(0.5, 1.0, 0.5), 0.1)
+        .twist(0.8)
+        .translate(Vec3::new(0.0, 1.0, 0.0));
+    world.hittables.push(Box::new(Sdf::new(
+        column,
+        Box::new(DiffuseMaterial::new(Color::new(0.7, 0.15, 0.1))?),
+    )?));
+
+    // Ball and ring melted together, with a capsule groove carved across the top.
+    let blob = SdfShape::sphere(0.8)
+        .smooth_union(
+            SdfShape::torus(0.9, 0.2).translate(Vec3::new(0.0, -0.3, 0.0)),
+            0.3,
+        )
+        .smooth_subtraction(
+            SdfShape::capsule(
+                Point3::new(-1.2, 0.8, 0.0),
+                Point3::new(1.2, 0.8, 0.0),
+                0.25,
+            ),
+            0.1,
+        )
+        .translate(Vec3::new(-4.0, 1.0, 0.0));
+    world.hittables.push(Box::new(Sdf::new(
+        blob,
+        Box::new(MetalMaterial::new(Color::new(0.7, 0.6, 0.5), 0.05)?),
+    )?));
+
+    // Grid of 5 x 3 x 5 beads from one sphere.
+    let beads = SdfShape::sphere(0.1)
+        .repeat(Vec3::new(0.3, 0.3, 0.3), [2, 1, 2])
+        .translate(Vec3::new(4.0, 0.4, 0.0));
+    world.hittables.push(Box::new(Sdf::new(
+        beads,
+        Box::new(DiffuseMaterial::new(Color::new(0.1, 0.3, 0.7))?),
+    )?));
+
+    // A distance function of our own: a ball with ripples, which steepen the field by up to
+    // the ripple height times its frequency.
+    let (height, frequency) = (0.04, 12.0);
+    let centre = Point3::new(0.0, 0.8, 2.5);
+    let rippled = SdfShape::custom(
+        move |point: &Point3| {
+            let offset = *point - centre;
+            offset.length()
+                - 0.7
+                - height
+                    * (frequency * offset.x()).sin()
+                    * (frequency * offset.y()).sin()
+                    * (frequency * offset.z()).sin()
+        },
+        Aabb::new(
+            centre - Vec3::new(0.8, 0.8, 0.8),
+            centre + Vec3::new(0.8, 0.8, 0.8),
+        ),
+        1.0 + height * frequency * (3.0 as Float).sqrt(),
+    );
+    world.hittables.push(Box::new(Sdf::new(
+        rippled,
+        Box::new(DielectricMaterial::new(1.5)?),
+    )?));
+
+    Ok(world)
+}
+
+// Terrain from a heightmap image, or rolling hills of fractal noise without one.
+pub fn generate_terrain_scene(heightmap: Option<&str>) -> Result<HittableList, Error> {
+    let mut world: HittableList = HittableList {
+        hittables: Vec::new(),
+    };
+
+    let corner = Point3::new(-12.0, -1.5, -12.0);
+    let extent = Vec3::new(24.0, 4.0, 24.0);
+    let ground = Box::new(DiffuseMaterial::new(Color::new(0.45, 0.4, 0.3))?);
+    let terrain = match heightmap {
+        Some(path) => Heightfield::load(path, corner, extent, ground)?,
+        None => {
+            let size = 257;
+            let samples = fractal_noise(size, 6);
+            Heightfield::new(size, size, &samples, corner, extent, ground)?
+        }
+    };
+    world.hittables.push(Box::new(terrain));
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, 2.2, 0.0),
+        0.6,
+        Box::new(MetalMaterial::new(Color::new(0.8, 0.8, 0.8), 0.0)?),
+    )?));
+
+    Ok(world)
+}
+
+// A head of brown hair between a lawn of grass blades and a few copper coils.
+pub fn generate_hair_scene() -> Result<HittableList, Error> {
+    let mut world: HittableList = HittableList {
+        hittables: Vec::new(),
+    };
+    let mut random = rand::thread_rng();
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5))?),
+    )?));
+
+    let centre = Point3::new(0.0, 0.8, 0.0);
+    let radius = 0.8;
+    world.hittables.push(Box::new(Sphere::new(
+        centre,
+        radius,
+        Box::new(DiffuseMaterial::new(Color::new(0.6, 0.45, 0.35))?),
+    )?));
+
+    // Hairs grow out of the upper half of the head and droop under their weight, along
+    // parabolas that cubic segments follow exactly.
+    let mut hairs = Vec::new();
+    while hairs.len() < 20000 {
+        let normal = random_on_unit_sphere();
+        if normal.y() < 0.1 {
+            continue;
+        }
+        let root = centre + radius * normal;
+        let direction = (normal + 0.3 * random_on_unit_sphere()).normalize();
+        let length = random.gen_range(0.5..0.8);
+        let droop = random.gen_range(0.4..0.7) * length;
+        let at = |s: Float| root + s * length * direction - Vec3::new(0.0, s * s * droop, 0.0);
+        let slope = |s: Float| length * direction - Vec3::new(0.0, 2.0 * s * droop, 0.0);
+        let mut points = vec![root];
+        for (start, end) in [(0.0, 0.5), (0.5, 1.0)] {
+            let third = (end - start) / 3.0;
+            points.push(at(start) + third * slope(start));
+            points.push(at(end) - third * slope(end));
+            points.push(at(end));
+        }
+        hairs.push(Strand::new(points, vec![0.012, 0.008, 0.002])?);
+    }
+    world.hittables.push(Box::new(Curves::new(
+        &hairs,
+        CurveShape::Flat,
+        Box::new(HairMaterial::from_melanin(1.3, 0.0)?),
+    )?));
+
+    // Blades of grass facing every which way, each bending towards its face.
+    let mut blades = Vec::new();
+    for _ in 0..3000 {
+        let root = Point3::new(
+            4.0 + random.gen_range(-0.8..0.8),
+            0.0,
+            random.gen_range(-0.8..0.8),
+        );
+        let angle = random.gen_range(0.0..2.0 * PI);
+        let facing = Vec3::new(angle.cos(), 0.0, angle.sin());
+        let height = random.gen_range(0.3..0.6);
+        let bend = random.gen_range(0.0..0.3) * height;
+        let points = vec![
+            root,
+            root + Vec3::new(0.0, height / 3.0, 0.0),
+            root + Vec3::new(0.0, 2.0 * height / 3.0, 0.0) + 0.5 * bend * facing,
+            root + Vec3::new(0.0, height, 0.0) + bend * facing,
+        ];
+        blades.push(
+            Strand::new(points, vec![0.04, 0.0])?
+                .with_normals(vec![Normal3::from(facing), Normal3::from(facing)])?,
+        );
+    }
+    world.hittables.push(Box::new(Curves::new(
+        &blades,
+        CurveShape::Ribbon,
+        Box::new(DiffuseMaterial::new(Color::new(0.2, 0.5, 0.1))?),
+    )?));
+
+    // Helix of quarter circle arcs, rising steadily.
+    let (coil_radius, rise) = (0.5, 0.1);
+    let handle = 0.5523 * coil_radius;
+    let around = |angle: Float, y: Float| {
+        Point3::new(
+            1.5 + coil_radius * angle.cos(),
+            y,
+            -2.5 + coil_radius * angle.sin(),
+        )
+    };
+    let tangent = |angle: Float| Vec3::new(-angle.sin(), 0.0, angle.cos());
+    let mut points = vec![around(0.0, 0.1)];
+    for quarter in 0..16 {
+        let (start, end) = (
+            quarter as Float * 0.5 * PI,
+            (quarter + 1) as Float * 0.5 * PI,
+        );
+        let (low, high) = (
+            0.1 + quarter as Float * rise,
+            0.1 + (quarter + 1) as Float * rise,
+        );
+        let lift = Vec3::new(0.0, rise / 3.0, 0.0);
+        points.push(around(start, low) + handle * tangent(start) + lift);
+        points.push(around(end, high) - handle * tangent(end) - lift);
+        points.push(around(end, high));
+    }
+    let coil = Strand::new(points, vec![0.1; 17])?;
+    world.hittables.push(Box::new(Curves::new(
+        &[coil],
+        CurveShape::Cylinder,
+        Box::new(MetalMaterial::new(Color::new(0.95, 0.64, 0.54), 0.2)?),
+    )?));
+
+    Ok(world)
+}
+
+// The same cube cage subdivided smooth, with creases, and displaced into a rock.
+pub fn generate_subdivision_scene(displacement: Option<&str>) -> Result<HittableList, Error> {
+    let mut world: HittableList = HittableList {
+        hittables: Vec::new(),
+    };
+
+    world.hittables.push(Box::new(Sphere::new(
+        Point3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5))?),
+    )?));
+
+    let mut add = |surface: SubdivisionSurface, material: &dyn Fn() -> Box<dyn Material>| {
+        for triangle in surface.triangles(material) {
+            world.hittables.push(Box::new(triangle));
+        }
+    };
+
+    // A cube without creases melts into a ball.
+    let red = DiffuseMaterial::new(Color::new(0.7, 0.15, 0.1))?;
+    add(
+        cube_cage(Point3::new(0.0, 0.75, -2.6), 1.0)?.refine(4),
+        &|| Box::new(red.clone()),
+    );
+
+    // Sharp edges around the top and ones softened after two levels elsewhere round it into
+    // a box.
+    let mut creased = cube_cage(Point3::new(0.0, 0.9, 0.0), 0.9)?;
+    for (start, end) in [
+        (0, 1),
+        (4, 5),
+        (0, 4),
+        (1, 5),
+        (0, 2),
+        (1, 3),
+        (4, 6),
+        (5, 7),
+    ] {
+        creased = creased.with_crease(start, end, 2.0)?;
+    }
+    for (start, end) in [(2, 6), (6, 7), (7, 3), (3, 2)] {
+        creased = creased.with_crease(start, end, Float::INFINITY)?;
+    }
+    let gold = MetalMaterial::new(Color::new(0.8, 0.7, 0.4), 0.1)?;
+    add(creased.refine(4), &|| Box::new(gold.clone()));
+
+    // Finely refined before the displacement roughens it.
+    let image = match displacement {
+        Some(path) => Image::load(path)?,
+        None => {
+            let size = 257;
+            Image {
+                width: size,
+                height: size,
+                pixels: fractal_noise(size, 7)
+                    .iter()
+                    .map(|height| {
+                        let height = to_f32(*height);
+                        Color::new(height, height, height)
+                    })
+                    .collect(),
+            }
+        }
+    };
+    let rock = cube_cage(Point3::new(0.0, 0.7, 2.6), 0.9)?
+        .refine(6)
+        .displace(&Displacement::Scalar { image, scale: 1.0 })?;
+    let stone = DiffuseMaterial::new(Color::new(0.45, 0.4, 0.35))?;
+    add(rock, &|| Box::new(stone.clone()));
+
+    Ok(world)
+}
+
+// Cube around `centre` with texture coordinates projected at a slant, so no face is stretched
+// along a single line.
+fn cube_cage(centre: Point3, half_size: Float) -> Result<SubdivisionSurface, Error> {
+    let corners: Vec<Vec3> = (0..8)
+        .map(|index| {
+            let side = |bit: usize| if index & bit == 0 { -1.0 } else { 1.0 };
+            Vec3::new(side(1), side(2), side(4))
+        })
+        .collect();
+    let uvs = corners
+        .iter()
+        .map(|corner| {
+            [
+                0.5 + 0.3 * (corner.x() + 0.5 * corner.y()),
+                0.5 + 0.3 * (corner.z() + 0.5 * corner.y()),
+            ]
+        })
+        .collect();
+    SubdivisionSurface::new(
+        corners
+            .iter()
+            .map(|corner| centre + half_size * *corner)
+            .collect(),
+        vec![
+            vec![0, 4, 6, 2],
+            vec![1, 3, 7, 5],
+            vec![0, 1, 5, 4],
+            vec![2, 6, 7, 3],
+            vec![0, 2, 3, 1],
+            vec![4, 5, 7, 6],
+        ],
+    )?
+    .with_uvs(uvs)
+}
+
+// `size` by `size` samples in [0, 1], octaves of random values on ever finer lattices blended
+// smoothly between their points, each half as strong as the one before.
+fn fractal_noise(size: usize, octaves: u32) -> Vec<Float> {
+    let mut random = rand::thread_rng();
+    let mut samples = vec![0.0; size * size];
+    let mut amplitude = 1.0;
+    let mut total = 0.0;
+    for octave in 0..octaves {
+        let cells = 2_usize << octave;
+        let lattice: Vec<Float> = (0..(cells + 1) * (cells + 1))
+            .map(|_| random.gen::<Float>())
+            .collect();
+        let at = |x: usize, z: usize| lattice[z * (cells + 1) + x];
+        for z in 0..size {
+            for x in 0..size {
+                let lattice_x = x as Float / (size - 1) as Float * cells as Float;
+                let lattice_z = z as Float / (size - 1) as Float * cells as Float;
+                let (x0, z0) = (
+                    (lattice_x as usize).min(cells - 1),
+                    (lattice_z as usize).min(cells - 1),
+                );
+                let smooth = |t: Float| t * t * (3.0 - 2.0 * t);
+                let (fx, fz) = (
+                    smooth(lattice_x - x0 as Float),
+                    smooth(lattice_z - z0 as Float),
+                );
+                let near = at(x0, z0) + (at(x0 + 1, z0) - at(x0, z0)) * fx;
+                let far = at(x0, z0 + 1) + (at(x0 + 1, z0 + 1) - at(x0, z0 + 1)) * fx;
+                samples[z * size + x] += amplitude * (near + (far - near) * fz);
+            }
+        }
+        total += amplitude;
+        amplitude *= 0.5;
+    }
+    samples.iter().map(|sample| sample / total).collect()
+}
@@ -0,0 +1,576 @@
+use std::ops;
+
+#[cfg(feature = "simd")]
+use wide::{CmpGe, CmpLe};
+
+use crate::float::Float;
+use crate::vector::{Point3, Vec3};
+
+// A fixed number of floats processed together. Comparisons return masks with all bits of a
+// lane set when it holds, to be combined with `&` and consumed by `select`.
+pub trait Lanes:
+    Copy
+    + ops::Add<Output = Self>
+    + ops::Sub<Output = Self>
+    + ops::Mul<Output = Self>
+    + ops::Div<Output = Self>
+    + ops::Neg<Output = Self>
+    + ops::BitAnd<Output = Self>
+    + ops::BitOr<Output = Self>
+{
+    const WIDTH: usize;
+
+    fn splat(value: Float) -> Self;
+    // Reads exactly `WIDTH` values.
+    fn load(values: &[Float]) -> Self;
+    // Writes exactly `WIDTH` values.
+    fn store(self, values: &mut [Float]);
+
+    fn sqrt(self) -> Self;
+    fn min(self, other: Self) -> Self;
+    fn max(self, other: Self) -> Self;
+    fn abs(self) -> Self;
+    fn ge(self, other: Self) -> Self;
+    fn le(self, other: Self) -> Self;
+    // `if_true` in the lanes where the mask is set, `if_false` elsewhere.
+    fn select(self, if_true: Self, if_false: Self) -> Self;
+    fn any(self) -> bool;
+}
+
+// Lanes backed by `wide` types where `$backed` holds, otherwise by arrays the compiler is
+// left to vectorize.
+macro_rules! lanes {
+    ($name:ident, $width:literal, $simd:ty, $backed:meta) => {
+        #[derive(Copy, Clone, Debug)]
+        pub struct $name {
+            #[cfg($backed)]
+            values: $simd,
+            #[cfg(not($backed))]
+            values: [Float; $width],
+        }
+
+        #[cfg($backed)]
+        impl $name {
+            fn from_array(values: [Float; $width]) -> Self {
+                $name {
+                    values: <$simd>::new(values),
+                }
+            }
+
+            fn to_array(self) -> [Float; $width] {
+                self.values.to_array()
+            }
+
+            fn map(self, operation: impl Fn($simd) -> $simd) -> Self {
+                $name {
+                    values: operation(self.values),
+                }
+            }
+
+            fn zip(self, other: Self, operation: impl Fn($simd, $simd) -> $simd) -> Self {
+                $name {
+                    values: operation(self.values, other.values),
+                }
+            }
+        }
+
+        #[cfg(not($backed))]
+        impl $name {
+            fn from_array(values: [Float; $width]) -> Self {
+                $name { values }
+            }
+
+            fn to_array(self) -> [Float; $width] {
+                self.values
+            }
+
+            fn map(self, operation: impl Fn(Float) -> Float) -> Self {
+                $name {
+                    values: self.values.map(operation),
+                }
+            }
+
+            fn zip(self, other: Self, operation: impl Fn(Float, Float) -> Float) -> Self {
+                let mut values = self.values;
+                for (value, other) in values.iter_mut().zip(other.values.iter()) {
+                    *value = operation(*value, *other);
+                }
+                $name { values }
+            }
+        }
+
+        impl ops::Add for $name {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self::Output {
+                self.zip(rhs, |a, b| a + b)
+            }
+        }
+
+        impl ops::Sub for $name {
+            type Output = Self;
+
+            fn sub(self, rhs: Self) -> Self::Output {
+                self.zip(rhs, |a, b| a - b)
+            }
+        }
+
+        impl ops::Mul for $name {
+            type Output = Self;
+
+            fn mul(self, rhs: Self) -> Self::Output {
+                self.zip(rhs, |a, b| a * b)
+            }
+        }
+
+        impl ops::Div for $name {
+            type Output = Self;
+
+            fn div(self, rhs: Self) -> Self::Output {
+                self.zip(rhs, |a, b| a / b)
+            }
+        }
+
+        impl ops::Neg for $name {
+            type Output = Self;
+
+            fn neg(self) -> Self::Output {
+                self.map(|a| -a)
+            }
+        }
+
+        impl ops::BitAnd for $name {
+            type Output = Self;
+
+            #[cfg($backed)]
+            fn bitand(self, rhs: Self) -> Self::Output {
+                self.zip(rhs, |a, b| a & b)
+            }
+
+            #[cfg(not($backed))]
+            fn bitand(self, rhs: Self) -> Self::Output {
+                self.zip(rhs, |a, b| Float::from_bits(a.to_bits() & b.to_bits()))
+            }
+        }
+
+        impl ops::BitOr for $name {
+            type Output = Self;
+
+            #[cfg($backed)]
+            fn bitor(self, rhs: Self) -> Self::Output {
+                self.zip(rhs, |a, b| a | b)
+            }
+
+            #[cfg(not($backed))]
+            fn bitor(self, rhs: Self) -> Self::Output {
+                self.zip(rhs, |a, b| Float::from_bits(a.to_bits() | b.to_bits()))
+            }
+        }
+
+        impl Lanes for $name {
+            const WIDTH: usize = $width;
+
+            fn splat(value: Float) -> Self {
+                $name::from_array([value; $width])
+            }
+
+            fn load(values: &[Float]) -> Self {
+                let mut array = [0.0; $width];
+                array.copy_from_slice(values);
+                $name::from_array(array)
+            }
+
+            fn store(self, values: &mut [Float]) {
+                values.copy_from_slice(&self.to_array());
+            }
+
+            fn sqrt(self) -> Self {
+                self.map(|a| a.sqrt())
+            }
+
+            fn min(self, other: Self) -> Self {
+                self.zip(other, |a, b| a.min(b))
+            }
+
+            fn max(self, other: Self) -> Self {
+                self.zip(other, |a, b| a.max(b))
+            }
+
+            fn abs(self) -> Self {
+                self.map(|a| a.abs())
+            }
+
+            #[cfg($backed)]
+            fn ge(self, other: Self) -> Self {
+                self.zip(other, |a, b| a.cmp_ge(b))
+            }
+
+            #[cfg(not($backed))]
+            fn ge(self, other: Self) -> Self {
+                self.zip(other, |a, b| mask(a >= b))
+            }
+
+            #[cfg($backed)]
+            fn le(self, other: Self) -> Self {
+                self.zip(other, |a, b| a.cmp_le(b))
+            }
+
+            #[cfg(not($backed))]
+            fn le(self, other: Self) -> Self {
+                self.zip(other, |a, b| mask(a <= b))
+            }
+
+            #[cfg($backed)]
+            fn select(self, if_true: Self, if_false: Self) -> Self {
+                $name {
+                    values: self.values.blend(if_true.values, if_false.values),
+                }
+            }
+
+            #[cfg(not($backed))]
+            fn select(self, if_true: Self, if_false: Self) -> Self {
+                let mut values = if_false.values;
+                for (index, value) in values.iter_mut().enumerate() {
+                    if self.values[index].to_bits() != 0 {
+                        *value = if_true.values[index];
+                    }
+                }
+                $name { values }
+            }
+
+            #[cfg($backed)]
+            fn any(self) -> bool {
+                self.values.any()
+            }
+
+            #[cfg(not($backed))]
+            fn any(self) -> bool {
+                self.values.iter().any(|value| value.to_bits() != 0)
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "f64"))]
+lanes!(FloatX4, 4, wide::f32x4, feature = "simd");
+#[cfg(not(feature = "f64"))]
+lanes!(FloatX8, 8, wide::f32x8, feature = "simd");
+#[cfg(feature = "f64")]
+lanes!(FloatX4, 4, wide::f64x4, feature = "simd");
+// `wide` has no eight lanes of doubles, these always use arrays.
+#[cfg(feature = "f64")]
+lanes!(FloatX8, 8, wide::f64x4, any());
+
+// The components of one vector in a register, for the arithmetic of `Vec3`, `Point3` and
+// `Normal3`. The fourth lane is padding. Without `simd` the operations apply per component,
+// either way every component rounds the same.
+#[cfg(all(feature = "simd", not(feature = "f64")))]
+pub type Float3 = wide::f32x4;
+#[cfg(all(feature = "simd", feature = "f64"))]
+pub type Float3 = wide::f64x4;
+#[cfg(not(feature = "simd"))]
+pub type Float3 = Float;
+
+#[cfg(feature = "simd")]
+fn to_float3(values: [Float; 3]) -> Float3 {
+    Float3::new([values[0], values[1], values[2], 0.0])
+}
+
+#[cfg(feature = "simd")]
+fn from_float3(values: Float3) -> [Float; 3] {
+    let [x, y, z, _] = values.to_array();
+    [x, y, z]
+}
+
+// The operations see every component at once, scalars broadcast with `Float3::from`.
+#[cfg(feature = "simd")]
+pub fn map3(a: [Float; 3], operation: impl Fn(Float3) -> Float3) -> [Float; 3] {
+    from_float3(operation(to_float3(a)))
+}
+
+#[cfg(not(feature = "simd"))]
+pub fn map3(a: [Float; 3], operation: impl Fn(Float3) -> Float3) -> [Float; 3] {
+    a.map(operation)
+}
+
+#[cfg(feature = "simd")]
+pub fn zip3(
+    a: [Float; 3],
+    b: [Float; 3],
+    operation: impl Fn(Float3, Float3) -> Float3,
+) -> [Float; 3] {
+    from_float3(operation(to_float3(a), to_float3(b)))
+}
+
+#[cfg(not(feature = "simd"))]
+pub fn zip3(
+    a: [Float; 3],
+    b: [Float; 3],
+    operation: impl Fn(Float3, Float3) -> Float3,
+) -> [Float; 3] {
+    [
+        operation(a[0], b[0]),
+        operation(a[1], b[1]),
+        operation(a[2], b[2]),
+    ]
+}
+
+// The products in one register, summed across its lanes in the order x, y, z (plus the zero
+// padding) like the scalar dot product.
+#[cfg(feature = "simd")]
+pub fn dot3(a: [Float; 3], b: [Float; 3]) -> Float {
+    (to_float3(a) * to_float3(b)).reduce_add()
+}
+
+#[cfg(not(feature = "simd"))]
+pub fn dot3(a: [Float; 3], b: [Float; 3]) -> Float {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+// a.yzx * b.zxy - a.zxy * b.yzx with the rotations loaded as registers.
+#[cfg(feature = "simd")]
+pub fn cross3(a: [Float; 3], b: [Float; 3]) -> [Float; 3] {
+    let yzx = |v: [Float; 3]| Float3::new([v[1], v[2], v[0], 0.0]);
+    let zxy = |v: [Float; 3]| Float3::new([v[2], v[0], v[1], 0.0]);
+    from_float3(yzx(a) * zxy(b) - zxy(a) * yzx(b))
+}
+
+#[cfg(not(feature = "simd"))]
+pub fn cross3(a: [Float; 3], b: [Float; 3]) -> [Float; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+// All bits of a lane set when `condition` holds.
+fn mask(condition: bool) -> Float {
+    Float::from_bits(if condition { !0 } else { 0 })
+}
+
+// One lane, plain floats, for the scalar code path that shares the packet code.
+#[derive(Copy, Clone, Debug)]
+pub struct FloatX1 {
+    value: Float,
+}
+
+impl ops::Add for FloatX1 {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        FloatX1 {
+            value: self.value + rhs.value,
+        }
+    }
+}
+
+impl ops::Sub for FloatX1 {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        FloatX1 {
+            value: self.value - rhs.value,
+        }
+    }
+}
+
+impl ops::Mul for FloatX1 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        FloatX1 {
+            value: self.value * rhs.value,
+        }
+    }
+}
+
+impl ops::Div for FloatX1 {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        FloatX1 {
+            value: self.value / rhs.value,
+        }
+    }
+}
+
+impl ops::Neg for FloatX1 {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        FloatX1 { value: -self.value }
+    }
+}
+
+impl ops::BitAnd for FloatX1 {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        FloatX1 {
+            value: Float::from_bits(self.value.to_bits() & rhs.value.to_bits()),
+        }
+    }
+}
+
+impl ops::BitOr for FloatX1 {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        FloatX1 {
+            value: Float::from_bits(self.value.to_bits() | rhs.value.to_bits()),
+        }
+    }
+}
+
+impl Lanes for FloatX1 {
+    const WIDTH: usize = 1;
+
+    fn splat(value: Float) -> Self {
+        FloatX1 { value }
+    }
+
+    fn load(values: &[Float]) -> Self {
+        FloatX1 { value: values[0] }
+    }
+
+    fn store(self, values: &mut [Float]) {
+        values[0] = self.value;
+    }
+
+    fn sqrt(self) -> Self {
+        FloatX1 {
+            value: self.value.sqrt(),
+        }
+    }
+
+    fn min(self, other: Self) -> Self {
+        FloatX1 {
+            value: self.value.min(other.value),
+        }
+    }
+
+    fn max(self, other: Self) -> Self {
+        FloatX1 {
+            value: self.value.max(other.value),
+        }
+    }
+
+    fn abs(self) -> Self {
+        FloatX1 {
+            value: self.value.abs(),
+        }
+    }
+
+    fn ge(self, other: Self) -> Self {
+        FloatX1 {
+            value: mask(self.value >= other.value),
+        }
+    }
+
+    fn le(self, other: Self) -> Self {
+        FloatX1 {
+            value: mask(self.value <= other.value),
+        }
+    }
+
+    fn select(self, if_true: Self, if_false: Self) -> Self {
+        if self.value.to_bits() != 0 {
+            if_true
+        } else {
+            if_false
+        }
+    }
+
+    fn any(self) -> bool {
+        self.value.to_bits() != 0
+    }
+}
+
+// Structure of arrays vector, lane i of every component is vector i.
+#[derive(Copy, Clone, Debug)]
+pub struct Vec3Lanes<L: Lanes> {
+    pub x: L,
+    pub y: L,
+    pub z: L,
+}
+
+impl<L: Lanes> Vec3Lanes<L> {
+    pub fn splat(vector: &Vec3) -> Self {
+        Vec3Lanes {
+            x: L::splat(vector.x()),
+            y: L::splat(vector.y()),
+            z: L::splat(vector.z()),
+        }
+    }
+
+    pub fn splat_point(point: &Point3) -> Self {
+        Vec3Lanes {
+            x: L::splat(point.x()),
+            y: L::splat(point.y()),
+            z: L::splat(point.z()),
+        }
+    }
+
+    // Gathers `WIDTH` vectors from component slices.
+    pub fn load(x: &[Float], y: &[Float], z: &[Float]) -> Self {
+        Vec3Lanes {
+            x: L::load(x),
+            y: L::load(y),
+            z: L::load(z),
+        }
+    }
+
+    pub fn axis(&self, index: usize) -> L {
+        match index {
+            0 => self.x,
+            1 => self.y,
+            _ => self.z,
+        }
+    }
+
+    // Sum of the absolute values of the components.
+    pub fn abs_sum(&self) -> L {
+        self.x.abs() + self.y.abs() + self.z.abs()
+    }
+
+    pub fn dot(&self, other: &Self) -> L {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(&self, other: &Self) -> Self {
+        Vec3Lanes {
+            x: self.y * other.z - self.z * other.y,
+            y: self.z * other.x - self.x * other.z,
+            z: self.x * other.y - self.y * other.x,
+        }
+    }
+
+    pub fn scale(&self, scale: L) -> Self {
+        Vec3Lanes {
+            x: self.x * scale,
+            y: self.y * scale,
+            z: self.z * scale,
+        }
+    }
+
+    pub fn mul_add(&self, scale: L, other: &Self) -> Self {
+        Vec3Lanes {
+            x: self.x + scale * other.x,
+            y: self.y + scale * other.y,
+            z: self.z + scale * other.z,
+        }
+    }
+}
+
+impl<L: Lanes> ops::Sub for Vec3Lanes<L> {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vec3Lanes {
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
+        }
+    }
+}
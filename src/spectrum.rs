@@ -0,0 +1,491 @@
+use std::{ops, sync::OnceLock};
+
+use rand::Rng;
+
+use crate::{
+    color::{
+        chromatic_adaptation, multiply, transform, Color, Matrix, Xyz, D65_WHITE, XYZ_TO_SRGB,
+    },
+    sampler,
+};
+
+pub const LAMBDA_MIN: f32 = 360.0;
+pub const LAMBDA_MAX: f32 = 830.0;
+
+// Wavelengths traced together along one path (hero wavelength sampling).
+pub const SPECTRUM_SAMPLES: usize = 4;
+
+// Nodes along every axis of the table of fitted coefficients.
+const TABLE_RESOLUTION: usize = 32;
+// Spacing of the wavelengths the fits integrate over.
+const FIT_STEP: f32 = 5.0;
+
+#[derive(Copy, Clone, Default)]
+pub struct SampledSpectrum {
+    pub values: [f32; SPECTRUM_SAMPLES],
+}
+
+impl SampledSpectrum {
+    pub fn constant(value: f32) -> SampledSpectrum {
+        SampledSpectrum {
+            values: [value; SPECTRUM_SAMPLES],
+        }
+    }
+
+    pub fn is_black(&self) -> bool {
+        self.values.iter().all(|value| *value == 0.0)
+    }
+
+    pub fn max_component(&self) -> f32 {
+        self.values.iter().copied().fold(0.0, f32::max)
+    }
+}
+
+impl ops::Add for SampledSpectrum {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        let mut values = self.values;
+        for (value, other) in values.iter_mut().zip(rhs.values.iter()) {
+            *value += other;
+        }
+        Self { values }
+    }
+}
+
+impl ops::Mul for SampledSpectrum {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut values = self.values;
+        for (value, other) in values.iter_mut().zip(rhs.values.iter()) {
+            *value *= other;
+        }
+        Self { values }
+    }
+}
+
+impl ops::Mul<SampledSpectrum> for f32 {
+    type Output = SampledSpectrum;
+
+    fn mul(self, rhs: SampledSpectrum) -> Self::Output {
+        SampledSpectrum {
+            values: rhs.values.map(|value| self * value),
+        }
+    }
+}
+
+// The first wavelength is the hero, the others are evenly rotated from it over the range.
+#[derive(Copy, Clone)]
+pub struct SampledWavelengths {
+    pub lambda: [f32; SPECTRUM_SAMPLES],
+    pub pdf: [f32; SPECTRUM_SAMPLES],
+}
+
+impl SampledWavelengths {
+    pub fn sample_uniform() -> SampledWavelengths {
+        let hero: f32 = sampler::rng().gen();
+        let range = LAMBDA_MAX - LAMBDA_MIN;
+
+        let mut lambda = [0.0; SPECTRUM_SAMPLES];
+        for (index, value) in lambda.iter_mut().enumerate() {
+            let offset = (hero + index as f32 / SPECTRUM_SAMPLES as f32).fract();
+            *value = LAMBDA_MIN + offset * range;
+        }
+
+        SampledWavelengths {
+            lambda,
+            pdf: [1.0 / range; SPECTRUM_SAMPLES],
+        }
+    }
+
+    pub fn hero(&self) -> f32 {
+        self.lambda[0]
+    }
+
+    // Wavelength dependent events like dispersion split the path, only the hero carries on.
+    pub fn terminate_secondary(&mut self) {
+        if self.pdf[1..].iter().all(|pdf| *pdf == 0.0) {
+            return;
+        }
+
+        for pdf in self.pdf[1..].iter_mut() {
+            *pdf = 0.0;
+        }
+        self.pdf[0] /= SPECTRUM_SAMPLES as f32;
+    }
+}
+
+// Multi-lobe Gaussian fit of the CIE 1931 color matching functions by Wyman, Sloan and Shirley.
+pub fn cie_xyz(lambda: f32) -> Xyz {
+    let lobe = |mean: f32, sigma_low: f32, sigma_high: f32| {
+        let sigma = if lambda < mean { sigma_low } else { sigma_high };
+        let t = (lambda - mean) / sigma;
+        (-0.5 * t * t).exp()
+    };
+
+    Xyz::new(
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7)
+            - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    )
+}
+
+// Integrals of the color matching functions over [LAMBDA_MIN..LAMBDA_MAX], so that a constant
+// spectrum of 1 has Y = 1.
+fn cie_integrals() -> Xyz {
+    let mut sum = Xyz::default();
+    let mut lambda = LAMBDA_MIN;
+    while lambda <= LAMBDA_MAX {
+        sum += cie_xyz(lambda);
+        lambda += 1.0;
+    }
+    sum
+}
+
+thread_local! {
+    static CIE_INTEGRALS: Xyz = cie_integrals();
+    static XYZ_TO_OUTPUT: Matrix = {
+        // The integrals are the white of a constant spectrum, adapt it to the sRGB white.
+        let integrals = cie_integrals();
+        let white = integrals / integrals.y();
+        multiply(&XYZ_TO_SRGB, &chromatic_adaptation(&white, &D65_WHITE))
+    };
+}
+
+static COEFFICIENT_TABLE: OnceLock<CoefficientTable> = OnceLock::new();
+
+// Monte Carlo estimate of the CIE XYZ color of the sampled spectrum, Y = 1 for a constant 1.
+pub fn to_xyz(spectrum: &SampledSpectrum, wavelengths: &SampledWavelengths) -> Xyz {
+    let mut xyz = Xyz::default();
+    for index in 0..SPECTRUM_SAMPLES {
+        if wavelengths.pdf[index] == 0.0 {
+            continue;
+        }
+        xyz +=
+            (spectrum.values[index] / wavelengths.pdf[index]) * cie_xyz(wavelengths.lambda[index]);
+    }
+
+    let y_integral = CIE_INTEGRALS.with(|integrals| integrals.y());
+    xyz / (SPECTRUM_SAMPLES as f32 * y_integral)
+}
+
+// Linear sRGB output color of the sampled spectrum.
+pub fn to_rgb(spectrum: &SampledSpectrum, wavelengths: &SampledWavelengths) -> Color {
+    let xyz = to_xyz(spectrum, wavelengths);
+    XYZ_TO_OUTPUT.with(|matrix| Color {
+        data: transform(matrix, &xyz.data),
+    })
+}
+
+// Smooth spectrum reproducing an RGB color, s(λ) = scale * sigmoid(c0 λ² + c1 λ + c2),
+// following "A Low-Dimensional Function Space for Efficient Spectral Upsampling"
+// by Jakob and Hanika. The polynomial is in wavelength normalized to [0..1].
+#[derive(Copy, Clone)]
+pub struct RgbSpectrum {
+    coefficients: [f32; 3],
+    scale: f32,
+}
+
+impl RgbSpectrum {
+    // Reflectance of a color clamped to [0, 1], which stays within [0, 1] at every wavelength.
+    pub fn albedo(rgb: &Color) -> RgbSpectrum {
+        RgbSpectrum {
+            coefficients: coefficient_table().lookup(rgb.data.map(|value| value.clamp(0.0, 1.0))),
+            scale: 1.0,
+        }
+    }
+
+    // Emission of any brightness. Half of the normalized color keeps the fit inside of what
+    // the sigmoid can reach, and the scale brings the brightness back.
+    pub fn unbounded(rgb: &Color) -> RgbSpectrum {
+        let max = rgb.max_component();
+        if max <= 0.0 {
+            return RgbSpectrum {
+                coefficients: [0.0; 3],
+                scale: 0.0,
+            };
+        }
+
+        let scale = 2.0 * max;
+        RgbSpectrum {
+            coefficients: coefficient_table()
+                .lookup(rgb.data.map(|value| (value / scale).max(0.0))),
+            scale,
+        }
+    }
+
+    pub fn evaluate(&self, lambda: f32) -> f32 {
+        self.scale * evaluate_sigmoid_polynomial(&self.coefficients, lambda)
+    }
+
+    pub fn sample(&self, wavelengths: &SampledWavelengths) -> SampledSpectrum {
+        SampledSpectrum {
+            values: wavelengths.lambda.map(|lambda| self.evaluate(lambda)),
+        }
+    }
+}
+
+fn evaluate_sigmoid_polynomial(coefficients: &[f32; 3], lambda: f32) -> f32 {
+    sigmoid_polynomial(
+        coefficients,
+        (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN),
+    )
+}
+
+fn sigmoid_polynomial(coefficients: &[f32; 3], x: f32) -> f32 {
+    let polynomial = (coefficients[0] * x + coefficients[1]) * x + coefficients[2];
+
+    if polynomial.is_infinite() {
+        return if polynomial > 0.0 { 1.0 } else { 0.0 };
+    }
+    0.5 + polynomial / (2.0 * (1.0 + polynomial * polynomial).sqrt())
+}
+
+fn coefficient_table() -> &'static CoefficientTable {
+    COEFFICIENT_TABLE.get_or_init(CoefficientTable::new)
+}
+
+// Coefficients fitted once over a grid of colors in [0, 1] and interpolated in between, like
+// pbrt's RGBToSpectrumTable. Colors are indexed by their largest component, by its value z and
+// by the other two divided by it. The z nodes crowd towards black and full brightness, where
+// the coefficients change fastest.
+struct CoefficientTable {
+    z_nodes: [f32; TABLE_RESOLUTION],
+    // By largest component, then z, y and x.
+    coefficients: Vec<[f32; 3]>,
+}
+
+impl CoefficientTable {
+    fn new() -> CoefficientTable {
+        let weights = FitWeights::new();
+        let last = (TABLE_RESOLUTION - 1) as f32;
+        let z_nodes = std::array::from_fn(|k| smoothstep(smoothstep(k as f32 / last)));
+        let mut coefficients = vec![[0.0; 3]; 3 * TABLE_RESOLUTION.pow(3)];
+
+        // Every fit starts from the one of the neighbouring z, away from the middle-dark z
+        // nodes that converge easily from zero.
+        let start = TABLE_RESOLUTION / 5;
+        for largest in 0..3 {
+            for y in 0..TABLE_RESOLUTION {
+                for x in 0..TABLE_RESOLUTION {
+                    let mut fit = |z: usize, guess: [f32; 3]| {
+                        let mut target = [0.0; 3];
+                        target[largest] = z_nodes[z];
+                        target[(largest + 1) % 3] = x as f32 / last * z_nodes[z];
+                        target[(largest + 2) % 3] = y as f32 / last * z_nodes[z];
+                        let fitted = weights.fit(&target, guess);
+                        coefficients[table_index(largest, z, y, x)] = fitted;
+                        fitted
+                    };
+                    (start..TABLE_RESOLUTION).fold([0.0; 3], |guess, z| fit(z, guess));
+                    (0..start).rev().fold([0.0; 3], |guess, z| fit(z, guess));
+                }
+            }
+        }
+
+        CoefficientTable {
+            z_nodes,
+            coefficients,
+        }
+    }
+
+    fn lookup(&self, rgb: [f32; 3]) -> [f32; 3] {
+        // Grays are exact with a constant polynomial.
+        if rgb[0] == rgb[1] && rgb[1] == rgb[2] {
+            let value = rgb[0];
+            return [0.0, 0.0, (value - 0.5) / (value * (1.0 - value)).sqrt()];
+        }
+
+        let largest = if rgb[0] > rgb[1] {
+            if rgb[0] > rgb[2] {
+                0
+            } else {
+                2
+            }
+        } else if rgb[1] > rgb[2] {
+            1
+        } else {
+            2
+        };
+        let z = rgb[largest];
+        let scale = (TABLE_RESOLUTION - 1) as f32 / z;
+        let x = rgb[(largest + 1) % 3] * scale;
+        let y = rgb[(largest + 2) % 3] * scale;
+
+        let x_index = (x as usize).min(TABLE_RESOLUTION - 2);
+        let y_index = (y as usize).min(TABLE_RESOLUTION - 2);
+        let z_index = self
+            .z_nodes
+            .partition_point(|node| *node <= z)
+            .clamp(1, TABLE_RESOLUTION - 1)
+            - 1;
+        let dx = x - x_index as f32;
+        let dy = y - y_index as f32;
+        let dz = (z - self.z_nodes[z_index]) / (self.z_nodes[z_index + 1] - self.z_nodes[z_index]);
+
+        let mut coefficients = [0.0; 3];
+        for (corner, weight) in [
+            ((0, 0, 0), (1.0 - dz) * (1.0 - dy) * (1.0 - dx)),
+            ((0, 0, 1), (1.0 - dz) * (1.0 - dy) * dx),
+            ((0, 1, 0), (1.0 - dz) * dy * (1.0 - dx)),
+            ((0, 1, 1), (1.0 - dz) * dy * dx),
+            ((1, 0, 0), dz * (1.0 - dy) * (1.0 - dx)),
+            ((1, 0, 1), dz * (1.0 - dy) * dx),
+            ((1, 1, 0), dz * dy * (1.0 - dx)),
+            ((1, 1, 1), dz * dy * dx),
+        ] {
+            let (cz, cy, cx) = corner;
+            let node =
+                &self.coefficients[table_index(largest, z_index + cz, y_index + cy, x_index + cx)];
+            for (coefficient, value) in coefficients.iter_mut().zip(node) {
+                *coefficient += weight * value;
+            }
+        }
+        coefficients
+    }
+}
+
+fn table_index(largest: usize, z: usize, y: usize, x: usize) -> usize {
+    ((largest * TABLE_RESOLUTION + z) * TABLE_RESOLUTION + y) * TABLE_RESOLUTION + x
+}
+
+fn smoothstep(x: f32) -> f32 {
+    x * x * (3.0 - 2.0 * x)
+}
+
+// Output RGB of every wavelength the fits integrate over, with its share of the integral.
+// The fits run in double precision, the dark and saturated colors need it.
+struct FitWeights {
+    samples: Vec<(f64, [f64; 3])>,
+}
+
+// Rows of 3x3 doubles.
+type Matrix64 = [[f64; 3]; 3];
+
+impl FitWeights {
+    fn new() -> FitWeights {
+        let y_integral = CIE_INTEGRALS.with(|integrals| integrals.y());
+        let mut samples = Vec::new();
+        let mut lambda = LAMBDA_MIN;
+        while lambda <= LAMBDA_MAX {
+            let xyz = FIT_STEP * cie_xyz(lambda) / y_integral;
+            let rgb = XYZ_TO_OUTPUT.with(|matrix| transform(matrix, &xyz.data));
+            let x = (lambda - LAMBDA_MIN) / (LAMBDA_MAX - LAMBDA_MIN);
+            samples.push((x as f64, rgb.map(f64::from)));
+            lambda += FIT_STEP;
+        }
+        FitWeights { samples }
+    }
+
+    // Difference between the color of the sigmoid spectrum and the target relative to the
+    // target's brightness, with its derivatives by the coefficients.
+    fn residual(&self, coefficients: &[f64; 3], target: &[f64; 3]) -> ([f64; 3], Matrix64) {
+        let brightness = target[0].max(target[1]).max(target[2]).max(1e-4);
+        let mut color = [0.0; 3];
+        // Sums of the sigmoid's derivative by the polynomial times x², x and 1.
+        let mut slopes = [[0.0; 3]; 3];
+        for (x, weights) in &self.samples {
+            let polynomial = (coefficients[0] * x + coefficients[1]) * x + coefficients[2];
+            let root = (1.0 + polynomial * polynomial).sqrt();
+            let value = 0.5 + polynomial / (2.0 * root);
+            let slope = 0.5 / (root * root * root);
+            for channel in 0..3 {
+                let weight = weights[channel];
+                color[channel] += value * weight;
+                slopes[channel][2] += slope * weight;
+                slopes[channel][1] += slope * x * weight;
+                slopes[channel][0] += slope * x * x * weight;
+            }
+        }
+
+        let mut residual = [0.0; 3];
+        let mut jacobian = [[0.0; 3]; 3];
+        for channel in 0..3 {
+            residual[channel] = (color[channel] - target[channel]) / brightness;
+            for coefficient in 0..3 {
+                jacobian[channel][coefficient] = slopes[channel][coefficient] / brightness;
+            }
+        }
+        (residual, jacobian)
+    }
+
+    // Levenberg-Marquardt fit of the coefficients starting from `guess`.
+    fn fit(&self, target: &[f32; 3], guess: [f32; 3]) -> [f32; 3] {
+        let target = target.map(f64::from);
+        let mut coefficients = guess.map(f64::from);
+        let mut damping = 1e-3;
+        let (mut residual, mut jacobian) = self.residual(&coefficients, &target);
+
+        for _ in 0..64 {
+            let error = squared_norm(&residual);
+            if error < 1e-12 {
+                break;
+            }
+
+            let mut normal = [[0.0; 3]; 3];
+            let mut gradient = [0.0; 3];
+            for row in 0..3 {
+                for column in 0..3 {
+                    normal[row][column] =
+                        (0..3).map(|k| jacobian[k][row] * jacobian[k][column]).sum();
+                }
+                normal[row][row] *= 1.0 + damping;
+                gradient[row] = -(0..3).map(|k| jacobian[k][row] * residual[k]).sum::<f64>();
+            }
+
+            let step = match solve(&normal, &gradient) {
+                Some(step) => step,
+                None => break,
+            };
+
+            let candidate = [
+                coefficients[0] + step[0],
+                coefficients[1] + step[1],
+                coefficients[2] + step[2],
+            ];
+            let (candidate_residual, candidate_jacobian) = self.residual(&candidate, &target);
+            if squared_norm(&candidate_residual) < error {
+                coefficients = candidate;
+                residual = candidate_residual;
+                jacobian = candidate_jacobian;
+                damping = (damping * 0.5).max(1e-9);
+            } else if damping > 1e9 {
+                // Out of gamut colors end up as close as they get.
+                break;
+            } else {
+                damping *= 10.0;
+            }
+        }
+        coefficients.map(|coefficient| coefficient as f32)
+    }
+}
+
+fn squared_norm(vector: &[f64; 3]) -> f64 {
+    vector.iter().map(|value| value * value).sum()
+}
+
+// Cramer's rule, None for a singular system.
+fn solve(matrix: &Matrix64, rhs: &[f64; 3]) -> Option<[f64; 3]> {
+    let determinant = |m: &Matrix64| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+
+    let base = determinant(matrix);
+    if base.abs() < 1e-40 {
+        return None;
+    }
+
+    let mut solution = [0.0; 3];
+    for (column, value) in solution.iter_mut().enumerate() {
+        let mut replaced = *matrix;
+        for row in 0..3 {
+            replaced[row][column] = rhs[row];
+        }
+        *value = determinant(&replaced) / base;
+    }
+    Some(solution)
+}
@@ -0,0 +1,249 @@
+use std::ops;
+
+use crate::{
+    error::Error,
+    float::{gamma, Float},
+    vector::{cross, Normal3, Point3, Vec3},
+};
+
+pub type Matrix4 = [[Float; 4]; 4];
+
+const IDENTITY: Matrix4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+// Affine transform kept together with its inverse, normals need the inverse transpose.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4,
+    inverse: Matrix4,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Transform {
+        Transform {
+            matrix: IDENTITY,
+            inverse: IDENTITY,
+        }
+    }
+
+    pub fn from_matrix(matrix: Matrix4) -> Result<Transform, Error> {
+        let inverse = invert(&matrix).ok_or_else(|| {
+            Error::InvalidGeometry(format!("transform {:?} is not invertible", matrix))
+        })?;
+
+        Ok(Transform { matrix, inverse })
+    }
+
+    pub fn translation(offset: &Vec3) -> Transform {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+        for axis in 0..3 {
+            matrix[axis][3] = offset.data[axis];
+            inverse[axis][3] = -offset.data[axis];
+        }
+
+        Transform { matrix, inverse }
+    }
+
+    pub fn scale(x: Float, y: Float, z: Float) -> Result<Transform, Error> {
+        Transform::from_matrix([
+            [x, 0.0, 0.0, 0.0],
+            [0.0, y, 0.0, 0.0],
+            [0.0, 0.0, z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    // Counter-clockwise looking down the axis, Rodrigues' formula.
+    pub fn rotation(axis: &Vec3, angle_deg: Float) -> Transform {
+        let axis = axis.normalize();
+        let (sin, cos) = angle_deg.to_radians().sin_cos();
+        let [x, y, z] = axis.data;
+
+        let mut matrix = IDENTITY;
+        matrix[0][0] = x * x + (1.0 - x * x) * cos;
+        matrix[0][1] = x * y * (1.0 - cos) - z * sin;
+        matrix[0][2] = x * z * (1.0 - cos) + y * sin;
+        matrix[1][0] = x * y * (1.0 - cos) + z * sin;
+        matrix[1][1] = y * y + (1.0 - y * y) * cos;
+        matrix[1][2] = y * z * (1.0 - cos) - x * sin;
+        matrix[2][0] = x * z * (1.0 - cos) - y * sin;
+        matrix[2][1] = y * z * (1.0 - cos) + x * sin;
+        matrix[2][2] = z * z + (1.0 - z * z) * cos;
+
+        // Rotations are orthogonal, the inverse is the transpose.
+        Transform {
+            matrix,
+            inverse: transpose(&matrix),
+        }
+    }
+
+    // Maps the local x, y and z axes to the given basis placed at `origin`.
+    pub fn from_frame(origin: &Point3, x: &Vec3, y: &Vec3) -> Result<Transform, Error> {
+        let z = cross(x, y);
+        Transform::from_matrix([
+            [x.x(), y.x(), z.x(), origin.x()],
+            [x.y(), y.y(), z.y(), origin.y()],
+            [x.z(), y.z(), z.z(), origin.z()],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn matrix(&self) -> &Matrix4 {
+        &self.matrix
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    pub fn transform_point(&self, point: &Point3) -> Point3 {
+        let m = &self.matrix;
+        let row = |index: usize| {
+            m[index][0] * point.x()
+                + m[index][1] * point.y()
+                + m[index][2] * point.z()
+                + m[index][3]
+        };
+        let w = row(3);
+        let point = Point3::new(row(0), row(1), row(2));
+
+        if w == 1.0 {
+            point
+        } else {
+            Point3 {
+                data: point.data.map(|value| value / w),
+            }
+        }
+    }
+
+    // Affine transform of a point known to within `error`, and the error it has afterwards
+    // (pbrt 3.9.4).
+    pub fn transform_point_with_error(&self, point: &Point3, error: &Vec3) -> (Point3, Vec3) {
+        let m = &self.matrix;
+        let row = |index: usize, values: &[Float; 3]| {
+            (0..3)
+                .map(|column| (m[index][column] * values[column]).abs())
+                .sum::<Float>()
+        };
+        let transformed_error = Vec3::new(
+            (gamma(3) + 1.0) * row(0, &error.data)
+                + gamma(3) * (row(0, &point.data) + m[0][3].abs()),
+            (gamma(3) + 1.0) * row(1, &error.data)
+                + gamma(3) * (row(1, &point.data) + m[1][3].abs()),
+            (gamma(3) + 1.0) * row(2, &error.data)
+                + gamma(3) * (row(2, &point.data) + m[2][3].abs()),
+        );
+        (self.transform_point(point), transformed_error)
+    }
+
+    pub fn transform_vector(&self, vector: &Vec3) -> Vec3 {
+        let m = &self.matrix;
+        let row = |index: usize| {
+            m[index][0] * vector.x() + m[index][1] * vector.y() + m[index][2] * vector.z()
+        };
+
+        Vec3::new(row(0), row(1), row(2))
+    }
+
+    // Normals stay perpendicular to transformed tangents with the inverse transpose.
+    pub fn transform_normal(&self, normal: &Normal3) -> Normal3 {
+        let inverse = &self.inverse;
+        let column = |index: usize| {
+            inverse[0][index] * normal.x()
+                + inverse[1][index] * normal.y()
+                + inverse[2][index] * normal.z()
+        };
+
+        Normal3::new(column(0), column(1), column(2))
+    }
+
+    // Whether the transform flips handedness, which turns front faces into back faces.
+    pub fn swaps_handedness(&self) -> bool {
+        let m = &self.matrix;
+        let determinant = m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+        determinant < 0.0
+    }
+}
+
+// `a * b` applies `b` first.
+impl ops::Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Transform {
+            matrix: multiply(&self.matrix, &rhs.matrix),
+            inverse: multiply(&rhs.inverse, &self.inverse),
+        }
+    }
+}
+
+fn multiply(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+    let mut result: Matrix4 = [[0.0; 4]; 4];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    result
+}
+
+fn transpose(matrix: &Matrix4) -> Matrix4 {
+    let mut result: Matrix4 = [[0.0; 4]; 4];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = matrix[column][row];
+        }
+    }
+    result
+}
+
+// Gauss-Jordan elimination with partial pivoting, None for a singular matrix.
+fn invert(matrix: &Matrix4) -> Option<Matrix4> {
+    let mut left = *matrix;
+    let mut right = IDENTITY;
+
+    for column in 0..4 {
+        let pivot =
+            (column..4).max_by(|a, b| left[*a][column].abs().total_cmp(&left[*b][column].abs()))?;
+        if left[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        left.swap(column, pivot);
+        right.swap(column, pivot);
+
+        let scale = 1.0 / left[column][column];
+        for k in 0..4 {
+            left[column][k] *= scale;
+            right[column][k] *= scale;
+        }
+
+        for row in 0..4 {
+            if row == column {
+                continue;
+            }
+            let factor = left[row][column];
+            for k in 0..4 {
+                left[row][k] -= factor * left[column][k];
+                right[row][k] -= factor * right[column][k];
+            }
+        }
+    }
+
+    Some(right)
+}
@@ -2,31 +2,73 @@ use std::ops;
 
 use rand::{distributions::Uniform, prelude::Distribution};
 
-#[derive(Copy, Clone, Default)]
-pub struct Vector {
-    pub data: [f32; 3],
+use crate::{
+    float::Float,
+    sampler,
+    simd::{cross3, dot3, map3, zip3, Float3},
+};
+
+// Geometry uses three distinct types so that only meaningful operations compile:
+// a `Point3` is a position, a `Vec3` a displacement or direction between positions
+// and a `Normal3` is perpendicular to a surface, which transforms differently.
+// Colors live in `color::Color`.
+
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct Vec3 {
+    pub data: [Float; 3],
 }
 
-pub fn dot(vec1: &Vector, vec2: &Vector) -> f32 {
-    vec1.data[0] * vec2.data[0] + vec1.data[1] * vec2.data[1] + vec1.data[2] * vec2.data[2]
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct Point3 {
+    pub data: [Float; 3],
 }
 
-pub fn cross(vec1: &Vector, vec2: &Vector) -> Vector {
-    let data: [f32; 3] = [
-        vec1.data[1] * vec2.data[2] - vec1.data[2] * vec2.data[1],
-        -(vec1.data[0] * vec2.data[2] - vec1.data[2] * vec2.data[0]),
-        vec1.data[0] * vec2.data[1] - vec1.data[1] * vec2.data[0],
-    ];
-    return Vector { data };
+#[derive(Copy, Clone, Default, Debug, PartialEq)]
+pub struct Normal3 {
+    pub data: [Float; 3],
 }
 
-pub fn lerp(start: &Vector, end: &Vector, t: f32) -> Vector {
-    (*start) + t * ((*end) - (*start))
+// Types with a direction that may take part in dot and cross products.
+pub trait Direction: Copy {
+    fn components(&self) -> [Float; 3];
 }
 
-pub fn random_on_unit_sphere() -> Vector {
-    let mut random = rand::thread_rng();
-    let chance = Uniform::<f32>::from(-1.0..1.0);
+impl Direction for Vec3 {
+    fn components(&self) -> [Float; 3] {
+        self.data
+    }
+}
+
+impl Direction for Normal3 {
+    fn components(&self) -> [Float; 3] {
+        self.data
+    }
+}
+
+pub fn dot(vec1: &impl Direction, vec2: &impl Direction) -> Float {
+    dot3(vec1.components(), vec2.components())
+}
+
+pub fn cross(vec1: &impl Direction, vec2: &impl Direction) -> Vec3 {
+    Vec3 {
+        data: cross3(vec1.components(), vec2.components()),
+    }
+}
+
+// Two unit vectors perpendicular to the unit vector `axis` and to each other.
+pub fn orthonormal_basis(axis: &Vec3) -> (Vec3, Vec3) {
+    let helper = if axis.x().abs() > 0.9 {
+        Vec3::new(0.0, 1.0, 0.0)
+    } else {
+        Vec3::new(1.0, 0.0, 0.0)
+    };
+    let tangent = cross(axis, &helper).normalize();
+    (tangent, cross(axis, &tangent))
+}
+
+pub fn random_on_unit_sphere() -> Vec3 {
+    let mut random = sampler::rng();
+    let chance = Uniform::<Float>::from(-1.0..1.0);
 
     loop {
         let data = [
@@ -35,7 +77,7 @@ pub fn random_on_unit_sphere() -> Vector {
             chance.sample(&mut random),
         ];
 
-        let random_vector = Vector { data: data };
+        let random_vector = Vec3 { data };
 
         if random_vector.squared_length() > 1.0 {
             continue;
@@ -45,19 +87,14 @@ pub fn random_on_unit_sphere() -> Vector {
     }
 }
 
-pub fn random_in_unit_disc() -> Vector
-{
-    let mut random = rand::thread_rng();
-    let chance = Uniform::<f32>::from(-1.0..1.0);
+pub fn random_in_unit_disc() -> Vec3 {
+    let mut random = sampler::rng();
+    let chance = Uniform::<Float>::from(-1.0..1.0);
 
     loop {
-        let data = [
-            chance.sample(&mut random),
-            chance.sample(&mut random),
-            0.0,
-        ];
+        let data = [chance.sample(&mut random), chance.sample(&mut random), 0.0];
 
-        let random_vector = Vector { data: data };
+        let random_vector = Vec3 { data };
 
         if random_vector.squared_length() >= 1.0 {
             continue;
@@ -67,159 +104,313 @@ pub fn random_in_unit_disc() -> Vector
     }
 }
 
-impl Vector {
-    pub fn x(&self) -> f32 {
+fn squared_length(data: &[Float; 3]) -> Float {
+    dot3(*data, *data)
+}
+
+fn is_finite(data: &[Float; 3]) -> bool {
+    data[0].is_finite() && data[1].is_finite() && data[2].is_finite()
+}
+
+impl Vec3 {
+    pub const fn new(x: Float, y: Float, z: Float) -> Vec3 {
+        Vec3 { data: [x, y, z] }
+    }
+
+    pub fn x(&self) -> Float {
         self.data[0]
     }
 
-    pub fn y(&self) -> f32 {
+    pub fn y(&self) -> Float {
         self.data[1]
     }
 
-    pub fn z(&self) -> f32 {
+    pub fn z(&self) -> Float {
         self.data[2]
     }
 
-    pub fn r(&self) -> f32 {
+    pub fn length(&self) -> Float {
+        self.squared_length().sqrt()
+    }
+
+    pub fn squared_length(&self) -> Float {
+        squared_length(&self.data)
+    }
+
+    pub fn normalize(&self) -> Self {
+        *self / self.length()
+    }
+
+    pub fn is_near_zero(&self) -> bool {
+        let epsilon: Float = 1e-8;
+        self.data[0].abs() < epsilon && self.data[1].abs() < epsilon && self.data[2].abs() < epsilon
+    }
+
+    pub fn is_finite(&self) -> bool {
+        is_finite(&self.data)
+    }
+
+    pub fn abs(&self) -> Vec3 {
+        Vec3 {
+            data: map3(self.data, |value| value.abs()),
+        }
+    }
+
+    pub fn reflect(&self, normal: &Normal3) -> Vec3 {
+        *self - 2.0 * dot(self, normal) * Vec3::from(*normal)
+    }
+
+    pub fn refract(&self, normal: &Normal3, refraction_ratio: Float) -> Vec3 {
+        let normal = Vec3::from(*normal);
+        let cos_theta = dot(&-(*self), &normal).min(1.0);
+        let r_out_perp = refraction_ratio * ((*self) + cos_theta * normal);
+        let r_out_parallel = -(1.0 - r_out_perp.squared_length()).abs().sqrt() * normal;
+        r_out_perp + r_out_parallel
+    }
+}
+
+impl Point3 {
+    pub const ORIGIN: Point3 = Point3::new(0.0, 0.0, 0.0);
+
+    pub const fn new(x: Float, y: Float, z: Float) -> Point3 {
+        Point3 { data: [x, y, z] }
+    }
+
+    pub fn x(&self) -> Float {
         self.data[0]
     }
 
-    pub fn g(&self) -> f32 {
+    pub fn y(&self) -> Float {
         self.data[1]
     }
 
-    pub fn b(&self) -> f32 {
+    pub fn z(&self) -> Float {
         self.data[2]
     }
 
-    pub fn length(&self) -> f32 {
-        ((self.data[0] * self.data[0] + self.data[1] * self.data[1] + self.data[2] * self.data[2])
-            as f32)
-            .sqrt()
+    pub fn is_finite(&self) -> bool {
+        is_finite(&self.data)
     }
 
-    pub fn squared_length(&self) -> f32 {
-        (self.data[0] * self.data[0] + self.data[1] * self.data[1] + self.data[2] * self.data[2])
-            as f32
+    pub fn distance(&self, other: &Point3) -> Float {
+        (*self - *other).length()
+    }
+
+    pub fn lerp(&self, other: &Point3, t: Float) -> Point3 {
+        *self + t * (*other - *self)
+    }
+}
+
+impl Normal3 {
+    pub const fn new(x: Float, y: Float, z: Float) -> Normal3 {
+        Normal3 { data: [x, y, z] }
+    }
+
+    pub fn x(&self) -> Float {
+        self.data[0]
+    }
+
+    pub fn y(&self) -> Float {
+        self.data[1]
+    }
+
+    pub fn z(&self) -> Float {
+        self.data[2]
+    }
+
+    pub fn length(&self) -> Float {
+        squared_length(&self.data).sqrt()
+    }
+
+    pub fn abs(&self) -> Normal3 {
+        Normal3 {
+            data: map3(self.data, |value| value.abs()),
+        }
     }
 
     pub fn normalize(&self) -> Self {
         let length = self.length();
-
-        let data: [f32; 3] = [
-            self.data[0] / length,
-            self.data[1] / length,
-            self.data[2] / length,
-        ];
-        return Self { data };
+        Normal3 {
+            data: map3(self.data, |value| value / Float3::from(length)),
+        }
     }
 
-    pub fn is_near_zero(&self) -> bool {
-        let epsilon: f32 = 1e-8;
-        self.data[0].abs() < epsilon && self.data[1].abs() < epsilon && self.data[2].abs() < epsilon
+    // Flipped to the hemisphere of `direction`.
+    pub fn face_forward(&self, direction: &impl Direction) -> Normal3 {
+        if dot(self, direction) < 0.0 {
+            -*self
+        } else {
+            *self
+        }
     }
+}
 
-    pub fn reflect(&self, normal : &Vector) -> Vector
-    {
-        *self - 2.0 * dot(self, normal) * (*normal)
+impl From<Normal3> for Vec3 {
+    fn from(normal: Normal3) -> Self {
+        Vec3 { data: normal.data }
     }
+}
 
-    pub fn refract(&self, normal : &Vector, refraction_ratio : f32) -> Vector
-    {
-      let cos_theta = dot(&-(*self), normal).min(1.0);
-      let r_out_perp = refraction_ratio * ((*self) + cos_theta * (*normal));
-      let r_out_parallel = - (1.0 - r_out_perp.squared_length()).abs().sqrt() * (*normal);
-      r_out_perp + r_out_parallel
+impl From<Vec3> for Normal3 {
+    fn from(vector: Vec3) -> Self {
+        Normal3 { data: vector.data }
     }
 }
 
-impl ops::Add for Vector {
+impl ops::Add for Vec3 {
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
-        let data: [f32; 3] = [
-            self.data[0] + rhs.data[0],
-            self.data[1] + rhs.data[1],
-            self.data[2] + rhs.data[2],
-        ];
-        return Self { data };
+        Vec3 {
+            data: zip3(self.data, rhs.data, |a, b| a + b),
+        }
     }
 }
 
-impl ops::AddAssign for Vector {
+impl ops::AddAssign for Vec3 {
     fn add_assign(&mut self, rhs: Self) {
-        let data: [f32; 3] = [
-            self.data[0] + rhs.data[0],
-            self.data[1] + rhs.data[1],
-            self.data[2] + rhs.data[2],
-        ];
-
-        *self = Vector { data: data }
+        *self = *self + rhs;
     }
 }
 
-impl ops::Sub for Vector {
+impl ops::Sub for Vec3 {
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        let data: [f32; 3] = [
-            self.data[0] - rhs.data[0],
-            self.data[1] - rhs.data[1],
-            self.data[2] - rhs.data[2],
-        ];
-        return Self { data };
+        Vec3 {
+            data: zip3(self.data, rhs.data, |a, b| a - b),
+        }
     }
 }
 
-impl ops::Mul for Vector {
+impl ops::SubAssign for Vec3 {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl ops::Neg for Vec3 {
     type Output = Self;
 
-    fn mul(self, rhs: Self) -> Self::Output {
-        let data: [f32; 3] = [
-            self.data[0] * rhs.data[0],
-            self.data[1] * rhs.data[1],
-            self.data[2] * rhs.data[2],
-        ];
-        return Self { data };
+    fn neg(self) -> Self::Output {
+        Vec3 {
+            data: map3(self.data, |value| -value),
+        }
+    }
+}
+
+impl ops::Mul<Vec3> for Float {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Vec3) -> Self::Output {
+        Vec3 {
+            data: map3(rhs.data, |value| Float3::from(self) * value),
+        }
+    }
+}
+
+impl ops::Mul<Float> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Float) -> Self::Output {
+        rhs * self
+    }
+}
+
+impl ops::Div<Float> for Vec3 {
+    type Output = Vec3;
+
+    fn div(self, rhs: Float) -> Self::Output {
+        Vec3 {
+            data: map3(self.data, |value| value / Float3::from(rhs)),
+        }
     }
 }
 
-impl ops::Div for Vector {
+impl ops::Add<Vec3> for Point3 {
+    type Output = Point3;
+
+    fn add(self, rhs: Vec3) -> Self::Output {
+        Point3 {
+            data: zip3(self.data, rhs.data, |a, b| a + b),
+        }
+    }
+}
+
+impl ops::AddAssign<Vec3> for Point3 {
+    fn add_assign(&mut self, rhs: Vec3) {
+        *self = *self + rhs;
+    }
+}
+
+impl ops::Sub<Vec3> for Point3 {
+    type Output = Point3;
+
+    fn sub(self, rhs: Vec3) -> Self::Output {
+        Point3 {
+            data: zip3(self.data, rhs.data, |a, b| a - b),
+        }
+    }
+}
+
+impl ops::SubAssign<Vec3> for Point3 {
+    fn sub_assign(&mut self, rhs: Vec3) {
+        *self = *self - rhs;
+    }
+}
+
+impl ops::Sub for Point3 {
+    type Output = Vec3;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Vec3 {
+            data: zip3(self.data, rhs.data, |a, b| a - b),
+        }
+    }
+}
+
+impl ops::Add for Normal3 {
     type Output = Self;
 
-    fn div(self, rhs: Self) -> Self::Output {
-        let data: [f32; 3] = [
-            self.data[0] / rhs.data[0],
-            self.data[1] / rhs.data[1],
-            self.data[2] / rhs.data[2],
-        ];
-        return Self { data };
+    fn add(self, rhs: Self) -> Self::Output {
+        Normal3 {
+            data: zip3(self.data, rhs.data, |a, b| a + b),
+        }
     }
 }
 
-impl ops::Neg for Vector {
-    type Output = Vector;
+impl ops::AddAssign for Normal3 {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl ops::Neg for Normal3 {
+    type Output = Self;
 
     fn neg(self) -> Self::Output {
-        let data: [f32; 3] = [-self.data[0], -self.data[1], -self.data[2]];
-        return Self { data };
+        Normal3 {
+            data: map3(self.data, |value| -value),
+        }
     }
 }
 
-impl ops::Mul<Vector> for f32 {
-    type Output = Vector;
+impl ops::Mul<Normal3> for Float {
+    type Output = Normal3;
 
-    fn mul(self, rhs: Vector) -> Self::Output {
-        let data: [f32; 3] = [self * rhs.data[0], self * rhs.data[1], self * rhs.data[2]];
-        return Self::Output { data };
+    fn mul(self, rhs: Normal3) -> Self::Output {
+        Normal3 {
+            data: map3(rhs.data, |value| Float3::from(self) * value),
+        }
     }
 }
 
-impl ops::Div<f32> for Vector {
-    type Output = Vector;
+impl ops::Div<Float> for Normal3 {
+    type Output = Normal3;
 
-    fn div(self, rhs: f32) -> Self::Output {
-        let data: [f32; 3] = [self.data[0] / rhs, self.data[1] / rhs, self.data[2] / rhs];
-        return Self::Output { data };
+    fn div(self, rhs: Float) -> Self::Output {
+        Normal3 {
+            data: map3(self.data, |value| value / Float3::from(rhs)),
+        }
     }
 }
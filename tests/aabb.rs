@@ -0,0 +1,44 @@
+// Rays along the faces of boxes, as the axis aligned rays through flat meshes are.
+
+use learning_rust_with_ray_tracing::{
+    float::Float,
+    hittables::aabb::Aabb,
+    vector::{Point3, Vec3},
+};
+
+fn hits(bounds: &Aabb, origin: Point3, direction: Vec3) -> bool {
+    let inverse_direction = Vec3 {
+        data: direction.data.map(|value| 1.0 / value),
+    };
+    bounds.hit(&origin, &inverse_direction, 0.0, Float::INFINITY)
+}
+
+#[test]
+fn rays_in_the_plane_of_a_face_are_inside() {
+    let bounds = Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+    let flat = Aabb::new(Point3::new(0.0, 0.5, 0.0), Point3::new(1.0, 0.5, 1.0));
+
+    for side in [1.0, -1.0] {
+        // Zeros of either sign, the inverses are infinities of either sign.
+        let direction = Vec3::new(side, 0.0 * side, -0.0 * side);
+        let start = 0.5 - 2.0 * side;
+        for (y, z) in [(0.0, 0.5), (1.0, 0.5), (0.0, 0.0), (1.0, 1.0)] {
+            assert!(
+                hits(&bounds, Point3::new(start, y, z), direction),
+                "{} {} {} along {:?} misses",
+                start,
+                y,
+                z,
+                direction.data
+            );
+        }
+        assert!(hits(&flat, Point3::new(start, 0.5, 0.5), direction));
+        // Beside the box or behind the ray.
+        assert!(!hits(&bounds, Point3::new(start, 1.5, 0.5), direction));
+        assert!(!hits(
+            &bounds,
+            Point3::new(0.5 + 2.0 * side, 0.0, 0.5),
+            direction
+        ));
+    }
+}
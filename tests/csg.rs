@@ -0,0 +1,150 @@
+// Spans of CSG nodes along a ray through two overlapping spheres, x = t - 5 on the ray, and
+// the emitters CSG turns down.
+
+use learning_rust_with_ray_tracing::{
+    color::Color,
+    error::Error,
+    float::Float,
+    hittables::{csg::Csg, cuboid::Cuboid, cylinder::Cylinder, hittable::Hittable, sphere::Sphere},
+    materials::{
+        diffuse_material::DiffuseMaterial, emissive_material::EmissiveMaterial, material::Material,
+    },
+    ray::Ray,
+    vector::{Point3, Vec3},
+};
+
+fn gray() -> Box<dyn Material> {
+    Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5)).unwrap())
+}
+
+fn light() -> Box<dyn Material> {
+    Box::new(EmissiveMaterial::new(Color::new(4.0, 4.0, 4.0)))
+}
+
+fn ball(x: Float, radius: Float) -> Box<dyn Hittable> {
+    Box::new(Sphere::new(Point3::new(x, 0.0, 0.0), radius, gray()).unwrap())
+}
+
+// Spheres over [-1.5, 0.5] and [-0.5, 1.5] along x.
+fn pair() -> (Box<dyn Hittable>, Box<dyn Hittable>) {
+    (ball(-0.5, 1.0), ball(0.5, 1.0))
+}
+
+fn along_x() -> Ray {
+    Ray {
+        origin: Point3::new(-5.0, 0.0, 0.0),
+        direction: Vec3::new(1.0, 0.0, 0.0),
+        time: 0.0,
+    }
+}
+
+// Where the spans enter and leave along x.
+fn spans(hittable: &dyn Hittable) -> Vec<(Float, Float)> {
+    hittable
+        .spans(&along_x())
+        .iter()
+        .map(|span| (span.enter.t - 5.0, span.exit.t - 5.0))
+        .collect()
+}
+
+fn assert_spans(hittable: &dyn Hittable, expected: &[(Float, Float)]) {
+    let actual = spans(hittable);
+    assert_eq!(actual.len(), expected.len(), "spans {:?}", actual);
+    for (actual, expected) in actual.iter().zip(expected) {
+        assert!(
+            (actual.0 - expected.0).abs() < 1e-4 && (actual.1 - expected.1).abs() < 1e-4,
+            "span {:?} instead of {:?}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+fn unions_join_overlapping_spans() {
+    let (first, second) = pair();
+    assert_spans(&Csg::union(first, second).unwrap(), &[(-1.5, 1.5)]);
+
+    // Apart they stay two.
+    let apart = Csg::union(ball(-2.0, 0.5), ball(2.0, 0.5)).unwrap();
+    assert_spans(&apart, &[(-2.5, -1.5), (1.5, 2.5)]);
+}
+
+#[test]
+fn intersections_keep_the_overlap() {
+    let (first, second) = pair();
+    let lens = Csg::intersection(first, second).unwrap();
+    assert_spans(&lens, &[(-0.5, 0.5)]);
+
+    let hit = lens.hit(&along_x(), 0.0, Float::INFINITY).unwrap();
+    assert!(hit.is_front_face);
+    assert!((hit.t - 4.5).abs() < 1e-4);
+
+    let apart = Csg::intersection(ball(-2.0, 0.5), ball(2.0, 0.5)).unwrap();
+    assert!(apart.spans(&along_x()).is_empty());
+    assert!(apart.hit(&along_x(), 0.0, Float::INFINITY).is_none());
+}
+
+#[test]
+fn differences_carve_the_second_out_of_the_first() {
+    let (first, second) = pair();
+    let bitten = Csg::difference(first, second).unwrap();
+    assert_spans(&bitten, &[(-1.5, -0.5)]);
+
+    // The carved surface is the second sphere's, left through its back.
+    let exit = bitten.hit(&along_x(), 4.0, Float::INFINITY).unwrap();
+    assert!((exit.t - 4.5).abs() < 1e-4);
+    assert!(!exit.is_front_face);
+    assert!(exit.normal.x() < 0.0);
+}
+
+#[test]
+fn nested_nodes_combine_the_spans_of_their_children() {
+    let (first, second) = pair();
+    let union = Csg::union(first, second).unwrap();
+    let hollow = Csg::difference(Box::new(union), ball(0.0, 0.25)).unwrap();
+    assert_spans(&hollow, &[(-1.5, -0.25), (0.25, 1.5)]);
+
+    // A node as the first child of another one.
+    let (first, second) = pair();
+    let cut = Csg::intersection(
+        Box::new(Csg::difference(first, ball(0.0, 0.25)).unwrap()),
+        second,
+    )
+    .unwrap();
+    assert_spans(&cut, &[(-0.5, -0.25), (0.25, 0.5)]);
+}
+
+#[test]
+fn emitters_of_every_shape_are_turned_down() {
+    let emitters: [Box<dyn Hittable>; 4] = [
+        Box::new(Sphere::new(Point3::ORIGIN, 1.0, light()).unwrap()),
+        Box::new(
+            Cuboid::new(
+                Point3::new(-1.0, -1.0, -1.0),
+                Point3::new(1.0, 1.0, 1.0),
+                light(),
+            )
+            .unwrap(),
+        ),
+        Box::new(
+            Cylinder::new(
+                Point3::new(0.0, -1.0, 0.0),
+                Point3::new(0.0, 1.0, 0.0),
+                1.0,
+                light(),
+            )
+            .unwrap(),
+        ),
+        Box::new(Csg::union(ball(0.0, 1.0), ball(1.0, 1.0)).unwrap()),
+    ];
+    for (index, emitter) in emitters.into_iter().enumerate() {
+        let emits = index < 3;
+        assert_eq!(emitter.emits(), emits, "shape {}", index);
+        match Csg::union(emitter, ball(0.5, 1.0)) {
+            Err(Error::InvalidMaterial(_)) => assert!(emits, "shape {} was turned down", index),
+            Err(error) => panic!("{} instead of an emissive material error", error),
+            Ok(_) => assert!(!emits, "emissive shape {} was combined", index),
+        }
+    }
+}
@@ -0,0 +1,332 @@
+// A small glTF file with everything embedded: one textured quad placed twice, once directly
+// and once through a parent node.
+
+use std::fs;
+
+use learning_rust_with_ray_tracing::{
+    error::Error,
+    float::Float,
+    gltf_scene::GltfScene,
+    hittables::hittable::Hittable,
+    ray::Ray,
+    vector::{Point3, Vec3},
+};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (index, byte)| {
+            group | (*byte as u32) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64[(group >> (18 - 6 * index) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// 2x2 image of a single 8-bit RGB color.
+fn png(color: [u8; 3]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut encoder = png::Encoder::new(&mut bytes, 2, 2);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().unwrap();
+    writer.write_image_data(&color.repeat(4)).unwrap();
+    writer.finish().unwrap();
+    bytes
+}
+
+// Unit quad in the xy plane facing +z. White, rough metal by its factors, but its textures
+// make it red and dielectric.
+fn write_gltf(name: &str) -> String {
+    let mut buffer = Vec::new();
+    let positions: [f32; 12] = [
+        -0.5, -0.5, 0.0, 0.5, -0.5, 0.0, 0.5, 0.5, 0.0, -0.5, 0.5, 0.0,
+    ];
+    let uvs: [f32; 8] = [0.0, 1.0, 1.0, 1.0, 1.0, 0.0, 0.0, 0.0];
+    for value in positions.iter().chain(uvs.iter()) {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    for index in [0u16, 1, 2, 0, 2, 3] {
+        buffer.extend_from_slice(&index.to_le_bytes());
+    }
+
+    let json = format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "buffers": [{{ "byteLength": {length}, "uri": "data:application/octet-stream;base64,{buffer}" }}],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 48 }},
+                {{ "buffer": 0, "byteOffset": 48, "byteLength": 32 }},
+                {{ "buffer": 0, "byteOffset": 80, "byteLength": 12 }}
+            ],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 4, "type": "VEC3",
+                   "min": [-0.5, -0.5, 0.0], "max": [0.5, 0.5, 0.0] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 4, "type": "VEC2" }},
+                {{ "bufferView": 2, "componentType": 5123, "count": 6, "type": "SCALAR" }}
+            ],
+            "images": [
+                {{ "uri": "data:image/png;base64,{red}" }},
+                {{ "uri": "data:image/png;base64,{dielectric}" }}
+            ],
+            "textures": [{{ "source": 0 }}, {{ "source": 1 }}],
+            "materials": [{{
+                "pbrMetallicRoughness": {{
+                    "baseColorFactor": [1.0, 1.0, 1.0, 1.0],
+                    "metallicFactor": 1.0,
+                    "roughnessFactor": 1.0,
+                    "baseColorTexture": {{ "index": 0 }},
+                    "metallicRoughnessTexture": {{ "index": 1 }}
+                }}
+            }}],
+            "meshes": [{{
+                "primitives": [{{
+                    "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1 }},
+                    "indices": 2,
+                    "material": 0
+                }}]
+            }}],
+            "nodes": [
+                {{ "mesh": 0, "translation": [-1.0, 0.0, 2.0] }},
+                {{ "translation": [0.0, 0.0, 2.0], "children": [2] }},
+                {{ "mesh": 0, "translation": [1.0, 0.0, 0.0] }}
+            ],
+            "scenes": [{{ "nodes": [0, 1] }}],
+            "scene": 0
+        }}"#,
+        length = buffer.len(),
+        buffer = base64(&buffer),
+        red = base64(&png([255, 0, 0])),
+        dielectric = base64(&png([0, 255, 0])),
+    );
+
+    let path = std::env::temp_dir().join(format!("learning_rust_with_ray_tracing_{}.gltf", name));
+    fs::write(&path, json).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+fn ray(x: Float, z_direction: Float) -> Ray {
+    Ray {
+        origin: Point3::new(x, 0.0, 0.0),
+        direction: Vec3::new(0.0, 0.0, z_direction),
+        time: 0.0,
+    }
+}
+
+#[test]
+fn instances_are_placed_by_their_nodes_with_z_flipped() {
+    let scene = GltfScene::load(&write_gltf("instances"), 1.0).unwrap();
+    assert_eq!(scene.world.hittables.len(), 2);
+
+    // glTF's +z ends up as -z, for the node placed directly and the one under the group.
+    for x in [-1.0, 1.0] {
+        let hit = scene
+            .world
+            .hit(&ray(x, -1.0), 0.0, Float::INFINITY)
+            .unwrap_or_else(|| panic!("no quad at x = {} behind the origin", x));
+        assert!((hit.t - 2.0).abs() < 1e-4, "quad at t = {}", hit.t);
+        assert!(scene
+            .world
+            .hit(&ray(x, 1.0), 0.0, Float::INFINITY)
+            .is_none());
+    }
+    assert!(scene
+        .world
+        .hit(&ray(0.0, -1.0), 0.0, Float::INFINITY)
+        .is_none());
+}
+
+#[test]
+fn textures_override_the_material_factors() {
+    let scene = GltfScene::load(&write_gltf("textures"), 1.0).unwrap();
+    let hit = scene
+        .world
+        .hit(&ray(-1.0, -1.0), 0.0, Float::INFINITY)
+        .unwrap();
+
+    let up = Vec3::new(0.0, 0.0, 1.0);
+    let bsdf = hit.material.eval(&hit, &up, &up);
+    // The red base makes the diffuse part red, while the green channel is left with the
+    // colorless reflection of a dielectric that a metal of the red base wouldn't have.
+    assert!(bsdf.g() > 0.0, "green {} of a metal", bsdf.g());
+    assert!(
+        bsdf.r() > 10.0 * bsdf.g(),
+        "base color {:?} isn't red",
+        bsdf.data
+    );
+}
+
+#[test]
+fn jpeg_textures_are_rejected() {
+    let path = write_gltf("jpeg");
+    let json = fs::read_to_string(&path)
+        .unwrap()
+        .replacen("data:image/png", "data:image/jpeg", 1);
+    fs::write(&path, json).unwrap();
+
+    match GltfScene::load(&path, 1.0) {
+        Err(Error::InvalidMaterial(message)) => assert!(message.contains("JPEG"), "{}", message),
+        Err(error) => panic!("{} instead of a JPEG error", error),
+        Ok(_) => panic!("JPEG texture was loaded"),
+    }
+}
+
+// Unit cube from the origin, four vertices to a face as exporters split them, with the zeros of
+// every other face written as -0. Two meshes share it, a smooth one and one creased all around
+// and pushed out by a white displacement texture.
+fn write_subdivision_gltf() -> String {
+    let corner = |index: usize| [index & 1, index >> 1 & 1, index >> 2 & 1].map(|bit| bit as f32);
+    let faces = [
+        [0, 4, 6, 2],
+        [1, 3, 7, 5],
+        [0, 1, 5, 4],
+        [2, 6, 7, 3],
+        [0, 2, 3, 1],
+        [4, 5, 7, 6],
+    ];
+    let mut buffer = Vec::new();
+    for (face, corners) in faces.iter().enumerate() {
+        for index in corners {
+            for value in corner(*index) {
+                let value = if face % 2 == 1 && value == 0.0 {
+                    -0.0
+                } else {
+                    value
+                };
+                buffer.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+    }
+    for _ in faces {
+        for value in [0.0f32, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0] {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    for face in 0..6u16 {
+        for index in [0, 1, 2, 0, 2, 3] {
+            buffer.extend_from_slice(&(4 * face + index).to_le_bytes());
+        }
+    }
+
+    // Every edge of the cube, by the first vertex of the face it was written for.
+    let creases: Vec<String> = (0..faces.len())
+        .flat_map(|face| {
+            (0..4).map(move |k| format!("[{}, {}, 10.0]", 4 * face + k, 4 * face + (k + 1) % 4))
+        })
+        .collect();
+
+    let json = format!(
+        r#"{{
+            "asset": {{ "version": "2.0" }},
+            "buffers": [{{ "byteLength": {length}, "uri": "data:application/octet-stream;base64,{buffer}" }}],
+            "bufferViews": [
+                {{ "buffer": 0, "byteOffset": 0, "byteLength": 288 }},
+                {{ "buffer": 0, "byteOffset": 288, "byteLength": 192 }},
+                {{ "buffer": 0, "byteOffset": 480, "byteLength": 72 }}
+            ],
+            "accessors": [
+                {{ "bufferView": 0, "componentType": 5126, "count": 24, "type": "VEC3",
+                   "min": [0.0, 0.0, 0.0], "max": [1.0, 1.0, 1.0] }},
+                {{ "bufferView": 1, "componentType": 5126, "count": 24, "type": "VEC2" }},
+                {{ "bufferView": 2, "componentType": 5123, "count": 36, "type": "SCALAR" }}
+            ],
+            "images": [{{ "uri": "data:image/png;base64,{white}" }}],
+            "textures": [{{ "source": 0 }}],
+            "meshes": [
+                {{
+                    "primitives": [{{ "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1 }}, "indices": 2 }}],
+                    "extras": {{ "subdivision": 2 }}
+                }},
+                {{
+                    "primitives": [{{ "attributes": {{ "POSITION": 0, "TEXCOORD_0": 1 }}, "indices": 2 }}],
+                    "extras": {{ "subdivision": {{
+                        "levels": 2,
+                        "creases": [{creases}],
+                        "displacement": {{ "texture": 0, "scale": 0.2 }}
+                    }} }}
+                }}
+            ],
+            "nodes": [
+                {{ "mesh": 0, "translation": [-3.0, 0.0, 0.0] }},
+                {{ "mesh": 1, "translation": [3.0, 0.0, 0.0] }}
+            ],
+            "scenes": [{{ "nodes": [0, 1] }}]
+        }}"#,
+        length = buffer.len(),
+        buffer = base64(&buffer),
+        white = base64(&png([255, 255, 255])),
+        creases = creases.join(", "),
+    );
+
+    let path = std::env::temp_dir().join("learning_rust_with_ray_tracing_subdivision.gltf");
+    fs::write(&path, json).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+fn from(origin: Point3, direction: Vec3) -> Ray {
+    Ray {
+        origin,
+        direction,
+        time: 0.0,
+    }
+}
+
+#[test]
+fn subdivided_cages_are_closed_and_smooth() {
+    let scene = GltfScene::load(&write_subdivision_gltf(), 1.0).unwrap();
+    // Centre of the smooth cube, z flipped.
+    let centre = Point3::new(-2.5, 0.5, -0.5);
+
+    // The faces were welded back together, rays from inside get out nowhere.
+    let mut previous: Option<Vec3> = None;
+    let steps = 400;
+    for step in 0..=steps {
+        let angle = (step as Float / steps as Float) * std::f64::consts::TAU as Float;
+        let direction = Vec3::new(angle.cos(), angle.sin(), 0.3);
+        let hit = scene
+            .world
+            .hit(&from(centre, direction), 0.0, Float::INFINITY)
+            .unwrap_or_else(|| panic!("crack in the direction {:?}", direction.data));
+
+        // Normals turn smoothly rather than jumping from facet to facet.
+        let normal = Vec3::from(hit.normal).normalize();
+        if let Some(previous) = previous {
+            let cosine =
+                normal.x() * previous.x() + normal.y() * previous.y() + normal.z() * previous.z();
+            assert!(
+                cosine > 0.999,
+                "normal turned by {} degrees",
+                cosine.acos().to_degrees()
+            );
+        }
+        previous = Some(normal);
+    }
+}
+
+#[test]
+fn creases_and_displacement_apply_to_subdivided_cages() {
+    let scene = GltfScene::load(&write_subdivision_gltf(), 1.0).unwrap();
+    let centre = Point3::new(3.5, 0.5, -0.5);
+
+    // Sharp all around the cube keeps its faces flat through refinement, and the white
+    // displacement pushes them out by half its scale.
+    for direction in [
+        Vec3::new(1.0, 0.0, 0.0),
+        Vec3::new(0.0, -1.0, 0.0),
+        Vec3::new(0.0, 0.0, 1.0),
+    ] {
+        let hit = scene
+            .world
+            .hit(&from(centre, direction), 0.0, Float::INFINITY)
+            .unwrap();
+        assert!((hit.t - 0.6).abs() < 1e-4, "face at {}", hit.t);
+    }
+}
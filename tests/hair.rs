@@ -0,0 +1,126 @@
+// White furnace: a fiber that absorbs nothing scatters all the light it receives, whatever the
+// roughness, the direction it's seen from and where across it it's hit.
+
+use learning_rust_with_ray_tracing::{
+    color::Color,
+    float::{consts::PI, Float},
+    hit_record::HitRecord,
+    hittables::hittable::Primitive,
+    materials::{
+        hair_material::{HairMaterial, HairPigment},
+        material::Material,
+    },
+    ray::Ray,
+    vector::{dot, Normal3, Point3, Vec3},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const ROUGHNESSES: [Float; 3] = [0.2, 0.5, 1.0];
+
+fn random_unit(random: &mut StdRng) -> Vec3 {
+    loop {
+        let vector = Vec3::new(
+            random.gen_range(-1.0..1.0),
+            random.gen_range(-1.0..1.0),
+            random.gen_range(-1.0..1.0),
+        );
+        if vector.length() > 0.1 && vector.length() <= 1.0 {
+            return vector.normalize();
+        }
+    }
+}
+
+// Hit on a fiber along x seen from `wo`, at a random offset across it as curves report it.
+fn hit<'a>(material: &'a dyn Material, wo: &Vec3, random: &mut StdRng) -> HitRecord<'a> {
+    // The normal faces the viewer, across the fiber.
+    let across = Vec3::new(0.0, wo.y(), wo.z()).normalize();
+    HitRecord {
+        origin: Point3::ORIGIN,
+        error: Vec3::default(),
+        normal: Normal3::from(across),
+        geometric_normal: Normal3::from(across),
+        t: 1.0,
+        is_front_face: true,
+        material,
+        uv: [0.5, random.gen::<Float>()],
+        barycentrics: None,
+        tangent: Some(Vec3::new(1.0, 0.0, 0.0)),
+        primitive: Primitive::Other,
+    }
+}
+
+fn white(longitudinal: Float, azimuthal: Float) -> HairMaterial {
+    HairMaterial::new(HairPigment::Absorption(Color::BLACK))
+        .unwrap()
+        .with_roughness(longitudinal, azimuthal)
+        .unwrap()
+}
+
+fn assert_near_one(value: f32, tolerance: f32, what: &str) {
+    assert!(
+        (value - 1.0).abs() < tolerance,
+        "{} scatters {} of the light",
+        what,
+        value
+    );
+}
+
+#[test]
+fn white_fibers_scatter_all_light_over_the_sphere() {
+    let mut random = StdRng::seed_from_u64(6);
+    let samples = 50_000;
+    // Smooth fibers make uniform directions too noisy for a test this short.
+    for (longitudinal, azimuthal) in [(0.4, 0.6), (0.7, 0.4), (1.0, 1.0)] {
+        let material = white(longitudinal, azimuthal);
+        let mut sum = 0.0;
+        for _ in 0..samples {
+            let wo = random_unit(&mut random);
+            let hit = hit(&material, &wo, &mut random);
+            // Uniform over the sphere, so the estimate doesn't rely on the sampling.
+            let wi = random_unit(&mut random);
+            let cos = dot(&wi, &Vec3::from(hit.normal)).abs();
+            sum += material.eval(&hit, &wo, &wi).g() * (cos * 4.0 * PI) as f32;
+        }
+        assert_near_one(
+            sum / samples as f32,
+            0.04,
+            &format!("roughness {} and {}", longitudinal, azimuthal),
+        );
+    }
+}
+
+#[test]
+fn white_fibers_keep_sampled_paths_at_full_weight() {
+    let mut random = StdRng::seed_from_u64(7);
+    let samples = 10_000;
+    for longitudinal in ROUGHNESSES {
+        for azimuthal in ROUGHNESSES {
+            let material = white(longitudinal, azimuthal);
+            let mut sum = 0.0;
+            let mut count = 0;
+            for _ in 0..samples {
+                let wo = random_unit(&mut random);
+                let hit = hit(&material, &wo, &mut random);
+                let ray = Ray {
+                    origin: Point3::ORIGIN + wo,
+                    direction: -wo,
+                    time: 0.0,
+                };
+                if let Some((attenuation, _)) = material.scatter(&ray, &hit) {
+                    sum += attenuation.g();
+                    count += 1;
+                }
+            }
+            assert!(
+                count > samples * 99 / 100,
+                "{} samples failed",
+                samples - count
+            );
+            assert_near_one(
+                sum / samples as f32,
+                0.02,
+                &format!("sampling roughness {} and {}", longitudinal, azimuthal),
+            );
+        }
+    }
+}
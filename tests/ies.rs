@@ -0,0 +1,133 @@
+// Type C profiles measured over part of the circle repeat by their symmetry, and files that
+// aren't LM-63 come back as errors.
+
+use learning_rust_with_ray_tracing::{error::Error, float::Float, lights::ies::IesProfile};
+
+// LM-63 text of a type C profile with a candela value for each horizontal angle, and within
+// those for each vertical angle.
+fn ies(vertical: &[Float], horizontal: &[Float], candela: &[Float]) -> String {
+    let list = |values: &[Float]| {
+        values
+            .iter()
+            .map(|value| value.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    format!(
+        "IESNA:LM-63-2002\n[TEST] test\nTILT=NONE\n1 1000 1 {} {} 1 1 0 0 0\n1 1 100\n{}\n{}\n{}\n",
+        vertical.len(),
+        horizontal.len(),
+        list(vertical),
+        list(horizontal),
+        list(candela)
+    )
+}
+
+fn assert_close(actual: Float, expected: Float) {
+    assert!(
+        (actual - expected).abs() < 1e-3,
+        "{} instead of {}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn one_horizontal_angle_is_the_same_all_around() {
+    let profile = IesProfile::parse(&ies(&[0.0, 90.0], &[0.0], &[100.0, 50.0]), "round").unwrap();
+    for horizontal in [0.0, 45.0, 135.0, 270.0, 359.0] {
+        assert_close(profile.candela(0.0, horizontal), 100.0);
+        assert_close(profile.candela(45.0, horizontal), 75.0);
+    }
+    // Above the last vertical angle nothing is measured.
+    assert_close(profile.candela(120.0, 0.0), 0.0);
+}
+
+#[test]
+fn a_quadrant_repeats_in_all_four() {
+    let profile = IesProfile::parse(
+        &ies(&[0.0, 90.0], &[0.0, 90.0], &[10.0, 10.0, 30.0, 30.0]),
+        "quadrant",
+    )
+    .unwrap();
+    for (horizontal, expected) in [
+        (0.0, 10.0),
+        (30.0, 16.667),
+        (90.0, 30.0),
+        (150.0, 16.667),
+        (180.0, 10.0),
+        (210.0, 16.667),
+        (270.0, 30.0),
+        (330.0, 16.667),
+    ] {
+        assert_close(profile.candela(45.0, horizontal), expected);
+    }
+}
+
+#[test]
+fn a_half_mirrors_about_the_0_180_plane() {
+    let profile = IesProfile::parse(
+        &ies(
+            &[0.0, 90.0],
+            &[0.0, 90.0, 180.0],
+            &[10.0, 10.0, 20.0, 20.0, 40.0, 40.0],
+        ),
+        "half",
+    )
+    .unwrap();
+    for (horizontal, expected) in [
+        (0.0, 10.0),
+        (45.0, 15.0),
+        (90.0, 20.0),
+        (180.0, 40.0),
+        (225.0, 30.0),
+        (270.0, 20.0),
+        (315.0, 15.0),
+    ] {
+        assert_close(profile.candela(30.0, horizontal), expected);
+    }
+}
+
+#[test]
+fn malformed_files_are_errors() {
+    let valid = ies(&[0.0, 90.0], &[0.0], &[100.0, 50.0]);
+    let cases = [
+        (
+            String::from("IESNA:LM-63-2002\n1 1000 1 2 1 1 1 0 0 0\n"),
+            "TILT",
+        ),
+        (valid.replace("TILT=NONE", "NO TILT"), "TILT"),
+        (valid.replace("100 50", "100"), "file ends"),
+        (valid.replace("100 50", "100 bright"), "bright"),
+        (valid.replace("1 1000 1 2 1 1", "1 1000 1 2 1 2"), "type"),
+        (valid.replace("1 1000 1 2 1", "1 1000 1 0 1"), "no angles"),
+        (valid.replace("\n0 90\n", "\n90 0\n"), "ascending"),
+        (valid.replace("100 50", "100 -50"), "negative"),
+        (valid.replace("100 50", "0 0"), "no light"),
+        (valid.replace("1 1000 1 2 1", "1 1000 1 2.5 1"), "whole"),
+        (valid.replace("1 1000 1 2 1", "1 1000 1 1e30 1e30"), "whole"),
+        (
+            valid.replace("TILT=NONE\n", "TILT=INCLUDE\n1 -3\n"),
+            "whole",
+        ),
+    ];
+    for (text, expected) in cases {
+        match IesProfile::parse(&text, "test.ies") {
+            Err(Error::InvalidGeometry(message)) => assert!(
+                message.starts_with("test.ies: ") && message.contains(expected),
+                "\"{}\" doesn't mention {}",
+                message,
+                expected
+            ),
+            Err(error) => panic!("{} instead of invalid geometry", error),
+            Ok(_) => panic!("parsed {:?}", text),
+        }
+    }
+}
+
+#[test]
+fn the_example_profile_loads() {
+    let profile = IesProfile::load("ies/downlight.ies").unwrap();
+    assert_close(profile.max_candela(), 2000.0);
+    assert!(profile.candela(60.0, 0.0) < profile.candela(0.0, 0.0));
+}
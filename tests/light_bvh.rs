@@ -0,0 +1,64 @@
+// The many-lights tree has to report the same chance for a light as walking down to it does,
+// multiple importance sampling weighs strategies with it.
+
+use learning_rust_with_ray_tracing::{
+    float::Float,
+    hittables::aabb::Aabb,
+    lights::light_bvh::{LightBounds, LightBvh},
+    vector::{Normal3, Point3, Vec3},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+fn lights(random: &mut StdRng) -> Vec<(usize, LightBounds)> {
+    (0..40)
+        .map(|index| {
+            let mut vector = |low: Float, high: Float| {
+                Vec3::new(
+                    random.gen_range(low..high),
+                    random.gen_range(low..high),
+                    random.gen_range(low..high),
+                )
+            };
+            let min = Point3::ORIGIN + vector(-5.0, 5.0);
+            let max = min + vector(0.0, 1.0);
+            let axis = vector(-1.0, 1.0).normalize();
+            let bounds = LightBounds {
+                bounds: Aabb::new(min, max),
+                power: random.gen_range(0.0..3.0),
+                axis,
+                cos_theta_o: random.gen_range(-1.0..1.0),
+                cos_theta_e: random.gen_range(-1.0..0.5),
+            };
+            // Indices in the light set don't have to be contiguous.
+            (3 * index, bounds)
+        })
+        .collect()
+}
+
+#[test]
+fn pmf_matches_the_picks() {
+    let mut random = StdRng::seed_from_u64(1);
+    let lights = lights(&mut random);
+    let bvh = LightBvh::new(&lights);
+    let point = Point3::new(0.3, -0.2, 0.5);
+    let normal = Normal3::new(0.0, 1.0, 0.0);
+
+    let total: Float = lights
+        .iter()
+        .map(|(light, _)| bvh.pmf(&point, Some(&normal), *light))
+        .sum();
+    assert!((total - 1.0).abs() < 1e-4, "chances sum to {}", total);
+
+    for _ in 0..10000 {
+        let (light, probability) = bvh.pick(&point, Some(&normal)).unwrap();
+        let pmf = bvh.pmf(&point, Some(&normal), light);
+        assert!(
+            (pmf - probability).abs() <= 1e-4 * probability,
+            "light {} picked with {} but its pmf is {}",
+            light,
+            probability,
+            pmf
+        );
+    }
+    assert_eq!(bvh.pmf(&point, Some(&normal), 1), 0.0);
+}
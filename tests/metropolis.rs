@@ -0,0 +1,98 @@
+// Metropolis light transport only moves the samples of the path tracer around the image, so
+// the image as a whole has to come out as bright.
+
+use learning_rust_with_ray_tracing::{
+    cameras::perspective_camera::PerspectiveCamera,
+    color::Color,
+    hittables::{hittable_list::HittableList, sphere::Sphere},
+    integrators::mlt::MetropolisSettings,
+    lights::sky_light::Sky,
+    materials::{diffuse_material::DiffuseMaterial, emissive_material::EmissiveMaterial},
+    renderer::{Integrator, RenderSettings, Renderer},
+    vector::{Point3, Vec3},
+    Framebuffer, Scene,
+};
+
+// A ball on the ground lit by a small bright lamp under a dim sky.
+fn scene() -> Scene {
+    let gray = DiffuseMaterial::new(Color::new(0.6, 0.5, 0.4)).unwrap();
+    let world = HittableList {
+        hittables: vec![
+            Box::new(
+                Sphere::new(
+                    Point3::new(0.0, -1000.0, 0.0),
+                    1000.0,
+                    Box::new(gray.clone()),
+                )
+                .unwrap(),
+            ),
+            Box::new(Sphere::new(Point3::new(0.0, 1.0, 0.0), 1.0, Box::new(gray)).unwrap()),
+            Box::new(
+                Sphere::new(
+                    Point3::new(2.0, 3.0, 1.0),
+                    0.3,
+                    Box::new(EmissiveMaterial::new(Color::new(20.0, 18.0, 15.0))),
+                )
+                .unwrap(),
+            ),
+        ],
+    };
+    let camera = PerspectiveCamera::new(
+        &Point3::new(0.0, 2.0, 8.0),
+        &Point3::new(0.0, 1.0, 0.0),
+        &Vec3::new(0.0, 1.0, 0.0),
+        4.0 / 3.0,
+        40.0,
+        0.0,
+        1.0,
+    )
+    .unwrap();
+    Scene::new(world, Box::new(camera)).with_sky(Sky {
+        horizon: Color::new(0.1, 0.1, 0.1),
+        zenith: Color::new(0.05, 0.05, 0.1),
+    })
+}
+
+fn mean_luminance(framebuffer: &Framebuffer) -> f32 {
+    framebuffer
+        .pixels
+        .iter()
+        .map(|pixel| pixel.luminance())
+        .sum::<f32>()
+        / framebuffer.pixels.len() as f32
+}
+
+#[test]
+fn metropolis_images_are_as_bright_as_path_traced_ones() {
+    let scene = scene();
+    let settings = |integrator: Integrator| RenderSettings {
+        width: 32,
+        height: 24,
+        samples_per_pixel: 64,
+        integrator,
+        metropolis: MetropolisSettings {
+            bootstrap_samples: 20_000,
+            chains: 64,
+            ..MetropolisSettings::default()
+        },
+        ..RenderSettings::default()
+    };
+
+    let path = mean_luminance(
+        &Renderer::new(settings(Integrator::Path))
+            .render(&scene)
+            .unwrap(),
+    );
+    let metropolis = mean_luminance(
+        &Renderer::new(settings(Integrator::Metropolis))
+            .render(&scene)
+            .unwrap(),
+    );
+    assert!(path > 0.0);
+    assert!(
+        (metropolis / path - 1.0).abs() < 0.05,
+        "Metropolis {} against path tracing {}",
+        metropolis,
+        path
+    );
+}
@@ -0,0 +1,99 @@
+// The kd-tree has to find the same photons around a point as checking every one of them.
+
+use learning_rust_with_ray_tracing::{
+    color::Color,
+    float::Float,
+    integrators::photon_map::{Photon, PhotonMap},
+    vector::{Point3, Vec3},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+fn photon(position: Point3) -> Photon {
+    Photon {
+        position,
+        incoming: Vec3::new(0.0, 1.0, 0.0),
+        power: Color::WHITE,
+    }
+}
+
+fn random_offset(random: &mut StdRng, scale: Vec3) -> Vec3 {
+    Vec3::new(
+        random.gen_range(-1.0..1.0) * scale.x(),
+        random.gen_range(-1.0..1.0) * scale.y(),
+        random.gen_range(-1.0..1.0) * scale.z(),
+    )
+}
+
+// Positions are unique among the photons but for the copies, so they name them.
+fn sorted(mut positions: Vec<[Float; 3]>) -> Vec<[Float; 3]> {
+    positions.sort_by(|a, b| {
+        a.iter()
+            .zip(b)
+            .map(|(a, b)| a.total_cmp(b))
+            .find(|order| order.is_ne())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    positions
+}
+
+#[test]
+fn neighbours_match_a_brute_force_search() {
+    let mut random = StdRng::seed_from_u64(8);
+
+    // A flat spread like photons on a floor, a cluster, exact copies and a grid whose
+    // coordinates fall on the split planes.
+    let mut positions = Vec::new();
+    for _ in 0..2000 {
+        positions.push(Point3::ORIGIN + random_offset(&mut random, Vec3::new(10.0, 0.01, 10.0)));
+    }
+    for _ in 0..500 {
+        positions.push(
+            Point3::new(2.0, 0.0, -3.0) + random_offset(&mut random, Vec3::new(0.1, 0.1, 0.1)),
+        );
+    }
+    for _ in 0..50 {
+        positions.push(Point3::new(-1.0, 0.0, 1.0));
+    }
+    for x in -5..=5 {
+        for z in -5..=5 {
+            positions.push(Point3::new(x as Float, 0.0, z as Float));
+        }
+    }
+    let map = PhotonMap::new(positions.iter().map(|position| photon(*position)).collect());
+    assert_eq!(map.len(), positions.len());
+
+    let mut centres: Vec<Point3> = (0..300)
+        .map(|_| Point3::ORIGIN + random_offset(&mut random, Vec3::new(11.0, 0.5, 11.0)))
+        .collect();
+    centres.extend([
+        Point3::new(2.0, 0.0, -3.0),
+        Point3::new(-1.0, 0.0, 1.0),
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(3.0, 0.0, 0.5),
+    ]);
+    for centre in centres {
+        for radius in [0.0, 0.05, 0.5, 1.0, 3.0] {
+            let expected: Vec<[Float; 3]> = positions
+                .iter()
+                .filter(|position| (**position - centre).squared_length() <= radius * radius)
+                .map(|position| position.data)
+                .collect();
+            let mut found = Vec::new();
+            map.for_each_within(&centre, radius, |photon| found.push(photon.position.data));
+            assert_eq!(
+                sorted(found),
+                sorted(expected),
+                "photons within {} of {:?}",
+                radius,
+                centre.data
+            );
+        }
+    }
+}
+
+#[test]
+fn empty_maps_find_nothing() {
+    let map = PhotonMap::new(Vec::new());
+    assert!(map.is_empty());
+    map.for_each_within(&Point3::ORIGIN, 1.0, |_| panic!("found a photon"));
+}
@@ -0,0 +1,189 @@
+// Paths of `calculate_color` end where their kind of bounce runs out, and Russian roulette
+// only changes how noisy they are, not what they average to.
+
+use std::cell::Cell;
+
+use learning_rust_with_ray_tracing::{
+    color::Color,
+    float::Float,
+    hit_record::HitRecord,
+    hittables::{aabb::Aabb, hittable::Hittable, hittable_list::HittableList, sphere::Sphere},
+    lights::sky_light::Sky,
+    materials::{
+        dielectric_material::DielectricMaterial, diffuse_material::DiffuseMaterial,
+        material::Material, metal_material::MetalMaterial,
+    },
+    ray::Ray,
+    renderer::{calculate_color, PathDepths},
+    vector::{Point3, Vec3},
+};
+
+// Counts the bounces of a path by the queries it makes.
+struct Counted<H: Hittable> {
+    object: H,
+    hits: Cell<u32>,
+}
+
+impl<H: Hittable> Counted<H> {
+    fn new(object: H) -> Counted<H> {
+        Counted {
+            object,
+            hits: Cell::new(0),
+        }
+    }
+}
+
+impl<H: Hittable> Hittable for Counted<H> {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord<'_>> {
+        let hit = self.object.hit(ray, t_min, t_max);
+        if hit.is_some() {
+            self.hits.set(self.hits.get() + 1);
+        }
+        hit
+    }
+
+    fn bounding_box(&self, time_start: Float, time_end: Float) -> Option<Aabb> {
+        self.object.bounding_box(time_start, time_end)
+    }
+}
+
+fn white_sky() -> Sky {
+    Sky {
+        horizon: Color::WHITE,
+        zenith: Color::WHITE,
+    }
+}
+
+fn sphere(radius: Float, material: Box<dyn Material>) -> Sphere {
+    Sphere::new(Point3::ORIGIN, radius, material).unwrap()
+}
+
+// Rays from the centre meet every sphere around it head on.
+fn from_centre() -> Ray {
+    Ray {
+        origin: Point3::ORIGIN,
+        direction: Vec3::new(0.3, 0.4, 0.5),
+        time: 0.0,
+    }
+}
+
+// Every kind allowed far more bounces than the test makes, and no roulette.
+fn unlimited() -> PathDepths {
+    PathDepths {
+        diffuse: 1000,
+        specular: 1000,
+        transmission: 1000,
+        roulette: u32::MAX,
+    }
+}
+
+#[test]
+fn black_paths_end_at_once() {
+    let world = Counted::new(sphere(
+        1.0,
+        Box::new(DiffuseMaterial::new(Color::BLACK).unwrap()),
+    ));
+    let color = calculate_color(&from_centre(), &world, &white_sky(), &unlimited());
+    assert!(color.is_black());
+    assert_eq!(world.hits.get(), 1);
+}
+
+#[test]
+fn each_kind_of_bounce_stops_at_its_limit() {
+    let limit = 7;
+
+    // Closed white and mirror spheres keep a path bouncing inside until its limit.
+    let white = Counted::new(sphere(
+        1.0,
+        Box::new(DiffuseMaterial::new(Color::WHITE).unwrap()),
+    ));
+    let mirror = Counted::new(sphere(
+        1.0,
+        Box::new(MetalMaterial::new(Color::WHITE, 0.0).unwrap()),
+    ));
+    // Shells of glass that doesn't bend or reflect light, crossed one after the other.
+    let shells = Counted::new(HittableList {
+        hittables: (1..=2 * limit)
+            .map(|radius| {
+                Box::new(sphere(
+                    radius as Float,
+                    Box::new(DielectricMaterial::new(1.0).unwrap()),
+                )) as Box<dyn Hittable>
+            })
+            .collect(),
+    });
+
+    let cases: [(&dyn Hittable, &Cell<u32>, PathDepths); 3] = [
+        (
+            &white,
+            &white.hits,
+            PathDepths {
+                diffuse: limit,
+                ..unlimited()
+            },
+        ),
+        (
+            &mirror,
+            &mirror.hits,
+            PathDepths {
+                specular: limit,
+                ..unlimited()
+            },
+        ),
+        (
+            &shells,
+            &shells.hits,
+            PathDepths {
+                transmission: limit,
+                ..unlimited()
+            },
+        ),
+    ];
+    for (world, hits, depths) in cases {
+        let color = calculate_color(&from_centre(), world, &white_sky(), &depths);
+        // The bounce past the limit is the last hit, and the path never reaches the sky.
+        assert_eq!(hits.get(), limit + 1, "{:?}", depths);
+        assert!(color.is_black(), "{:?} reached the sky", depths);
+    }
+}
+
+#[test]
+fn russian_roulette_keeps_the_average() {
+    // Outside a convex gray sphere under a white sky, a path bounces once and then sees the
+    // sky, so it gathers the albedo.
+    let albedo = 0.3;
+    let world = sphere(
+        1.0,
+        Box::new(DiffuseMaterial::new(Color::new(albedo, albedo, albedo)).unwrap()),
+    );
+    let ray = Ray {
+        origin: Point3::new(0.0, 0.0, 5.0),
+        direction: Vec3::new(0.0, 0.0, -1.0),
+        time: 0.0,
+    };
+    let depths = PathDepths {
+        roulette: 0,
+        ..PathDepths::default()
+    };
+
+    let samples = 20000;
+    let mut sum = 0.0;
+    let mut ended = 0;
+    for _ in 0..samples {
+        let color = calculate_color(&ray, &world, &white_sky(), &depths);
+        if color.is_black() {
+            ended += 1;
+        }
+        sum += color.g();
+    }
+    let mean = sum / samples as f32;
+
+    // Survivors carry 1 with probability 0.3, four standard deviations of that are 0.013.
+    assert!(ended > samples / 2, "only {} paths ended early", ended);
+    assert!(
+        (mean - albedo).abs() < 0.013,
+        "{} instead of {} on average",
+        mean,
+        albedo
+    );
+}
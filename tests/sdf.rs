@@ -0,0 +1,108 @@
+// Sphere tracing a sphere's distance field has to land where the ray meets the analytic sphere.
+
+use learning_rust_with_ray_tracing::{
+    color::Color,
+    float::Float,
+    hittables::{
+        hittable::Hittable,
+        sdf::{Sdf, SdfShape},
+        sphere::Sphere,
+    },
+    materials::{diffuse_material::DiffuseMaterial, material::Material},
+    ray::Ray,
+    vector::{dot, Point3, Vec3},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+fn gray() -> Box<dyn Material> {
+    Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5)).unwrap())
+}
+
+fn random_unit(random: &mut StdRng) -> Vec3 {
+    loop {
+        let vector = Vec3::new(
+            random.gen_range(-1.0..1.0),
+            random.gen_range(-1.0..1.0),
+            random.gen_range(-1.0..1.0),
+        );
+        if vector.length() > 0.1 && vector.length() <= 1.0 {
+            return vector.normalize();
+        }
+    }
+}
+
+#[test]
+fn sphere_fields_hit_where_spheres_do() {
+    let centre = Point3::new(1.0, -2.0, 3.0);
+    let radius = 1.5;
+    let field = Sdf::new(
+        SdfShape::sphere(radius).translate(centre - Point3::ORIGIN),
+        gray(),
+    )
+    .unwrap();
+    let sphere = Sphere::new(centre, radius, gray()).unwrap();
+    let tolerance = 1e-3 * radius;
+
+    let mut random = StdRng::seed_from_u64(5);
+    let mut hits = 0;
+    for index in 0..2000 {
+        // Rays from outside towards the sphere and around it, some from inside, and directions
+        // that aren't unit length.
+        let origin = if index % 4 == 0 {
+            centre + 0.5 * radius * random_unit(&mut random)
+        } else {
+            centre + 4.0 * radius * random_unit(&mut random)
+        };
+        let target = centre + 1.3 * radius * random.gen::<Float>() * random_unit(&mut random);
+        let ray = Ray {
+            origin,
+            direction: random.gen_range(0.5..2.0) * (target - origin).normalize(),
+            time: 0.0,
+        };
+
+        // Rays grazing the silhouette are left out, marching may stop short of them.
+        let to_centre = centre - origin;
+        let along = dot(&to_centre, &ray.direction.normalize());
+        let passing = (dot(&to_centre, &to_centre) - along * along)
+            .max(0.0)
+            .sqrt();
+        if (passing - radius).abs() < 0.01 * radius {
+            continue;
+        }
+
+        let expected = sphere.hit(&ray, 0.0, Float::INFINITY);
+        let actual = field.hit(&ray, 0.0, Float::INFINITY);
+        match (expected, actual) {
+            (Some(expected), Some(actual)) => {
+                hits += 1;
+                // Marching stops within the precision of the surface, before crossing it.
+                let off_surface = (actual.origin - centre).length() - radius;
+                assert!(
+                    off_surface.abs() < tolerance,
+                    "field hit {} off the sphere",
+                    off_surface
+                );
+                assert!(
+                    actual.t <= expected.t + tolerance,
+                    "field hit at {} past {}",
+                    actual.t,
+                    expected.t
+                );
+                assert_eq!(actual.is_front_face, expected.is_front_face);
+                assert!(
+                    dot(&actual.normal, &expected.normal) > 0.999,
+                    "normal {:?} instead of {:?}",
+                    actual.normal.data,
+                    expected.normal.data
+                );
+            }
+            (None, None) => {}
+            (expected, actual) => panic!(
+                "sphere hit {}, field hit {}",
+                expected.is_some(),
+                actual.is_some()
+            ),
+        }
+    }
+    assert!(hits > 1000, "only {} rays hit", hits);
+}
@@ -0,0 +1,209 @@
+// Rays spawned off a surface must never hit it again, whatever the scale of the scene. The
+// scene is traced with t_min = 0, so only the error bounds keep the rays clear.
+
+use learning_rust_with_ray_tracing::{
+    color::Color,
+    float::Float,
+    hittables::{heightfield::Heightfield, hittable::Hittable, sphere::Sphere, triangle::Triangle},
+    materials::diffuse_material::DiffuseMaterial,
+    ray::{spawn_ray_to, Ray},
+    vector::{dot, Point3, Vec3},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+const SCALES: [Float; 3] = [1e-4, 1.0, 1e4];
+const RAYS: usize = 2000;
+const GLASS_RATIO: Float = 1.0 / 1.5;
+
+struct Scene {
+    scale: Float,
+    sphere: Sphere,
+    ground: Triangle,
+    // Away from the origin so positions carry rounding errors of their own.
+    offset: Vec3,
+}
+
+fn scene(scale: Float) -> Scene {
+    let offset = scale * Vec3::new(30.0, 20.0, 10.0);
+    let material = || Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5)).unwrap());
+    let at = |x: Float, y: Float, z: Float| Point3::ORIGIN + offset + scale * Vec3::new(x, y, z);
+
+    Scene {
+        scale,
+        sphere: Sphere::new(at(0.0, 1.0, 0.0), scale, material()).unwrap(),
+        ground: Triangle::new(
+            [
+                at(-20.0, 0.0, -20.0),
+                at(20.0, 0.0, -20.0),
+                at(0.0, 0.0, 20.0),
+            ],
+            material(),
+        )
+        .unwrap(),
+        offset,
+    }
+}
+
+// Rays from above the scene towards the sphere and the ground around it.
+fn rays(scene: &Scene, random: &mut StdRng) -> Vec<Ray> {
+    let point = |random: &mut StdRng, low: [Float; 3], high: [Float; 3]| {
+        let relative = Vec3::new(
+            random.gen_range(low[0]..high[0]),
+            random.gen_range(low[1]..high[1]),
+            random.gen_range(low[2]..high[2]),
+        );
+        Point3::ORIGIN + scene.offset + scene.scale * relative
+    };
+
+    (0..RAYS)
+        .map(|_| {
+            let origin = point(random, [-3.0, 3.0, -3.0], [3.0, 5.0, 3.0]);
+            let target = point(random, [-1.5, 0.0, -1.5], [1.5, 2.0, 1.5]);
+            Ray {
+                origin,
+                direction: target - origin,
+                time: 0.0,
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn reflections_and_refractions_leave_the_sphere() {
+    let mut random = StdRng::seed_from_u64(1);
+    for scale in SCALES {
+        let scene = scene(scale);
+        for ray in rays(&scene, &mut random) {
+            let Some(hit) = scene.sphere.hit(&ray, 0.0, Float::INFINITY) else {
+                continue;
+            };
+            let direction = ray.direction.normalize();
+
+            // Nothing of a convex shape is in front of its reflections.
+            let reflected = hit.spawn_ray(direction.reflect(&hit.normal), 0.0);
+            assert!(
+                scene.sphere.hit(&reflected, 0.0, Float::INFINITY).is_none(),
+                "reflection off the sphere hit it again at scale {}",
+                scale
+            );
+
+            // Glass bends rays by at most 42 degrees inside, so their chords are longer than
+            // the radius.
+            let refracted = hit.spawn_ray(direction.refract(&hit.normal, GLASS_RATIO), 0.0);
+            let exit = scene
+                .sphere
+                .hit(&refracted, 0.0, Float::INFINITY)
+                .expect("refraction into the sphere leaves it again");
+            assert!(
+                (exit.origin - hit.origin).length() > scale,
+                "refraction into the sphere hit it again at {} at scale {}",
+                exit.t,
+                scale
+            );
+        }
+    }
+}
+
+#[test]
+fn reflections_and_refractions_leave_the_ground() {
+    let mut random = StdRng::seed_from_u64(2);
+    for scale in SCALES {
+        let scene = scene(scale);
+        for ray in rays(&scene, &mut random) {
+            let Some(hit) = scene.ground.hit(&ray, 0.0, Float::INFINITY) else {
+                continue;
+            };
+            let direction = ray.direction.normalize();
+
+            for spawned in [
+                hit.spawn_ray(direction.reflect(&hit.normal), 0.0),
+                hit.spawn_ray(direction.refract(&hit.normal, GLASS_RATIO), 0.0),
+            ] {
+                assert!(
+                    scene.ground.hit(&spawned, 0.0, Float::INFINITY).is_none(),
+                    "ray off the ground hit it again at scale {}",
+                    scale
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn shadow_rays_reach_the_sphere_from_the_ground() {
+    let mut random = StdRng::seed_from_u64(3);
+    for scale in SCALES {
+        let scene = scene(scale);
+        for ray in rays(&scene, &mut random) {
+            let Some(hit) = scene.ground.hit(&ray, 0.0, Float::INFINITY) else {
+                continue;
+            };
+            // Only the points on the side of the sphere facing the ground point are in view.
+            let sample = scene.sphere.sample_surface(0.0);
+            if dot(&sample.normal, &(hit.origin - sample.point)) <= 0.0 {
+                continue;
+            }
+
+            let shadow_ray = spawn_ray_to(
+                &hit.origin,
+                &hit.error,
+                Some(&hit.normal),
+                &sample.point,
+                &sample.error,
+                Some(&sample.normal),
+                0.0,
+            );
+            assert!(
+                scene.sphere.hit(&shadow_ray, 0.0, 1.0).is_none()
+                    && scene.ground.hit(&shadow_ray, 0.0, 1.0).is_none(),
+                "shadow ray between the ground and the sphere blocked by either at scale {}",
+                scale
+            );
+        }
+    }
+}
+
+// A ridge between two slopes, the samples on it have normals straight up while the faces on
+// either side are steep.
+#[test]
+fn rays_leave_heightfields_off_their_faces_not_their_smooth_normals() {
+    let mut random = StdRng::seed_from_u64(4);
+    for scale in SCALES {
+        let offset = scale * Vec3::new(30.0, 20.0, 10.0);
+        let ridge = Heightfield::new(
+            3,
+            2,
+            &[0.0, 1.0, 0.0, 0.0, 1.0, 0.0],
+            Point3::ORIGIN + offset,
+            scale * Vec3::new(2.0, 1.0, 1.0),
+            Box::new(DiffuseMaterial::new(Color::new(0.5, 0.5, 0.5)).unwrap()),
+        )
+        .unwrap();
+
+        for _ in 0..RAYS {
+            let ray = Ray {
+                origin: Point3::ORIGIN
+                    + offset
+                    + scale
+                        * Vec3::new(
+                            random.gen_range(0.05..1.95),
+                            3.0,
+                            random.gen_range(0.05..0.95),
+                        ),
+                direction: Vec3::new(0.0, -1.0, 0.0),
+                time: 0.0,
+            };
+            let hit = ridge.hit(&ray, 0.0, Float::INFINITY).unwrap();
+
+            // Down the slope, barely above the face and against the smooth normal near the ridge.
+            let face = Vec3::from(hit.geometric_normal);
+            let downhill = (ray.direction - dot(&ray.direction, &face) * face).normalize();
+            let spawned = hit.spawn_ray(downhill + 1e-3 * face, 0.0);
+            assert!(
+                ridge.hit(&spawned, 0.0, Float::INFINITY).is_none(),
+                "ray down the ridge hit it again at scale {}",
+                scale
+            );
+        }
+    }
+}
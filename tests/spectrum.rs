@@ -0,0 +1,88 @@
+// Upsampled colors have to come back as themselves, and reflectances can't reflect more than
+// they receive at any wavelength.
+
+use learning_rust_with_ray_tracing::{
+    color::Color,
+    spectrum::{to_rgb, RgbSpectrum, SampledWavelengths, LAMBDA_MAX, LAMBDA_MIN, SPECTRUM_SAMPLES},
+};
+
+// Color of the spectrum integrated over evenly spaced wavelengths.
+fn color(spectrum: &RgbSpectrum) -> Color {
+    let count = 470;
+    let step = (LAMBDA_MAX - LAMBDA_MIN) / count as f32;
+    let mut sum = [0.0; 3];
+    for group in 0..count / SPECTRUM_SAMPLES {
+        let wavelengths = SampledWavelengths {
+            lambda: std::array::from_fn(|index| {
+                LAMBDA_MIN + ((group * SPECTRUM_SAMPLES + index) as f32 + 0.5) * step
+            }),
+            pdf: [1.0 / (LAMBDA_MAX - LAMBDA_MIN); SPECTRUM_SAMPLES],
+        };
+        let rgb = to_rgb(&spectrum.sample(&wavelengths), &wavelengths);
+        for (total, value) in sum.iter_mut().zip(rgb.data) {
+            *total += value;
+        }
+    }
+    Color {
+        data: sum.map(|total| total / (count / SPECTRUM_SAMPLES) as f32),
+    }
+}
+
+fn assert_close(actual: &Color, expected: [f32; 3], tolerance: f32) {
+    for (actual, expected) in actual.data.iter().zip(expected) {
+        assert!(
+            (actual - expected).abs() <= tolerance,
+            "{:?} instead of {:?}",
+            actual,
+            expected
+        );
+    }
+}
+
+#[test]
+fn albedos_stay_within_one() {
+    for rgb in [
+        [1.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0],
+        [0.2, 0.4, 1.0],
+        [1.0, 1.0, 1.0],
+    ] {
+        let spectrum = RgbSpectrum::albedo(&Color { data: rgb });
+        let mut lambda = LAMBDA_MIN;
+        while lambda <= LAMBDA_MAX {
+            let value = spectrum.evaluate(lambda);
+            assert!(
+                (0.0..=1.0).contains(&value),
+                "{:?} reflects {} at {} nm",
+                rgb,
+                value,
+                lambda
+            );
+            lambda += 1.0;
+        }
+    }
+}
+
+#[test]
+fn upsampled_colors_round_trip() {
+    for rgb in [
+        [0.5, 0.5, 0.5],
+        [0.8, 0.3, 0.1],
+        [0.1, 0.6, 0.3],
+        [0.25, 0.2, 0.7],
+    ] {
+        assert_close(
+            &color(&RgbSpectrum::albedo(&Color { data: rgb })),
+            rgb,
+            0.02,
+        );
+    }
+
+    // Emission keeps its brightness past one.
+    let rgb = [4.0, 2.0, 1.0];
+    assert_close(
+        &color(&RgbSpectrum::unbounded(&Color { data: rgb })),
+        rgb,
+        0.1,
+    );
+}